@@ -0,0 +1,153 @@
+// src/audit.rs
+use crate::{
+    ApiError, AppState,
+    auth::{Role, User},
+};
+use axum::{
+    Json,
+    extract::{ConnectInfo, MatchedPath, Query, Request, State},
+    http::{Method, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+use tracing::warn;
+use uuid::Uuid;
+
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 1000;
+
+// 누가 언제 무엇을 시작/정지/삭제/변경했는지. 카탈로그 데이터베이스에 남긴다.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub timestamp: DateTime<Local>,
+    // "http" 또는 명령을 낸 서비스 ("mqtt", "gpio", "schedule", "motion")
+    pub source: String,
+    // 인증한 사용자 (api_keys, bearer_tokens 의 키는 "api_key #1" 처럼). 인증을 쓰지 않거나 키가 틀렸으면 None
+    pub user: Option<String>,
+    pub role: Option<Role>,
+    pub client_ip: Option<IpAddr>,
+    // HTTP 면 "POST /start" 처럼 메서드와 경로 (쿼리는 키가 들어 있을 수 있어 뺀다)
+    pub action: String,
+    // HTTP 응답 코드. 서비스의 명령도 같은 API 를 부르므로 그 결과 코드
+    pub status: u16,
+    // 서비스가 시작한 녹화 세션
+    pub session_id: Option<Uuid>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AuditQuery {
+    pub user: Option<String>,
+    pub source: Option<String>,
+    // [from, to] 에 남긴 것만 (RFC 3339)
+    pub from: Option<DateTime<Local>>,
+    pub to: Option<DateTime<Local>>,
+    // 최신순으로 이만큼 (기본 100, 최대 1000)
+    pub limit: Option<usize>,
+}
+
+impl AuditQuery {
+    pub fn limit(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+    }
+}
+
+// 읽기만 하는 요청 (와 미리보기, 시작 확인) 은 남기지 않는다. legacy_get_routes 의 GET /start, /stop 은 남긴다.
+fn audited(method: &Method, route: &str) -> bool {
+    match *method {
+        Method::GET => matches!(route, "/start" | "/stop"),
+        Method::HEAD | Method::OPTIONS => false,
+        Method::POST => !matches!(route, "/webrtc/offer" | "/start/validate"),
+        _ => true,
+    }
+}
+
+// 응답을 늦추지 않도록 따로 쓰고, 쓰지 못하면 로그만 남긴다.
+fn save(state: &AppState, entry: AuditEntry) {
+    let catalog = state.catalog.clone();
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = catalog.add_audit(&entry) {
+            warn!("Failed to write the audit log: {:#}", e);
+        }
+    });
+}
+
+// 인증 바깥에서 감싸 거절된 (401, 403) 요청도 남긴다. 인증한 사용자는 auth::require 가 응답에 넣어 준다.
+pub async fn record(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| request.uri().path(), MatchedPath::as_str);
+    if !audited(request.method(), route) {
+        return next.run(request).await;
+    }
+    let action = format!("{} {}", request.method(), request.uri().path());
+    let client_ip = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+
+    let response = next.run(request).await;
+    let user = response.extensions().get::<User>();
+    save(
+        &state,
+        AuditEntry {
+            id: 0,
+            timestamp: Local::now(),
+            source: "http".to_string(),
+            user: user.map(|user| user.name.clone()),
+            role: user.map(|user| user.role),
+            client_ip,
+            action,
+            status: response.status().as_u16(),
+            session_id: None,
+        },
+    );
+    response
+}
+
+// MQTT, GPIO, 예약, 움직임 감지가 낸 명령
+pub fn record_command(
+    state: &AppState,
+    source: &str,
+    action: impl Into<String>,
+    result: Result<Option<Uuid>, &ApiError>,
+) {
+    let (status, session_id) = match result {
+        Ok(session_id) => (StatusCode::OK, session_id),
+        Err(e) => (e.status, None),
+    };
+    save(
+        state,
+        AuditEntry {
+            id: 0,
+            timestamp: Local::now(),
+            source: source.to_string(),
+            user: None,
+            role: None,
+            client_ip: None,
+            action: action.into(),
+            status: status.as_u16(),
+            session_id,
+        },
+    );
+}
+
+// GET /audit - 최신순
+pub async fn handle_list(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<Vec<AuditEntry>>, ApiError> {
+    let catalog = state.catalog.clone();
+    let entries = tokio::task::spawn_blocking(move || catalog.audit(&query))
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))?
+        .map_err(|e| ApiError::internal(format!("{:#}", e)))?;
+    Ok(Json(entries))
+}
@@ -0,0 +1,407 @@
+// src/auth.rs
+use crate::{ApiError, AppState};
+use anyhow::{Result, bail};
+use axum::{
+    Extension, Json,
+    extract::{MatchedPath, Request, State},
+    http::{HeaderValue, Method, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD as BASE64};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::{collections::HashSet, sync::Arc};
+use uuid::Uuid;
+
+const API_KEY_HEADER: &str = "X-API-Key";
+
+// 헤더를 붙일 수 없는 클라이언트(브라우저 WebSocket, HLS 플레이어)가 POST /auth/token 으로 받은
+// 접근 토큰을 보내는 쿼리 매개변수. 키 자체는 로그에 남지 않게 헤더로만 받는다.
+pub const ACCESS_TOKEN_QUERY: &str = "token";
+
+// 오케스트레이터나 systemd 가 키 없이 확인할 수 있어야 하는 경로와 API 문서
+const PUBLIC_PATHS: &[&str] = &["/", "/healthz", "/readyz", "/openapi.json", "/docs"];
+
+// 아래 역할은 위 역할이 할 수 있는 것을 모두 할 수 있다.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    // 상태, 미리보기, 녹화 목록과 내려받기
+    Viewer,
+    // 녹화 시작/정지, 카메라 설정, 예약, 잘라 내기
+    Operator,
+    // 설정, 웹훅, 프로필과 모든 삭제
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Operator => "operator",
+            Role::Admin => "admin",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "viewer" => Some(Role::Viewer),
+            "operator" => Some(Role::Operator),
+            "admin" => Some(Role::Admin),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserConfig {
+    pub name: String,
+    pub role: Role,
+    // X-API-Key 나 Bearer 토큰으로 보내는 이 사용자의 키. /config 에는 노출하지 않는다.
+    #[serde(skip_serializing, default)]
+    pub key: String,
+}
+
+// 인증한 요청의 사용자. 요청과 응답의 extensions 에 들어 있다.
+#[derive(Debug, Clone)]
+pub struct User {
+    pub name: String,
+    pub role: Role,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuthConfig {
+    // X-API-Key 헤더로 보내는 키. 키나 토큰이 하나라도 있으면 인증을 요구한다.
+    // /config 에는 노출하지 않는다.
+    #[serde(skip_serializing)]
+    pub api_keys: Vec<String>,
+    // Authorization: Bearer <token> 으로 보내는 토큰
+    #[serde(skip_serializing)]
+    pub bearer_tokens: Vec<String>,
+    // 역할이 있는 사용자 (api_keys 와 bearer_tokens 는 admin 으로 본다)
+    pub users: Vec<UserConfig>,
+    // true 면 GET /status 는 인증 없이 허용 (상태 모니터링용)
+    pub public_status: bool,
+    // POST /auth/token 으로 받은 접근 토큰이 유효한 시간 (초)
+    pub token_ttl_secs: u64,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            api_keys: Vec::new(),
+            bearer_tokens: Vec::new(),
+            users: Vec::new(),
+            public_status: false,
+            token_ttl_secs: 600,
+        }
+    }
+}
+
+impl AuthConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.token_ttl_secs == 0 {
+            bail!("auth.token_ttl_secs must be non-zero");
+        }
+        if self
+            .api_keys
+            .iter()
+            .chain(&self.bearer_tokens)
+            .any(|key| key.trim().is_empty())
+        {
+            bail!("auth keys and tokens must not be empty");
+        }
+        let mut names = HashSet::new();
+        let mut keys: HashSet<&str> = self
+            .api_keys
+            .iter()
+            .chain(&self.bearer_tokens)
+            .map(String::as_str)
+            .collect();
+        for user in &self.users {
+            if user.name.trim().is_empty() {
+                bail!("auth.users names must not be empty");
+            }
+            if !names.insert(user.name.as_str()) {
+                bail!("auth.users has more than one user named {:?}", user.name);
+            }
+            if user.key.trim().is_empty() {
+                bail!("auth.users {:?} needs a key", user.name);
+            }
+            if !keys.insert(user.key.as_str()) {
+                bail!(
+                    "auth.users {:?} shares its key with another user or key",
+                    user.name
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.api_keys.is_empty() || !self.bearer_tokens.is_empty() || !self.users.is_empty()
+    }
+
+    // api_keys, bearer_tokens 의 키는 몇 번째 키인지로 구분한다 ("api_key #1").
+    fn admin(kind: &str, index: usize) -> User {
+        User {
+            name: format!("{} #{}", kind, index + 1),
+            role: Role::Admin,
+        }
+    }
+
+    fn user(&self, key: &str) -> Option<User> {
+        let keys = self.users.iter().map(|user| user.key.as_str());
+        position(keys, key).map(|index| User {
+            name: self.users[index].name.clone(),
+            role: self.users[index].role,
+        })
+    }
+
+    // 키나 토큰 하나에 맞는 사용자 (RTSP Basic 인증의 비밀번호처럼 HTTP 밖에서 받은 것)
+    pub fn user_for_key(&self, key: &str) -> Option<User> {
+        if let Some(index) = position(self.api_keys.iter().map(String::as_str), key) {
+            return Some(Self::admin("api_key", index));
+        }
+        if let Some(index) = position(self.bearer_tokens.iter().map(String::as_str), key) {
+            return Some(Self::admin("bearer_token", index));
+        }
+        self.user(key)
+    }
+
+    // 요청 헤더의 키나 토큰에 맞는 사용자
+    fn authenticate(&self, request: &Request) -> Option<User> {
+        let headers = request.headers();
+        let api_key = headers
+            .get(API_KEY_HEADER)
+            .and_then(|value| value.to_str().ok());
+        if let Some(key) = api_key {
+            if let Some(index) = position(self.api_keys.iter().map(String::as_str), key) {
+                return Some(Self::admin("api_key", index));
+            }
+            if let Some(user) = self.user(key) {
+                return Some(user);
+            }
+        }
+        let token = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))?
+            .trim();
+        if let Some(index) = position(self.bearer_tokens.iter().map(String::as_str), token) {
+            return Some(Self::admin("bearer_token", index));
+        }
+        self.user(token)
+    }
+}
+
+// 쿼리로 보내는 접근 토큰. 로그나 Referer 에 남을 수 있으므로 짧게만 유효하고, 한 경로
+// (/events 나 /live/<session_id>/ 아래) 의 GET 만 viewer 로 허용한다.
+// 토큰은 "<만료 시각>.<경로>.<사용자 이름>.<서명>" 이고, 서명 키는 실행할 때마다 새로 만들므로
+// 서버를 다시 시작하면 그 전의 토큰은 모두 무효가 된다.
+pub struct AccessTokens {
+    secret: [u8; 32],
+}
+
+impl Default for AccessTokens {
+    fn default() -> Self {
+        let mut secret = [0; 32];
+        secret[..16].copy_from_slice(Uuid::new_v4().as_bytes());
+        secret[16..].copy_from_slice(Uuid::new_v4().as_bytes());
+        Self { secret }
+    }
+}
+
+impl AccessTokens {
+    fn mac(&self, payload: &str) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts keys of any size");
+        mac.update(payload.as_bytes());
+        mac
+    }
+
+    pub fn issue(&self, user: &User, scope: &str, expires_at: DateTime<Utc>) -> String {
+        let payload = format!(
+            "{}.{}.{}",
+            expires_at.timestamp(),
+            BASE64.encode(scope),
+            BASE64.encode(&user.name)
+        );
+        let signature = hex::encode(self.mac(&payload).finalize().into_bytes());
+        format!("{}.{}", payload, signature)
+    }
+
+    // 서명과 만료 시각이 맞고 요청이 토큰의 경로 안의 GET 이면 토큰을 받은 사용자 (viewer)
+    fn verify(&self, token: &str, method: &Method, path: &str) -> Option<User> {
+        let (payload, signature) = token.rsplit_once('.')?;
+        self.mac(payload)
+            .verify_slice(&hex::decode(signature).ok()?)
+            .ok()?;
+        let mut parts = payload.split('.');
+        let expires_at = parts.next()?.parse::<i64>().ok()?;
+        let scope = String::from_utf8(BASE64.decode(parts.next()?).ok()?).ok()?;
+        let name = String::from_utf8(BASE64.decode(parts.next()?).ok()?).ok()?;
+        let in_scope = path == scope || (scope.ends_with('/') && path.starts_with(&scope));
+        let readable = method == Method::GET || method == Method::HEAD;
+        (Utc::now().timestamp() < expires_at && in_scope && readable).then_some(User {
+            name,
+            role: Role::Viewer,
+        })
+    }
+}
+
+// 접근 토큰으로 열 수 있는 경로: 이벤트 스트림과 한 세션의 HLS 재생 목록과 세그먼트
+fn token_scope(path: &str) -> Option<String> {
+    if path == "/events" {
+        return Some(path.to_string());
+    }
+    let session_id = path.strip_prefix("/live/")?.trim_end_matches('/');
+    let session_id = Uuid::parse_str(session_id).ok()?;
+    Some(format!("/live/{}/", session_id))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TokenRequest {
+    // "/events" 나 "/live/<session_id>/"
+    path: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenResponse {
+    token: String,
+    // 토큰이 열 수 있는 경로
+    path: String,
+    expires_at: DateTime<Utc>,
+}
+
+// POST /auth/token - 헤더를 붙일 수 없는 클라이언트를 위한 짧게 유효한 쿼리 토큰
+pub async fn handle_token(
+    State(state): State<Arc<AppState>>,
+    user: Option<Extension<User>>,
+    Json(body): Json<TokenRequest>,
+) -> Result<Json<TokenResponse>, ApiError> {
+    let Some(Extension(user)) = user else {
+        return Err(ApiError::bad_request(
+            "Access tokens need [auth] keys or users",
+        ));
+    };
+    let Some(path) = token_scope(&body.path) else {
+        return Err(ApiError::bad_request(format!(
+            "Access tokens only cover /events and /live/<session_id>/, not {:?}",
+            body.path
+        )));
+    };
+    let expires_at =
+        Utc::now() + chrono::Duration::seconds(state.config.auth.token_ttl_secs as i64);
+    let token = state.access_tokens.issue(&user, &path, expires_at);
+    Ok(Json(TokenResponse {
+        token,
+        path,
+        expires_at,
+    }))
+}
+
+// 경로 (라우터에 등록한 패턴) 와 메서드마다 필요한 역할. 목록에 없는 것은 admin 만 부를 수 있다.
+fn required_role(method: &Method, route: &str) -> Role {
+    match (method.as_str(), route) {
+        // 설정과 웹훅 주소는 보는 것도 admin 만
+        ("GET", "/config" | "/webhooks" | "/audit") => Role::Admin,
+        // legacy_get_routes 의 GET /start, /stop
+        ("GET", "/start" | "/stop") => Role::Operator,
+        ("GET" | "HEAD", _) => Role::Viewer,
+        ("POST", "/webrtc/offer" | "/auth/token") => Role::Viewer,
+        (
+            "POST",
+            "/start"
+            | "/start/validate"
+            | "/stop"
+            | "/pause"
+            | "/resume"
+            | "/switch"
+            | "/cameras/warmup"
+            | "/recordings/current/marker"
+            | "/recordings/*name"
+            | "/schedules"
+            | "/projects",
+        ) => Role::Operator,
+        ("PUT", "/cameras/:id/settings" | "/cameras/:id/masks" | "/schedules/:id") => {
+            Role::Operator
+        }
+        _ => Role::Admin,
+    }
+}
+
+pub fn query_value<'a>(request: &'a Request, name: &str) -> Option<&'a str> {
+    request.uri().query()?.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        (key == name).then_some(value)
+    })
+}
+
+// 길이가 같으면 내용과 상관없이 같은 시간이 걸리도록 비교하고, 몇 번째 키가 맞았는지도
+// 시간으로 드러나지 않게 모든 키와 비교한다.
+pub fn position<'a>(keys: impl Iterator<Item = &'a str>, candidate: &str) -> Option<usize> {
+    keys.enumerate().fold(None, |found, (index, key)| {
+        let matches = key.len() == candidate.len()
+            && key
+                .bytes()
+                .zip(candidate.bytes())
+                .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+                == 0;
+        found.or(matches.then_some(index))
+    })
+}
+
+// 키나 토큰이 설정되어 있으면 /, 상태 확인용 /healthz, /readyz, API 문서 (와 public_status 일 때 /status) 를 뺀
+// 모든 요청에 인증을 요구하고, 사용자의 역할이 그 경로에 모자라면 403 으로 거절한다.
+pub async fn require(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let config = &state.config.auth;
+    let path = request.uri().path();
+    let public = PUBLIC_PATHS.contains(&path) || (config.public_status && path == "/status");
+    if !config.enabled() || public {
+        return next.run(request).await;
+    }
+    let user = config.authenticate(&request).or_else(|| {
+        let token = query_value(&request, ACCESS_TOKEN_QUERY)?;
+        state.access_tokens.verify(token, request.method(), path)
+    });
+    let Some(user) = user else {
+        let mut response = ApiError::unauthorized("Missing or invalid API key").into_response();
+        response
+            .headers_mut()
+            .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        return response;
+    };
+
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or(path, MatchedPath::as_str);
+    let required = required_role(request.method(), route);
+    if user.role < required {
+        let mut response = ApiError::forbidden(format!(
+            "{} {} needs the {} role; user {:?} is {}",
+            request.method(),
+            path,
+            required.as_str(),
+            user.name,
+            user.role.as_str()
+        ))
+        .into_response();
+        response.extensions_mut().insert(user);
+        return response;
+    }
+    // 핸들러와 바깥의 audit::record 가 쓸 수 있게 요청과 응답에 넣는다.
+    request.extensions_mut().insert(user.clone());
+    let mut response = next.run(request).await;
+    response.extensions_mut().insert(user);
+    response
+}
@@ -0,0 +1,136 @@
+// src/bookmarks.rs
+use crate::{ApiError, AppState, events::EventKind, find_session, overlay};
+use axum::{Json, extract::State};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::info;
+use uuid::Uuid;
+
+// 합성본에 이름을 그려 두는 시간 (초)
+const BURN_IN_SECS: f64 = 1.0;
+const MAX_LABEL_CHARS: usize = 200;
+
+// 녹화 중에 남긴 표시 (예: "trial 3 start"). 세션에 모아 두었다가 마무리할 때 그 시각이 든
+// 파일의 메타데이터 사이드카 (카탈로그에도 함께 들어간다) 에 넣는다.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub label: String,
+    pub at: DateTime<Local>,
+    // 파일 첫 프레임 기준 초. 파일 사이드카에만 있다.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset_secs: Option<f64>,
+    // 합성본에 BURN_IN_SECS 동안 이름을 그린다.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub burn_in: bool,
+}
+
+// start 이후 end 전에 찍힌 표시에 파일 기준 시각을 붙인다 (이어지는 파일에 겹치지 않게).
+pub fn in_file(
+    bookmarks: &[Bookmark],
+    (start, end): (DateTime<Local>, DateTime<Local>),
+) -> Vec<Bookmark> {
+    bookmarks
+        .iter()
+        .filter(|bookmark| bookmark.at >= start && bookmark.at < end)
+        .map(|bookmark| Bookmark {
+            offset_secs: Some((bookmark.at - start).num_milliseconds() as f64 / 1000.0),
+            ..bookmark.clone()
+        })
+        .collect()
+}
+
+// in_file 로 고른 표시 중 burn_in 인 것을 화면 위쪽 가운데에 그리는 필터
+pub fn burn_in_filter(bookmarks: &[Bookmark]) -> Option<String> {
+    let filters: Vec<String> = bookmarks
+        .iter()
+        .filter(|bookmark| bookmark.burn_in)
+        .filter_map(|bookmark| {
+            let at = bookmark.offset_secs?;
+            Some(format!(
+                "drawtext=text='{}':x=(w-tw)/2:y=h/12:fontsize=h/24:fontcolor=white:\
+                 box=1:boxcolor=black@0.6:boxborderw=10:enable='between(t,{:.3},{:.3})'",
+                overlay::escape_text(&bookmark.label),
+                at,
+                at + BURN_IN_SECS
+            ))
+        })
+        .collect();
+    (!filters.is_empty()).then(|| filters.join(","))
+}
+
+// Body of POST /recordings/current/marker
+#[derive(Debug, Deserialize)]
+pub struct BookmarkRequest {
+    #[serde(default)]
+    pub session_id: Option<Uuid>,
+    pub label: String,
+    #[serde(default)]
+    pub burn_in: bool,
+}
+
+#[derive(Serialize)]
+pub struct BookmarkResponse {
+    session_id: Uuid,
+    // 세션 시작 기준 초
+    elapsed_secs: f64,
+    #[serde(flatten)]
+    bookmark: Bookmark,
+}
+
+// POST /recordings/current/marker - 녹화 중인 세션에 지금 시각의 표시를 남긴다.
+pub async fn handle_add(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<BookmarkRequest>,
+) -> Result<Json<BookmarkResponse>, ApiError> {
+    let label = request.label.trim();
+    if label.is_empty() {
+        return Err(ApiError::bad_request("label must not be empty"));
+    }
+    if label.chars().count() > MAX_LABEL_CHARS {
+        return Err(ApiError::bad_request(format!(
+            "label must be at most {} characters",
+            MAX_LABEL_CHARS
+        )));
+    }
+    if label.chars().any(char::is_control) {
+        return Err(ApiError::bad_request(
+            "label must not contain control characters",
+        ));
+    }
+    let session = find_session(&state, request.session_id)?;
+    if !session.is_capturing() {
+        return Err(ApiError::conflict("Recording is not currently active."));
+    }
+    // 멈춘 동안의 장면은 파일에 없다.
+    if session.pause_requested() {
+        return Err(ApiError::conflict("Recording is paused."));
+    }
+
+    let bookmark = Bookmark {
+        label: label.to_string(),
+        at: Local::now(),
+        offset_secs: None,
+        burn_in: request.burn_in,
+    };
+    session.bookmarks.lock().unwrap().push(bookmark.clone());
+    let elapsed_secs = (bookmark.at - session.started_at).num_milliseconds() as f64 / 1000.0;
+    info!(
+        session_id = %session.id,
+        "Bookmark {:?} at {:.1}s.",
+        bookmark.label,
+        elapsed_secs
+    );
+    state.events.publish(
+        session.id,
+        EventKind::BookmarkAdded {
+            label: bookmark.label.clone(),
+            elapsed_secs,
+        },
+    );
+    Ok(Json(BookmarkResponse {
+        session_id: session.id,
+        elapsed_secs,
+        bookmark,
+    }))
+}
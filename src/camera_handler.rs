@@ -1,62 +1,2491 @@
 // src/camera_handler.rs
-use anyhow::{Context, Result};
+use crate::{
+    bookmarks::{self, Bookmark},
+    camera_settings::{CameraSettings, CropRegion},
+    chapters::{self, ChapterMode},
+    color_match::ColorMatchConfig,
+    compositor::{self, Arrangement, CompositeInput, Layout, PipConfig},
+    config::Config,
+    depth::{self, DepthConfig, DepthOutput},
+    detection::{self, Detection, DetectionConfig},
+    encoder::Encoder,
+    events::{EventBus, EventKind},
+    feed::QueueStats,
+    filename::{self, FileNames},
+    frame_log::{self, FrameLog},
+    frame_sync::{self, FrameEvent, FrameSync, PtsReaders},
+    keep_alive::WarmCameras,
+    lens::LensCorrection,
+    live::{self, LiveStream},
+    masks::CameraMasks,
+    metadata::{self, RecordingMetadata},
+    overlay::OverlayConfig,
+    panorama::PanoramaConfig,
+    preroll::{self, Clip},
+    projects,
+    reconnect::{self, Part, Placeholder, ReconnectConfig},
+    recordings,
+    recovery::{self, Marker},
+    session::{RecordingSession, SessionState},
+    sink::{self, Backend, Encoding, SinkConfig},
+    slow_motion::SlowMotionConfig,
+    source::{CameraSource, DeviceCamera, NetworkCamera, TestPattern},
+    stream,
+    timelapse::TimelapseConfig,
+    watermark::WatermarkConfig,
+};
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
 use std::{
+    collections::BTreeMap,
     fs,
-    path::PathBuf,
-    process::Command,
-    sync::{
-        Arc,
-        atomic::{AtomicBool, Ordering},
-    },
+    path::{Path, PathBuf},
+    process::{Child, Command},
+    sync::{Arc, Mutex},
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+// libcamera-vid 가 SIGINT 후 파일을 닫을 때까지 기다리는 최대 시간
+const STOP_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+// 녹화 중 디스크 여유 공간 확인 주기
+const DISK_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+// 프레임 누락을 이벤트로 알리는 최소 간격
+const FRAME_DROP_REPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+// 프레임 간격이 기대값의 이 배수를 넘으면 그 사이 프레임이 빠진 것으로 본다.
+const FRAME_DROP_GAP_FACTOR: f64 = 1.5;
+
+// 슬로 모션: 카메라마다 이만큼의 프레임이 쌓이면 실제 FPS 를 확인한다 (자동 노출이 자리 잡는 시간 포함).
+const SLOW_MOTION_CHECK_MS: f64 = 2000.0;
+
+// 녹화 요청마다 달라질 수 있는 설정
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecordingConfig {
+    pub cameras: Vec<u32>,
+    // true 면 연결되지 않은 카메라는 건너뛰고 남은 카메라만 녹화
+    pub allow_missing_cameras: bool,
+    pub layout: Layout,
+    // Layout::Grid 전용, None 이면 카메라 수에 맞춰 자동 결정
+    pub grid_columns: Option<u32>,
+    // Layout::PictureInPicture 전용
+    pub pip: PipConfig,
+    // Layout::Panorama 전용
+    pub panorama: PanoramaConfig,
+    // 카메라별 설정이 없을 때의 촬영 해상도/FPS 이자 합성 영상의 기준.
+    // 합성할 때 다른 해상도의 입력은 이 높이(세로/그리드 배치는 이 크기의 칸)에 맞춘다.
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+    // 일부 카메라만 다른 해상도/FPS 로 촬영할 때
+    pub camera_formats: Vec<CameraFormat>,
+    // 초 단위, None 이면 /stop 요청까지 계속 녹화
+    #[serde(alias = "duration_limit")]
+    pub max_duration: Option<u64>,
+    // 초 단위, 설정하면 이 길이마다 새 파일로 나누어 저장
+    pub segment_duration: Option<u64>,
+    // libcamera 카메라 대신 RTSP/ONVIF 로 받는 카메라 번호와 주소
+    pub network_cameras: Vec<NetworkCamera>,
+    // 카메라 대신 ffmpeg 가 그리는 테스트 패턴으로 녹화할 카메라 번호 (하드웨어 없이 시험할 때)
+    pub test_patterns: Vec<u32>,
+    // libcamera 카메라 대신 ffmpeg 가 v4l2/dshow/avfoundation 으로 여는 카메라 장치 (USB 웹캠 등)
+    pub device_cameras: Vec<DeviceCamera>,
+    // 카메라별 노출/게인/화이트 밸런스 등. 녹화를 시작할 때 /cameras/:id/settings 의 값으로 채운다.
+    pub camera_settings: Vec<CameraSettings>,
+    // 카메라별로 검게 칠하거나 흐리게 가릴 영역. 녹화를 시작할 때 /cameras/:id/masks 의 값으로 채운다.
+    pub masks: Vec<CameraMasks>,
+    // 카메라별 렌즈 왜곡 보정 (OpenCV 보정 파일). 설정 파일에서만 정한다.
+    pub lens_corrections: Vec<LensCorrection>,
+    // 카메라별 시각/이름/프레임 번호 표시 (없으면 그리지 않음)
+    pub overlays: Vec<OverlayConfig>,
+    pub reconnect: ReconnectConfig,
+    // true 면 프레임마다 찍힌 시각으로 카메라 FPS 의 칸에 놓아, 평균 FPS 로 늘이거나 줄이지 않고
+    // 실제 시간에 맞춘다. 빠진 칸은 앞 프레임을 반복하고, 다시 인코딩하는 합성본에서는 남는
+    // 프레임을 버린다. 분할 녹화와 reconnect.placeholder 와는 함께 쓸 수 없다.
+    pub constant_frame_rate: bool,
+    // 합성본만 남길지, 카메라별 원본 파일도 남길지
+    pub output_mode: OutputMode,
+    // true 면 프레임마다 찍힌 시각을 녹화 파일 옆 .frames.csv 에 남긴다.
+    // 화면에 새기려면 overlays 의 milliseconds 를 켠다.
+    pub frame_timestamps: bool,
+    // 두 카메라로 만든 시차(깊이) 지도를 따로 남기거나 합성본에 넣는다.
+    pub depth: DepthConfig,
+    // 합성이나 오버레이처럼 다시 인코딩해야 할 때 쓰는 인코더 (원본 remux 에는 쓰지 않음)
+    pub encoder: Encoder,
+    // 다시 인코딩한 영상을 쓰는 방식 (ffmpeg 또는 GStreamer)과 비트레이트
+    pub sink: SinkConfig,
+    // interval_secs 마다 한 프레임만 남겨 빠르게 재생되는 영상으로 마무리한다.
+    pub timelapse: TimelapseConfig,
+    // 높은 FPS 로 촬영해 느리게 재생되는 영상으로 마무리한다.
+    pub slow_motion: SlowMotionConfig,
+    // 완성본의 모든 프레임에 겹치는 PNG 로고
+    pub watermark: WatermarkConfig,
+    // 합성본에서 카메라끼리 밝기와 색을 맞춘다.
+    pub color_match: ColorMatchConfig,
+    // 마무리할 때 YOLO 로 물체를 찾아 .detections.jsonl 에 남기고, 원하면 상자를 그린다.
+    pub detection: DetectionConfig,
+    // 녹화 중에 남긴 표시로 완성본에 챕터를 넣거나 파일을 나눈다.
+    pub chapters: ChapterMode,
+    // 저장 디렉토리 기준 파일 이름 틀 (예: "{date}/{camera_set}_{start_time}"). '/' 로 하위
+    // 디렉토리를 만든다. {date}, {time}, {start_time}, {session_id}, {camera_set} 을 쓸 수 있다.
+    pub filename: String,
+    // 저장 디렉토리 아래에 이 하위 디렉토리 (예: "projectX", "lab/run-3") 를 만들어
+    // 이 녹화의 파일을 모은다. 저장 디렉토리 밖 (절대 경로나 "..") 은 가리킬 수 없다.
+    pub output_dir: Option<String>,
+    // 이 녹화가 속하는 프로젝트 (POST /projects 로 만든 것). output_dir 을 생략하면 프로젝트 이름의
+    // 디렉토리에 저장한다.
+    pub project: Option<String>,
+}
+
+impl Default for RecordingConfig {
+    fn default() -> Self {
+        Self {
+            cameras: vec![0],
+            allow_missing_cameras: false,
+            layout: Layout::default(),
+            grid_columns: None,
+            pip: PipConfig::default(),
+            panorama: PanoramaConfig::default(),
+            width: 1280,
+            height: 720,
+            fps: 24,
+            camera_formats: Vec::new(),
+            network_cameras: Vec::new(),
+            test_patterns: Vec::new(),
+            device_cameras: Vec::new(),
+            camera_settings: Vec::new(),
+            masks: Vec::new(),
+            lens_corrections: Vec::new(),
+            max_duration: None,
+            segment_duration: None,
+            overlays: Vec::new(),
+            reconnect: ReconnectConfig::default(),
+            constant_frame_rate: false,
+            output_mode: OutputMode::default(),
+            frame_timestamps: false,
+            depth: DepthConfig::default(),
+            encoder: Encoder::default(),
+            sink: SinkConfig::default(),
+            timelapse: TimelapseConfig::default(),
+            slow_motion: SlowMotionConfig::default(),
+            watermark: WatermarkConfig::default(),
+            color_match: ColorMatchConfig::default(),
+            detection: DetectionConfig::default(),
+            chapters: ChapterMode::default(),
+            filename: filename::DEFAULT_TEMPLATE.to_string(),
+            output_dir: None,
+            project: None,
+        }
+    }
+}
 
-const SAVE_DIR_BASE: &str = "~/Desktop/recordings"; // 경로 확인 필요
+impl RecordingConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.cameras.is_empty() {
+            bail!("at least one camera is required");
+        }
+        let mut unique = self.cameras.clone();
+        unique.sort_unstable();
+        unique.dedup();
+        if unique.len() != self.cameras.len() {
+            bail!("cameras must not contain duplicates");
+        }
+        if self.grid_columns == Some(0) {
+            bail!("grid_columns must be non-zero");
+        }
+        self.pip.validate()?;
+        if self.layout == Layout::Panorama {
+            if self.cameras.len() != 2 {
+                bail!("layout = \"panorama\" requires exactly two cameras");
+            }
+            self.panorama.validate(self.width, self.height)?;
+        }
+        if self.width == 0 || self.height == 0 {
+            bail!("width and height must be non-zero");
+        }
+        if self.fps == 0 {
+            bail!("fps must be non-zero");
+        }
+        if self.max_duration == Some(0) {
+            bail!("max_duration must be non-zero");
+        }
+        if self.segment_duration == Some(0) {
+            bail!("segment_duration must be non-zero");
+        }
+        for (i, format) in self.camera_formats.iter().enumerate() {
+            if format.width == Some(0) || format.height == Some(0) || format.fps == Some(0) {
+                bail!(
+                    "camera {} width, height and fps must be non-zero",
+                    format.camera
+                );
+            }
+            if self.camera_formats[..i]
+                .iter()
+                .any(|other| other.camera == format.camera)
+            {
+                bail!("camera {} has more than one format", format.camera);
+            }
+        }
+        for (i, settings) in self.camera_settings.iter().enumerate() {
+            settings.validate()?;
+            if self.camera_settings[..i]
+                .iter()
+                .any(|other| other.camera == settings.camera)
+            {
+                bail!(
+                    "camera {} has more than one settings entry",
+                    settings.camera
+                );
+            }
+        }
+        for (i, masks) in self.masks.iter().enumerate() {
+            masks.validate()?;
+            if self.masks[..i]
+                .iter()
+                .any(|other| other.camera == masks.camera)
+            {
+                bail!("camera {} has more than one masks entry", masks.camera);
+            }
+        }
+        for (i, lens) in self.lens_corrections.iter().enumerate() {
+            lens.validate()?;
+            if self.lens_corrections[..i]
+                .iter()
+                .any(|other| other.camera == lens.camera)
+            {
+                bail!(
+                    "camera {} has more than one lens correction entry",
+                    lens.camera
+                );
+            }
+        }
+        for (i, network) in self.network_cameras.iter().enumerate() {
+            network.validate()?;
+            if self.network_cameras[..i]
+                .iter()
+                .any(|other| other.camera == network.camera)
+            {
+                bail!("camera {} has more than one network source", network.camera);
+            }
+        }
+        for (i, &camera) in self.test_patterns.iter().enumerate() {
+            if self.test_patterns[..i].contains(&camera) || self.network_camera(camera).is_some() {
+                bail!("camera {} has more than one source", camera);
+            }
+        }
+        for (i, device) in self.device_cameras.iter().enumerate() {
+            device.validate()?;
+            if self.device_cameras[..i]
+                .iter()
+                .any(|other| other.camera == device.camera)
+                || self.network_camera(device.camera).is_some()
+                || self.test_patterns.contains(&device.camera)
+            {
+                bail!("camera {} has more than one source", device.camera);
+            }
+        }
+        self.reconnect.validate()?;
+        self.sink.validate()?;
+        self.timelapse.validate(self.fps)?;
+        self.slow_motion.validate(self.fps)?;
+        self.watermark.validate()?;
+        self.color_match.validate(&self.cameras)?;
+        self.detection.validate()?;
+        if self.timelapse.enabled && self.slow_motion.enabled {
+            bail!("timelapse and slow_motion cannot both be enabled");
+        }
+        // 표시 시각은 촬영 시간 기준이라 재생 속도가 다른 영상에는 맞지 않는다.
+        if self.chapters != ChapterMode::None
+            && (self.timelapse.enabled || self.slow_motion.enabled)
+        {
+            bail!("chapters cannot be combined with timelapse or slow_motion");
+        }
+        filename::validate(&self.filename)?;
+        if let Some(output_dir) = &self.output_dir {
+            filename::validate_output_dir(output_dir)?;
+        }
+        if let Some(project) = &self.project {
+            projects::validate_name(project)?;
+        }
+        if self.constant_frame_rate && self.segment_duration.is_some() {
+            bail!("constant_frame_rate cannot be combined with segment_duration");
+        }
+        if self.constant_frame_rate && self.reconnect.enabled && self.reconnect.placeholder {
+            bail!("constant_frame_rate cannot be combined with reconnect.placeholder");
+        }
+        for (i, overlay) in self.overlays.iter().enumerate() {
+            overlay.validate()?;
+            if self.overlays[..i]
+                .iter()
+                .any(|other| other.camera == overlay.camera)
+            {
+                bail!("camera {} has more than one overlay", overlay.camera);
+            }
+        }
+        self.depth.validate(self.layout)?;
+        if self.depth.enabled
+            && self.depth.output == DepthOutput::Panel
+            && self.output_mode == OutputMode::Separate
+        {
+            bail!("depth.output = \"panel\" requires a composite output_mode");
+        }
+        Ok(())
+    }
 
-pub fn run_recording_blocking(stop_requested: Arc<AtomicBool>) -> Result<PathBuf> {
-    let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S").to_string();
+    pub fn overlay_for(&self, camera: u32) -> Option<&OverlayConfig> {
+        self.overlays
+            .iter()
+            .find(|overlay| overlay.camera == camera)
+    }
 
-    // SAVE_DIR_BASE 경로 처리 (홈 디렉토리 '~' 확장)
-    let save_dir_base_str = shellexpand::tilde(SAVE_DIR_BASE).into_owned();
-    let save_dir = PathBuf::from(save_dir_base_str);
-    if !save_dir.exists() {
-        println!("Save directory {:?} does not exist. Creating it.", save_dir);
-        fs::create_dir_all(&save_dir)
-            .with_context(|| format!("Failed to create save directory: {:?}", save_dir))?;
+    // camera_formats 에서 생략한 값은 기본 width/height/fps. crop 이 있으면 그만큼 줄인 크기
+    pub fn format_for(&self, camera: u32) -> CaptureFormat {
+        let format = self
+            .camera_formats
+            .iter()
+            .find(|format| format.camera == camera);
+        let width = format.and_then(|f| f.width).unwrap_or(self.width);
+        let height = format.and_then(|f| f.height).unwrap_or(self.height);
+        let (width, height) = match self.crop_for(camera) {
+            Some(crop) => crop.fit(width, height),
+            None => (width, height),
+        };
+        CaptureFormat {
+            width,
+            height,
+            fps: format.and_then(|f| f.fps).unwrap_or(self.fps),
+        }
     }
 
-    let final_filename = format!("{}.mp4", timestamp);
-    let final_path = save_dir.join(&final_filename);
+    // 센서에서 잘라낼 부분 (libcamera 가 아닌 카메라는 camera_settings 를 쓰지 않는다)
+    pub fn crop_for(&self, camera: u32) -> Option<CropRegion> {
+        if !self.is_local(camera) {
+            return None;
+        }
+        self.settings_for(camera).and_then(|settings| settings.crop)
+    }
+
+    // 분할 녹화는 세그먼트 번호를 카메라끼리 맞춰야 하므로 재연결하지 않는다.
+    pub fn can_reconnect(&self) -> bool {
+        self.reconnect.enabled && self.segment_duration.is_none()
+    }
+
+    pub fn network_camera(&self, camera: u32) -> Option<&NetworkCamera> {
+        self.network_cameras
+            .iter()
+            .find(|network| network.camera == camera)
+    }
 
-    println!("Starting libcamera recording...");
+    pub fn device_camera(&self, camera: u32) -> Option<&DeviceCamera> {
+        self.device_cameras
+            .iter()
+            .find(|device| device.camera == camera)
+    }
+
+    // 카메라 번호를 여는 방법. network_cameras, test_patterns, device_cameras 에 없으면 libcamera
+    pub fn source(&self, camera: u32) -> Box<dyn CameraSource> {
+        if let Some(network) = self.network_camera(camera) {
+            return Box::new(network.clone());
+        }
+        if self.test_patterns.contains(&camera) {
+            return Box::new(TestPattern { camera });
+        }
+        if let Some(device) = self.device_camera(camera) {
+            return Box::new(device.clone());
+        }
+        Box::new(LibCamera { index: camera })
+    }
+
+    pub fn is_local(&self, camera: u32) -> bool {
+        self.source(camera).is_local()
+    }
+
+    // 카메라 원래 해상도에서 가릴 영역을 칠하는 필터 (없으면 None)
+    pub fn mask_filter(&self, camera: u32) -> Option<String> {
+        self.masks
+            .iter()
+            .find(|masks| masks.camera == camera)
+            .and_then(CameraMasks::filter)
+    }
+
+    // 센서에서 할 수 없는 90/270 도 회전 (뒤집기와 180 도는 libcamera-vid 가 한다)
+    pub fn rotate_filter(&self, camera: u32) -> Option<String> {
+        if !self.is_local(camera) {
+            return None;
+        }
+        self.settings_for(camera)
+            .and_then(CameraSettings::rotate_filter)
+            .map(String::from)
+    }
+
+    // 촬영한 해상도 그대로 렌즈 왜곡을 펴는 필터
+    pub fn lens_filter(&self, camera: u32) -> Option<String> {
+        let format = self.format_for(camera);
+        self.lens_corrections
+            .iter()
+            .find(|lens| lens.camera == camera)
+            .and_then(|lens| lens.filter(format.width, format.height))
+    }
+
+    // 카메라 스트림에 가장 먼저 거는 필터: 렌즈 왜곡을 펴고 돌린 뒤 가림 영역을 칠한다
+    // (가림 영역은 돌린 화면 기준).
+    pub fn camera_filter(&self, camera: u32) -> Option<String> {
+        let chain: Vec<String> = [
+            self.lens_filter(camera),
+            self.rotate_filter(camera),
+            self.mask_filter(camera),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        (!chain.is_empty()).then(|| chain.join(","))
+    }
+
+    // camera_filter 를 거친 프레임 크기 (90/270 도 회전이면 가로세로가 바뀐다)
+    pub fn frame_size(&self, camera: u32) -> (u32, u32) {
+        let format = self.format_for(camera);
+        match self.rotate_filter(camera) {
+            Some(_) => (format.height, format.width),
+            None => (format.width, format.height),
+        }
+    }
+
+    fn settings_for(&self, camera: u32) -> Option<&CameraSettings> {
+        self.camera_settings
+            .iter()
+            .find(|settings| settings.camera == camera)
+    }
 
+    // 완성된 녹화 파일의 확장자
+    pub fn extension(&self) -> &'static str {
+        self.sink.container.extension()
+    }
+
+    // 영상 대신 프레임 수만 남기는 녹화 (Backend::Null). 카메라의 원본 스트림을 지우지 않고,
+    // 마무리한 파일 대신 <출력>.frames.json 을 돌려준다.
+    pub fn discards_video(&self) -> bool {
+        self.sink.backend == Backend::Null
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        Encoding {
+            encoder: self.encoder,
+            sink: self.sink.clone(),
+        }
+    }
+
+    // Layout::Switch 의 전환 목록은 녹화를 마무리할 때 채운다.
+    pub fn arrangement(&self) -> Arrangement {
+        Arrangement {
+            layout: self.layout,
+            grid_columns: self.grid_columns,
+            width: self.width,
+            height: self.height,
+            pip: self.pip.clone(),
+            panorama: self.panorama.clone(),
+            switches: Vec::new(),
+            output_filter: self
+                .timelapse
+                .filter()
+                .or_else(|| self.slow_motion.filter(self.fps)),
+            watermark: self.watermark.clone(),
+        }
+    }
+
+    // 완성본의 FPS. 타임랩스와 슬로 모션은 촬영 FPS 가 아니라 재생 FPS 로 쓴다.
+    pub fn output_fps(&self) -> u32 {
+        if self.timelapse.enabled {
+            self.timelapse.playback_fps
+        } else if self.slow_motion.enabled {
+            self.slow_motion.playback_fps
+        } else {
+            self.fps
+        }
+    }
+
+    // 타임랩스나 슬로 모션이면 완성본의 재생 FPS
+    pub fn playback_fps(&self) -> Option<u32> {
+        (self.timelapse.enabled || self.slow_motion.enabled).then(|| self.output_fps())
+    }
+
+    // 기본 해상도와 다르게 촬영하는 카메라를 합성 배치에 맞추는 필터 (같으면 None)
+    pub fn fit_filter(&self, camera: u32) -> Option<String> {
+        (self.frame_size(camera) != (self.width, self.height))
+            .then(|| compositor::fit_filter(self.layout, self.width, self.height))
+    }
+}
+
+// 여러 카메라를 녹화할 때 남기는 파일. 카메라 한 대면 어느 쪽이든 파일 하나
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputMode {
+    // layout 으로 합성한 파일 하나
+    #[default]
+    Composite,
+    // 카메라마다 원본 스트림을 그대로 담은 <시각>_cam<N>.mp4 (합성하지 않음)
+    Separate,
+    // 합성본과 카메라별 파일 모두
+    Both,
+}
+
+// 카메라 한 대의 해상도/FPS 설정 (생략한 값은 기본값)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CameraFormat {
+    pub camera: u32,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub fps: Option<u32>,
+}
+
+// 카메라 한 대가 실제로 촬영하는 형식
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptureFormat {
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+}
+
+// 녹화 루프가 주기적으로 갱신하는 실시간 통계 (/status 에서 사용)
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct RecordingStats {
+    pub cameras: Vec<u32>,
+    pub elapsed_seconds: f64,
+    // max_duration 이 설정된 경우 자동 종료까지 남은 시간
+    pub remaining_seconds: Option<f64>,
+    pub frames_captured: u64,
+    // 모든 카메라에서 빠진 것으로 추정되는 프레임 수
+    pub dropped_frames: u64,
+    // constant_frame_rate 에서 빠진 칸을 채우려고 반복하는 프레임 수
+    pub duplicated_frames: u64,
+    pub output_path: Option<PathBuf>,
+    // 카메라별로 지금 쓰고 있는 파일 (분할 녹화면 %04d 패턴)
+    pub camera_outputs: BTreeMap<u32, PathBuf>,
+    // 녹화가 끝난 뒤 확정된 파일 목록 (분할 녹화 시 세그먼트별)
+    pub segments: Vec<PathBuf>,
+    pub measured_fps: f64,
+    pub paused: bool,
+    // 누적 일시정지 시간 (elapsed_seconds 에는 포함, max_duration 에는 미포함)
+    pub paused_seconds: f64,
+    // 저장 디렉토리가 있는 파일 시스템의 남은 공간
+    pub free_space_bytes: Option<u64>,
+    // 카메라 간 첫 프레임 시각 차이 (합성할 때 이만큼 맞춰 자른다)
+    pub sync_skew_ms: f64,
+    // 끊겨서 다시 연결을 기다리는 카메라
+    pub disconnected_cameras: Vec<u32>,
+    // 녹화 중에만 있는 HLS 재생 목록 주소
+    pub live_playlist: Option<String>,
+    // HLS 로 내보내는 카메라별 쓰기 대기열
+    pub live_queues: BTreeMap<u32, QueueStats>,
+    // Layout::Switch 에서 지금 화면에 나오는 카메라
+    pub active_camera: Option<u32>,
+}
+
+// pts 읽기 스레드가 보낸 타임스탬프로 프레임 수와 실제 FPS, 누락 프레임을 계산한다.
+struct PtsTracker {
+    first_ms: Option<f64>,
+    last_ms: Option<f64>,
+    frames: u64,
+    // 요청한 FPS 기준 프레임 간격
+    expected_interval_ms: f64,
+    dropped: u64,
+    // 일시정지 중에는 프레임이 기록되지 않으므로 그 시간은 FPS 계산에서 뺀다.
+    paused_ms: f64,
+    // 직전 프레임 시점의 paused_ms
+    paused_ms_at_last: f64,
+    // 재연결 후 pts 가 0 부터 다시 시작하므로 이전 구간 끝에 이어지도록 더하는 값
+    offset_ms: f64,
+    // 재연결 직후 첫 프레임은 누락 계산에서 뺀다.
+    restarted: bool,
+    // 고정 FPS 칸: 마지막 프레임이 놓인 칸 번호와, 칸이 빈 곳마다 (프레임 번호, 빈 칸 수)
+    last_slot: Option<u64>,
+    holds: Vec<(u64, u64)>,
+    // 파일 앞에 붙인 녹화 전 영상의 프레임 수 (칸 계산에는 들어가지 않고 번호만 밀린다)
+    prepended: u64,
+    // 프레임마다 카메라가 찍은 시각에서 그때까지 일시정지한 시간을 뺀 값 (파일마다 FPS 를 따로 잴 때)
+    times: Vec<f64>,
+}
+
+impl PtsTracker {
+    fn new(fps: u32) -> Self {
+        Self {
+            first_ms: None,
+            last_ms: None,
+            frames: 0,
+            expected_interval_ms: 1000.0 / fps as f64,
+            dropped: 0,
+            paused_ms: 0.0,
+            paused_ms_at_last: 0.0,
+            offset_ms: 0.0,
+            restarted: false,
+            last_slot: None,
+            holds: Vec::new(),
+            prepended: 0,
+            times: Vec::new(),
+        }
+    }
+
+    fn restart(&mut self) {
+        if let Some(last) = self.last_ms {
+            self.offset_ms = last + self.expected_interval_ms;
+        }
+        self.restarted = true;
+    }
+
+    fn record(&mut self, ms: f64) {
+        let ms = ms + self.offset_ms;
+        // 그 사이 일시정지나 재연결이 있었으면 간격이 벌어진 것이 당연하므로 세지 않는다.
+        let continuous = self.paused_ms == self.paused_ms_at_last && !self.restarted;
+        self.restarted = false;
+        if let Some(last) = self.last_ms.filter(|_| continuous) {
+            self.dropped += dropped_between(last, ms, self.expected_interval_ms);
+        }
+        let first = *self.first_ms.get_or_insert(ms);
+        // 일시정지한 시간은 빼고, 가장 가까운 칸에 놓는다. 이미 찬 칸이면 다음 칸으로 민다.
+        let slot = ((ms - first - self.paused_ms) / self.expected_interval_ms)
+            .round()
+            .max(0.0) as u64;
+        let next = self.last_slot.map_or(0, |last| last + 1);
+        if slot > next {
+            self.holds.push((self.frames, slot - next));
+        }
+        self.last_slot = Some(slot.max(next));
+        self.times.push(ms - self.paused_ms);
+        self.last_ms = Some(ms);
+        self.paused_ms_at_last = self.paused_ms;
+        self.frames += 1;
+    }
+
+    fn duplicated(&self) -> u64 {
+        self.holds.iter().map(|(_, slots)| slots).sum()
+    }
+
+    // 파일 속 프레임 번호 N 을 고정 FPS 칸의 시각(초)으로 바꾸는 식. 칸이 빈 곳부터는 그만큼 더한다.
+    // gte(N, a) 대신 쉼표 없는 (1+sgn(N-a+0.5))/2 를 쓴다.
+    fn retime(&self) -> String {
+        let mut expression = String::from("N");
+        for (frame, slots) in &self.holds {
+            expression.push_str(&format!(
+                "+{}*(1+sgn(N-{}.5))/2",
+                slots,
+                frame + self.prepended
+            ));
+        }
+        format!("({})*{}/1000", expression, self.expected_interval_ms)
+    }
+
+    // 첫 프레임부터 마지막 프레임까지 녹화한 시간 (일시정지 제외)
+    fn span_ms(&self) -> f64 {
+        match (self.first_ms, self.last_ms) {
+            (Some(first), Some(last)) => last - first - self.paused_ms,
+            _ => 0.0,
+        }
+    }
+
+    fn measured_fps(&self) -> f64 {
+        match (self.first_ms, self.last_ms) {
+            (Some(first), Some(last)) if self.frames > 1 && last - first > self.paused_ms => {
+                (self.frames - 1) as f64 * 1000.0 / (last - first - self.paused_ms)
+            }
+            _ => 0.0,
+        }
+    }
+
+    // from 번째부터 count 개 프레임의 실제 FPS. 다음 프레임이 있으면 그 시각까지를 길이로 보아,
+    // 카메라가 프레임을 몰아서 보내거나 빠뜨려도 파일 길이가 찍힌 시간과 맞는다.
+    fn measured_fps_of(&self, from: usize, count: usize) -> Option<f64> {
+        let first = *self.times.get(from)?;
+        let (frames, last) = match self.times.get(from + count) {
+            Some(&next) => (count, next),
+            None => (self.times.len() - from - 1, *self.times.last()?),
+        };
+        (frames > 0 && last > first).then(|| frames as f64 * 1000.0 / (last - first))
+    }
+}
+
+// 두 프레임 사이에 빠진 것으로 보이는 프레임 수
+fn dropped_between(last_ms: f64, ms: f64, interval_ms: f64) -> u64 {
+    let gap = ms - last_ms;
+    if gap > interval_ms * FRAME_DROP_GAP_FACTOR {
+        ((gap / interval_ms).round() as u64).saturating_sub(1)
+    } else {
+        0
+    }
+}
+
+// 녹화 시작 전 남은 공간을 확인하고, 부족하면 녹화를 시작하지 않는다.
+fn check_free_space(save_dir: &Path, min_free_bytes: u64) -> Result<u64> {
+    let free = fs4::available_space(save_dir)
+        .with_context(|| format!("Failed to check free space of {:?}", save_dir))?;
+    if free < min_free_bytes {
+        bail!(
+            "Not enough free disk space in {:?}: {} MB available, {} MB required",
+            save_dir,
+            free / 1024 / 1024,
+            min_free_bytes / 1024 / 1024
+        );
+    }
+    Ok(free)
+}
+
+// libcamera-hello --list-cameras 의 출력
+//   0 : imx219 [3280x2464] (/base/soc/i2c0mux/i2c@1/imx219@10)
+//       Modes: 'SRGGB10_CSI2P' : 640x480 [103.33 fps - (1000, 752)/1280x960 crop]
+//                                1640x1232 [41.85 fps - (0, 0)/3280x2464 crop]
+fn camera_listing() -> Result<String> {
+    let output = Command::new("libcamera-hello")
+        .arg("--list-cameras")
+        .output()
+        .context("Failed to run libcamera-hello --list-cameras")?;
+    if !output.status.success() {
+        bail!("libcamera-hello --list-cameras failed: {}", output.status);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// 카메라 줄이면 그 번호
+fn listed_camera(line: &str) -> Option<u32> {
+    let (index, _) = line.trim().split_once(" : ")?;
+    index.trim().parse().ok()
+}
+
+// 연결된 카메라 인덱스 목록
+pub fn list_cameras() -> Result<Vec<u32>> {
+    Ok(camera_listing()?
+        .lines()
+        .filter_map(listed_camera)
+        .collect())
+}
+
+// 붙어 있는 카메라마다 센서의 최대 해상도 (목록에 나오지 않으면 None)
+pub fn sensor_sizes() -> Result<BTreeMap<u32, Option<(u32, u32)>>> {
+    Ok(camera_listing()?
+        .lines()
+        .filter_map(|line| {
+            let camera = listed_camera(line)?;
+            let size = line
+                .split_once('[')
+                .and_then(|(_, rest)| rest.split_once(']'))
+                // 새 libcamera 는 "[3280x2464 10-bit RGGB]" 처럼 비트 수와 순서를 덧붙인다.
+                .and_then(|(size, _)| size.split_whitespace().next()?.split_once('x'))
+                .and_then(|(width, height)| {
+                    Some((width.trim().parse().ok()?, height.trim().parse().ok()?))
+                });
+            Some((camera, size))
+        })
+        .collect())
+}
+
+// 카메라별로 센서 모드 중 가장 높은 FPS
+fn max_sensor_fps() -> Result<BTreeMap<u32, f64>> {
+    let listing = camera_listing()?;
+    let mut max_fps = BTreeMap::new();
+    let mut camera = None;
+    for line in listing.lines() {
+        if let Some(index) = listed_camera(line) {
+            camera = Some(index);
+        }
+        let Some(camera) = camera else {
+            continue;
+        };
+        for (_, rest) in line.match_indices('[').map(|(at, _)| line.split_at(at + 1)) {
+            if let Some(fps) = rest
+                .split_once(" fps")
+                .and_then(|(fps, _)| fps.trim().parse::<f64>().ok())
+            {
+                let max = max_fps.entry(camera).or_insert(0.0);
+                *max = f64::max(*max, fps);
+            }
+        }
+    }
+    Ok(max_fps)
+}
+
+// 슬로 모션: 요청한 FPS 를 낼 수 있는 센서 모드가 없는 카메라로는 시작하지 않는다.
+// 목록을 읽지 못하거나 모드가 나오지 않으면 녹화 중 측정으로만 확인한다.
+pub fn check_sensor_modes(config: &RecordingConfig, cameras: &[u32]) -> Result<()> {
+    let max_fps = match max_sensor_fps() {
+        Ok(max_fps) => max_fps,
+        Err(e) => {
+            warn!(
+                "{:#}. Checking the slow-motion frame rate while recording.",
+                e
+            );
+            return Ok(());
+        }
+    };
+    for &camera in cameras {
+        if !config.is_local(camera) {
+            continue;
+        }
+        let requested = config.format_for(camera).fps;
+        if let Some(&max) = max_fps.get(&camera)
+            && max < requested as f64 * config.slow_motion.min_rate_ratio
+        {
+            bail!(
+                "camera {} reaches at most {:.1} fps in any sensor mode; {} fps was requested",
+                camera,
+                max,
+                requested
+            );
+        }
+    }
+    Ok(())
+}
+
+pub fn resolve_cameras(config: &RecordingConfig) -> Result<Vec<u32>> {
+    if config.cameras.is_empty() {
+        bail!("No cameras requested");
+    }
+    if !config.allow_missing_cameras {
+        return Ok(config.cameras.clone());
+    }
+
+    // 네트워크 카메라는 붙어 있는지 미리 알 수 없으므로 열어 본다 (끊기면 재연결이 맡는다).
+    let local = config.cameras.iter().any(|&index| config.is_local(index));
+    let available = if local { list_cameras()? } else { Vec::new() };
+    let (present, missing): (Vec<u32>, Vec<u32>) = config
+        .cameras
+        .iter()
+        .partition(|&&index| available.contains(&index) || !config.is_local(index));
+    if present.is_empty() {
+        bail!(
+            "None of the requested cameras {:?} are attached (available: {:?})",
+            config.cameras,
+            available
+        );
+    }
+    if !missing.is_empty() {
+        warn!(
+            "Cameras {:?} are not attached. Recording from {:?} only.",
+            missing, present
+        );
+    }
+    Ok(present)
+}
+
+// 카메라 한 대에 대응하는 libcamera-vid 프로세스
+struct CameraProcess {
+    index: u32,
+    // None 이면 카메라가 끊겨 다시 연결을 기다리는 중
+    child: Option<Child>,
+    // 지금 쓰고 있는 구간의 파일
+    output: PathBuf,
+    pts_path: PathBuf,
+    // 재연결 전에 끝난 구간들
+    parts: Vec<Part>,
+    outage: Option<Outage>,
+    last_frame_at: Instant,
+    pts: PtsTracker,
+    // 마지막으로 이벤트로 알린 누락 프레임 수
+    reported_dropped: u64,
+    // 슬로 모션: 실제 FPS 를 확인했는지
+    rate_checked: bool,
+}
+
+struct Outage {
+    since: Instant,
+    attempts: u32,
+    next_attempt: Instant,
+}
+
+// warm 에 같은 설정으로 열어 둔 카메라가 있으면 새로 띄우지 않고 그 프로세스를 쓴다.
+fn spawn_camera(
+    index: u32,
+    config: &RecordingConfig,
+    output: &Path,
+    pts_path: &Path,
+    warm: &WarmCameras,
+) -> Result<CameraProcess> {
+    let child = match warm.take(index, config, output, pts_path) {
+        Some(child) => child,
+        None => config.source(index).spawn(config, output, pts_path)?,
+    };
+    Ok(CameraProcess {
+        index,
+        child: Some(child),
+        output: output.to_path_buf(),
+        pts_path: pts_path.to_path_buf(),
+        parts: Vec::new(),
+        outage: None,
+        last_frame_at: Instant::now(),
+        pts: PtsTracker::new(config.format_for(index).fps),
+        reported_dropped: 0,
+        rate_checked: false,
+    })
+}
+
+// 라즈베리 파이에 붙은 카메라 (libcamera-vid)
+struct LibCamera {
+    index: u32,
+}
+
+impl CameraSource for LibCamera {
+    fn spawn(&self, config: &RecordingConfig, output: &Path, pts_path: &Path) -> Result<Child> {
+        spawn_libcamera(self.index, config, output, pts_path)
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+
+    fn backend(&self) -> &'static str {
+        "libcamera"
+    }
+}
+
+fn spawn_libcamera(
+    index: u32,
+    config: &RecordingConfig,
+    output: &Path,
+    pts_path: &Path,
+) -> Result<Child> {
     // libcamera-vid 명령어 실행
-    let mut child = Command::new("libcamera-vid")
-        .arg("--width")
-        .arg("1280")
-        .arg("--height")
-        .arg("720")
-        .arg("--framerate")
-        .arg("24")
+    Command::new("libcamera-vid")
+        .args(libcamera_args(index, config))
+        .arg("--save-pts")
+        .arg(pts_path.to_str().context("Invalid pts path")?)
         .arg("--output")
-        .arg(final_path.to_str().context("Invalid output path")?)
+        .arg(output.to_str().context("Invalid output path")?)
         .spawn()
-        .context("Failed to start libcamera-vid")?;
+        .with_context(|| format!("Failed to start libcamera-vid for camera {}", index))
+}
+
+// 출력 경로를 뺀 libcamera-vid 인자 (keep_alive 는 이것이 같으면 열어 둔 카메라를 넘긴다)
+pub fn libcamera_args(index: u32, config: &RecordingConfig) -> Vec<String> {
+    let format = config.format_for(index);
+    let mut args = Vec::new();
+    if let Some(segment) = config.segment_duration {
+        args.extend(["--segment".to_string(), (segment * 1000).to_string()]);
+    }
+    // --signal: SIGUSR1 로 녹화/일시정지 전환, SIGINT 로 정상 종료
+    // --inline/--intra: 1초마다 헤더가 붙은 I 프레임을 넣어 세그먼트 분할과
+    // 녹화 중 스냅샷(파일 끝부분만 디코딩)이 가능하게 한다.
+    args.extend([
+        "--signal".to_string(),
+        "--inline".to_string(),
+        "--intra".to_string(),
+        format.fps.to_string(),
+        "--camera".to_string(),
+        index.to_string(),
+        "--width".to_string(),
+        format.width.to_string(),
+        "--height".to_string(),
+        format.height.to_string(),
+        "--framerate".to_string(),
+        format.fps.to_string(),
+    ]);
+    args.extend(
+        config
+            .settings_for(index)
+            .map(CameraSettings::args)
+            .unwrap_or_default(),
+    );
+    // --timeout 0 은 종료 신호를 받을 때까지 녹화 (기본값은 5초).
+    // 최대 녹화 시간은 녹화 루프에서 직접 확인한다.
+    args.extend(["--timeout".to_string(), "0".to_string()]);
+    args
+}
+
+// SIGINT 를 보내 libcamera-vid 가 파일을 정상적으로 닫게 한다.
+#[cfg(unix)]
+fn request_exit(child: &Child) {
+    // SAFETY: kill(2) 은 메모리를 건드리지 않으며 잘못된 pid 는 오류만 반환한다.
+    unsafe {
+        libc::kill(child.id() as libc::pid_t, libc::SIGINT);
+    }
+}
+
+#[cfg(not(unix))]
+fn request_exit(_child: &Child) {}
+
+// --signal 모드의 libcamera-vid 는 SIGUSR1 을 받을 때마다 녹화/일시정지를 전환한다.
+#[cfg(unix)]
+pub fn toggle_pause(child: &Child) -> Result<()> {
+    // SAFETY: kill(2) 은 메모리를 건드리지 않으며 잘못된 pid 는 오류만 반환한다.
+    let result = unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGUSR1) };
+    if result != 0 {
+        bail!(
+            "Failed to signal libcamera-vid: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn toggle_pause(_child: &Child) -> Result<()> {
+    bail!("Pausing is only supported on Unix")
+}
+
+// 일시정지 구간을 누적해 실제 녹화 시간을 계산한다.
+#[derive(Default)]
+struct PauseClock {
+    paused_since: Option<Instant>,
+    total: Duration,
+}
+
+impl PauseClock {
+    fn is_paused(&self) -> bool {
+        self.paused_since.is_some()
+    }
 
-    // Stop 요청을 대기
-    while !stop_requested.load(Ordering::SeqCst) {
-        thread::sleep(Duration::from_millis(100));
+    fn pause(&mut self) {
+        self.paused_since.get_or_insert_with(Instant::now);
     }
 
-    println!("Stop signal received. Terminating libcamera-vid...");
+    fn resume(&mut self) {
+        if let Some(since) = self.paused_since.take() {
+            self.total += since.elapsed();
+        }
+    }
+
+    fn paused_total(&self) -> Duration {
+        self.total
+            + self
+                .paused_since
+                .map_or(Duration::ZERO, |since| since.elapsed())
+    }
+}
+
+// grace 안에 끝나지 않으면 죽인다 (0 이면 바로).
+fn stop_all(processes: &mut [CameraProcess], grace: Duration) {
+    for process in processes.iter_mut() {
+        if let Some(child) = &process.child {
+            request_exit(child);
+        }
+    }
+
+    let deadline = Instant::now() + grace;
+    for process in processes.iter_mut() {
+        if let Some(child) = process.child.as_mut() {
+            wait_for_exit(process.index, child, deadline);
+        }
+    }
+}
+
+fn wait_for_exit(index: u32, child: &mut Child, deadline: Instant) {
+    loop {
+        match child.try_wait() {
+            Ok(Some(_)) => break,
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(50)),
+            Ok(None) => {
+                warn!(
+                    "libcamera-vid (camera {}) did not exit in time. Killing it.",
+                    index
+                );
+                // 이미 종료된 프로세스에 대한 kill 실패는 무시
+                let _ = child.kill();
+                if let Err(e) = child.wait() {
+                    error!(
+                        "Failed to wait for libcamera-vid (camera {}) to exit: {}",
+                        index, e
+                    );
+                }
+                break;
+            }
+            Err(e) => {
+                error!(
+                    "Failed to wait for libcamera-vid (camera {}) to exit: {}",
+                    index, e
+                );
+                break;
+            }
+        }
+    }
+}
+
+pub fn run_recording_blocking(
+    server_config: Arc<Config>,
+    session: Arc<RecordingSession>,
+    events: Arc<EventBus>,
+    pre_roll: BTreeMap<u32, Clip>,
+    warm: &WarmCameras,
+) -> Result<Vec<PathBuf>> {
+    let config = &session.config;
+    let stats = &session.stats;
+    let session_start = chrono::Local::now();
+
+    let save_dir = server_config.save_dir();
+    // staging_dir 이 있으면 녹화와 마무리는 그 안에서 하고, 끝난 파일만 save_dir 로 옮긴다.
+    let staging_dir = server_config.staging_dir();
+    let work_dir = staging_dir.clone().unwrap_or_else(|| save_dir.clone());
+    for dir in [&save_dir, &work_dir] {
+        if !dir.exists() {
+            info!("Save directory {:?} does not exist. Creating it.", dir);
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create save directory: {:?}", dir))?;
+        }
+    }
+
+    let min_free_bytes = server_config.min_free_space_mb * 1024 * 1024;
+    if staging_dir.is_some() {
+        check_free_space(&save_dir, min_free_bytes)?;
+    }
+    let free_bytes = check_free_space(&work_dir, min_free_bytes)?;
+
+    let cameras = resolve_cameras(config)?;
+    if config.slow_motion.enabled {
+        check_sensor_modes(config, &cameras)?;
+    }
+    // 저장 디렉토리 기준 상대 경로 (확장자 없음). 카메라별 임시 파일도 같은 디렉토리에 둔다.
+    let names = FileNames::new(&session, &cameras);
+    let timestamp = names.stem(session_start);
+    let final_path = work_dir.join(format!("{}.{}", timestamp, config.extension()));
+    create_parent(&final_path)?;
+
+    // 서버가 녹화 중에 죽으면 다음 시작 때 이 표시를 보고 남은 스트림을 살린다.
+    let marker = Marker::new(&session, &cameras, session_start);
+    if let Err(e) = recovery::begin(&final_path, &marker) {
+        warn!("{:#}. This recording cannot be recovered after a crash.", e);
+    }
+
+    *stats.lock().unwrap() = RecordingStats {
+        cameras: cameras.clone(),
+        output_path: Some(final_path.clone()),
+        free_space_bytes: Some(free_bytes),
+        active_camera: (config.layout == Layout::Switch).then_some(cameras[0]),
+        ..Default::default()
+    };
+
+    info!("Starting libcamera recording from cameras {:?}...", cameras);
+
+    // 카메라별 임시 H.264 파일에 쓴 뒤 마무리 단계에서 MP4 로 옮기거나 합성
+    let segmented = config.segment_duration.is_some();
+    let mut processes = Vec::with_capacity(cameras.len());
+    for &index in &cameras {
+        let (output, pts_path) = if segmented {
+            (
+                work_dir.join(format!("{}_cam{}_%04d.h264", timestamp, index)),
+                work_dir.join(format!("{}_cam{}.pts", timestamp, index)),
+            )
+        } else {
+            (
+                work_dir.join(format!("{}_cam{}.h264", timestamp, index)),
+                work_dir.join(format!("{}_cam{}.pts", timestamp, index)),
+            )
+        };
+        match spawn_camera(index, config, &output, &pts_path, warm) {
+            Ok(process) => processes.push(process),
+            Err(e) => {
+                stop_all(&mut processes, STOP_GRACE_PERIOD);
+                return Err(e);
+            }
+        }
+    }
+
+    stats.lock().unwrap().camera_outputs = processes
+        .iter()
+        .map(|process| (process.index, process.output.clone()))
+        .collect();
+
+    // 카메라마다 스레드 하나가 pts 파일을 읽어 프레임 도착을 알려 준다.
+    let (sender, receiver) = frame_sync::channel();
+    let frame_log = if config.frame_timestamps {
+        match FrameLog::create(frame_log::sidecar_path(&final_path), Instant::now()) {
+            Ok(log) => Some(Arc::new(log)),
+            Err(e) => {
+                warn!("{:#}. Recording without frame timestamps.", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let mut readers = PtsReaders::new(frame_log.clone());
+    for process in &processes {
+        if let Err(e) = readers.spawn(process.index, process.pts_path.clone(), sender.clone()) {
+            stop_all(&mut processes, STOP_GRACE_PERIOD);
+            return Err(e);
+        }
+    }
+    let mut sync = FrameSync::default();
+    for process in &processes {
+        if let Some(network) = config.network_camera(process.index) {
+            sync.set_latency(process.index, Duration::from_millis(network.latency_ms));
+        }
+    }
+
+    let mut live = None;
+    if server_config.live.enabled {
+        let sources: Vec<(u32, PathBuf)> = processes
+            .iter()
+            .map(|process| (process.index, process.output.clone()))
+            .collect();
+        match LiveStream::start(
+            session.id,
+            &server_config.live,
+            &server_config.write_queue,
+            config,
+            &sources,
+        ) {
+            Ok(stream) => {
+                stats.lock().unwrap().live_playlist = Some(live::playlist_url(session.id));
+                live = Some(stream);
+            }
+            Err(e) => warn!("{:#}. Recording without a live stream.", e),
+        }
+    }
+
+    let reconnect_enabled = config.can_reconnect();
+    // 녹화 중 화질 설정이 바뀌면 이 사본을 고쳐 해당 카메라를 다시 연다.
+    let mut live_config = config.clone();
+    let stall_timeout = Duration::from_secs(config.reconnect.stall_timeout_secs);
+
+    let started = Instant::now();
+    let max_duration = config.max_duration.map(Duration::from_secs);
+    let mut last_space_check = Instant::now();
+    let mut last_drop_report = Instant::now();
+    let mut low_disk = false;
+    let mut pause = PauseClock::default();
+    // Layout::Switch: (녹화 시작 기준 초, 카메라). 일시정지한 시간은 빼고 센다.
+    let mut switches: Vec<(f64, u32)> = Vec::new();
+    // 모든 카메라에서 첫 프레임이 왔는지 (/start 가 이것을 기다린다). 그때까지 세션은 Starting 이다.
+    let mut capturing = false;
+
+    // Stop 요청, 최대 녹화 시간 도달, 디스크 공간 부족을 대기하면서 통계 갱신
+    loop {
+        // 카메라는 열어 둔 채 libcamera-vid 의 출력만 멈추거나 재개
+        let want_paused = session.pause_requested();
+        if want_paused != pause.is_paused() {
+            for process in &mut processes {
+                if let Some(Err(e)) = process.child.as_ref().map(toggle_pause) {
+                    warn!("camera {}: {:#}", process.index, e);
+                }
+                // 일시정지 동안 프레임이 없는 것은 멈춘 것이 아니다.
+                process.last_frame_at = Instant::now();
+            }
+            let next = if want_paused {
+                pause.pause();
+                info!("Recording paused.");
+                SessionState::Paused
+            } else {
+                pause.resume();
+                info!("Recording resumed.");
+                SessionState::Recording
+            };
+            if let Err(e) = session.transition(next, &events) {
+                warn!("{:#}", e);
+            }
+        }
+
+        if let Some(camera) = session.switch_requested.lock().unwrap().take() {
+            let at = started
+                .elapsed()
+                .saturating_sub(pause.paused_total())
+                .as_secs_f64();
+            switches.push((at, camera));
+            stats.lock().unwrap().active_camera = Some(camera);
+            info!("Switched to camera {} at {:.1}s.", camera, at);
+            events.publish(session.id, EventKind::CameraSwitched { camera });
+        }
+
+        let requested = std::mem::take(&mut *session.settings_requested.lock().unwrap());
+        for settings in requested {
+            let camera = settings.camera;
+            live_config
+                .camera_settings
+                .retain(|other| other.camera != camera);
+            live_config.camera_settings.push(settings);
+            // 재연결과 같은 방법으로 다시 열어 다음 구간 파일에 이어 쓴다 (끊겨 있으면 다시 열 때 적용).
+            let Some(process) = processes.iter_mut().find(|p| p.index == camera) else {
+                continue;
+            };
+            if let Some(mut child) = process.child.take() {
+                info!("camera {}: reopening with new settings.", camera);
+                request_exit(&child);
+                wait_for_exit(camera, &mut child, Instant::now() + STOP_GRACE_PERIOD);
+                process.outage = Some(Outage {
+                    since: Instant::now(),
+                    attempts: 0,
+                    next_attempt: Instant::now(),
+                });
+            }
+        }
+
+        if last_space_check.elapsed() >= DISK_CHECK_INTERVAL {
+            last_space_check = Instant::now();
+            match fs4::available_space(&work_dir) {
+                Ok(free) => {
+                    stats.lock().unwrap().free_space_bytes = Some(free);
+                    low_disk = free < min_free_bytes;
+                    if low_disk {
+                        events.publish(
+                            session.id,
+                            EventKind::DiskLow {
+                                free_bytes: free,
+                                min_free_bytes,
+                            },
+                        );
+                    }
+                }
+                Err(e) => warn!("Failed to check free disk space: {}", e),
+            }
+        }
+
+        let stop_reason = if session.stop_requested() {
+            Some("Stop signal received")
+        } else if max_duration
+            .is_some_and(|limit| started.elapsed().saturating_sub(pause.paused_total()) >= limit)
+        {
+            Some("Maximum recording duration reached")
+        } else if low_disk {
+            Some("Free disk space dropped below the configured minimum")
+        } else {
+            None
+        };
+        if let Some(reason) = stop_reason {
+            info!("{}. Terminating libcamera-vid...", reason);
+
+            // libcamera-vid 프로세스 종료 (강제로 멈추면 기다리지 않는다)
+            let grace = if session.cancel_requested() {
+                Duration::ZERO
+            } else {
+                STOP_GRACE_PERIOD
+            };
+            stop_all(&mut processes, grace);
+            break;
+        }
+
+        let mut finished = 0;
+        for i in 0..processes.len() {
+            let process = &mut processes[i];
+            let Some(child) = process.child.as_mut() else {
+                continue;
+            };
+            let status = child
+                .try_wait()
+                .context("Failed to poll libcamera-vid status")?;
+            let failure = match status {
+                Some(status) if !status.success() => {
+                    Some(format!("libcamera-vid exited: {}", status))
+                }
+                // libcamera 가 아닌 카메라의 스트림은 스스로 끝나지 않으므로 끝났다면 연결이 끊긴 것이다.
+                Some(_) if !live_config.is_local(process.index) => {
+                    Some("camera stream ended".to_string())
+                }
+                Some(_) => {
+                    finished += 1;
+                    None
+                }
+                None if !pause.is_paused() && process.last_frame_at.elapsed() >= stall_timeout => {
+                    // 프로세스는 살아 있지만 프레임이 나오지 않으면 다시 시작한다.
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    Some(format!("no frames for {}s", stall_timeout.as_secs()))
+                }
+                None => None,
+            };
+            let Some(reason) = failure else {
+                continue;
+            };
+
+            let index = process.index;
+            events.publish(
+                session.id,
+                EventKind::CameraDisconnected {
+                    camera: index,
+                    reason: reason.clone(),
+                },
+            );
+            if !reconnect_enabled {
+                stop_all(&mut processes, STOP_GRACE_PERIOD);
+                bail!("libcamera-vid (camera {}) failed: {}", index, reason);
+            }
+            warn!("camera {}: {}. Trying to reconnect...", index, reason);
+            process.child = None;
+            process.outage = Some(Outage {
+                since: Instant::now(),
+                attempts: 0,
+                next_attempt: Instant::now() + config.reconnect.backoff(1),
+            });
+            stats.lock().unwrap().disconnected_cameras.push(index);
+        }
+        if finished == processes.len() {
+            info!("libcamera-vid finished on its own.");
+            break;
+        }
+
+        for event in receiver.try_iter() {
+            record_frame(&mut processes, &mut sync, event);
+        }
+        for process in processes.iter_mut() {
+            if process.outage.is_none() {
+                continue;
+            }
+            let reconnected = try_reconnect(
+                process,
+                &live_config,
+                &work_dir,
+                &timestamp,
+                pause.is_paused(),
+            );
+            match reconnected {
+                Ok(None) => {}
+                Ok(Some(outage_seconds)) => {
+                    readers.spawn(process.index, process.pts_path.clone(), sender.clone())?;
+                    if let Some(live) = &live {
+                        live.follow(process.index, &process.output);
+                    }
+                    let mut stats = stats.lock().unwrap();
+                    // 설정을 바꾸려고 다시 연 경우에는 끊긴 적이 없다.
+                    if stats.disconnected_cameras.contains(&process.index) {
+                        events.publish(
+                            session.id,
+                            EventKind::CameraReconnected {
+                                camera: process.index,
+                                outage_seconds,
+                            },
+                        );
+                    }
+                    stats
+                        .disconnected_cameras
+                        .retain(|&camera| camera != process.index);
+                    stats
+                        .camera_outputs
+                        .insert(process.index, process.output.clone());
+                }
+                Err(e) => {
+                    stop_all(&mut processes, STOP_GRACE_PERIOD);
+                    return Err(e);
+                }
+            }
+        }
+
+        wait_for_stop(session.stop_token(), Duration::from_millis(100));
+        for event in receiver.try_iter() {
+            record_frame(&mut processes, &mut sync, event);
+        }
+        if !capturing && processes.iter().all(|process| process.pts.frames > 0) {
+            capturing = true;
+            info!("All cameras are capturing.");
+            if let Err(e) = session.transition(SessionState::Recording, &events) {
+                stop_all(&mut processes, STOP_GRACE_PERIOD);
+                return Err(e);
+            }
+            session.signal_ready(Ok(()));
+        }
+        publish_stats(stats, &mut processes, &sync, started, &pause, max_duration);
+        if let Some(live) = &live {
+            stats.lock().unwrap().live_queues = live.queue_stats();
+        }
+
+        if config.slow_motion.enabled {
+            for process in processes.iter_mut() {
+                if process.rate_checked || process.pts.span_ms() < SLOW_MOTION_CHECK_MS {
+                    continue;
+                }
+                process.rate_checked = true;
+                let requested = config.format_for(process.index).fps;
+                let measured = process.pts.measured_fps();
+                if let Err(e) = config
+                    .slow_motion
+                    .check_rate(process.index, requested, measured)
+                {
+                    stop_all(&mut processes, STOP_GRACE_PERIOD);
+                    return Err(e);
+                }
+                info!(
+                    "camera {}: delivering {:.1} fps for slow motion.",
+                    process.index, measured
+                );
+            }
+        }
+
+        if last_drop_report.elapsed() >= FRAME_DROP_REPORT_INTERVAL {
+            last_drop_report = Instant::now();
+            for process in processes.iter_mut() {
+                let dropped = process.pts.dropped - process.reported_dropped;
+                if dropped > 0 {
+                    warn!("camera {}: {} frame(s) dropped.", process.index, dropped);
+                    events.publish(
+                        session.id,
+                        EventKind::FrameDrop {
+                            camera: process.index,
+                            dropped,
+                            total_dropped: process.pts.dropped,
+                        },
+                    );
+                    process.reported_dropped = process.pts.dropped;
+                }
+            }
+        }
+    }
+    let session_end = chrono::Local::now();
+    if let Err(e) = session.transition(SessionState::Finalizing, &events) {
+        warn!("{:#}", e);
+    }
+    pause.resume();
+    drop(sender);
+    readers.finish(&receiver, |event| {
+        record_frame(&mut processes, &mut sync, event)
+    });
+    if let Some(log) = frame_log {
+        info!("Frame timestamps saved to: {:?}", log.finish());
+    }
+    publish_stats(stats, &mut processes, &sync, started, &pause, max_duration);
+    if let Some(live) = live {
+        live.finish();
+        let mut stats = stats.lock().unwrap();
+        stats.live_playlist = None;
+        stats.live_queues.clear();
+    }
+    // 강제로 멈추면 마무리하지 않는다. 카메라별 임시 스트림과 복구 표시를 남겨 두므로
+    // 다음에 시작할 때 recover_interrupted 로 살릴 수 있다.
+    if session.cancel_requested() {
+        bail!("Recording was cancelled before finalizing; the camera streams were kept");
+    }
+    // 마무리 중에 취소되면 돌고 있는 ffmpeg 을 멈춘다.
+    let _cancel = sink::cancel_scope(session.cancel_token());
+    let offsets = sync.start_offsets();
+    let (pre_roll_secs, pre_roll_trim) = attach_pre_roll(&mut processes, &pre_roll, segmented);
+    let first_offsets: BTreeMap<u32, f64> = offsets
+        .iter()
+        .map(|(camera, offset)| (*camera, offset + pre_roll_trim.get(camera).unwrap_or(&0.0)))
+        .collect();
+    // 앞에 붙인 영상만큼 첫 파일의 첫 프레임 시각이 앞당겨진다 (오버레이 시각용).
+    let first_start =
+        session_start - chrono::Duration::milliseconds((pre_roll_secs * 1000.0) as i64);
+    let marks = session.bookmarks.lock().unwrap().clone();
+
+    let files = if let Some(segment) = config.segment_duration {
+        finalize_segments(
+            &processes,
+            (&first_offsets, &offsets),
+            config,
+            (&work_dir, &names),
+            (first_start, session_start, session_end),
+            segment,
+            (&switches, &marks),
+        )?
+    } else {
+        join_reconnected_parts(&mut processes, config)?;
+        let sources: Vec<(PathBuf, Option<f64>)> =
+            processes.iter().map(|p| (p.output.clone(), None)).collect();
+        finalize_output(
+            &processes,
+            &sources,
+            &first_offsets,
+            config,
+            (first_start, session_end),
+            (&switches_from(&switches, -pre_roll_secs), &marks),
+            &final_path,
+        )?
+    };
+    let files = if config.discards_video() {
+        files
+            .into_iter()
+            .map(|file| FinishedFile {
+                path: Some(sink::summary_path(&file.path))
+                    .filter(|summary| summary.exists())
+                    .unwrap_or(file.path),
+                ..file
+            })
+            .collect()
+    } else {
+        apply_chapters(files, config, &marks)
+    };
+    let outputs: Vec<PathBuf> = files.iter().map(|file| file.path.clone()).collect();
+    let final_stats = {
+        let mut stats = stats.lock().unwrap();
+        stats.camera_outputs.clear();
+        stats.segments = outputs.clone();
+        stats.clone()
+    };
+    for file in files.iter().filter(|_| !config.discards_video()) {
+        let mut metadata = RecordingMetadata::new(&session, &final_stats, &file.path, file.times);
+        metadata.cameras = file.cameras.clone();
+        if let Err(e) = metadata::write(&file.path, &metadata) {
+            warn!("{:#}", e);
+        }
+    }
+
+    recovery::finish(&final_path);
+    let outputs = match &staging_dir {
+        Some(staging_dir) => recordings::publish(staging_dir, &save_dir, &outputs)
+            .with_context(|| format!("Finished files were left in {:?}", staging_dir))?,
+        None => outputs,
+    };
+    info!("Recording complete. Video saved to: {:?}", outputs);
+    Ok(outputs)
+}
+
+// 녹화 루프의 한 박자: timeout 만큼 기다리되 멈추라는 요청이 오면 바로 돌아온다.
+// spawn_blocking 안에서 불리므로 런타임 핸들로 토큰을 기다릴 수 있다.
+fn wait_for_stop(stop: &CancellationToken, timeout: Duration) {
+    match tokio::runtime::Handle::try_current() {
+        Ok(runtime) => {
+            let _ = runtime.block_on(tokio::time::timeout(timeout, stop.cancelled()));
+        }
+        Err(_) => thread::sleep(timeout),
+    }
+}
+
+fn create_parent(path: &Path) -> Result<()> {
+    match path.parent() {
+        Some(dir) if !dir.exists() => fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create directory: {:?}", dir)),
+        _ => Ok(()),
+    }
+}
+
+// 마무리 단계에서 확정된 파일 하나
+struct FinishedFile {
+    path: PathBuf,
+    // 이 파일에 담긴 카메라 (카메라별 파일이면 한 대)
+    cameras: Vec<u32>,
+    // 첫 프레임과 마지막 프레임의 시각
+    times: (
+        chrono::DateTime<chrono::Local>,
+        chrono::DateTime<chrono::Local>,
+    ),
+}
+
+// chapters 에 따라 표시가 있는 파일에 챕터를 넣거나 표시마다 나눈다. 실패하면 그대로 둔다.
+fn apply_chapters(
+    files: Vec<FinishedFile>,
+    config: &RecordingConfig,
+    marks: &[Bookmark],
+) -> Vec<FinishedFile> {
+    if config.chapters == ChapterMode::None || marks.is_empty() {
+        return files;
+    }
+    let mut chaptered = Vec::with_capacity(files.len());
+    for file in files {
+        let (start, end) = file.times;
+        let marks = bookmarks::in_file(marks, file.times);
+        if marks.is_empty() || !chapters::supports(&file.path) {
+            chaptered.push(file);
+            continue;
+        }
+        let duration = (end - start).num_milliseconds() as f64 / 1000.0;
+        if config.chapters == ChapterMode::Metadata {
+            if let Err(e) =
+                chapters::write_metadata(&file.path, &marks, duration, config.sink.container)
+            {
+                warn!("{:#}", e);
+            }
+            chaptered.push(file);
+            continue;
+        }
+        match chapters::split(&file.path, &marks, duration) {
+            Ok(pieces) => {
+                let at = |secs: f64| {
+                    start + chrono::Duration::milliseconds((secs * 1000.0).round() as i64)
+                };
+                chaptered.extend(pieces.into_iter().map(|(path, (from, to))| FinishedFile {
+                    path,
+                    cameras: file.cameras.clone(),
+                    times: (at(from), at(to)),
+                }));
+            }
+            Err(e) => {
+                warn!("{:#}. Keeping {:?} as one file.", e, file.path);
+                chaptered.push(file);
+            }
+        }
+    }
+    chaptered
+}
+
+// 녹화 시작 기준 전환 시각을 from 초에 시작하는 파일 기준으로 바꾼다.
+// 파일이 시작하기 전의 마지막 전환은 파일 첫머리로 옮긴다.
+fn switches_from(switches: &[(f64, u32)], from: f64) -> Vec<(f64, u32)> {
+    let mut shifted = Vec::new();
+    for &(at, camera) in switches {
+        let at = at - from;
+        if at <= 0.0 {
+            shifted.clear();
+        }
+        shifted.push((at.max(0.0), camera));
+    }
+    shifted
+}
+
+// 카메라별 (파일, 그 파일만 잰 FPS) (sources, processes 와 같은 순서) 를 output_mode 에 따라 합성본이나
+// 카메라별 파일로 확정한다. switches 는 이 파일 기준 카메라 전환 시각 (Layout::Switch 전용),
+// marks 는 세션에 남긴 표시 전체 (이 파일 구간의 것만 그린다)
+fn finalize_output(
+    processes: &[CameraProcess],
+    sources: &[(PathBuf, Option<f64>)],
+    offsets: &BTreeMap<u32, f64>,
+    config: &RecordingConfig,
+    (start, end): (
+        chrono::DateTime<chrono::Local>,
+        chrono::DateTime<chrono::Local>,
+    ),
+    (switches, marks): (&[(f64, u32)], &[Bookmark]),
+    output: &Path,
+) -> Result<Vec<FinishedFile>> {
+    // 가림 영역은 물체를 찾기 전에 칠한다 (가린 곳에서는 찾지 않는다).
+    let mut inputs: Vec<CompositeInput> = processes
+        .iter()
+        .zip(sources)
+        .map(|(process, (source, fps))| CompositeInput {
+            path: source.clone(),
+            fps: match fps.unwrap_or_else(|| process.pts.measured_fps()) {
+                fps if fps > 0.0 && !config.constant_frame_rate => fps,
+                _ => config.format_for(process.index).fps as f64,
+            },
+            filter: config.camera_filter(process.index),
+            retime: config.constant_frame_rate.then(|| process.pts.retime()),
+            start_offset: offsets.get(&process.index).copied().unwrap_or(0.0),
+        })
+        .collect();
+    let detections = detect_objects(processes, &inputs, config, start);
+    let mut temp_files = Vec::new();
+    let mut colors = match_colors(processes, &inputs, config, output, &mut temp_files);
+
+    // 회전, 가림 영역, 색 맞춤, 오버레이, 감지 상자는 카메라 원래 해상도에서 처리한 뒤 배치에 맞춰 크기를 바꾼다.
+    for (process, input) in processes.iter().zip(&mut inputs) {
+        let (_, height) = config.frame_size(process.index);
+        let overlay = match config.overlay_for(process.index) {
+            Some(overlay) => Some(overlay.filter(start, height)),
+            // 따로 설정한 오버레이가 없는 카메라에는 시각만 그린다.
+            None if config.timelapse.enabled && config.timelapse.clock => Some(
+                OverlayConfig {
+                    camera: process.index,
+                    ..OverlayConfig::default()
+                }
+                .filter(start, height),
+            ),
+            None => None,
+        };
+        let boxes = detections.get(&process.index).and_then(|found| {
+            let path = output.with_extension(format!("cam{}.boxes.ass", process.index));
+            boxes_filter(process.index, found, 0.0, config, path, &mut temp_files)
+        });
+        let fit = (processes.len() > 1)
+            .then(|| config.fit_filter(process.index))
+            .flatten();
+        let color = colors.remove(&process.index);
+        let chain: Vec<String> = [input.filter.take(), color, overlay, boxes, fit]
+            .into_iter()
+            .flatten()
+            .collect();
+        input.filter = (!chain.is_empty()).then(|| chain.join(","));
+    }
+
+    let burn_in = bookmarks::burn_in_filter(&bookmarks::in_file(marks, (start, end)));
+    let result = finalize_files(
+        processes,
+        &inputs,
+        &detections,
+        config,
+        switches,
+        burn_in.as_deref(),
+        output,
+    )
+    .map(|files| {
+        files
+            .into_iter()
+            .map(|(path, cameras)| FinishedFile {
+                path,
+                cameras,
+                times: (start, end),
+            })
+            .collect::<Vec<_>>()
+    });
+    remove_temp_files(&temp_files);
+    result
+}
+
+// finalize_output 의 나머지: 합성본, 깊이 영상, 카메라별 파일을 만들고 (경로, 카메라) 를 돌려준다.
+// burn_in 은 합성본에만 그리는 표시 이름 필터
+fn finalize_files(
+    processes: &[CameraProcess],
+    inputs: &[CompositeInput],
+    detections: &BTreeMap<u32, Vec<Detection>>,
+    config: &RecordingConfig,
+    switches: &[(f64, u32)],
+    burn_in: Option<&str>,
+    output: &Path,
+) -> Result<Vec<(PathBuf, Vec<u32>)>> {
+    if inputs.len() == 1 && inputs[0].filter.is_none() && burn_in.is_none() {
+        let output = finalize_camera(&processes[0], &inputs[0], config, output)?;
+        write_detections(&output, &[(processes[0].index, 0.0)], detections, config);
+        return Ok(vec![(output, vec![processes[0].index])]);
+    }
+
+    let mut files = Vec::new();
+    let depth = render_depth(processes, inputs, config, output);
+    if config.output_mode != OutputMode::Separate {
+        let mut inputs = inputs.to_vec();
+        let panel = depth
+            .as_ref()
+            .filter(|_| config.depth.output == DepthOutput::Panel);
+        if let Some(panel) = panel {
+            inputs.push(panel.clone());
+        }
+        let result = compose_output(processes, &inputs, config, switches, burn_in, output);
+        if let Some(panel) = panel
+            && let Err(e) = fs::remove_file(&panel.path)
+        {
+            warn!("Failed to remove {:?}: {}", panel.path, e);
+        }
+        result?;
+        let cameras: Vec<(u32, f64)> = processes.iter().map(|p| (p.index, 0.0)).collect();
+        write_detections(output, &cameras, detections, config);
+        files.push((
+            output.to_path_buf(),
+            processes.iter().map(|p| p.index).collect(),
+        ));
+    }
+    if let Some(depth) = depth.filter(|_| config.depth.output == DepthOutput::File) {
+        files.push((depth.path, vec![config.depth.left, config.depth.right]));
+    }
+    if config.output_mode == OutputMode::Composite {
+        for input in inputs.iter().filter(|_| !config.discards_video()) {
+            if let Err(e) = fs::remove_file(&input.path) {
+                warn!("Failed to remove {:?}: {}", input.path, e);
+            }
+        }
+        return Ok(files);
+    }
+
+    // 카메라별 파일에는 오버레이나 크기 조정 없이 원본 스트림을 담는다 (가림 영역과 감지 상자만 그린다).
+    // 카메라별 파일은 잘라내지 않으므로 상자를 start_offset 만큼 늦춰 그린다.
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let mut temp_files = Vec::new();
+    let mut result = Ok(());
+    for (process, input) in processes.iter().zip(inputs) {
+        let boxes = detections.get(&process.index).and_then(|found| {
+            let path = output.with_extension(format!("cam{}.raw.boxes.ass", process.index));
+            boxes_filter(
+                process.index,
+                found,
+                input.start_offset,
+                config,
+                path,
+                &mut temp_files,
+            )
+        });
+        let filter: Vec<String> = [config.camera_filter(process.index), boxes]
+            .into_iter()
+            .flatten()
+            .collect();
+        let raw = CompositeInput {
+            path: input.path.clone(),
+            fps: input.fps,
+            filter: (!filter.is_empty()).then(|| filter.join(",")),
+            start_offset: 0.0,
+            retime: input.retime.clone(),
+        };
+        let path = output.with_file_name(format!(
+            "{}_cam{}.{}",
+            stem,
+            process.index,
+            config.extension()
+        ));
+        match finalize_camera(process, &raw, config, &path) {
+            Ok(path) => {
+                let cameras = [(process.index, input.start_offset)];
+                write_detections(&path, &cameras, detections, config);
+                files.push((path, vec![process.index]));
+            }
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        }
+    }
+    remove_temp_files(&temp_files);
+    result.map(|()| files)
+}
+
+// detection 이 맡은 카메라마다 물체를 찾는다. 실패한 카메라는 건너뛴다 (녹화는 그대로 확정).
+fn detect_objects(
+    processes: &[CameraProcess],
+    inputs: &[CompositeInput],
+    config: &RecordingConfig,
+    start: chrono::DateTime<chrono::Local>,
+) -> BTreeMap<u32, Vec<Detection>> {
+    let mut detections = BTreeMap::new();
+    for (process, input) in processes.iter().zip(inputs) {
+        if !config.detection.watches(process.index) {
+            continue;
+        }
+        let frame_size = config.frame_size(process.index);
+        match detection::detect(&config.detection, process.index, input, frame_size, start) {
+            Ok(found) => {
+                detections.insert(process.index, found);
+            }
+            Err(e) => warn!("camera {}: object detection failed: {:#}", process.index, e),
+        }
+    }
+    detections
+}
+
+// color_match 를 켠 합성이면 기준 카메라가 아닌 카메라마다 게인을 거는 필터.
+// sendcmd 명령 파일은 files 에 넣어 두었다가 다 쓰고 지운다.
+fn match_colors(
+    processes: &[CameraProcess],
+    inputs: &[CompositeInput],
+    config: &RecordingConfig,
+    output: &Path,
+    files: &mut Vec<PathBuf>,
+) -> BTreeMap<u32, String> {
+    let mut filters = BTreeMap::new();
+    if !config.color_match.enabled
+        || processes.len() < 2
+        || config.output_mode == OutputMode::Separate
+    {
+        return filters;
+    }
+    let cameras: Vec<u32> = processes.iter().map(|process| process.index).collect();
+    let Some(reference) = config
+        .color_match
+        .reference(&cameras)
+        .and_then(|reference| cameras.iter().position(|&camera| camera == reference))
+    else {
+        warn!("The color_match reference camera is not recording; colors are left as they are.");
+        return filters;
+    };
+    for (process, input) in processes.iter().zip(inputs) {
+        if process.index == cameras[reference] {
+            continue;
+        }
+        let commands = output.with_extension(format!("cam{}.color.cmd", process.index));
+        match config
+            .color_match
+            .filter(process.index, &inputs[reference], input, &commands)
+        {
+            Ok(filter) => {
+                filters.insert(process.index, filter);
+            }
+            Err(e) => warn!("camera {}: color matching failed: {:#}", process.index, e),
+        }
+        if commands.exists() {
+            files.push(commands);
+        }
+    }
+    filters
+}
+
+fn remove_temp_files(paths: &[PathBuf]) {
+    for path in paths {
+        if let Err(e) = fs::remove_file(path) {
+            warn!("Failed to remove {:?}: {}", path, e);
+        }
+    }
+}
+
+// draw_boxes 면 카메라 한 대의 상자를 path 에 써 두고 그리는 필터를 돌려준다.
+// 만든 파일은 files 에 넣어 두었다가 다 쓰고 지운다.
+fn boxes_filter(
+    camera: u32,
+    detections: &[Detection],
+    shift: f64,
+    config: &RecordingConfig,
+    path: PathBuf,
+    files: &mut Vec<PathBuf>,
+) -> Option<String> {
+    if !config.detection.draw_boxes {
+        return None;
+    }
+    let size = config.frame_size(camera);
+    let shifted: Vec<Detection> = detections
+        .iter()
+        .map(|detection| detection.shifted(shift))
+        .collect();
+    let interval = 1.0 / config.detection.fps;
+    match detection::write_boxes(&shifted, size, interval, &path) {
+        Ok(()) => {
+            let filter = detection::boxes_filter(&path);
+            files.push(path);
+            Some(filter)
+        }
+        Err(e) => {
+            warn!("camera {}: not drawing detections: {:#}", camera, e);
+            None
+        }
+    }
+}
+
+// 확정한 파일 옆에 그 파일에 담긴 카메라의 감지 결과를 .detections.jsonl 로 남긴다.
+// cameras 는 (카메라, 파일 기준으로 늦출 시간): 잘라내지 않은 카메라별 파일은 start_offset 만큼 늦다.
+fn write_detections(
+    video: &Path,
+    cameras: &[(u32, f64)],
+    detections: &BTreeMap<u32, Vec<Detection>>,
+    config: &RecordingConfig,
+) {
+    if !config.detection.enabled {
+        return;
+    }
+    let mut lines: Vec<Detection> = Vec::new();
+    for &(camera, shift) in cameras {
+        for detection in detections.get(&camera).into_iter().flatten() {
+            lines.push(detection.shifted(shift));
+        }
+    }
+    lines.sort_by_key(|detection| detection.time_ms);
+    if let Err(e) = detection::write_sidecar(video, &lines) {
+        warn!("{:#}", e);
+    }
+}
+
+// depth 가 켜져 있으면 두 카메라의 시차 영상을 만든다. Panel 이면 합성본에 넣을 입력,
+// File 이면 <stem>_depth.mp4. 실패해도 녹화는 그대로 확정한다.
+fn render_depth(
+    processes: &[CameraProcess],
+    inputs: &[CompositeInput],
+    config: &RecordingConfig,
+    output: &Path,
+) -> Option<CompositeInput> {
+    let depth = &config.depth;
+    if !depth.enabled {
+        return None;
+    }
+    let input = |camera: u32| {
+        let position = processes.iter().position(|p| p.index == camera);
+        position.map(|position| &inputs[position])
+    };
+    let (Some(left), Some(right)) = (input(depth.left), input(depth.right)) else {
+        warn!(
+            "Skipping the depth map: cameras {} and {} are not both recording.",
+            depth.left, depth.right
+        );
+        return None;
+    };
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let (path, filter) = match depth.output {
+        DepthOutput::File => (
+            output.with_file_name(format!("{}_depth.{}", stem, config.extension())),
+            None,
+        ),
+        DepthOutput::Panel => (
+            output.with_file_name(format!("{}_depth.h264", stem)),
+            Some(compositor::fit_filter(
+                config.layout,
+                config.width,
+                config.height,
+            )),
+        ),
+    };
+    match depth::render(depth, (left, right), &config.encoding(), &path) {
+        Ok(()) => Some(CompositeInput {
+            path,
+            fps: depth.fps as f64,
+            filter,
+            start_offset: 0.0,
+            retime: None,
+        }),
+        Err(e) => {
+            warn!("Failed to compute the depth map: {:#}", e);
+            let _ = fs::remove_file(&path);
+            None
+        }
+    }
+}
+
+fn compose_output(
+    processes: &[CameraProcess],
+    inputs: &[CompositeInput],
+    config: &RecordingConfig,
+    switches: &[(f64, u32)],
+    burn_in: Option<&str>,
+    output: &Path,
+) -> Result<()> {
+    let mut arrangement = config.arrangement();
+    // 타임랩스처럼 프레임을 고르기 전에 그려야 표시 시각과 맞는다.
+    if let Some(burn_in) = burn_in {
+        arrangement.output_filter = Some(match arrangement.output_filter {
+            Some(filter) => format!("{},{}", burn_in, filter),
+            None => burn_in.to_string(),
+        });
+    }
+    arrangement.switches = switches
+        .iter()
+        .filter_map(|&(at, camera)| {
+            let input = processes.iter().position(|p| p.index == camera)?;
+            Some((at, input))
+        })
+        .collect();
+    compositor::compose(
+        inputs,
+        &arrangement,
+        config.output_fps(),
+        &config.encoding(),
+        output,
+    )
+}
+
+// 카메라 한 대의 파일을 확정한다. 타임스탬프 파일은 영상과 같은 이름으로 남긴다
+// (삭제할 때 함께 지워짐, 분할 녹화면 첫 파일에만).
+fn finalize_camera(
+    process: &CameraProcess,
+    input: &CompositeInput,
+    config: &RecordingConfig,
+    output: &Path,
+) -> Result<PathBuf> {
+    let output = finalize_single(input, process.index, config, output)?;
+    // null sink 이면 타임스탬프는 남겨 둔 원본 스트림 옆에 둔다.
+    if process.pts_path.exists() && !config.discards_video() {
+        let pts = output.with_extension("pts");
+        if let Err(e) = fs::rename(&process.pts_path, &pts) {
+            warn!("Failed to move {:?} to {:?}: {}", process.pts_path, pts, e);
+        }
+    }
+    Ok(output)
+}
+
+// 카메라 한 대: 디코딩 없이 컨테이너만 씌우고, 안 되면 다시 인코딩한다.
+// 타임랩스, 워터마크, 가림 영역은 프레임을 바꿔야 하므로 늘 다시 인코딩하고, 슬로 모션은 느린 FPS 로 담는다.
+// ffmpeg 를 쓸 수 없으면 원본 스트림을 .h264 로 남긴다 (가림 영역이 있으면 남기지 않는다).
+fn finalize_single(
+    input: &CompositeInput,
+    camera: u32,
+    config: &RecordingConfig,
+    output: &Path,
+) -> Result<PathBuf> {
+    let fps = config.format_for(camera).fps;
+    let encoding = config.encoding();
+    let result = if config.timelapse.enabled
+        || config.watermark.enabled
+        || input.filter.is_some()
+        || config.discards_video()
+    {
+        compositor::compose(
+            std::slice::from_ref(input),
+            &config.arrangement(),
+            config.output_fps(),
+            &encoding,
+            output,
+        )
+    } else {
+        // 슬로 모션은 프레임마다 시각만 factor 배로 늘려 담으므로 다시 인코딩하지 않아도 된다.
+        let slowed = config.slow_motion.factor(fps).map(|factor| CompositeInput {
+            fps: if input.fps > 0.0 {
+                input.fps
+            } else {
+                fps as f64
+            } / factor,
+            retime: input
+                .retime
+                .as_ref()
+                .map(|retime| format!("({})*{:.6}", retime, factor)),
+            ..input.clone()
+        });
+        let (input, fps) = match &slowed {
+            Some(slowed) => (slowed, config.slow_motion.playback_fps),
+            None => (input, fps),
+        };
+        compositor::remux(input, fps, encoding.sink.container, output).or_else(|e| {
+            if sink::cancelled() {
+                return Err(e);
+            }
+            warn!("{:#}. Falling back to a full re-encode.", e);
+            compositor::reencode(input, fps, &encoding, output)
+        })
+    };
+    match result {
+        Ok(()) => {
+            if !config.discards_video()
+                && let Err(e) = fs::remove_file(&input.path)
+            {
+                warn!("Failed to remove {:?}: {}", input.path, e);
+            }
+            Ok(output.to_path_buf())
+        }
+        // 취소되면 원본 스트림을 그대로 두어 다음 시작 때 복구한다.
+        Err(e) if sink::cancelled() => Err(e),
+        Err(e) if input.filter.is_some() && config.mask_filter(camera).is_some() => {
+            if let Err(remove) = fs::remove_file(&input.path) {
+                warn!("Failed to remove {:?}: {}", input.path, remove);
+            }
+            Err(e.context(format!(
+                "camera {} has privacy masks, so its unmasked stream was discarded",
+                camera
+            )))
+        }
+        Err(e) => {
+            warn!("{:#}. Keeping the raw H.264 stream instead.", e);
+            let raw = output.with_extension("h264");
+            fs::rename(&input.path, &raw)
+                .with_context(|| format!("Failed to move {:?} to {:?}", input.path, raw))?;
+            Ok(raw)
+        }
+    }
+}
+
+// libcamera-vid --segment 가 만든 0000, 0001, ... 파일을 세그먼트 시작 시각 이름으로 확정
+fn finalize_segments(
+    processes: &[CameraProcess],
+    // (첫 세그먼트, 나머지): 첫 세그먼트에는 녹화 전 영상이 붙어 있을 수 있다.
+    offsets: (&BTreeMap<u32, f64>, &BTreeMap<u32, f64>),
+    config: &RecordingConfig,
+    // 세그먼트 파일 이름은 save_dir/<세그먼트 시작 시각으로 만든 이름>.mp4
+    (save_dir, names): (&Path, &FileNames),
+    (first_start, session_start, session_end): (
+        chrono::DateTime<chrono::Local>,
+        chrono::DateTime<chrono::Local>,
+        chrono::DateTime<chrono::Local>,
+    ),
+    segment: u64,
+    (switches, marks): (&[(f64, u32)], &[Bookmark]),
+) -> Result<Vec<FinishedFile>> {
+    // 첫 세그먼트 앞에 붙인 녹화 전 영상의 길이
+    let pre_roll = (session_start - first_start).num_milliseconds() as f64 / 1000.0;
+    let mut outputs = Vec::new();
+    // 카메라마다 앞 세그먼트들에 들어간 프레임 수 (FPS 를 잴 pts 의 시작 위치)
+    let mut consumed = vec![0; processes.len()];
+    for number in 0.. {
+        let paths: Vec<PathBuf> = processes
+            .iter()
+            .map(|process| {
+                let pattern = process.output.to_string_lossy();
+                PathBuf::from(pattern.replace("%04d", &format!("{:04}", number)))
+            })
+            .collect();
+        if !paths.iter().all(|source| source.exists()) {
+            for source in paths.iter().filter(|source| source.exists()) {
+                warn!(
+                    "Segment {:?} has no counterpart from every camera. Leaving it as is.",
+                    source
+                );
+            }
+            break;
+        }
+        // 세그먼트 길이는 카메라 시각으로 정해지므로 세션 평균 FPS 대신 그 세그먼트의 프레임으로 잰다.
+        let sources: Vec<(PathBuf, Option<f64>)> = processes
+            .iter()
+            .zip(paths)
+            .zip(consumed.iter_mut())
+            .map(|((process, path), consumed)| {
+                let fps = match stream::count_frames(&path) {
+                    Ok(frames) => {
+                        let prepended = if number == 0 {
+                            process.pts.prepended
+                        } else {
+                            0
+                        };
+                        let frames = frames.saturating_sub(prepended) as usize;
+                        let fps = process.pts.measured_fps_of(*consumed, frames);
+                        *consumed += frames;
+                        fps
+                    }
+                    Err(e) => {
+                        warn!("{:#}. Using the session's average frame rate.", e);
+                        None
+                    }
+                };
+                (path, fps)
+            })
+            .collect();
+
+        let start = session_start + chrono::Duration::seconds((number * segment) as i64);
+        let output = save_dir.join(format!("{}.{}", names.stem(start), config.extension()));
+        // {date} 가 들어 있으면 자정을 넘긴 세그먼트는 다음 날 디렉토리에 들어간다.
+        create_parent(&output)?;
+        let end = (start + chrono::Duration::seconds(segment as i64)).min(session_end);
+        let (offsets, start, from) = if number == 0 {
+            (offsets.0, first_start, -pre_roll)
+        } else {
+            (offsets.1, start, (number * segment) as f64)
+        };
+        let files = finalize_output(
+            processes,
+            &sources,
+            offsets,
+            config,
+            (start, end),
+            (&switches_from(switches, from), marks),
+            &output,
+        )?;
+        for file in &files {
+            info!("Segment {} saved to: {:?}", number, file.path);
+        }
+        outputs.extend(files);
+    }
+    Ok(outputs)
+}
+
+// 녹화 전에 모아 둔 영상을 카메라별 첫 파일 앞에 붙인다. 붙인 길이(초, 가장 짧은 카메라 기준)와
+// 카메라마다 그보다 더 붙은 만큼 합성할 때 잘라낼 시간을 돌려준다.
+fn attach_pre_roll(
+    processes: &mut [CameraProcess],
+    clips: &BTreeMap<u32, Clip>,
+    segmented: bool,
+) -> (f64, BTreeMap<u32, f64>) {
+    if clips.is_empty() {
+        return (0.0, BTreeMap::new());
+    }
+    let mut durations = BTreeMap::new();
+    for process in processes {
+        let mut duration = 0.0;
+        if let Some(clip) = clips.get(&process.index) {
+            let target = if segmented {
+                PathBuf::from(process.output.to_string_lossy().replace("%04d", "0000"))
+            } else {
+                process
+                    .parts
+                    .first()
+                    .map_or(&process.output, |part| &part.output)
+                    .clone()
+            };
+            match preroll::prepend(clip, &target) {
+                Ok(()) => {
+                    info!(
+                        "camera {}: prepended {:.1}s of pre-roll.",
+                        process.index, clip.duration
+                    );
+                    duration = clip.duration;
+                    process.pts.prepended = clip.frames;
+                }
+                Err(e) => warn!(
+                    "camera {}: failed to prepend the pre-roll: {:#}",
+                    process.index, e
+                ),
+            }
+        }
+        durations.insert(process.index, duration);
+    }
+    let shortest = durations.values().copied().fold(f64::INFINITY, f64::min);
+    let trim = durations
+        .into_iter()
+        .map(|(camera, duration)| (camera, duration - shortest))
+        .collect();
+    (shortest, trim)
+}
+
+fn record_frame(processes: &mut [CameraProcess], sync: &mut FrameSync, event: FrameEvent) {
+    sync.observe(&event);
+    if let Some(process) = processes.iter_mut().find(|p| p.index == event.camera) {
+        process.last_frame_at = event.arrived;
+        process.pts.record(event.pts_ms);
+    }
+}
+
+// 재시도할 때가 되었으면 새 구간 파일로 libcamera-vid 를 다시 시작한다.
+// 다시 연결되면 끊겨 있던 시간(초)을 돌려준다.
+fn try_reconnect(
+    process: &mut CameraProcess,
+    config: &RecordingConfig,
+    save_dir: &Path,
+    timestamp: &str,
+    paused: bool,
+) -> Result<Option<f64>> {
+    let Some(outage) = process.outage.as_mut() else {
+        return Ok(None);
+    };
+    if Instant::now() < outage.next_attempt {
+        return Ok(None);
+    }
+    outage.attempts += 1;
+
+    let part = process.parts.len() + 1;
+    let output = save_dir.join(format!(
+        "{}_cam{}_part{}.h264",
+        timestamp, process.index, part
+    ));
+    let pts_path = save_dir.join(format!(
+        "{}_cam{}_part{}.pts",
+        timestamp, process.index, part
+    ));
+    match config
+        .source(process.index)
+        .spawn(config, &output, &pts_path)
+    {
+        Ok(child) => {
+            // 일시정지 중이면 새 프로세스도 일시정지 상태로 맞춘다.
+            if let Some(Err(e)) = paused.then(|| toggle_pause(&child)) {
+                warn!("camera {}: {:#}", process.index, e);
+            }
+            let gap = outage.since.elapsed();
+            process.parts.push(Part {
+                output: std::mem::replace(&mut process.output, output),
+                pts_path: std::mem::replace(&mut process.pts_path, pts_path),
+                gap,
+            });
+            process.child = Some(child);
+            process.outage = None;
+            process.last_frame_at = Instant::now();
+            process.pts.restart();
+            info!(
+                "camera {}: reconnected after {:.1}s.",
+                process.index,
+                gap.as_secs_f64()
+            );
+            Ok(Some(gap.as_secs_f64()))
+        }
+        Err(e) if outage.attempts >= config.reconnect.max_attempts => Err(e.context(format!(
+            "camera {} could not be reconnected after {} attempt(s)",
+            process.index, outage.attempts
+        ))),
+        Err(e) => {
+            let delay = config.reconnect.backoff(outage.attempts + 1);
+            warn!(
+                "camera {}: reconnect attempt {} failed ({:#}). Retrying in {}s.",
+                process.index,
+                outage.attempts,
+                e,
+                delay.as_secs()
+            );
+            outage.next_attempt = Instant::now() + delay;
+            Ok(None)
+        }
+    }
+}
+
+// 재연결로 나뉜 구간 파일을 카메라별 스트림 하나로 합친다.
+fn join_reconnected_parts(processes: &mut [CameraProcess], config: &RecordingConfig) -> Result<()> {
+    for process in processes.iter_mut().filter(|p| !p.parts.is_empty()) {
+        let format = config.format_for(process.index);
+        let placeholder = Placeholder {
+            width: format.width,
+            height: format.height,
+            fps: format.fps,
+            encoding: config.encoding(),
+        };
+        let joined = process.parts[0].output.with_extension("joined.h264");
+        reconnect::join_parts(
+            &process.parts,
+            &process.output,
+            config.reconnect.placeholder.then_some(&placeholder),
+            &joined,
+        )?;
+        process.parts.clear();
+        process.output = joined;
+    }
+    Ok(())
+}
+
+fn publish_stats(
+    stats: &Mutex<RecordingStats>,
+    processes: &mut [CameraProcess],
+    sync: &FrameSync,
+    started: Instant,
+    pause: &PauseClock,
+    max_duration: Option<Duration>,
+) {
+    let paused = pause.paused_total();
+    for process in processes.iter_mut() {
+        process.pts.paused_ms = paused.as_secs_f64() * 1000.0;
+    }
 
-    // libcamera-vid 프로세스 종료
-    child.kill().context("Failed to stop libcamera-vid")?;
-    child
-        .wait()
-        .context("Failed to wait for libcamera-vid to exit")?;
+    // 합성 영상은 가장 느린 카메라에 맞춰지므로 최소 프레임 수를 보고한다.
+    let frames = processes.iter().map(|p| p.pts.frames).min().unwrap_or(0);
+    let fps = processes
+        .iter()
+        .map(|p| p.pts.measured_fps())
+        .fold(f64::INFINITY, f64::min);
 
-    println!("Recording complete. Video saved to: {:?}", final_path);
-    Ok(final_path)
+    let mut stats = stats.lock().unwrap();
+    stats.elapsed_seconds = started.elapsed().as_secs_f64();
+    stats.remaining_seconds = max_duration.map(|limit| {
+        let recorded = started.elapsed().saturating_sub(paused);
+        limit.saturating_sub(recorded).as_secs_f64()
+    });
+    stats.paused = pause.is_paused();
+    stats.paused_seconds = paused.as_secs_f64();
+    stats.frames_captured = frames;
+    stats.dropped_frames = processes.iter().map(|p| p.pts.dropped).sum();
+    stats.duplicated_frames = processes.iter().map(|p| p.pts.duplicated()).sum();
+    stats.sync_skew_ms = sync.skew_ms();
+    stats.measured_fps = if fps.is_finite() { fps } else { 0.0 };
 }
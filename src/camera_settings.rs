@@ -0,0 +1,377 @@
+// src/camera_settings.rs
+use crate::{ApiError, AppState};
+use anyhow::{Result, bail};
+use axum::{
+    Json,
+    extract::{Path, State},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+use tracing::info;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AwbMode {
+    Auto,
+    Incandescent,
+    Tungsten,
+    Fluorescent,
+    Indoor,
+    Daylight,
+    Cloudy,
+    // awb_gains 로 직접 지정
+    Custom,
+}
+
+impl AwbMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Incandescent => "incandescent",
+            Self::Tungsten => "tungsten",
+            Self::Fluorescent => "fluorescent",
+            Self::Indoor => "indoor",
+            Self::Daylight => "daylight",
+            Self::Cloudy => "cloudy",
+            Self::Custom => "custom",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutofocusMode {
+    Default,
+    // lens_position 에 고정
+    Manual,
+    // 시작할 때 한 번 맞춘다.
+    Auto,
+    Continuous,
+}
+
+impl AutofocusMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::Manual => "manual",
+            Self::Auto => "auto",
+            Self::Continuous => "continuous",
+        }
+    }
+}
+
+// 센서에서 녹화할 부분 (센서 크기에 대한 비율 0.0 ~ 1.0). libcamera-vid --roi 로 넘겨
+// 합성 전에 잘라내고, 캡처 해상도도 같은 비율로 줄어 화소 밀도는 그대로 파일만 작아진다.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CropRegion {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl CropRegion {
+    fn validate(&self, camera: u32) -> Result<()> {
+        let fraction = |value: f64| value.is_finite() && (0.0..=1.0).contains(&value);
+        if ![self.x, self.y, self.width, self.height]
+            .into_iter()
+            .all(fraction)
+        {
+            bail!(
+                "camera {} crop x, y, width and height must be between 0.0 and 1.0",
+                camera
+            );
+        }
+        if self.width == 0.0 || self.height == 0.0 {
+            bail!("camera {} crop width and height must be non-zero", camera);
+        }
+        if self.x + self.width > 1.0 || self.y + self.height > 1.0 {
+            bail!("camera {} crop must lie inside the sensor", camera);
+        }
+        Ok(())
+    }
+
+    // 잘라낸 부분을 담는 캡처 크기 (인코더가 받도록 짝수로 내림, 최소 2)
+    pub fn fit(&self, width: u32, height: u32) -> (u32, u32) {
+        let even = |size: u32, fraction: f64| (((size as f64 * fraction) as u32) & !1).max(2);
+        (even(width, self.width), even(height, self.height))
+    }
+
+    pub fn roi(&self) -> String {
+        format!(
+            "{:.4},{:.4},{:.4},{:.4}",
+            self.x, self.y, self.width, self.height
+        )
+    }
+}
+
+// 카메라 한 대의 화질 설정. libcamera-vid 옵션으로 넘기며, 생략한 값은 카메라 기본값
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraSettings {
+    pub camera: u32,
+    // 노출 시간 (마이크로초). 생략하면 자동 노출
+    pub shutter_us: Option<u64>,
+    // 아날로그 게인 (1.0 이상)
+    pub gain: Option<f64>,
+    // 자동 노출 보정 (-10 ~ 10 스톱)
+    pub ev: Option<f64>,
+    // -1.0 ~ 1.0, 0 이 기본
+    pub brightness: Option<f64>,
+    // 1.0 이 기본
+    pub contrast: Option<f64>,
+    pub saturation: Option<f64>,
+    pub sharpness: Option<f64>,
+    pub awb: Option<AwbMode>,
+    // awb = custom 일 때 (빨강, 파랑) 게인
+    pub awb_gains: Option<(f64, f64)>,
+    pub autofocus: Option<AutofocusMode>,
+    // autofocus = manual 일 때 초점 (디옵터, 0 이면 무한대)
+    pub lens_position: Option<f64>,
+    // 생략하면 센서 전체
+    pub crop: Option<CropRegion>,
+    // 시계 방향 회전 (0, 90, 180, 270). 뒤집기 다음에 적용한다.
+    pub rotation: Option<u32>,
+    // 좌우 / 상하 뒤집기
+    pub hflip: bool,
+    pub vflip: bool,
+}
+
+impl CameraSettings {
+    pub fn validate(&self) -> Result<()> {
+        let camera = self.camera;
+        let in_range = |value: Option<f64>, min: f64, max: f64| {
+            value.is_none_or(|value| value.is_finite() && (min..=max).contains(&value))
+        };
+        if self.shutter_us == Some(0) {
+            bail!("camera {} shutter_us must be non-zero", camera);
+        }
+        if !in_range(self.gain, 1.0, f64::MAX) {
+            bail!("camera {} gain must be at least 1.0", camera);
+        }
+        if !in_range(self.ev, -10.0, 10.0) {
+            bail!("camera {} ev must be between -10 and 10", camera);
+        }
+        if !in_range(self.brightness, -1.0, 1.0) {
+            bail!("camera {} brightness must be between -1 and 1", camera);
+        }
+        for (name, value) in [
+            ("contrast", self.contrast),
+            ("saturation", self.saturation),
+            ("sharpness", self.sharpness),
+            ("lens_position", self.lens_position),
+        ] {
+            if !in_range(value, 0.0, f64::MAX) {
+                bail!("camera {} {} must not be negative", camera, name);
+            }
+        }
+        if let Some((red, blue)) = self.awb_gains {
+            if !in_range(Some(red), 0.0, f64::MAX) || !in_range(Some(blue), 0.0, f64::MAX) {
+                bail!("camera {} awb_gains must not be negative", camera);
+            }
+            if self.awb.is_some_and(|awb| awb != AwbMode::Custom) {
+                bail!("camera {} awb_gains requires awb = \"custom\"", camera);
+            }
+        } else if self.awb == Some(AwbMode::Custom) {
+            bail!("camera {} awb = \"custom\" requires awb_gains", camera);
+        }
+        if self.lens_position.is_some()
+            && self
+                .autofocus
+                .is_some_and(|mode| mode != AutofocusMode::Manual)
+        {
+            bail!(
+                "camera {} lens_position requires autofocus = \"manual\"",
+                camera
+            );
+        }
+        if let Some(crop) = &self.crop {
+            crop.validate(camera)?;
+        }
+        if self
+            .rotation
+            .is_some_and(|rotation| ![0, 90, 180, 270].contains(&rotation))
+        {
+            bail!("camera {} rotation must be 0, 90, 180 or 270", camera);
+        }
+        Ok(())
+    }
+
+    // libcamera 는 90/270 도 회전을 못 하므로 녹화 파일에 ffmpeg 로 돌리는 필터
+    pub fn rotate_filter(&self) -> Option<&'static str> {
+        match self.rotation {
+            Some(90) => Some("transpose=clock"),
+            Some(270) => Some("transpose=cclock"),
+            _ => None,
+        }
+    }
+
+    // libcamera-vid 에 붙일 옵션
+    pub fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        let mut push = |flag: &str, value: String| {
+            args.push(flag.to_string());
+            args.push(value);
+        };
+        if let Some(shutter) = self.shutter_us {
+            push("--shutter", shutter.to_string());
+        }
+        if let Some(gain) = self.gain {
+            push("--gain", gain.to_string());
+        }
+        if let Some(ev) = self.ev {
+            push("--ev", ev.to_string());
+        }
+        if let Some(brightness) = self.brightness {
+            push("--brightness", brightness.to_string());
+        }
+        if let Some(contrast) = self.contrast {
+            push("--contrast", contrast.to_string());
+        }
+        if let Some(saturation) = self.saturation {
+            push("--saturation", saturation.to_string());
+        }
+        if let Some(sharpness) = self.sharpness {
+            push("--sharpness", sharpness.to_string());
+        }
+        // awb_gains 만 지정하면 custom 으로 본다.
+        if let Some((red, blue)) = self.awb_gains {
+            push("--awb", AwbMode::Custom.as_str().to_string());
+            push("--awbgains", format!("{},{}", red, blue));
+        } else if let Some(awb) = self.awb {
+            push("--awb", awb.as_str().to_string());
+        }
+        if let Some(lens_position) = self.lens_position {
+            push(
+                "--autofocus-mode",
+                AutofocusMode::Manual.as_str().to_string(),
+            );
+            push("--lens-position", lens_position.to_string());
+        } else if let Some(autofocus) = self.autofocus {
+            push("--autofocus-mode", autofocus.as_str().to_string());
+        }
+        if let Some(crop) = self.crop {
+            push("--roi", crop.roi());
+        }
+        // 180 도 회전은 좌우와 상하를 모두 뒤집는 것과 같아 센서에서 처리한다.
+        let half_turn = self.rotation == Some(180);
+        if self.hflip != half_turn {
+            args.push("--hflip".to_string());
+        }
+        if self.vflip != half_turn {
+            args.push("--vflip".to_string());
+        }
+        args
+    }
+}
+
+// 지금 적용할 카메라별 화질 설정. recording.camera_settings 로 시작하고 PUT 으로 바꾼다.
+// 새로 여는 libcamera-vid (다음 녹화, 미리보기) 는 항상 이 값을 쓴다.
+pub struct CameraControls {
+    settings: Mutex<BTreeMap<u32, CameraSettings>>,
+}
+
+impl CameraControls {
+    pub fn new(initial: &[CameraSettings]) -> Self {
+        Self {
+            settings: Mutex::new(
+                initial
+                    .iter()
+                    .map(|settings| (settings.camera, settings.clone()))
+                    .collect(),
+            ),
+        }
+    }
+
+    pub fn get(&self, camera: u32) -> CameraSettings {
+        self.settings
+            .lock()
+            .unwrap()
+            .get(&camera)
+            .cloned()
+            .unwrap_or(CameraSettings {
+                camera,
+                ..Default::default()
+            })
+    }
+
+    // 값이 바뀌었으면 true
+    fn set(&self, settings: CameraSettings) -> bool {
+        let mut all = self.settings.lock().unwrap();
+        let previous = all.insert(settings.camera, settings.clone());
+        previous.unwrap_or(CameraSettings {
+            camera: settings.camera,
+            ..Default::default()
+        }) != settings
+    }
+
+    pub fn all(&self) -> Vec<CameraSettings> {
+        self.settings.lock().unwrap().values().cloned().collect()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SettingsResponse {
+    #[serde(flatten)]
+    pub settings: CameraSettings,
+    // 이 카메라를 잠깐 다시 열어 바로 적용할 녹화 세션
+    pub applied_to_sessions: Vec<Uuid>,
+    // 분할 녹화처럼 다시 열 수 없거나 crop 이나 90/270 도 회전이 바뀌어 (파일 해상도가
+    // 달라지므로) 끝날 때까지 이전 설정으로 녹화하는 세션
+    pub pending_sessions: Vec<Uuid>,
+}
+
+// GET /cameras/:id/settings
+pub async fn handle_get(
+    State(state): State<Arc<AppState>>,
+    Path(camera): Path<u32>,
+) -> Json<CameraSettings> {
+    Json(state.camera_controls.get(camera))
+}
+
+// PUT /cameras/:id/settings - 전체를 바꾼다 (생략한 값은 카메라 기본값으로 돌아간다).
+pub async fn handle_put(
+    State(state): State<Arc<AppState>>,
+    Path(camera): Path<u32>,
+    Json(settings): Json<CameraSettings>,
+) -> Result<Json<SettingsResponse>, ApiError> {
+    let settings = CameraSettings { camera, ..settings };
+    settings
+        .validate()
+        .map_err(|e| ApiError::bad_request(e.to_string()))?;
+    let changed = state.camera_controls.set(settings.clone());
+
+    let mut applied_to_sessions = Vec::new();
+    let mut pending_sessions = Vec::new();
+    if changed {
+        info!("Camera {} settings changed: {:?}", camera, settings.args());
+        let running = state.sessions.lock().unwrap().running();
+        for session in running {
+            if !session.stats.lock().unwrap().cameras.contains(&camera) {
+                continue;
+            }
+            if session.config.can_reconnect() && session.config.crop_for(camera) == settings.crop {
+                session
+                    .settings_requested
+                    .lock()
+                    .unwrap()
+                    .push(settings.clone());
+                applied_to_sessions.push(session.id);
+            } else {
+                pending_sessions.push(session.id);
+            }
+        }
+        // 미리보기가 카메라를 직접 열고 있으면 닫아서 새 설정으로 다시 열게 한다.
+        state.streams.release_cameras();
+    }
+
+    Ok(Json(SettingsResponse {
+        settings,
+        applied_to_sessions,
+        pending_sessions,
+    }))
+}
@@ -0,0 +1,279 @@
+// src/chapters.rs
+use crate::{
+    bookmarks::Bookmark,
+    compositor::FFMPEG,
+    detection::{self, Detection},
+    frame_log,
+    sink::{self, Container},
+};
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+use tracing::{info, warn};
+
+// 녹화 중에 남긴 표시 (POST /recordings/current/marker) 로 완성본을 나누는 방식
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChapterMode {
+    #[default]
+    None,
+    // 파일은 하나 그대로 두고 컨테이너에 챕터 정보를 넣는다.
+    Metadata,
+    // 표시마다 새 파일로 나눈다 (<이름>_ch01.mp4, ...). 다시 인코딩하지 않으므로 각 파일은
+    // 표시 뒤 첫 키프레임에서 시작한다.
+    Split,
+}
+
+// 챕터를 담을 수 있는 컨테이너인지 (ffmpeg 로 마무리하지 못해 남긴 .h264 는 아니다)
+pub fn supports(video: &Path) -> bool {
+    matches!(
+        video.extension().and_then(|extension| extension.to_str()),
+        Some("mp4" | "mkv")
+    )
+}
+
+// 파일 첫머리에서 시작해 표시마다 이어지는 (시작 초, 끝 초, 이름)
+fn chapters(marks: &[Bookmark], duration: f64) -> Vec<(f64, f64, String)> {
+    let mut starts: Vec<(f64, String)> = marks
+        .iter()
+        .filter_map(|mark| Some((mark.offset_secs?, mark.label.clone())))
+        .filter(|(at, _)| *at < duration)
+        .collect();
+    if starts.first().is_none_or(|(at, _)| *at > 0.0) {
+        starts.insert(0, (0.0, "Start".to_string()));
+    }
+    let ends: Vec<f64> = starts
+        .iter()
+        .skip(1)
+        .map(|(at, _)| *at)
+        .chain([duration])
+        .collect();
+    starts
+        .into_iter()
+        .zip(ends)
+        .map(|((start, label), end)| (start, end, label))
+        .collect()
+}
+
+// ffmetadata 에서 값에 그대로 쓸 수 없는 문자
+fn escape_metadata(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// ChapterMode::Metadata: 다시 인코딩하지 않고 챕터를 넣은 파일을 만들어 video 를 바꾼다.
+// marks 는 bookmarks::in_file 로 이 파일 기준 시각을 붙인 것, duration 은 파일 길이 (초)
+pub fn write_metadata(
+    video: &Path,
+    marks: &[Bookmark],
+    duration: f64,
+    container: Container,
+) -> Result<()> {
+    let mut script = String::from(";FFMETADATA1\n");
+    let chapters = chapters(marks, duration);
+    for (start, end, label) in &chapters {
+        let _ = write!(
+            script,
+            "[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            (start * 1000.0).round() as u64,
+            (end * 1000.0).round() as u64,
+            escape_metadata(label)
+        );
+    }
+    let metadata = video.with_extension("chapters.txt");
+    fs::write(&metadata, script).with_context(|| format!("Failed to write {:?}", metadata))?;
+    let extension = video.extension().unwrap_or_default().to_string_lossy();
+    let chaptered = video.with_extension(format!("chapters.{}", extension));
+    let status = sink::run(
+        Command::new(FFMPEG)
+            .arg("-y")
+            .arg("-loglevel")
+            .arg("error")
+            .arg("-i")
+            .arg(video)
+            .arg("-f")
+            .arg("ffmetadata")
+            .arg("-i")
+            .arg(&metadata)
+            .arg("-map")
+            .arg("0")
+            .arg("-map_metadata")
+            .arg("0")
+            .arg("-map_chapters")
+            .arg("1")
+            .arg("-c")
+            .arg("copy")
+            .args(container.mux_args(&chaptered))
+            .arg(&chaptered),
+    );
+    let _ = fs::remove_file(&metadata);
+    let status = status?;
+    if !status.success() {
+        let _ = fs::remove_file(&chaptered);
+        bail!("ffmpeg failed to add chapters to {:?}: {}", video, status);
+    }
+    fs::rename(&chaptered, video)
+        .with_context(|| format!("Failed to move {:?} to {:?}", chaptered, video))?;
+    info!("Added {} chapter(s) to {:?}.", chapters.len(), video);
+    Ok(())
+}
+
+// ChapterMode::Split: video 를 표시마다 나누고 (파일, (챕터 시작 초, 끝 초)) 를 돌려준다.
+// 나눌 표시가 없으면 video 하나. 타임스탬프 파일은 첫 파일에 붙이고, 감지 결과는 나눠 담는다.
+pub fn split(
+    video: &Path,
+    marks: &[Bookmark],
+    duration: f64,
+) -> Result<Vec<(PathBuf, (f64, f64))>> {
+    let chapters = chapters(marks, duration);
+    if chapters.len() < 2 {
+        return Ok(vec![(video.to_path_buf(), (0.0, duration))]);
+    }
+    let cuts: Vec<String> = chapters[1..]
+        .iter()
+        .map(|(start, _, _)| format!("{:.3}", start))
+        .collect();
+    let stem = video.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = video.extension().unwrap_or_default().to_string_lossy();
+    let pattern = video.with_file_name(format!("{}_ch%02d.{}", stem, extension));
+    let list = video.with_extension("chapters.csv");
+    let status = sink::run(
+        Command::new(FFMPEG)
+            .arg("-y")
+            .arg("-loglevel")
+            .arg("error")
+            .arg("-i")
+            .arg(video)
+            .arg("-map")
+            .arg("0")
+            .arg("-c")
+            .arg("copy")
+            .arg("-f")
+            .arg("segment")
+            .arg("-segment_times")
+            .arg(cuts.join(","))
+            .arg("-segment_start_number")
+            .arg("1")
+            .arg("-reset_timestamps")
+            .arg("1")
+            .arg("-segment_list")
+            .arg(&list)
+            .arg("-segment_list_type")
+            .arg("csv")
+            .arg(&pattern),
+    )?;
+    // 한 줄에 "파일 이름,시작 초,끝 초" (실제로 자른 키프레임 기준)
+    let written = fs::read_to_string(&list).unwrap_or_default();
+    let _ = fs::remove_file(&list);
+    let pieces: Vec<(PathBuf, f64)> = written
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.rsplitn(3, ',');
+            let _end = fields.next()?;
+            let start: f64 = fields.next()?.parse().ok()?;
+            let name = fields.next()?.trim_matches('"');
+            Some((video.with_file_name(name), start))
+        })
+        .collect();
+    if !status.success() || pieces.is_empty() {
+        for (piece, _) in &pieces {
+            let _ = fs::remove_file(piece);
+        }
+        bail!(
+            "ffmpeg failed to split {:?} into chapters: {}",
+            video,
+            status
+        );
+    }
+    if pieces.len() < chapters.len() {
+        warn!(
+            "{:?}: only {} of {} chapter(s) started on a keyframe; the rest were merged.",
+            video,
+            pieces.len(),
+            chapters.len()
+        );
+    }
+
+    move_sidecars(video, &pieces);
+    fs::remove_file(video).with_context(|| format!("Failed to remove {:?}", video))?;
+    info!("Split {:?} into {} chapter file(s).", video, pieces.len());
+    // 조각마다 실제 시작 직전의 표시부터 다음 조각의 그 표시까지를 챕터 구간으로 본다
+    // (키프레임이 없어 합쳐진 챕터는 앞 조각에 들어간다).
+    let starts: Vec<f64> = pieces
+        .iter()
+        .map(|(_, actual)| {
+            chapters
+                .iter()
+                .rfind(|(at, _, _)| *at <= actual + 0.001)
+                .map_or(0.0, |(at, _, _)| *at)
+        })
+        .collect();
+    let ends = starts.iter().skip(1).copied().chain([duration]);
+    let files = pieces
+        .into_iter()
+        .zip(starts.iter().copied().zip(ends))
+        .map(|((piece, _), window)| (piece, window))
+        .collect();
+    Ok(files)
+}
+
+// pieces 는 (파일, 원래 파일 기준 시작 초)
+fn move_sidecars(video: &Path, pieces: &[(PathBuf, f64)]) {
+    let first = &pieces[0].0;
+    for (from, to) in [
+        (video.with_extension("pts"), first.with_extension("pts")),
+        (
+            frame_log::sidecar_path(video),
+            frame_log::sidecar_path(first),
+        ),
+    ] {
+        if from.exists()
+            && let Err(e) = fs::rename(&from, &to)
+        {
+            warn!("Failed to move {:?} to {:?}: {}", from, to, e);
+        }
+    }
+
+    let sidecar = detection::sidecar_path(video);
+    if !sidecar.exists() {
+        return;
+    }
+    let detections = match detection::read_sidecar(video) {
+        Ok(detections) => detections,
+        Err(e) => {
+            warn!("{:#}", e);
+            return;
+        }
+    };
+    for (i, (piece, start)) in pieces.iter().enumerate() {
+        let from = (start * 1000.0).round() as u64;
+        let to = pieces
+            .get(i + 1)
+            .map_or(u64::MAX, |(_, next)| (next * 1000.0).round() as u64);
+        let lines: Vec<Detection> = detections
+            .iter()
+            .filter(|detection| (from..to).contains(&detection.time_ms))
+            .map(|detection| Detection {
+                time_ms: detection.time_ms - from,
+                ..detection.clone()
+            })
+            .collect();
+        if let Err(e) = detection::write_sidecar(piece, &lines) {
+            warn!("{:#}", e);
+        }
+    }
+    if let Err(e) = fs::remove_file(&sidecar) {
+        warn!("Failed to remove {:?}: {}", sidecar, e);
+    }
+}
@@ -0,0 +1,228 @@
+// src/chat.rs
+use crate::{
+    AppState,
+    events::{Event, EventKind},
+};
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{path::Path, sync::Arc, time::Duration};
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+const TELEGRAM_API: &str = "https://api.telegram.org";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatEvent {
+    Started,
+    Stopped,
+    Failed,
+    DiskLow,
+}
+
+// Telegram 봇이 메시지를 보낼 대화 (bot_token 과 chat_id 를 모두 정해야 보낸다)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TelegramConfig {
+    // /config 에는 노출하지 않는다.
+    #[serde(skip_serializing)]
+    pub bot_token: Option<String>,
+    // 대화 id 또는 "@채널이름"
+    pub chat_id: String,
+}
+
+impl TelegramConfig {
+    fn enabled(&self) -> bool {
+        self.bot_token.is_some()
+    }
+}
+
+// 녹화 시작/종료/실패와 디스크 부족을 사람이 읽는 메시지로 Slack 이나 Telegram 에 올린다.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChatConfig {
+    // Slack incoming webhook 주소. 주소만 알면 누구나 올릴 수 있으므로 /config 에는 노출하지 않는다.
+    #[serde(skip_serializing)]
+    pub slack_urls: Vec<String>,
+    pub telegram: TelegramConfig,
+    // 올릴 이벤트
+    pub events: Vec<ChatEvent>,
+    // 여러 장비의 메시지를 구분하도록 앞에 붙인다.
+    pub prefix: String,
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        Self {
+            slack_urls: Vec::new(),
+            telegram: TelegramConfig::default(),
+            events: vec![
+                ChatEvent::Started,
+                ChatEvent::Stopped,
+                ChatEvent::Failed,
+                ChatEvent::DiskLow,
+            ],
+            prefix: "[recorder]".to_string(),
+        }
+    }
+}
+
+impl ChatConfig {
+    pub fn validate(&self) -> Result<()> {
+        for url in &self.slack_urls {
+            if !url.starts_with("https://") {
+                bail!("webhooks.chat.slack_urls entries must start with https://");
+            }
+        }
+        if self.telegram.enabled() && self.telegram.chat_id.trim().is_empty() {
+            bail!("webhooks.chat.telegram.chat_id must be set with a bot_token");
+        }
+        if !self.telegram.enabled() && !self.telegram.chat_id.is_empty() {
+            bail!("webhooks.chat.telegram.bot_token must be set with a chat_id");
+        }
+        Ok(())
+    }
+
+    fn enabled(&self) -> bool {
+        (!self.slack_urls.is_empty() || self.telegram.enabled()) && !self.events.is_empty()
+    }
+}
+
+fn file_names(outputs: &[impl AsRef<Path>]) -> String {
+    outputs
+        .iter()
+        .map(|output| {
+            let output = output.as_ref();
+            output
+                .file_name()
+                .unwrap_or(output.as_os_str())
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// 올리지 않는 이벤트면 None
+fn message(state: &AppState, config: &ChatConfig, event: &Event) -> Option<String> {
+    let session = state.sessions.lock().unwrap().get(event.session_id);
+    let project = session
+        .as_ref()
+        .and_then(|session| session.config.project.clone())
+        .map_or_else(String::new, |project| format!(" for project {}", project));
+    let (kind, text) = match &event.kind {
+        EventKind::RecordingStarted { cameras } => {
+            let cameras: Vec<String> = cameras.iter().map(u32::to_string).collect();
+            (
+                ChatEvent::Started,
+                format!(
+                    "Recording started{} on camera(s) {}.",
+                    project,
+                    cameras.join(", ")
+                ),
+            )
+        }
+        EventKind::RecordingStopped {
+            error: None,
+            outputs,
+        } => {
+            let elapsed =
+                session.map_or(0.0, |session| session.stats.lock().unwrap().elapsed_seconds);
+            (
+                ChatEvent::Stopped,
+                format!(
+                    "Recording finished{} after {:.0} s: {}",
+                    project,
+                    elapsed,
+                    file_names(outputs)
+                ),
+            )
+        }
+        EventKind::RecordingStopped {
+            error: Some(error), ..
+        } => (
+            ChatEvent::Failed,
+            format!("Recording failed{}: {}", project, error),
+        ),
+        EventKind::DiskLow {
+            free_bytes,
+            min_free_bytes,
+        } => (
+            ChatEvent::DiskLow,
+            format!(
+                "Disk space is low: {} MB free (minimum {} MB). The recording is stopping.",
+                free_bytes / 1_000_000,
+                min_free_bytes / 1_000_000
+            ),
+        ),
+        _ => return None,
+    };
+    config.events.contains(&kind).then(|| {
+        format!("{} {} (session {})", config.prefix, text, event.session_id)
+            .trim_start()
+            .to_string()
+    })
+}
+
+async fn post(client: &reqwest::Client, target: &str, url: &str, body: serde_json::Value) {
+    match client.post(url).json(&body).send().await {
+        Ok(response) if response.status().is_success() => {}
+        Ok(response) => warn!("Failed to post to {}: HTTP {}", target, response.status()),
+        // 오류에 주소 (Telegram 은 봇 토큰) 가 들어가지 않게 뺀다.
+        Err(e) => warn!("Failed to post to {}: {}", target, e.without_url()),
+    }
+}
+
+async fn deliver(client: reqwest::Client, config: Arc<ChatConfig>, text: String) {
+    for url in &config.slack_urls {
+        post(&client, "Slack", url, json!({ "text": text })).await;
+    }
+    if let Some(token) = &config.telegram.bot_token {
+        let url = format!("{}/bot{}/sendMessage", TELEGRAM_API, token);
+        let body = json!({ "chat_id": config.telegram.chat_id, "text": text });
+        post(&client, "Telegram", &url, body).await;
+    }
+}
+
+async fn run(state: Arc<AppState>, client: reqwest::Client, config: Arc<ChatConfig>) {
+    let mut receiver = state.events.subscribe();
+    loop {
+        match receiver.recv().await {
+            Ok(event) => {
+                if let Some(text) = message(&state, &config, &event) {
+                    tokio::spawn(deliver(client.clone(), config.clone(), text));
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                warn!("Chat notifier fell behind; {} event(s) skipped.", skipped);
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        }
+    }
+}
+
+pub fn spawn(state: Arc<AppState>) -> Result<()> {
+    let config = &state.config.webhooks;
+    if !config.chat.enabled() {
+        return Ok(());
+    }
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(config.timeout_secs))
+        .build()?;
+    info!(
+        "Posting recording messages to {} Slack webhook(s){}.",
+        config.chat.slack_urls.len(),
+        if config.chat.telegram.enabled() {
+            " and a Telegram chat"
+        } else {
+            ""
+        }
+    );
+    let chat = Arc::new(config.chat.clone());
+    let tasks = state.tasks.clone();
+    tasks.spawn_service("chat", move || {
+        run(state.clone(), client.clone(), chat.clone())
+    });
+    Ok(())
+}
@@ -0,0 +1,111 @@
+// src/cli.rs
+use crate::config::Config;
+use clap::Parser;
+use std::path::PathBuf;
+
+// 명령줄 인자는 설정 파일과 환경 변수보다 우선한다.
+#[derive(Debug, Clone, Parser)]
+#[command(version, about = "Camera recording server")]
+pub struct Cli {
+    /// Path to the TOML config file (default: $SERVER_CONFIG or ./server.toml)
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// Port to listen on
+    #[arg(long)]
+    pub port: Option<u16>,
+
+    /// Addresses to listen on, e.g. 127.0.0.1,192.168.1.20 or 0.0.0.0:9000
+    #[arg(long, value_delimiter = ',')]
+    pub bind: Option<Vec<String>>,
+
+    /// Directory recordings are written to
+    #[arg(long)]
+    pub save_dir: Option<String>,
+
+    /// Default cameras to record from, e.g. 0,2
+    #[arg(long, value_delimiter = ',')]
+    pub cameras: Option<Vec<u32>>,
+
+    /// Default frame width
+    #[arg(long)]
+    pub width: Option<u32>,
+
+    /// Default frame height
+    #[arg(long)]
+    pub height: Option<u32>,
+
+    /// Default requested frame rate
+    #[arg(long)]
+    pub fps: Option<u32>,
+
+    /// Also serve the legacy GET /start and /stop routes
+    #[arg(long)]
+    pub legacy_get_routes: bool,
+
+    /// Log filter, e.g. info or server=debug
+    #[arg(long)]
+    pub log_level: Option<String>,
+
+    /// Emit logs as JSON lines
+    #[arg(long)]
+    pub log_json: bool,
+
+    /// Append logs (stdout and stderr) to this file
+    #[arg(long)]
+    pub log_file: Option<String>,
+
+    /// Write the server's process ID to this file while it runs
+    #[arg(long)]
+    pub pid_file: Option<String>,
+
+    /// Detach from the terminal and run in the background (Unix); the launching process exits
+    /// once the server has started
+    #[arg(long)]
+    pub daemon: bool,
+
+    /// Write a self-signed certificate for tls.hostnames to tls.cert_path and tls.key_path, then exit
+    #[arg(long)]
+    pub generate_cert: bool,
+}
+
+impl Cli {
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(port) = self.port {
+            config.port = port;
+        }
+        if let Some(bind) = &self.bind {
+            config.bind = bind.clone();
+        }
+        if let Some(save_dir) = &self.save_dir {
+            config.save_dir = save_dir.clone();
+        }
+        if let Some(cameras) = &self.cameras {
+            config.recording.cameras = cameras.clone();
+        }
+        if let Some(width) = self.width {
+            config.recording.width = width;
+        }
+        if let Some(height) = self.height {
+            config.recording.height = height;
+        }
+        if let Some(fps) = self.fps {
+            config.recording.fps = fps;
+        }
+        if self.legacy_get_routes {
+            config.legacy_get_routes = true;
+        }
+        if let Some(log_level) = &self.log_level {
+            config.log_level = log_level.clone();
+        }
+        if self.log_json {
+            config.log_json = true;
+        }
+        if let Some(log_file) = &self.log_file {
+            config.log_file = log_file.clone();
+        }
+        if let Some(pid_file) = &self.pid_file {
+            config.pid_file = pid_file.clone();
+        }
+    }
+}
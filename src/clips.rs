@@ -0,0 +1,264 @@
+// src/clips.rs
+use crate::{
+    ApiError, AppState,
+    compositor::FFMPEG,
+    detection::{self, Detection},
+    metadata,
+    recordings::{self, RecordingEntry},
+};
+use anyhow::{Context, Result, bail};
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use chrono::{DateTime, Local};
+use serde::Deserialize;
+use std::{
+    fs,
+    path::{Path as FsPath, PathBuf},
+    process::Command,
+    sync::Arc,
+};
+use tracing::{info, warn};
+
+// 파일 기준 초 (예: 12.5) 또는 벽시계 시각 (RFC 3339, 사이드카의 started_at 기준으로 바꾼다)
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(untagged)]
+pub enum ClipTime {
+    Offset(f64),
+    At(DateTime<Local>),
+}
+
+impl ClipTime {
+    fn offset(self, started_at: Option<DateTime<Local>>) -> Result<f64, ApiError> {
+        match (self, started_at) {
+            (Self::Offset(secs), _) => Ok(secs),
+            (Self::At(at), Some(started_at)) => {
+                Ok((at - started_at).num_milliseconds() as f64 / 1000.0)
+            }
+            (Self::At(_), None) => Err(ApiError::bad_request(
+                "This recording has no metadata; give start and end in seconds",
+            )),
+        }
+    }
+}
+
+// Body of POST /recordings/{name}/clip
+#[derive(Debug, Deserialize)]
+pub struct ClipRequest {
+    pub start: ClipTime,
+    pub end: ClipTime,
+    // true 면 다시 인코딩해 정확히 start 에서 자른다. false 면 스트림을 그대로 복사하므로
+    // start 직전 키프레임부터 담긴다 (복사할 수 없는 파일은 다시 인코딩한다).
+    #[serde(default)]
+    pub accurate: bool,
+}
+
+// POST /recordings/{name}/clip - 녹화의 일부를 잘라 새 녹화로 저장하고 카탈로그에 넣는다.
+// /recordings/*name 에 함께 걸려 있으므로 이름이 /clip 으로 끝나야 한다.
+pub async fn handle_create(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Json(request): Json<ClipRequest>,
+) -> Result<(StatusCode, Json<RecordingEntry>), ApiError> {
+    let Some(name) = name.strip_suffix("/clip") else {
+        return Err(ApiError::not_found(format!("No such endpoint: {}", name)));
+    };
+    let save_dir = state.config.save_dir();
+    let source = recordings::resolve(&save_dir, name)?;
+    if !source.is_file() || !recordings::is_video(&source) {
+        return Err(ApiError::not_found(format!("Recording {} not found", name)));
+    }
+    if recordings::is_active_output(&state, &source) {
+        return Err(ApiError::conflict(format!(
+            "Recording {} is still being written",
+            name
+        )));
+    }
+
+    let task_state = state.clone();
+    let name = name.to_string();
+    let entry = tokio::task::spawn_blocking(move || create(&task_state, &name, &source, &request))
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))??;
+    Ok((StatusCode::CREATED, Json(entry)))
+}
+
+fn create(
+    state: &AppState,
+    name: &str,
+    source: &FsPath,
+    request: &ClipRequest,
+) -> Result<RecordingEntry, ApiError> {
+    let source_metadata = metadata::read(source);
+    let started_at = source_metadata.as_ref().map(|metadata| metadata.started_at);
+    let start = request.start.offset(started_at)?;
+    let mut end = request.end.offset(started_at)?;
+    if !start.is_finite() || !end.is_finite() || start < 0.0 || end <= start {
+        return Err(ApiError::bad_request(
+            "start must be at least 0 and end must be after start",
+        ));
+    }
+    if let Some(duration) = recordings::probe_duration(source) {
+        if start >= duration {
+            return Err(ApiError::bad_request(format!(
+                "start is past the end of the recording ({:.3}s)",
+                duration
+            )));
+        }
+        end = end.min(duration);
+    }
+
+    let clip = clip_path(source, (start, end));
+    if clip.exists() {
+        return Err(ApiError::conflict(format!(
+            "Clip {} already exists",
+            recordings::relative_name(&state.config.save_dir(), &clip)
+        )));
+    }
+    cut(source, (start, end), request.accurate, &clip)
+        .map_err(|e| ApiError::internal(format!("{:#}", e)))?;
+    if let Some(source_metadata) = &source_metadata {
+        let metadata = source_metadata.clip(name, (start, end), &clip);
+        if let Err(e) = metadata::write(&clip, &metadata) {
+            warn!("{:#}", e);
+        }
+    }
+    copy_detections(source, (start, end), &clip);
+
+    let save_dir = state.config.save_dir();
+    let file = fs::metadata(&clip).map_err(|e| ApiError::internal(e.to_string()))?;
+    let entry = recordings::load_entry(&save_dir, &clip, &file, true)
+        .map_err(|e| ApiError::internal(format!("{:#}", e)))?;
+    if let Err(e) = state.catalog.add(&save_dir, std::slice::from_ref(&clip)) {
+        warn!("Failed to add the clip to the catalog: {:#}", e);
+    }
+    state.uploader.enqueue(std::slice::from_ref(&clip));
+    info!(
+        "Clipped {} ({:.3}s - {:.3}s) into {}",
+        name, start, end, entry.name
+    );
+    Ok(entry)
+}
+
+// <원래 이름>_clip_<시작 ms>-<끝 ms>.<확장자> (사이드카 이름이 꼬이지 않도록 점을 넣지 않는다)
+fn clip_path(source: &FsPath, (start, end): (f64, f64)) -> PathBuf {
+    let stem = source.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = source.extension().unwrap_or_default().to_string_lossy();
+    source.with_file_name(format!(
+        "{}_clip_{}-{}.{}",
+        stem,
+        (start * 1000.0).round() as u64,
+        (end * 1000.0).round() as u64,
+        extension
+    ))
+}
+
+// 숨긴 .<이름>.partial 에 다 쓴 뒤 이름을 바꿔, 실패하거나 쓰는 중인 파일이 목록에 잡히지 않게 한다.
+fn cut(source: &FsPath, (start, end): (f64, f64), accurate: bool, clip: &FsPath) -> Result<()> {
+    let name = clip.file_name().unwrap_or_default().to_string_lossy();
+    let partial = clip.with_file_name(format!(".{}.partial", name));
+    let extension = clip.extension().unwrap_or_default().to_string_lossy();
+    let run = |copy: bool| -> Result<()> {
+        let mut command = Command::new(FFMPEG);
+        command
+            .arg("-y")
+            .arg("-loglevel")
+            .arg("error")
+            .arg("-ss")
+            .arg(format!("{:.3}", start))
+            .arg("-i")
+            .arg(source)
+            .arg("-t")
+            .arg(format!("{:.3}", end - start));
+        if copy {
+            command
+                .arg("-map")
+                .arg("0")
+                .arg("-c")
+                .arg("copy")
+                .arg("-avoid_negative_ts")
+                .arg("make_zero");
+        } else {
+            command
+                .arg("-map")
+                .arg("0:v")
+                .arg("-map")
+                .arg("0:a?")
+                .arg("-c:v")
+                .arg("libx264")
+                .arg("-preset")
+                .arg("veryfast")
+                .arg("-crf")
+                .arg("18")
+                .arg("-c:a")
+                .arg("copy");
+        }
+        // .partial 로는 형식을 알 수 없으므로 직접 고른다.
+        let status = command
+            .arg("-f")
+            .arg(muxer(&extension))
+            .arg(&partial)
+            .status()
+            .context("Failed to run ffmpeg")?;
+        if !status.success() {
+            bail!("ffmpeg failed to clip {:?}: {}", source, status);
+        }
+        Ok(())
+    };
+    let result = if accurate {
+        run(false)
+    } else {
+        run(true).or_else(|e| {
+            warn!("{:#}. Re-encoding the clip instead.", e);
+            run(false)
+        })
+    };
+    if let Err(e) = result {
+        let _ = fs::remove_file(&partial);
+        return Err(e);
+    }
+    fs::rename(&partial, clip)
+        .with_context(|| format!("Failed to move {:?} to {:?}", partial, clip))
+}
+
+// 확장자에 맞는 ffmpeg 출력 형식 (recordings::VIDEO_EXTENSIONS)
+fn muxer(extension: &str) -> &str {
+    match extension.to_ascii_lowercase().as_str() {
+        "mkv" => "matroska",
+        "ts" => "mpegts",
+        "avi" => "avi",
+        "h264" => "h264",
+        _ => "mp4",
+    }
+}
+
+// 원래 녹화의 감지 결과 중 잘라 낸 구간의 것만 clip 기준 시각으로 옮긴다.
+fn copy_detections(source: &FsPath, (start, end): (f64, f64), clip: &FsPath) {
+    if !detection::sidecar_path(source).exists() {
+        return;
+    }
+    let detections = match detection::read_sidecar(source) {
+        Ok(detections) => detections,
+        Err(e) => {
+            warn!("{:#}", e);
+            return;
+        }
+    };
+    let (from, to) = (
+        (start * 1000.0).round() as u64,
+        (end * 1000.0).round() as u64,
+    );
+    let lines: Vec<Detection> = detections
+        .iter()
+        .filter(|detection| (from..to).contains(&detection.time_ms))
+        .map(|detection| Detection {
+            time_ms: detection.time_ms - from,
+            ..detection.clone()
+        })
+        .collect();
+    if let Err(e) = detection::write_sidecar(clip, &lines) {
+        warn!("{:#}", e);
+    }
+}
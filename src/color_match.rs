@@ -0,0 +1,199 @@
+// src/color_match.rs
+use crate::{
+    compositor::{CompositeInput, FFMPEG},
+    overlay,
+    scaling::Scaling,
+};
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Write as _,
+    fs,
+    io::Read,
+    path::Path,
+    process::{Command, Stdio},
+};
+use tracing::info;
+
+// 평균을 잴 때 줄이는 크기 (평균만 보므로 작아도 된다)
+const SAMPLE_SIZE: usize = 64;
+
+// 합성본에서 카메라마다 밝기와 색이 다르게 보이지 않도록, 기준 카메라의 채널별 평균에 맞춰
+// 다른 카메라에 게인을 건다 (gray world 방식). 마무리할 때 합성하는 영상에만 적용하고
+// 카메라별 파일은 그대로 둔다.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColorMatchConfig {
+    pub enabled: bool,
+    // 다른 카메라를 맞출 카메라. None 이면 첫 카메라
+    pub reference: Option<u32>,
+    // 평균을 재는 간격 (초당 프레임 수)
+    pub sample_fps: f64,
+    // 새로 잰 게인의 비중 (0 ~ 1). 작을수록 천천히 바뀌어 깜빡이지 않는다.
+    pub smoothing: f64,
+    // 이보다 크게 (또는 1 / max_gain 보다 작게) 고치지 않는다.
+    pub max_gain: f64,
+}
+
+impl Default for ColorMatchConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            reference: None,
+            sample_fps: 1.0,
+            smoothing: 0.3,
+            max_gain: 2.0,
+        }
+    }
+}
+
+impl ColorMatchConfig {
+    pub fn validate(&self, cameras: &[u32]) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        if !self.sample_fps.is_finite() || self.sample_fps <= 0.0 {
+            bail!("color_match.sample_fps must be positive");
+        }
+        if !(self.smoothing > 0.0 && self.smoothing <= 1.0) {
+            bail!("color_match.smoothing must be in (0, 1]");
+        }
+        if !self.max_gain.is_finite() || self.max_gain < 1.0 {
+            bail!("color_match.max_gain must be at least 1.0");
+        }
+        if let Some(reference) = self.reference
+            && !cameras.contains(&reference)
+        {
+            bail!("color_match.reference camera {} is not recorded", reference);
+        }
+        Ok(())
+    }
+
+    pub fn reference(&self, cameras: &[u32]) -> Option<u32> {
+        self.reference.or_else(|| cameras.first().copied())
+    }
+
+    // 기준 카메라와 맞출 카메라의 평균에서 시각별 (초, [R, G, B] 게인) 을 구한다.
+    // 첫 게인은 그대로 쓰고, 그다음부터 smoothing 만큼씩 따라간다.
+    fn gains(&self, reference: &[[f64; 3]], camera: &[[f64; 3]]) -> Vec<(f64, [f64; 3])> {
+        let mut current: [Option<f64>; 3] = [None; 3];
+        reference
+            .iter()
+            .zip(camera)
+            .enumerate()
+            .map(|(i, (reference, camera))| {
+                for ((current, &reference), &camera) in
+                    current.iter_mut().zip(reference).zip(camera)
+                {
+                    // 거의 검은 프레임은 비율이 의미 없으므로 앞의 게인을 그대로 쓴다.
+                    if camera < 1.0 || reference < 1.0 {
+                        continue;
+                    }
+                    let gain = (reference / camera).clamp(1.0 / self.max_gain, self.max_gain);
+                    *current = Some(match *current {
+                        Some(previous) => previous + (gain - previous) * self.smoothing,
+                        None => gain,
+                    });
+                }
+                (
+                    i as f64 / self.sample_fps,
+                    current.map(|gain| gain.unwrap_or(1.0)),
+                )
+            })
+            .collect()
+    }
+
+    // camera 의 스트림에 거는 필터. 게인 변화를 commands 에 sendcmd 명령으로 써 둔다.
+    // 두 입력은 이미 같은 시각에서 시작하도록 잘려 있어야 한다.
+    pub fn filter(
+        &self,
+        camera: u32,
+        reference: &CompositeInput,
+        input: &CompositeInput,
+        commands: &Path,
+    ) -> Result<String> {
+        let reference_means = measure(reference, self.sample_fps)?;
+        let means = measure(input, self.sample_fps)?;
+        let gains = self.gains(&reference_means, &means);
+        let Some(&(_, first)) = gains.first() else {
+            bail!("No frames decoded for color matching");
+        };
+
+        let name = format!("colorchannelmixer@match{}", camera);
+        let mut script = String::new();
+        for (time, [red, green, blue]) in &gains {
+            let _ = writeln!(
+                script,
+                "{:.3} {name} rr {:.4}, {name} gg {:.4}, {name} bb {:.4};",
+                time, red, green, blue
+            );
+        }
+        fs::write(commands, script).with_context(|| format!("Failed to write {:?}", commands))?;
+        let last = gains.last().map_or(first, |&(_, gains)| gains);
+        info!(
+            "camera {}: matching colors over {} sample(s), final gains R {:.2} G {:.2} B {:.2}.",
+            camera,
+            gains.len(),
+            last[0],
+            last[1],
+            last[2]
+        );
+        Ok(format!(
+            "sendcmd=f='{}',{}=rr={:.4}:gg={:.4}:bb={:.4}",
+            overlay::escape_option(&commands.to_string_lossy()),
+            name,
+            first[0],
+            first[1],
+            first[2]
+        ))
+    }
+}
+
+// 입력을 fps 로 줄여 디코딩하고 프레임마다 채널별 평균 (0 ~ 255) 을 구한다.
+fn measure(input: &CompositeInput, fps: f64) -> Result<Vec<[f64; 3]>> {
+    let mut command = Command::new(FFMPEG);
+    command.arg("-loglevel").arg("error");
+    if let Some(offset) = input.seek() {
+        command.arg("-ss").arg(format!("{:.3}", offset));
+    }
+    if input.fps > 0.0 {
+        command.arg("-r").arg(format!("{:.3}", input.fps));
+    }
+    let mut filter = format!(
+        "fps={},{},format=rgb24",
+        fps,
+        Scaling::stretch(SAMPLE_SIZE as u32, SAMPLE_SIZE as u32).filter()
+    );
+    if let Some(before) = input.full_filter() {
+        filter = format!("{},{}", before, filter);
+    }
+    let mut decoder = command
+        .arg("-i")
+        .arg(&input.path)
+        .arg("-vf")
+        .arg(filter)
+        .arg("-f")
+        .arg("rawvideo")
+        .arg("pipe:1")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("Failed to start ffmpeg to measure colors")?;
+    let mut stdout = decoder.stdout.take().context("ffmpeg stdout unavailable")?;
+
+    let mut frame = vec![0u8; SAMPLE_SIZE * SAMPLE_SIZE * 3];
+    let mut means = Vec::new();
+    while stdout.read_exact(&mut frame).is_ok() {
+        let mut sums = [0u64; 3];
+        for pixel in frame.chunks_exact(3) {
+            for (sum, &value) in sums.iter_mut().zip(pixel) {
+                *sum += value as u64;
+            }
+        }
+        let pixels = (SAMPLE_SIZE * SAMPLE_SIZE) as f64;
+        means.push(sums.map(|sum| sum as f64 / pixels));
+    }
+    let _ = decoder.kill();
+    let _ = decoder.wait();
+    Ok(means)
+}
@@ -0,0 +1,506 @@
+// src/compositor.rs
+use crate::{
+    encoder::Encoder,
+    overlay::OverlayPosition,
+    panorama::PanoramaConfig,
+    sink::{self, Container, Encoding},
+    watermark::WatermarkConfig,
+};
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    time::Duration,
+};
+use tracing::info;
+
+pub const FFMPEG: &str = "ffmpeg";
+
+// 여러 카메라 영상을 한 프레임에 배치하는 방식
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Layout {
+    #[default]
+    Horizontal,
+    Vertical,
+    Grid,
+    // 첫 카메라 위 한쪽 구석에 두 번째 카메라를 작게 겹친다.
+    PictureInPicture,
+    // 한 번에 한 카메라만 보여 주고, 녹화 중 POST /switch 로 바꾼다.
+    Switch,
+    // 겹쳐 찍는 두 카메라를 호모그래피로 이어 붙여 한 장의 파노라마로 만든다.
+    Panorama,
+}
+
+// Layout::PictureInPicture 의 작은 화면 위치와 크기
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PipConfig {
+    pub corner: OverlayPosition,
+    // 출력 너비 대비 작은 화면의 너비 (0 ~ 1)
+    pub scale: f64,
+    // 출력 가장자리와의 여백 (픽셀)
+    pub margin: u32,
+}
+
+impl Default for PipConfig {
+    fn default() -> Self {
+        Self {
+            corner: OverlayPosition::BottomRight,
+            scale: 0.3,
+            margin: 16,
+        }
+    }
+}
+
+impl PipConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !(self.scale > 0.0 && self.scale < 1.0) {
+            bail!("pip.scale must be between 0 and 1");
+        }
+        Ok(())
+    }
+}
+
+// 합성 배치에 필요한 설정을 모은 것
+#[derive(Debug, Clone)]
+pub struct Arrangement {
+    pub layout: Layout,
+    // Layout::Grid 전용
+    pub grid_columns: Option<u32>,
+    // 기준 출력 크기. 해상도가 다른 입력은 fit_filter 로 여기에 맞춘다.
+    pub width: u32,
+    pub height: u32,
+    pub pip: PipConfig,
+    // Layout::Panorama 전용
+    pub panorama: PanoramaConfig,
+    // Layout::Switch 전용: (파일 시작 기준 초, 입력 번호) 의 시간순 목록. 첫 전환 전에는 첫 입력
+    pub switches: Vec<(f64, usize)>,
+    // 배치를 마친 영상 전체에 적용할 필터 (예: 타임랩스 프레임 고르기)
+    pub output_filter: Option<String>,
+    // 마지막에 겹칠 로고
+    pub watermark: WatermarkConfig,
+}
+
+// 합성 입력 하나: 카메라별 원본 H.264 스트림과 실제 측정 FPS
+#[derive(Clone)]
+pub struct CompositeInput {
+    pub path: PathBuf,
+    pub fps: f64,
+    // 배치 전에 이 입력에만 적용할 필터 (예: 오버레이)
+    pub filter: Option<String>,
+    // 다른 카메라보다 먼저 시작한 만큼 앞부분을 잘라낼 시간 (초)
+    pub start_offset: f64,
+    // 고정 FPS 모드: 프레임 번호 N 을 그 프레임이 놓일 시각(초)으로 바꾸는 식.
+    // 쉼표가 없어 필터 그래프와 비트스트림 필터 어디에나 그대로 넣을 수 있다.
+    pub retime: Option<String>,
+}
+
+impl CompositeInput {
+    // 디코딩 전에 -ss 로 앞부분을 잘라낼 시간. 시각을 다시 매기는 입력은 프레임 번호가
+    // 처음부터 세어져야 하므로 timing_filter 에서 자른다.
+    pub fn seek(&self) -> Option<f64> {
+        (self.retime.is_none() && self.start_offset > 0.0).then_some(self.start_offset)
+    }
+
+    // 디코딩한 프레임에 retime 시각을 매기고 start_offset 만큼 자르는 필터
+    pub fn timing_filter(&self) -> Option<String> {
+        let retime = self.retime.as_ref()?;
+        let mut filter = format!("setpts=({})/TB", retime);
+        if self.start_offset > 0.0 {
+            filter.push_str(&format!(
+                ",trim=start={:.3},setpts=PTS-STARTPTS",
+                self.start_offset
+            ));
+        }
+        Some(filter)
+    }
+
+    // timing_filter 다음에 filter 를 적용한다.
+    pub fn full_filter(&self) -> Option<String> {
+        match (self.timing_filter(), &self.filter) {
+            (Some(timing), Some(filter)) => Some(format!("{},{}", timing, filter)),
+            (timing, filter) => timing.or_else(|| filter.clone()),
+        }
+    }
+}
+
+// 그리드 열 수가 지정되지 않으면 가능한 정사각형에 가깝게 배치 (4대 -> 2x2)
+pub fn grid_columns(inputs: usize, columns: Option<u32>) -> usize {
+    match columns {
+        Some(columns) if columns > 0 => (columns as usize).min(inputs),
+        _ => (inputs as f64).sqrt().ceil() as usize,
+    }
+}
+
+// 해상도가 다른 입력을 배치할 수 있게 맞추는 필터. 가로 배치는 비율을 유지한 채 높이만 맞추고,
+// 나머지 배치는 width x height 칸 가운데에 넣고 남는 부분을 검게 채운다. 파노라마는 호모그래피가
+// width x height 기준이므로 그 크기로 늘인다.
+pub fn fit_filter(layout: Layout, width: u32, height: u32) -> String {
+    match layout {
+        Layout::Horizontal => format!("scale=-2:{},setsar=1", height),
+        Layout::Panorama => format!("scale={}:{},setsar=1", width, height),
+        Layout::Vertical | Layout::Grid | Layout::PictureInPicture | Layout::Switch => format!(
+            "scale={w}:{h}:force_original_aspect_ratio=decrease,\
+             pad={w}:{h}:(ow-iw)/2:(oh-ih)/2,setsar=1",
+            w = width,
+            h = height
+        ),
+    }
+}
+
+// 입력별 필터가 있으면 먼저 적용한 뒤 배치한다.
+pub fn filter_graph(arrangement: &Arrangement, filters: &[Option<String>]) -> String {
+    let inputs = filters.len();
+    let (layout, columns) = (arrangement.layout, arrangement.grid_columns);
+    let stacked = !matches!(
+        layout,
+        Layout::PictureInPicture | Layout::Switch | Layout::Panorama
+    );
+    if stacked && filters.iter().all(Option::is_none) {
+        return stack_filter(layout, inputs, columns);
+    }
+    if inputs == 1 {
+        return format!("[0:v]{}", filters[0].as_deref().unwrap_or("null"));
+    }
+
+    let mut chains: Vec<String> = filters
+        .iter()
+        .enumerate()
+        .map(|(i, filter)| format!("[{}:v]{}[v{}]", i, filter.as_deref().unwrap_or("null"), i))
+        .collect();
+    match layout {
+        Layout::PictureInPicture => chains.extend(pip_chains(arrangement, inputs)),
+        Layout::Switch => chains.extend(switch_chains(&arrangement.switches, inputs)),
+        Layout::Panorama => chains.extend(arrangement.panorama.chains(
+            "v0",
+            "v1",
+            arrangement.width,
+            arrangement.height,
+        )),
+        _ => {
+            let labels: String = (0..inputs).map(|i| format!("[v{}]", i)).collect();
+            chains.push(format!(
+                "{}{}",
+                labels,
+                stack_filter(layout, inputs, columns)
+            ));
+        }
+    }
+    chains.join(";")
+}
+
+fn stack_filter(layout: Layout, inputs: usize, columns: Option<u32>) -> String {
+    match layout {
+        Layout::Vertical => format!("vstack=inputs={}", inputs),
+        Layout::Grid => {
+            // 모든 입력의 해상도가 같으므로 (다르면 fit_filter 로 맞춘다) w0/h0 의 배수로 위치를 지정한다.
+            let columns = grid_columns(inputs, columns);
+            let offset = |count: usize, unit: &str| {
+                if count == 0 {
+                    "0".to_string()
+                } else {
+                    vec![unit; count].join("+")
+                }
+            };
+            let positions: Vec<String> = (0..inputs)
+                .map(|i| {
+                    format!(
+                        "{}_{}",
+                        offset(i % columns, "w0"),
+                        offset(i / columns, "h0")
+                    )
+                })
+                .collect();
+            format!(
+                "xstack=inputs={}:layout={}:fill=black",
+                inputs,
+                positions.join("|")
+            )
+        }
+        _ => format!("hstack=inputs={}", inputs),
+    }
+}
+
+// 두 번째 입력부터 줄여서 첫 입력 위 구석에 겹친다 (세 대 이상이면 구석에서 안쪽으로 나란히).
+fn pip_chains(arrangement: &Arrangement, inputs: usize) -> Vec<String> {
+    let pip = &arrangement.pip;
+    let inset_width = ((arrangement.width as f64 * pip.scale) as u32 / 2 * 2).max(2);
+    let mut chains = Vec::with_capacity(inputs * 2);
+    let mut base = "v0".to_string();
+    for i in 1..inputs {
+        chains.push(format!("[v{}]scale={}:-2[inset{}]", i, inset_width, i));
+        // 앞선 작은 화면들만큼 안쪽으로 민다.
+        let shift = (i as u32 - 1) * (inset_width + pip.margin) + pip.margin;
+        let (x, y) = match pip.corner {
+            OverlayPosition::TopLeft => (shift.to_string(), pip.margin.to_string()),
+            OverlayPosition::TopRight => (
+                format!("main_w-overlay_w-{}", shift),
+                pip.margin.to_string(),
+            ),
+            OverlayPosition::BottomLeft => (
+                shift.to_string(),
+                format!("main_h-overlay_h-{}", pip.margin),
+            ),
+            OverlayPosition::BottomRight => (
+                format!("main_w-overlay_w-{}", shift),
+                format!("main_h-overlay_h-{}", pip.margin),
+            ),
+        };
+        let label = if i + 1 == inputs {
+            String::new()
+        } else {
+            format!("[pip{}]", i)
+        };
+        chains.push(format!(
+            "[{}][inset{}]overlay=x={}:y={}{}",
+            base, i, x, y, label
+        ));
+        base = format!("pip{}", i);
+    }
+    chains
+}
+
+// 입력마다 화면에 나와야 하는 구간에만 첫 입력 위에 겹친다 (모든 입력이 같은 크기).
+fn switch_chains(switches: &[(f64, usize)], inputs: usize) -> Vec<String> {
+    let mut chains = Vec::with_capacity(inputs);
+    let mut base = "v0".to_string();
+    for i in 1..inputs {
+        let mut intervals = Vec::new();
+        for (n, &(start, input)) in switches.iter().enumerate() {
+            if input != i {
+                continue;
+            }
+            intervals.push(match switches.get(n + 1) {
+                Some(&(end, _)) => format!("between(t,{:.3},{:.3})", start, end),
+                None => format!("gte(t,{:.3})", start),
+            });
+        }
+        let enable = if intervals.is_empty() {
+            "0".to_string()
+        } else {
+            intervals.join("+")
+        };
+        let label = if i + 1 == inputs {
+            String::new()
+        } else {
+            format!("[switch{}]", i)
+        };
+        chains.push(format!(
+            "[{}][v{}]overlay=enable='{}'{}",
+            base, i, enable, label
+        ));
+        base = format!("switch{}", i);
+    }
+    chains
+}
+
+// 카메라별 정지 이미지를 녹화와 같은 배치로 한 장에 합친다.
+// 카메라마다 해상도가 다를 수 있으므로 모두 배치의 기준 크기에 맞춘다.
+pub fn compose_image(inputs: &[PathBuf], arrangement: &Arrangement, output: &Path) -> Result<()> {
+    if inputs.len() < 2 {
+        bail!("Composing requires at least two inputs");
+    }
+
+    let mut command = Command::new(FFMPEG);
+    command.arg("-y").arg("-loglevel").arg("error");
+    for input in inputs {
+        command.arg("-i").arg(input);
+    }
+    let status = command
+        .arg("-filter_complex")
+        .arg(filter_graph(
+            arrangement,
+            &vec![
+                Some(fit_filter(
+                    arrangement.layout,
+                    arrangement.width,
+                    arrangement.height
+                ));
+                inputs.len()
+            ],
+        ))
+        .arg("-frames:v")
+        .arg("1")
+        .arg(output)
+        .status()
+        .context("Failed to run ffmpeg")?;
+    if !status.success() {
+        bail!("ffmpeg failed to compose snapshot images: {}", status);
+    }
+    Ok(())
+}
+
+pub fn compose(
+    inputs: &[CompositeInput],
+    arrangement: &Arrangement,
+    fps: u32,
+    encoding: &Encoding,
+    output: &Path,
+) -> Result<()> {
+    // 한 대만 있으면 필터를 적용할 때만 의미가 있다.
+    let filtered = arrangement.output_filter.is_some() || arrangement.watermark.enabled;
+    if inputs.is_empty() || (inputs.len() == 1 && inputs[0].filter.is_none() && !filtered) {
+        bail!("Composing requires at least two inputs or a filter");
+    }
+    info!(
+        "Composing {} camera stream(s) ({:?}) into {:?}...",
+        inputs.len(),
+        arrangement.layout,
+        output
+    );
+
+    let filters: Vec<Option<String>> = inputs.iter().map(CompositeInput::full_filter).collect();
+    // 한 대뿐이고 입력 필터도 없으면 배치할 것이 없다.
+    let mut graph = if inputs.len() == 1 && filters[0].is_none() {
+        "[0:v]null".to_string()
+    } else {
+        filter_graph(arrangement, &filters)
+    };
+    if let Some(filter) = &arrangement.output_filter {
+        graph = format!("{},{}", graph, filter);
+    }
+    let graph = arrangement.watermark.apply(&graph);
+    let build = |encoder: Encoder| {
+        let mut command = Command::new(FFMPEG);
+        command
+            .arg("-y")
+            .arg("-loglevel")
+            .arg("error")
+            .args(encoder.input_args());
+        for input in inputs {
+            if let Some(offset) = input.seek() {
+                command.arg("-ss").arg(format!("{:.3}", offset));
+            }
+            command
+                .arg("-r")
+                .arg(format!("{:.3}", input_fps(input, fps)))
+                .arg("-i")
+                .arg(&input.path);
+        }
+        command
+            .arg("-filter_complex")
+            .arg(encoder.with_upload(&graph))
+            .arg("-r")
+            .arg(fps.to_string());
+        command
+    };
+    let status = sink::encode(encoding, build, output)?;
+    if !status.success() {
+        bail!("ffmpeg failed to compose camera streams: {}", status);
+    }
+    Ok(())
+}
+
+// 한 대의 원본 스트림을 디코딩 없이 container 에 담고 실제 FPS 를 기록한다.
+// retime 이 있으면 프레임마다 그 시각을 매긴다 (빠진 칸은 앞 프레임이 그만큼 길게 보인다).
+pub fn remux(input: &CompositeInput, fps: u32, container: Container, output: &Path) -> Result<()> {
+    let mut command = Command::new(FFMPEG);
+    command
+        .arg("-y")
+        .arg("-loglevel")
+        .arg("error")
+        .arg("-r")
+        .arg(format!("{:.3}", input_fps(input, fps)))
+        .arg("-i")
+        .arg(&input.path)
+        .arg("-c")
+        .arg("copy");
+    if let Some(retime) = &input.retime {
+        command
+            .arg("-bsf:v")
+            .arg(format!("setts=ts=({})/TB", retime));
+    }
+    let status = sink::run(command.args(container.mux_args(output)).arg(output))?;
+    if !status.success() {
+        bail!("ffmpeg failed to remux {:?}: {}", input.path, status);
+    }
+    Ok(())
+}
+
+// remux 가 안 되는 스트림이나 가림 영역처럼 프레임을 바꿔야 할 때: 전체를 다시 인코딩한다.
+pub fn reencode(
+    input: &CompositeInput,
+    fps: u32,
+    encoding: &Encoding,
+    output: &Path,
+) -> Result<()> {
+    info!("Re-encoding {:?} into {:?}...", input.path, output);
+    let build = |encoder: Encoder| {
+        let mut command = Command::new(FFMPEG);
+        command
+            .arg("-y")
+            .arg("-loglevel")
+            .arg("error")
+            .args(encoder.input_args())
+            .arg("-r")
+            .arg(format!("{:.3}", input_fps(input, fps)))
+            .arg("-i")
+            .arg(&input.path)
+            .arg("-vf")
+            .arg(encoder.with_upload(input.full_filter().as_deref().unwrap_or("null")))
+            .arg("-r")
+            .arg(fps.to_string());
+        command
+    };
+    let status = sink::encode(encoding, build, output)?;
+    if !status.success() {
+        bail!("ffmpeg failed to re-encode {:?}: {}", input.path, status);
+    }
+    Ok(())
+}
+
+// 측정값이 없으면 요청한 FPS
+fn input_fps(input: &CompositeInput, fps: u32) -> f64 {
+    if input.fps > 0.0 {
+        input.fps
+    } else {
+        fps as f64
+    }
+}
+
+// 카메라가 끊긴 구간을 채울 "NO SIGNAL" 화면 (녹화와 같은 raw H.264 형식)
+pub fn no_signal_clip(
+    width: u32,
+    height: u32,
+    fps: u32,
+    duration: Duration,
+    encoding: &Encoding,
+    output: &Path,
+) -> Result<()> {
+    let source = format!(
+        "color=c=black:s={}x{}:r={}:d={:.3}",
+        width,
+        height,
+        fps,
+        duration.as_secs_f64()
+    );
+    let text = format!(
+        "drawtext=text='NO SIGNAL':x=(w-tw)/2:y=(h-th)/2:fontsize={}:fontcolor=white",
+        height / 10
+    );
+    let build = |encoder: Encoder| {
+        let mut command = Command::new(FFMPEG);
+        command
+            .arg("-y")
+            .arg("-loglevel")
+            .arg("error")
+            .args(encoder.input_args())
+            .arg("-f")
+            .arg("lavfi")
+            .arg("-i")
+            .arg(&source)
+            .arg("-vf")
+            .arg(encoder.with_upload(&text))
+            .arg("-f")
+            .arg("h264");
+        command
+    };
+    // output 이 .h264 이므로 GStreamer 도 raw H.264 로 쓴다.
+    let status = sink::encode(encoding, build, output)?;
+    if !status.success() {
+        bail!("ffmpeg failed to generate a placeholder clip: {}", status);
+    }
+    Ok(())
+}
@@ -0,0 +1,394 @@
+// src/config.rs
+use crate::{
+    StartRequest, auth::AuthConfig, camera_handler::RecordingConfig, cors::CorsConfig,
+    email::EmailConfig, feed::WriteQueueConfig, gpio::GpioConfig, keep_alive::KeepAliveConfig,
+    limits::LimitsConfig, live::LiveConfig, motion::MotionConfig, mqtt::MqttConfig,
+    preroll::PreRollConfig, profiles, renditions::RenditionConfig, retention::RetentionConfig,
+    rtsp::RtspConfig, selftest::SelfTestConfig, supervisor::SupervisorConfig, tls::TlsConfig,
+    upload::UploadConfig, webhooks::WebhookConfig, webrtc_preview::WebRtcConfig,
+};
+use anyhow::{Context, Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    net::{IpAddr, SocketAddr},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+const DEFAULT_CONFIG_PATH: &str = "server.toml";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // 포트를 생략한 bind 주소에 쓰는 포트
+    pub port: u16,
+    // 받을 주소 목록: "IP" 또는 "IP:포트" (IPv6 는 "[::1]:8000"). 모두 동시에 받는다.
+    pub bind: Vec<String>,
+    pub save_dir: String,
+    // 녹화 중인 임시 파일과 마무리 중인 파일을 두는 디렉토리. 마무리가 끝난 파일만 save_dir 로
+    // 옮긴다 (NFS 처럼 쓰는 도중의 파일이 목록에 보이는 곳에 저장할 때). 비워 두면 save_dir 에 바로 쓴다.
+    pub staging_dir: String,
+    pub legacy_get_routes: bool,
+    // tracing EnvFilter 형식 (예: "info", "server=debug"), RUST_LOG 가 있으면 그쪽이 우선
+    pub log_level: String,
+    // true 면 로그를 JSON 한 줄씩 출력 (Loki 등 수집용)
+    pub log_json: bool,
+    // 로그 (표준 출력과 오류) 를 덧붙일 파일. 비워 두면 터미널에 쓰고, --daemon 이면 버린다.
+    pub log_file: String,
+    // 실행 중인 서버의 pid 를 적어 둘 파일 (init 스크립트용). 비워 두면 쓰지 않는다.
+    pub pid_file: String,
+    // 남은 공간이 이보다 적으면 녹화를 시작하지 않고, 녹화 중이면 멈춘다.
+    pub min_free_space_mb: u64,
+    // 종료 신호를 받은 뒤 진행 중인 녹화가 마무리되기를 기다리는 최대 시간 (초)
+    pub shutdown_timeout_secs: u64,
+    // /start 가 모든 카메라의 첫 프레임을 기다리는 최대 시간 (초). 넘으면 503 으로 답하고 녹화를 멈춘다.
+    pub start_timeout_secs: u64,
+    // 예약 녹화 목록을 저장하는 JSON 파일
+    pub schedules_file: String,
+    // API 로 만든 녹화 프로필을 저장하는 JSON 파일
+    pub profiles_file: String,
+    // 녹화 목록과 메타데이터를 보관하는 SQLite 파일
+    pub catalog_file: String,
+    // 시작할 때 서버가 죽어 끊긴 녹화의 임시 스트림을 카메라별 파일로 살려 목록에 넣는다.
+    pub recover_interrupted: bool,
+    // 설정 파일이 바뀌면 실행 중에 바꿀 수 있는 항목 (POST /config 와 같다) 을 다시 읽어 적용한다.
+    pub watch_config: bool,
+    // /start 요청에서 생략된 값은 이 기본값을 사용
+    pub recording: RecordingConfig,
+    // 이름 붙인 녹화 설정 묶음 (/start?profile=이름). /start 요청과 같은 형식으로 기본값 위에 덮어쓴다.
+    pub profiles: BTreeMap<String, StartRequest>,
+    // HTTPS (rustls)
+    pub tls: TlsConfig,
+    // API 키 / Bearer 토큰 인증
+    pub auth: AuthConfig,
+    // 다른 출처의 브라우저 대시보드에서 API 를 부를 때 붙이는 CORS 헤더
+    pub cors: CorsConfig,
+    // 클라이언트 IP 별 요청 속도 제한과 무거운 요청의 동시 처리 수 제한
+    pub limits: LimitsConfig,
+    // 오래된 녹화를 주기적으로 지우는 규칙
+    pub retention: RetentionConfig,
+    // 내려받을 때 브라우저에서 재생할 수 있게 바꾼 변환본 (?format=mp4&height=480)
+    pub renditions: RenditionConfig,
+    // 녹화가 끝난 파일을 S3 호환 저장소로 업로드
+    pub upload: UploadConfig,
+    // 녹화 시작/종료/실패 시 알림을 받을 주소
+    pub webhooks: WebhookConfig,
+    // 녹화가 끝나거나 실패하면 보내는 메일 (SMTP)
+    pub email: EmailConfig,
+    // 녹화 중인 영상의 HLS 실시간 스트림
+    pub live: LiveConfig,
+    // NVR 등에서 가져갈 수 있는 RTSP 스트림
+    pub rtsp: RtspConfig,
+    // 브라우저용 WebRTC 미리보기
+    pub webrtc: WebRtcConfig,
+    // 스트림 (HLS, RTSP, WebRTC) 으로 보낼 때 카메라 읽기와 인코더 사이의 프레임 대기열
+    pub write_queue: WriteQueueConfig,
+    // 움직임이 감지되면 자동으로 녹화
+    pub motion: MotionConfig,
+    // MQTT 브로커로 녹화 명령을 받고 상태와 이벤트를 보낸다 (Home Assistant 등).
+    pub mqtt: MqttConfig,
+    // Raspberry Pi 의 버튼으로 녹화를 시작/정지하고 LED 로 녹화 상태를 보여 준다.
+    pub gpio: GpioConfig,
+    // 녹화 시작 전 몇 초를 메모리에 모아 두었다가 녹화 파일 앞에 붙인다.
+    pub pre_roll: PreRollConfig,
+    // 녹화 사이에도 카메라를 일시정지 상태로 열어 두어 /start 를 빠르게 한다.
+    pub keep_alive: KeepAliveConfig,
+    // 백그라운드 작업이 패닉으로 끝났을 때 다시 시작하는 규칙
+    pub supervisor: SupervisorConfig,
+    // 시작할 때 카메라, 인코더, 저장 디렉토리를 확인한다 (GET /selftest).
+    pub self_test: SelfTestConfig,
+    // 설정을 읽어 온 파일 (없으면 기본값 사용)
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            port: 8000,
+            bind: vec!["127.0.0.1".to_string()],
+            // 예전 설치본이 기본값에 의존하므로 그대로 둔다 ({videos}/recordings 등으로 바꿀 수 있다).
+            save_dir: "~/Desktop/recordings".to_string(),
+            staging_dir: String::new(),
+            legacy_get_routes: false,
+            log_level: "info".to_string(),
+            log_json: false,
+            log_file: String::new(),
+            pid_file: String::new(),
+            min_free_space_mb: 500,
+            shutdown_timeout_secs: 30,
+            start_timeout_secs: 15,
+            schedules_file: "schedules.json".to_string(),
+            profiles_file: "profiles.json".to_string(),
+            catalog_file: "recordings.db".to_string(),
+            recover_interrupted: true,
+            watch_config: true,
+            recording: RecordingConfig::default(),
+            profiles: BTreeMap::new(),
+            tls: TlsConfig::default(),
+            auth: AuthConfig::default(),
+            cors: CorsConfig::default(),
+            limits: LimitsConfig::default(),
+            retention: RetentionConfig::default(),
+            renditions: RenditionConfig::default(),
+            upload: UploadConfig::default(),
+            webhooks: WebhookConfig::default(),
+            email: EmailConfig::default(),
+            live: LiveConfig::default(),
+            rtsp: RtspConfig::default(),
+            webrtc: WebRtcConfig::default(),
+            write_queue: WriteQueueConfig::default(),
+            motion: MotionConfig::default(),
+            mqtt: MqttConfig::default(),
+            gpio: GpioConfig::default(),
+            pre_roll: PreRollConfig::default(),
+            keep_alive: KeepAliveConfig::default(),
+            supervisor: SupervisorConfig::default(),
+            self_test: SelfTestConfig::default(),
+            source: None,
+        }
+    }
+}
+
+impl Config {
+    // 지정한 파일(없으면 SERVER_CONFIG, ./server.toml 순)을 읽고 환경 변수로 덮어쓴다.
+    // 명령줄 인자 적용 후 validate() 를 호출해야 한다.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let (path, required) = match (path, env::var("SERVER_CONFIG")) {
+            (Some(path), _) => (path.to_path_buf(), true),
+            (None, Ok(path)) => (PathBuf::from(path), true),
+            (None, Err(_)) => (PathBuf::from(DEFAULT_CONFIG_PATH), false),
+        };
+
+        let mut config = if path.exists() {
+            let mut config = Self::from_file(&path)?;
+            config.source = Some(path);
+            config
+        } else if required {
+            bail!("Config file {:?} does not exist", path);
+        } else {
+            Self::default()
+        };
+
+        config.apply_env()?;
+        Ok(config)
+    }
+
+    fn from_file(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file: {:?}", path))?;
+        toml::from_str(&contents).with_context(|| format!("Invalid config file: {:?}", path))
+    }
+
+    fn apply_env(&mut self) -> Result<()> {
+        override_from_env("PORT", &mut self.port)?;
+        override_from_env("SAVE_DIR", &mut self.save_dir)?;
+        override_from_env("STAGING_DIR", &mut self.staging_dir)?;
+        override_from_env("LEGACY_GET_ROUTES", &mut self.legacy_get_routes)?;
+        override_from_env("MIN_FREE_SPACE_MB", &mut self.min_free_space_mb)?;
+        override_from_env("LOG_LEVEL", &mut self.log_level)?;
+        override_from_env("LOG_JSON", &mut self.log_json)?;
+        override_from_env("LOG_FILE", &mut self.log_file)?;
+        override_from_env("PID_FILE", &mut self.pid_file)?;
+        override_from_env("FRAME_WIDTH", &mut self.recording.width)?;
+        override_from_env("FRAME_HEIGHT", &mut self.recording.height)?;
+        override_from_env("REQUESTED_FPS", &mut self.recording.fps)?;
+        if let Ok(secret) = env::var("WEBHOOK_SECRET") {
+            self.webhooks.secret = Some(secret);
+        }
+        if let Ok(token) = env::var("TELEGRAM_BOT_TOKEN") {
+            self.webhooks.chat.telegram.bot_token = Some(token);
+        }
+        if let Ok(password) = env::var("SMTP_PASSWORD") {
+            self.email.password = Some(password);
+        }
+        if let Ok(key) = env::var("API_KEY") {
+            self.auth.api_keys.push(key);
+        }
+
+        if let Ok(bind) = env::var("BIND") {
+            self.bind = bind
+                .split(',')
+                .map(|address| address.trim().to_string())
+                .collect();
+        }
+        if let Ok(cameras) = env::var("CAMERAS") {
+            self.recording.cameras = parse_camera_list(&cameras)
+                .with_context(|| format!("CAMERAS must be a comma-separated list: {}", cameras))?;
+        }
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.port == 0 {
+            bail!("port must be non-zero");
+        }
+        if self.bind.is_empty() {
+            bail!("bind must list at least one address");
+        }
+        self.bind_addresses()?;
+        if self.save_dir.trim().is_empty() {
+            bail!("save_dir must not be empty");
+        }
+        // save_dir 아래는 하위 디렉토리까지 목록에 나오므로 임시 파일이 보인다.
+        if let Some(staging_dir) = self.staging_dir()
+            && staging_dir.starts_with(self.save_dir())
+        {
+            bail!("staging_dir must not be inside save_dir");
+        }
+        if self.start_timeout_secs == 0 {
+            bail!("start_timeout_secs must be non-zero");
+        }
+        if self.schedules_file.trim().is_empty() {
+            bail!("schedules_file must not be empty");
+        }
+        if self.profiles_file.trim().is_empty() {
+            bail!("profiles_file must not be empty");
+        }
+        if self.catalog_file.trim().is_empty() {
+            bail!("catalog_file must not be empty");
+        }
+        self.tls.validate()?;
+        self.auth.validate()?;
+        self.cors.validate()?;
+        self.limits.validate()?;
+        self.retention.validate()?;
+        self.renditions.validate()?;
+        self.upload.validate()?;
+        self.webhooks.validate()?;
+        self.email.validate()?;
+        self.live.validate()?;
+        self.rtsp.validate()?;
+        // RTSP 에는 인증을 건너뛰는 길이 없으므로 다른 기기에 열려면 키가 있어야 한다.
+        if self.rtsp.enabled && !self.rtsp.is_loopback() && !self.auth.enabled() {
+            bail!("rtsp.bind {:?} needs [auth] keys or users", self.rtsp.bind);
+        }
+        self.webrtc.validate()?;
+        self.write_queue.validate()?;
+        self.motion.validate()?;
+        self.mqtt.validate()?;
+        self.gpio.validate()?;
+        self.pre_roll.validate()?;
+        self.keep_alive.validate()?;
+        // 미리보기, 움직임 감지, 녹화 전 구간은 녹화 사이에 카메라를 직접 연다.
+        if self.keep_alive.enabled
+            && (self.rtsp.enabled
+                || self.webrtc.enabled
+                || self.motion.enabled
+                || self.pre_roll.enabled)
+        {
+            bail!("keep_alive cannot be enabled together with rtsp, webrtc, motion or pre_roll");
+        }
+        self.supervisor.validate()?;
+        self.self_test.validate()?;
+        self.recording
+            .validate()
+            .context("Invalid [recording] defaults")?;
+        for (name, profile) in &self.profiles {
+            profiles::validate(name, profile, &self.recording)?;
+        }
+        Ok(())
+    }
+
+    // bind 의 각 항목을 주소로 바꾼다. 포트가 없으면 port 를 쓴다.
+    pub fn bind_addresses(&self) -> Result<Vec<SocketAddr>> {
+        let mut addresses = Vec::with_capacity(self.bind.len());
+        for entry in &self.bind {
+            let address = entry
+                .parse::<SocketAddr>()
+                .or_else(|_| {
+                    // "[::1]" 처럼 괄호로 감싼 IPv6 도 받는다.
+                    entry
+                        .trim_start_matches('[')
+                        .trim_end_matches(']')
+                        .parse::<IpAddr>()
+                        .map(|ip| SocketAddr::new(ip, self.port))
+                })
+                .map_err(|_| anyhow!("bind address {:?} is not an IP or IP:port", entry))?;
+            if addresses.contains(&address) {
+                bail!("bind address {} is listed twice", address);
+            }
+            addresses.push(address);
+        }
+        Ok(addresses)
+    }
+
+    // '~' 와 {videos} 같은 폴더 이름을 확장한 저장 경로 (expand_path)
+    pub fn save_dir(&self) -> PathBuf {
+        expand_path(&self.save_dir)
+    }
+
+    // 설정하지 않았으면 None
+    pub fn staging_dir(&self) -> Option<PathBuf> {
+        (!self.staging_dir.trim().is_empty()).then(|| expand_path(&self.staging_dir))
+    }
+
+    pub fn log_file(&self) -> Option<PathBuf> {
+        (!self.log_file.trim().is_empty()).then(|| expand_path(&self.log_file))
+    }
+
+    pub fn pid_file(&self) -> Option<PathBuf> {
+        (!self.pid_file.trim().is_empty()).then(|| expand_path(&self.pid_file))
+    }
+
+    pub fn schedules_file(&self) -> PathBuf {
+        expand_path(&self.schedules_file)
+    }
+
+    pub fn profiles_file(&self) -> PathBuf {
+        expand_path(&self.profiles_file)
+    }
+
+    pub fn catalog_file(&self) -> PathBuf {
+        expand_path(&self.catalog_file)
+    }
+}
+
+// 앞의 "~" 와 {home}, {videos}, {documents}, {desktop} 을 이 플랫폼의 폴더로 바꾼다
+// (Windows 의 동영상/문서 폴더, macOS 의 Movies 등). 그 폴더가 없으면 (XDG 사용자 폴더가 없는 Linux 등)
+// 홈 디렉토리를 쓴다. 구분자는 '/' 와 (Windows 에서) '\\' 를 모두 받는다.
+pub fn expand_path(path: &str) -> PathBuf {
+    type Folder = fn() -> Option<PathBuf>;
+    let folders: [(&str, Folder); 4] = [
+        ("{home}", dirs::home_dir),
+        ("{videos}", dirs::video_dir),
+        ("{documents}", dirs::document_dir),
+        ("{desktop}", dirs::desktop_dir),
+    ];
+    for (placeholder, folder) in folders {
+        if let Some(rest) = path.strip_prefix(placeholder)
+            && let Some(base) = folder().or_else(dirs::home_dir)
+        {
+            let rest = rest.trim_start_matches(['/', std::path::MAIN_SEPARATOR]);
+            return if rest.is_empty() {
+                base
+            } else {
+                base.join(rest)
+            };
+        }
+    }
+    PathBuf::from(shellexpand::tilde(path).into_owned())
+}
+
+fn override_from_env<T>(name: &str, target: &mut T) -> Result<()>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    if let Ok(value) = env::var(name) {
+        *target = value
+            .trim()
+            .parse()
+            .with_context(|| format!("{} has an invalid value: {}", name, value))?;
+    }
+    Ok(())
+}
+
+fn parse_camera_list(value: &str) -> Result<Vec<u32>> {
+    value
+        .split(',')
+        .map(|index| index.trim().parse::<u32>().map_err(Into::into))
+        .collect()
+}
@@ -0,0 +1,221 @@
+// src/config_watch.rs
+use crate::{
+    AppState,
+    cli::Cli,
+    config::Config,
+    events::EventKind,
+    settings::{self, Changes},
+};
+use anyhow::{Context, Result};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde_json::Value;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+// 편집기는 파일을 여러 번에 나눠 쓰거나 새 파일로 바꿔치기하므로 조용해질 때까지 기다린다.
+const SETTLE: Duration = Duration::from_millis(500);
+
+// 다시 시작하지 않고 적용하는 항목 (settings::apply 가 바꿀 수 있는 것)
+const LIVE: [&str; 5] = [
+    "log_level",
+    "recording.fps",
+    "recording.overlays",
+    "retention",
+    "webhooks.urls",
+];
+
+// 바꿔치기한 파일도 따라가도록 파일이 아닌 디렉토리를 지켜본다.
+fn watch(path: &Path, sender: mpsc::UnboundedSender<()>) -> Result<RecommendedWatcher> {
+    let file_name = path.file_name().map(ToOwned::to_owned);
+    let mut watcher =
+        notify::recommended_watcher(move |result: notify::Result<notify::Event>| match result {
+            Ok(event) if event.kind.is_access() => {}
+            Ok(event) => {
+                if event
+                    .paths
+                    .iter()
+                    .any(|changed| changed.file_name() == file_name.as_deref())
+                {
+                    let _ = sender.send(());
+                }
+            }
+            Err(e) => warn!("Config file watcher error: {}", e),
+        })
+        .context("Failed to watch the config file")?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    watcher
+        .watch(dir, RecursiveMode::NonRecursive)
+        .with_context(|| format!("Failed to watch {:?}", dir))?;
+    Ok(watcher)
+}
+
+// 시작할 때처럼 환경 변수와 명령줄 인자를 덮어쓰고 확인한다.
+fn load(path: &Path, cli: &Cli) -> Result<Config> {
+    let mut config = Config::load(Some(path))?;
+    cli.apply(&mut config);
+    config.validate()?;
+    Ok(config)
+}
+
+fn show(value: &Value) -> String {
+    match value {
+        Value::Null => "(none)".to_string(),
+        value => value.to_string(),
+    }
+}
+
+// 달라진 값을 "retention.max_files: 50 -> 100" 처럼 (배열은 통째로) 모은다.
+fn diff(path: &str, old: &Value, new: &Value, changes: &mut Vec<(String, String)>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                diff(
+                    &path,
+                    old.get(key).unwrap_or(&Value::Null),
+                    new.get(key).unwrap_or(&Value::Null),
+                    changes,
+                );
+            }
+        }
+        (old, new) if old != new => {
+            changes.push((path.to_string(), format!("{} -> {}", show(old), show(new))))
+        }
+        _ => {}
+    }
+}
+
+fn live(path: &str) -> Option<&'static str> {
+    LIVE.into_iter().find(|live| {
+        path == *live
+            || path
+                .strip_prefix(live)
+                .is_some_and(|rest| rest.starts_with('.'))
+    })
+}
+
+// 지난번에 읽은 파일과 비교해 바뀐 항목만 적용한다. POST /config 로 바꾼 값은 파일에서 같은 항목을
+// 고치지 않는 한 그대로 둔다.
+fn reload(state: &AppState, cli: &Cli, path: &Path, last: &mut Config) {
+    let config = match load(path, cli) {
+        Ok(config) => config,
+        Err(e) => {
+            error!(
+                "Config file changed but was not applied: {:#}. Keeping the current settings.",
+                e
+            );
+            return;
+        }
+    };
+    let (old, new) = match (serde_json::to_value(&*last), serde_json::to_value(&config)) {
+        (Ok(old), Ok(new)) => (old, new),
+        (Err(e), _) | (_, Err(e)) => {
+            error!("Failed to compare the config file: {}", e);
+            return;
+        }
+    };
+    let mut differences = Vec::new();
+    diff("", &old, &new, &mut differences);
+    if differences.is_empty() {
+        return;
+    }
+
+    let mut changes = Changes::default();
+    let mut changed = Vec::new();
+    let mut restart_required = Vec::new();
+    for (name, difference) in differences {
+        match live(&name) {
+            Some(setting) => {
+                match setting {
+                    "log_level" => changes.log_level = Some(config.log_level.clone()),
+                    "recording.fps" => changes.fps = Some(config.recording.fps),
+                    "recording.overlays" => {
+                        changes.overlays = Some(config.recording.overlays.clone())
+                    }
+                    "retention" => changes.retention = Some(config.retention.clone()),
+                    _ => changes.webhook_urls = Some(config.webhooks.urls.clone()),
+                }
+                info!("Config file changed {}: {}", name, difference);
+                changed.push(name);
+            }
+            None => {
+                warn!(
+                    "Config file changed {}: {}; restart the server to apply it.",
+                    name, difference
+                );
+                restart_required.push(name);
+            }
+        }
+    }
+    if let Err(e) = settings::apply(state, changes) {
+        error!(
+            "Failed to apply the config file: {:#}. Keeping the current settings.",
+            e
+        );
+        return;
+    }
+    *last = config;
+    state.events.publish(
+        Uuid::nil(),
+        EventKind::ConfigReloaded {
+            changed,
+            restart_required,
+        },
+    );
+}
+
+async fn run(state: Arc<AppState>, cli: Cli, path: PathBuf) {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    // 지켜보는 동안 살아 있어야 한다.
+    let _watcher = match watch(&path, sender) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            error!("{:#}; config file changes need a restart.", e);
+            return;
+        }
+    };
+    info!("Watching {:?} for changes.", path);
+    let mut last = state.config.as_ref().clone();
+    while receiver.recv().await.is_some() {
+        loop {
+            tokio::time::sleep(SETTLE).await;
+            let mut more = false;
+            while receiver.try_recv().is_ok() {
+                more = true;
+            }
+            if !more {
+                break;
+            }
+        }
+        reload(&state, &cli, &path, &mut last);
+    }
+}
+
+pub fn spawn(state: Arc<AppState>, cli: Cli) {
+    if !state.config.watch_config {
+        return;
+    }
+    let Some(path) = state.config.source.clone() else {
+        return;
+    };
+    let tasks = state.tasks.clone();
+    tasks.spawn_service("config-watch", move || {
+        run(state.clone(), cli.clone(), path.clone())
+    });
+}
@@ -0,0 +1,102 @@
+// src/cors.rs
+use anyhow::{Context, Result, bail};
+use axum::http::{HeaderName, HeaderValue, Method};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+// 다른 출처(origin)의 브라우저 페이지가 API 를 부를 수 있게 하는 CORS 응답 헤더
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CorsConfig {
+    // 허용할 출처 (예: "https://dashboard.example.com"). "*" 는 모든 출처, 비어 있으면 CORS 를 끈다.
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    // 요청에 붙일 수 있는 헤더 (인증 헤더를 쓰려면 포함해야 한다)
+    pub allowed_headers: Vec<String>,
+    // true 면 쿠키 같은 자격 증명을 함께 보내도록 허용한다 ("*" 와 함께 쓸 수 없음).
+    pub allow_credentials: bool,
+    // 브라우저가 사전 요청(preflight) 결과를 저장해 두는 시간 (초)
+    pub max_age_secs: u64,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: Vec::new(),
+            allowed_methods: ["GET", "POST", "PUT", "DELETE"].map(String::from).to_vec(),
+            allowed_headers: ["content-type", "authorization", "x-api-key"]
+                .map(String::from)
+                .to_vec(),
+            allow_credentials: false,
+            max_age_secs: 600,
+        }
+    }
+}
+
+impl CorsConfig {
+    pub fn validate(&self) -> Result<()> {
+        self.layer().map(|_| ())
+    }
+
+    fn any_origin(&self) -> bool {
+        self.allowed_origins.iter().any(|origin| origin == "*")
+    }
+
+    // 설정한 출처가 없으면 None
+    pub fn layer(&self) -> Result<Option<CorsLayer>> {
+        if self.allowed_origins.is_empty() {
+            return Ok(None);
+        }
+        if self.any_origin() && self.allowed_origins.len() > 1 {
+            bail!("cors.allowed_origins must not list other origins together with \"*\"");
+        }
+        if self.any_origin() && self.allow_credentials {
+            bail!("cors.allow_credentials cannot be combined with allowed_origins = [\"*\"]");
+        }
+        let origin = if self.any_origin() {
+            AllowOrigin::any()
+        } else {
+            let origins = self
+                .allowed_origins
+                .iter()
+                .map(|origin| {
+                    let trimmed = origin.trim_end_matches('/');
+                    if !trimmed.starts_with("http://") && !trimmed.starts_with("https://") {
+                        bail!(
+                            "cors origin {:?} must look like https://host[:port]",
+                            origin
+                        );
+                    }
+                    HeaderValue::from_str(trimmed)
+                        .with_context(|| format!("Invalid cors origin {:?}", origin))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            AllowOrigin::list(origins)
+        };
+        let methods = self
+            .allowed_methods
+            .iter()
+            .map(|method| {
+                Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+                    .with_context(|| format!("Invalid cors method {:?}", method))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let headers = self
+            .allowed_headers
+            .iter()
+            .map(|header| {
+                HeaderName::from_bytes(header.as_bytes())
+                    .with_context(|| format!("Invalid cors header {:?}", header))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Some(
+            CorsLayer::new()
+                .allow_origin(origin)
+                .allow_methods(methods)
+                .allow_headers(headers)
+                .allow_credentials(self.allow_credentials)
+                .max_age(Duration::from_secs(self.max_age_secs)),
+        ))
+    }
+}
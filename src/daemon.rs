@@ -0,0 +1,191 @@
+// src/daemon.rs
+use crate::sink;
+use anyhow::{Context, Result, bail};
+use std::{
+    fs::{self, File, OpenOptions},
+    path::{Path, PathBuf},
+};
+use tracing::warn;
+
+// --daemon 으로 띄운 첫 프로세스가 기다리는 파이프의 쓰는 쪽. 시작을 마치면 started() 가 한 바이트를 쓴다.
+#[cfg(unix)]
+static STARTED: std::sync::Mutex<Option<File>> = std::sync::Mutex::new(None);
+
+// 로그 (표준 출력과 오류) 를 덧붙일 파일. 없으면 버린다.
+fn open_log(log_file: Option<&Path>) -> Result<File> {
+    match log_file {
+        Some(path) => OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open log file {:?}", path)),
+        None => OpenOptions::new()
+            .write(true)
+            .open(sink::NULL_DEVICE)
+            .context("Failed to open the null device"),
+    }
+}
+
+// 표준 출력과 오류를 log_file 로 돌린다 (--daemon 없이 log_file 만 설정했을 때).
+#[cfg(unix)]
+pub fn redirect_output(log_file: &Path) -> Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let log = open_log(Some(log_file))?;
+    for fd in [libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+        // SAFETY: dup2(2) 는 열려 있는 두 fd 만 다루며 실패하면 -1 을 돌려준다.
+        if unsafe { libc::dup2(log.as_raw_fd(), fd) } < 0 {
+            bail!(
+                "Failed to redirect output to {:?}: {}",
+                log_file,
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn redirect_output(_log_file: &Path) -> Result<()> {
+    bail!("log_file is only supported on Unix")
+}
+
+// 터미널과 세션에서 떨어져 백그라운드에서 돈다. 처음 프로세스는 데몬이 started() 를 부를 때까지
+// 기다렸다가 0 으로, 그 전에 데몬이 끝나면 1 로 끝난다 (init 스크립트가 시작 실패를 알 수 있게).
+// 런타임이 스레드를 만들기 전에 불러야 한다 (fork 는 부른 스레드만 복사한다).
+#[cfg(unix)]
+pub fn detach(log_file: Option<&Path>) -> Result<()> {
+    use std::os::fd::{AsRawFd, FromRawFd};
+
+    // 터미널이 있을 때 열어 보아 잘못된 경로를 거기에 알린다.
+    let log = open_log(log_file)?;
+    let stdin = File::open(sink::NULL_DEVICE).context("Failed to open the null device")?;
+    let mut fds = [0; 2];
+    // SAFETY: pipe(2) 는 fds 에 두 fd 를 쓰고, 성공했을 때만 File 로 넘겨받는다.
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        bail!(
+            "Failed to create a pipe: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+    // SAFETY: 방금 만든 fd 이며 다른 곳에서 쓰지 않는다.
+    let (read, write) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+
+    // SAFETY: 아직 스레드가 하나뿐이므로 자식에서 잠긴 채 남는 lock 이 없다.
+    match unsafe { libc::fork() } {
+        -1 => bail!("Failed to fork: {}", std::io::Error::last_os_error()),
+        0 => drop(read),
+        _ => {
+            drop(write);
+            wait_for_daemon(read);
+        }
+    }
+    // SAFETY: setsid(2) 는 메모리를 건드리지 않는다.
+    if unsafe { libc::setsid() } < 0 {
+        bail!(
+            "Failed to start a new session: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+    // 세션 리더가 아니게 한 번 더 갈라져 터미널을 다시 얻지 못하게 한다.
+    // SAFETY: 위와 같다. 중간 프로세스는 Rust 정리 없이 바로 끝낸다.
+    match unsafe { libc::fork() } {
+        -1 => bail!("Failed to fork: {}", std::io::Error::last_os_error()),
+        0 => {}
+        _ => unsafe { libc::_exit(0) },
+    }
+
+    for (file, fd) in [
+        (&stdin, libc::STDIN_FILENO),
+        (&log, libc::STDOUT_FILENO),
+        (&log, libc::STDERR_FILENO),
+    ] {
+        // SAFETY: dup2(2) 는 열려 있는 두 fd 만 다루며 실패하면 -1 을 돌려준다.
+        if unsafe { libc::dup2(file.as_raw_fd(), fd) } < 0 {
+            bail!(
+                "Failed to redirect standard streams: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+    *STARTED.lock().unwrap() = Some(write);
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn detach(_log_file: Option<&Path>) -> Result<()> {
+    bail!("--daemon is only supported on Unix")
+}
+
+// 처음 프로세스: 데몬이 시작을 마쳤다고 알리거나 끝날 때까지 기다린다.
+#[cfg(unix)]
+fn wait_for_daemon(mut read: File) -> ! {
+    use std::io::Read;
+
+    let mut byte = [0u8; 1];
+    if matches!(read.read(&mut byte), Ok(1)) {
+        std::process::exit(0);
+    }
+    eprintln!("The server exited during startup; see its log for the reason.");
+    std::process::exit(1);
+}
+
+// 시작을 마쳤다. --daemon 으로 띄웠으면 기다리던 처음 프로세스를 끝낸다.
+pub fn started() {
+    #[cfg(unix)]
+    if let Some(mut write) = STARTED.lock().unwrap().take() {
+        use std::io::Write;
+        let _ = write.write_all(b"1");
+    }
+}
+
+// 실행 중인 서버의 pid 를 적어 두는 파일. 서버가 정상적으로 끝나면 지운다.
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    // 다른 살아 있는 프로세스의 pid 가 적혀 있으면 실패한다. 죽은 프로세스가 남긴 것은 덮어쓴다.
+    pub fn create(path: PathBuf) -> Result<Self> {
+        let pid = std::process::id();
+        let existing = fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| contents.trim().parse::<u32>().ok());
+        if let Some(existing) = existing
+            && existing != pid
+            && is_running(existing)
+        {
+            bail!(
+                "PID file {:?} belongs to running process {}; is the server already running?",
+                path,
+                existing
+            );
+        }
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {:?}", parent))?;
+        }
+        fs::write(&path, format!("{}\n", pid))
+            .with_context(|| format!("Failed to write PID file {:?}", path))?;
+        Ok(Self { path })
+    }
+
+    pub fn remove(&self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            warn!("Failed to remove PID file {:?}: {}", self.path, e);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    // SAFETY: 신호 0 은 보내지 않고 프로세스가 있는지만 확인한다.
+    let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    // EPERM 이면 있지만 다른 사용자의 프로세스
+    result == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn is_running(_pid: u32) -> bool {
+    false
+}
@@ -0,0 +1,416 @@
+// src/depth.rs
+use crate::{
+    compositor::{CompositeInput, FFMPEG, Layout},
+    scaling::Scaling,
+    sink::{self, Encoding, VideoSink},
+};
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{Read, Write},
+    path::Path,
+    process::{Child, ChildStdout, Command, Stdio},
+};
+use tracing::{info, warn};
+
+// 시차 계산 방식 (OpenCV 의 StereoBM / StereoSGBM 에 해당)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Matcher {
+    // 블록 SAD 가 가장 작은 시차를 고른다. 빠르지만 무늬가 없는 곳은 비어 있다.
+    #[default]
+    Block,
+    // 같은 비용에 4방향 경로 평활화를 더한다. 느리지만 더 매끄럽다.
+    SemiGlobal,
+}
+
+// 깊이 영상을 남기는 방식
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DepthOutput {
+    // <시각>_depth.mp4 로 따로 저장
+    #[default]
+    File,
+    // 합성본에 한 칸으로 넣는다 (가로/세로/그리드 배치 전용).
+    Panel,
+}
+
+// 나란히 놓인 두 카메라의 영상으로 시차(깊이) 지도를 만든다. 두 카메라는 같은 방향을 보고
+// 높이가 맞아야 한다 (보정/정렬은 하지 않음). 가까운 곳이 붉게, 먼 곳이 푸르게 칠해진다.
+// 두 카메라를 모두 녹화하는 세션에서만 만든다.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DepthConfig {
+    pub enabled: bool,
+    // 왼쪽/오른쪽 카메라
+    pub left: u32,
+    pub right: u32,
+    pub matcher: Matcher,
+    pub output: DepthOutput,
+    // 계산 해상도와 FPS. 프레임마다 CPU 로 계산하므로 작게 둔다.
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+    // 찾는 최대 시차 (픽셀, 16 의 배수). 가까운 물체가 많을수록 크게
+    pub num_disparities: u32,
+    // 비교하는 블록 크기 (홀수)
+    pub block_size: u32,
+}
+
+impl Default for DepthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            left: 0,
+            right: 1,
+            matcher: Matcher::default(),
+            output: DepthOutput::default(),
+            width: 320,
+            height: 240,
+            fps: 5,
+            num_disparities: 64,
+            block_size: 9,
+        }
+    }
+}
+
+impl DepthConfig {
+    pub fn validate(&self, layout: Layout) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        if self.left == self.right {
+            bail!("depth.left and depth.right must be different cameras");
+        }
+        if self.width == 0 || self.height == 0 || self.fps == 0 {
+            bail!("depth width, height and fps must be non-zero");
+        }
+        if self.num_disparities == 0 || !self.num_disparities.is_multiple_of(16) {
+            bail!("depth.num_disparities must be a positive multiple of 16");
+        }
+        if self.num_disparities >= self.width {
+            bail!("depth.num_disparities must be smaller than depth.width");
+        }
+        if self.block_size.is_multiple_of(2) || !(3..=31).contains(&self.block_size) {
+            bail!("depth.block_size must be an odd number between 3 and 31");
+        }
+        if self.output == DepthOutput::Panel
+            && matches!(
+                layout,
+                Layout::PictureInPicture | Layout::Switch | Layout::Panorama
+            )
+        {
+            bail!("depth.output = \"panel\" requires a horizontal, vertical or grid layout");
+        }
+        Ok(())
+    }
+}
+
+// left/right 원본 스트림에서 시차 영상을 만들어 output 에 인코딩한다.
+// 인코딩에 실패하면 sink::encode 처럼 다음 방식으로 다시 계산한다.
+pub fn render(
+    config: &DepthConfig,
+    (left, right): (&CompositeInput, &CompositeInput),
+    encoding: &Encoding,
+    output: &Path,
+) -> Result<()> {
+    info!(
+        "Computing the depth map of cameras {} and {} into {:?}...",
+        config.left, config.right, output
+    );
+    let sinks = sink::candidates(encoding);
+    let mut result = Ok(());
+    for (i, sink) in sinks.iter().enumerate() {
+        result = render_with(config, (left, right), sink.as_ref(), output);
+        match (&result, sinks.get(i + 1)) {
+            (Err(e), Some(next)) => warn!(
+                "{:#} with {}. Retrying with {}.",
+                e,
+                sink.describe(),
+                next.describe()
+            ),
+            _ => break,
+        }
+    }
+    result
+}
+
+fn render_with(
+    config: &DepthConfig,
+    (left, right): (&CompositeInput, &CompositeInput),
+    sink: &dyn VideoSink,
+    output: &Path,
+) -> Result<()> {
+    let pixels = (config.width * config.height) as usize;
+    let mut children = Vec::new();
+    let result = (|| {
+        let mut left_out = spawn_decoder(config, left, &mut children)?;
+        let mut right_out = spawn_decoder(config, right, &mut children)?;
+        let encoder = sink.encoder();
+        let mut command = Command::new(FFMPEG);
+        command
+            .arg("-y")
+            .arg("-loglevel")
+            .arg("error")
+            .args(encoder.input_args())
+            .arg("-f")
+            .arg("rawvideo")
+            .arg("-pix_fmt")
+            .arg("rgb24")
+            .arg("-s")
+            .arg(format!("{}x{}", config.width, config.height))
+            .arg("-r")
+            .arg(config.fps.to_string())
+            .arg("-i")
+            .arg("pipe:0")
+            .arg("-vf")
+            .arg(encoder.with_upload("format=yuv420p"))
+            .stdin(Stdio::piped());
+        let mut pipeline = sink
+            .spawn(command, output)
+            .context("Failed to start encoding the depth map")?;
+        let mut stdin = pipeline.take_stdin().context("ffmpeg stdin unavailable")?;
+
+        let mut matcher = StereoMatcher::new(config);
+        // 버퍼는 모두 여기서 한 번만 만들고 프레임마다 다시 쓴다.
+        let mut left_frame = vec![0u8; pixels];
+        let mut right_frame = vec![0u8; pixels];
+        let mut rgb = Vec::with_capacity(pixels * 3);
+        let mut frames = 0u64;
+        // 어느 한쪽이 끝나면 멈춘다.
+        while left_out.read_exact(&mut left_frame).is_ok()
+            && right_out.read_exact(&mut right_frame).is_ok()
+        {
+            let disparity = matcher.compute(&left_frame, &right_frame);
+            colorize(disparity, config.num_disparities, &mut rgb);
+            stdin
+                .write_all(&rgb)
+                .context("Failed to write a depth frame to ffmpeg")?;
+            frames += 1;
+        }
+        drop(stdin);
+        let status = pipeline.wait()?;
+        if !status.success() {
+            bail!("Failed to encode the depth map: {}", status);
+        }
+        if frames == 0 {
+            bail!("No frames decoded for the depth map");
+        }
+        info!("Depth map: {} frame(s).", frames);
+        Ok(())
+    })();
+    for mut child in children {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+    result
+}
+
+// 원본 스트림을 계산 해상도의 흑백 프레임으로 풀어 stdout 으로 내보낸다.
+fn spawn_decoder(
+    config: &DepthConfig,
+    input: &CompositeInput,
+    children: &mut Vec<Child>,
+) -> Result<ChildStdout> {
+    let mut command = Command::new(FFMPEG);
+    command.arg("-loglevel").arg("error");
+    if let Some(offset) = input.seek() {
+        command.arg("-ss").arg(format!("{:.3}", offset));
+    }
+    let mut filter = format!(
+        "fps={},{},format=gray",
+        config.fps,
+        Scaling::stretch(config.width, config.height).filter()
+    );
+    if let Some(timing) = input.timing_filter() {
+        filter = format!("{},{}", timing, filter);
+    }
+    if input.fps > 0.0 {
+        command.arg("-r").arg(format!("{:.3}", input.fps));
+    }
+    let mut child = command
+        .arg("-i")
+        .arg(&input.path)
+        .arg("-vf")
+        .arg(filter)
+        .arg("-f")
+        .arg("rawvideo")
+        .arg("pipe:1")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("Failed to start ffmpeg to decode a depth input")?;
+    let stdout = child.stdout.take().context("ffmpeg stdout unavailable")?;
+    children.push(child);
+    Ok(stdout)
+}
+
+// 시차 0 은 찾지 못한 곳 (검게 칠한다). 계산에 쓰는 버퍼는 new 에서 한 번만 만든다.
+struct StereoMatcher {
+    width: usize,
+    height: usize,
+    disparities: usize,
+    radius: usize,
+    matcher: Matcher,
+    // SemiGlobal 의 인접 시차 / 큰 시차 변화 벌점 (블록 넓이에 비례, OpenCV 권장값)
+    penalties: (u32, u32),
+    // (y * width + x) * disparities + d
+    costs: Vec<u32>,
+    aggregated: Vec<u32>,
+    disparity: Vec<u8>,
+    // block_costs 의 합 영상 ((width + 1) * (height + 1))
+    integral: Vec<u32>,
+    // aggregate_path 의 직전 / 지금 픽셀 경로 비용 (disparities)
+    previous: Vec<u32>,
+    current: Vec<u32>,
+}
+
+impl StereoMatcher {
+    fn new(config: &DepthConfig) -> Self {
+        let (width, height) = (config.width as usize, config.height as usize);
+        let disparities = config.num_disparities as usize;
+        let area = config.block_size * config.block_size;
+        let volume = match config.matcher {
+            Matcher::Block => 0,
+            Matcher::SemiGlobal => width * height * disparities,
+        };
+        Self {
+            width,
+            height,
+            disparities,
+            radius: config.block_size as usize / 2,
+            matcher: config.matcher,
+            penalties: (8 * area, 32 * area),
+            costs: vec![0; width * height * disparities],
+            aggregated: vec![0; volume],
+            disparity: vec![0; width * height],
+            integral: vec![0; (width + 1) * (height + 1)],
+            previous: vec![0; disparities],
+            current: vec![0; disparities],
+        }
+    }
+
+    fn compute(&mut self, left: &[u8], right: &[u8]) -> &[u8] {
+        self.block_costs(left, right);
+        if self.matcher == Matcher::SemiGlobal {
+            self.aggregate();
+        }
+        let volume = match self.matcher {
+            Matcher::Block => &self.costs,
+            Matcher::SemiGlobal => &self.aggregated,
+        };
+        for (pixel, costs) in volume.chunks_exact(self.disparities).enumerate() {
+            let x = pixel % self.width;
+            // 오른쪽 영상에서 찾을 수 있는 시차만 본다.
+            let usable = &costs[..self.disparities.min(x + 1)];
+            let (best, &cost) = usable
+                .iter()
+                .enumerate()
+                .min_by_key(|&(_, cost)| *cost)
+                .unwrap_or((0, &u32::MAX));
+            self.disparity[pixel] = if cost == u32::MAX { 0 } else { best as u8 };
+        }
+        &self.disparity
+    }
+
+    // 시차마다 |L(x, y) - R(x - d, y)| 를 블록 크기 상자로 더한 값 (합 영상으로 계산)
+    fn block_costs(&mut self, left: &[u8], right: &[u8]) {
+        let (w, h, r) = (self.width, self.height, self.radius);
+        let integral = &mut self.integral;
+        for d in 0..self.disparities {
+            for y in 0..h {
+                let mut row = 0u32;
+                for x in 0..w {
+                    if x >= d {
+                        row += left[y * w + x].abs_diff(right[y * w + x - d]) as u32;
+                    }
+                    integral[(y + 1) * (w + 1) + x + 1] = integral[y * (w + 1) + x + 1] + row;
+                }
+            }
+            for y in 0..h {
+                let (top, bottom) = (y.saturating_sub(r), (y + r + 1).min(h));
+                for x in 0..w {
+                    let index = (y * w + x) * self.disparities + d;
+                    // 블록이 오른쪽 영상 밖으로 나가면 비교하지 않는다.
+                    if x < d + r {
+                        self.costs[index] = u32::MAX;
+                        continue;
+                    }
+                    let (left_edge, right_edge) = (x - r, (x + r + 1).min(w));
+                    self.costs[index] = integral[bottom * (w + 1) + right_edge]
+                        + integral[top * (w + 1) + left_edge]
+                        - integral[top * (w + 1) + right_edge]
+                        - integral[bottom * (w + 1) + left_edge];
+                }
+            }
+        }
+    }
+
+    // 좌->우, 우->좌, 위->아래, 아래->위 경로 비용을 더한다.
+    fn aggregate(&mut self) {
+        self.aggregated.fill(0);
+        let (w, h) = (self.width, self.height);
+        for y in 0..h {
+            self.aggregate_path((0..w).map(|x| y * w + x));
+            self.aggregate_path((0..w).rev().map(|x| y * w + x));
+        }
+        for x in 0..w {
+            self.aggregate_path((0..h).map(|y| y * w + x));
+            self.aggregate_path((0..h).rev().map(|y| y * w + x));
+        }
+    }
+
+    // L(p, d) = C(p, d) + min(L(p-1, d), L(p-1, d±1) + P1, min L(p-1) + P2) - min L(p-1)
+    fn aggregate_path(&mut self, pixels: impl Iterator<Item = usize>) {
+        let n = self.disparities;
+        let (p1, p2) = self.penalties;
+        let (previous, current) = (&mut self.previous, &mut self.current);
+        let mut first = true;
+        for pixel in pixels {
+            let costs = &self.costs[pixel * n..(pixel + 1) * n];
+            if first {
+                current.copy_from_slice(costs);
+                first = false;
+            } else {
+                let floor = previous.iter().copied().min().unwrap_or(0);
+                for d in 0..n {
+                    if costs[d] == u32::MAX {
+                        current[d] = u32::MAX;
+                        continue;
+                    }
+                    let mut best = previous[d].min(floor.saturating_add(p2));
+                    if d > 0 {
+                        best = best.min(previous[d - 1].saturating_add(p1));
+                    }
+                    if d + 1 < n {
+                        best = best.min(previous[d + 1].saturating_add(p1));
+                    }
+                    current[d] = costs[d].saturating_add(best.saturating_sub(floor));
+                }
+            }
+            let sums = &mut self.aggregated[pixel * n..(pixel + 1) * n];
+            for (sum, cost) in sums.iter_mut().zip(current.iter()) {
+                *sum = sum.saturating_add(*cost);
+            }
+            std::mem::swap(previous, current);
+        }
+    }
+}
+
+// 시차를 파랑(멀리) -> 초록 -> 빨강(가까이) 색으로 바꾼 RGB24 프레임을 rgb 에 채운다.
+fn colorize(disparity: &[u8], disparities: u32, rgb: &mut Vec<u8>) {
+    rgb.clear();
+    let max = (disparities - 1).max(1) as f64;
+    for &d in disparity {
+        if d == 0 {
+            rgb.extend([0, 0, 0]);
+            continue;
+        }
+        let t = d as f64 / max;
+        let channel = |center: f64| {
+            let value = 1.5 - (4.0 * (t - center)).abs();
+            (value.clamp(0.0, 1.0) * 255.0) as u8
+        };
+        rgb.extend([channel(0.75), channel(0.5), channel(0.25)]);
+    }
+}
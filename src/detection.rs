@@ -0,0 +1,645 @@
+// src/detection.rs
+use crate::{
+    compositor::{CompositeInput, FFMPEG},
+    config, overlay,
+    scaling::{ScaleMode, Scaling},
+};
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Local, SecondsFormat};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Write as _,
+    fs::{self, File},
+    io::{BufRead, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+use tracing::info;
+
+// labels 를 비워 두면 쓰는 COCO 클래스 이름 (YOLO 공개 가중치의 순서)
+const COCO_LABELS: [&str; 80] = [
+    "person",
+    "bicycle",
+    "car",
+    "motorcycle",
+    "airplane",
+    "bus",
+    "train",
+    "truck",
+    "boat",
+    "traffic light",
+    "fire hydrant",
+    "stop sign",
+    "parking meter",
+    "bench",
+    "bird",
+    "cat",
+    "dog",
+    "horse",
+    "sheep",
+    "cow",
+    "elephant",
+    "bear",
+    "zebra",
+    "giraffe",
+    "backpack",
+    "umbrella",
+    "handbag",
+    "tie",
+    "suitcase",
+    "frisbee",
+    "skis",
+    "snowboard",
+    "sports ball",
+    "kite",
+    "baseball bat",
+    "baseball glove",
+    "skateboard",
+    "surfboard",
+    "tennis racket",
+    "bottle",
+    "wine glass",
+    "cup",
+    "fork",
+    "knife",
+    "spoon",
+    "bowl",
+    "banana",
+    "apple",
+    "sandwich",
+    "orange",
+    "broccoli",
+    "carrot",
+    "hot dog",
+    "pizza",
+    "donut",
+    "cake",
+    "chair",
+    "couch",
+    "potted plant",
+    "bed",
+    "dining table",
+    "toilet",
+    "tv",
+    "laptop",
+    "mouse",
+    "remote",
+    "keyboard",
+    "cell phone",
+    "microwave",
+    "oven",
+    "toaster",
+    "sink",
+    "refrigerator",
+    "book",
+    "clock",
+    "vase",
+    "scissors",
+    "teddy bear",
+    "hair drier",
+    "toothbrush",
+];
+
+// 마무리할 때 카메라 원본을 fps 로 솎아 YOLO 로 물체를 찾고, 결과를 녹화 파일 옆
+// .detections.jsonl 에 남긴다. draw_boxes 면 찾은 상자를 녹화 파일에도 그린다.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DetectionConfig {
+    // detection 기능으로 빌드해야 한다 (OpenCV dnn).
+    pub enabled: bool,
+    // YOLOv5 / YOLOv8 을 ONNX 로 내보낸 모델 파일 ('~' 확장)
+    pub model: String,
+    // 클래스 이름 파일 (한 줄에 하나, 모델의 클래스 순서). 비워 두면 COCO 80 클래스
+    pub labels: String,
+    // 감지할 카메라 (비어 있으면 녹화한 카메라 전체)
+    pub cameras: Vec<u32>,
+    // 1초에 검사하는 프레임 수. 프레임마다 CPU 로 계산하므로 작게 둔다.
+    pub fps: f64,
+    // 모델 입력 크기 (정사각형, 32 의 배수). 프레임을 이 크기로 줄이거나 늘여 넣는다.
+    pub input_size: u32,
+    // letterbox 는 비율을 유지하고 남는 곳을 채운다 (YOLO 가 학습한 방식). stretch 는 늘인다.
+    pub scale_mode: ScaleMode,
+    // 이보다 확신이 낮은 상자는 버린다 (0.0 ~ 1.0).
+    pub confidence: f64,
+    // 같은 클래스의 상자가 이 비율 이상 겹치면 확신이 높은 쪽만 남긴다.
+    pub nms_threshold: f64,
+    // 이 클래스만 남긴다 (비어 있으면 전부)
+    pub classes: Vec<String>,
+    // 찾은 상자와 클래스 이름을 녹화 파일에 그린다 (ffmpeg 의 subtitles 필터, libass 필요).
+    pub draw_boxes: bool,
+}
+
+impl Default for DetectionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            model: String::new(),
+            labels: String::new(),
+            cameras: Vec::new(),
+            fps: 2.0,
+            input_size: 640,
+            scale_mode: ScaleMode::default(),
+            confidence: 0.5,
+            nms_threshold: 0.45,
+            classes: Vec::new(),
+            draw_boxes: false,
+        }
+    }
+}
+
+impl DetectionConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        if !cfg!(feature = "detection") {
+            bail!("detection.enabled requires a build with `--features detection`");
+        }
+        if !Path::new(&expand(&self.model)).is_file() {
+            bail!("detection.model {:?} is not a file", self.model);
+        }
+        if !self.fps.is_finite() || self.fps <= 0.0 {
+            bail!("detection.fps must be positive");
+        }
+        if self.input_size == 0 || !self.input_size.is_multiple_of(32) {
+            bail!("detection.input_size must be a positive multiple of 32");
+        }
+        for (name, value) in [
+            ("confidence", self.confidence),
+            ("nms_threshold", self.nms_threshold),
+        ] {
+            if !(value > 0.0 && value <= 1.0) {
+                bail!("detection.{} must be in (0, 1]", name);
+            }
+        }
+        let labels = self.labels()?;
+        if let Some(class) = self.classes.iter().find(|class| !labels.contains(class)) {
+            bail!(
+                "detection.classes: {:?} is not one of the model's labels",
+                class
+            );
+        }
+        Ok(())
+    }
+
+    pub fn watches(&self, camera: u32) -> bool {
+        self.enabled && (self.cameras.is_empty() || self.cameras.contains(&camera))
+    }
+
+    fn labels(&self) -> Result<Vec<String>> {
+        if self.labels.trim().is_empty() {
+            return Ok(COCO_LABELS.iter().map(|label| label.to_string()).collect());
+        }
+        let path = expand(&self.labels);
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read detection.labels {:?}", self.labels))?;
+        let labels: Vec<String> = contents
+            .lines()
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect();
+        if labels.is_empty() {
+            bail!("detection.labels {:?} has no class names", self.labels);
+        }
+        Ok(labels)
+    }
+}
+
+fn expand(path: &str) -> String {
+    config::expand_path(path).to_string_lossy().into_owned()
+}
+
+// 프레임 안의 사각형 (프레임 크기에 대한 비율, 0.0 - 1.0)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct BoundingBox {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl BoundingBox {
+    fn iou(&self, other: &Self) -> f64 {
+        let width = (self.x + self.width).min(other.x + other.width) - self.x.max(other.x);
+        let height = (self.y + self.height).min(other.y + other.height) - self.y.max(other.y);
+        if width <= 0.0 || height <= 0.0 {
+            return 0.0;
+        }
+        let overlap = width * height;
+        overlap / (self.width * self.height + other.width * other.height - overlap)
+    }
+
+    // 모델 입력 기준 상자를 원래 프레임 기준으로. 원본에 걸치지 않으면 None
+    fn in_source(&self, scaling: &Scaling) -> Option<Self> {
+        let (left, top) = scaling.source_point((self.x, self.y));
+        let (right, bottom) = scaling.source_point((self.x + self.width, self.y + self.height));
+        (right > left && bottom > top).then_some(Self {
+            x: left,
+            y: top,
+            width: right - left,
+            height: bottom - top,
+        })
+    }
+}
+
+// .detections.jsonl 의 한 줄
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Detection {
+    pub camera: u32,
+    // 녹화 파일 시작 기준 (밀리초)
+    pub time_ms: u64,
+    // 그 프레임의 벽시계 시각 (RFC 3339, 밀리초)
+    pub wall_clock: String,
+    pub class: String,
+    pub class_id: usize,
+    pub confidence: f64,
+    pub bbox: BoundingBox,
+}
+
+impl Detection {
+    // seconds 만큼 늦게 시작하는 파일 기준으로 바꾼다.
+    pub fn shifted(&self, seconds: f64) -> Self {
+        Self {
+            time_ms: self.time_ms + (seconds * 1000.0).round() as u64,
+            ..self.clone()
+        }
+    }
+}
+
+// 모델 출력에서 걸러 낸 상자 하나
+struct Candidate {
+    class_id: usize,
+    confidence: f64,
+    bbox: BoundingBox,
+}
+
+// YOLOv8 은 [1, 4 + 클래스, 후보], YOLOv5 는 [1, 후보, 5 + 클래스] (물체일 확률이 따로 있다).
+// 상자는 입력 크기 기준 (중심 x, 중심 y, 너비, 높이). 찾은 후보는 found 를 비우고 채운다.
+fn decode(
+    dims: &[i32],
+    values: &[f32],
+    classes: usize,
+    (size, threshold): (f64, f64),
+    found: &mut Vec<Candidate>,
+) -> Result<()> {
+    found.clear();
+    let (rows, columns) = match dims {
+        [1, rows, columns] => (*rows as usize, *columns as usize),
+        _ => bail!("Unexpected detection model output shape {:?}", dims),
+    };
+    if values.len() < rows * columns {
+        bail!(
+            "Detection model output is shorter than its shape {:?}",
+            dims
+        );
+    }
+    // YOLOv8 은 후보가 열 방향으로 놓인다.
+    let objectness = rows != classes + 4;
+    if objectness && columns != classes + 5 {
+        bail!(
+            "Detection model output {:?} does not match {} labels \
+             (expected a YOLOv5 or YOLOv8 ONNX export)",
+            dims,
+            classes
+        );
+    }
+    let candidates = if objectness { rows } else { columns };
+    let value = |i: usize, j: usize| {
+        let index = if objectness {
+            i * columns + j
+        } else {
+            j * columns + i
+        };
+        values[index] as f64
+    };
+
+    let first_class = if objectness { 5 } else { 4 };
+    for i in 0..candidates {
+        let (class_id, score) = (0..classes)
+            .map(|class| (class, value(i, first_class + class)))
+            .fold(
+                (0, f64::MIN),
+                |best, next| if next.1 > best.1 { next } else { best },
+            );
+        let confidence = if objectness {
+            score * value(i, 4)
+        } else {
+            score
+        };
+        if confidence < threshold {
+            continue;
+        }
+        let (cx, cy, w, h) = (value(i, 0), value(i, 1), value(i, 2), value(i, 3));
+        let left = ((cx - w / 2.0) / size).clamp(0.0, 1.0);
+        let top = ((cy - h / 2.0) / size).clamp(0.0, 1.0);
+        let right = ((cx + w / 2.0) / size).clamp(0.0, 1.0);
+        let bottom = ((cy + h / 2.0) / size).clamp(0.0, 1.0);
+        if right <= left || bottom <= top {
+            continue;
+        }
+        found.push(Candidate {
+            class_id,
+            confidence,
+            bbox: BoundingBox {
+                x: left,
+                y: top,
+                width: right - left,
+                height: bottom - top,
+            },
+        });
+    }
+    Ok(())
+}
+
+// 클래스마다 확신이 높은 상자부터 남기고, 그것과 많이 겹치는 상자는 버린다 (candidates 안에서 정리한다).
+fn suppress(candidates: &mut Vec<Candidate>, threshold: f64) {
+    candidates.sort_unstable_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    let mut kept = 0;
+    for i in 0..candidates.len() {
+        let candidate = &candidates[i];
+        let overlaps = candidates[..kept].iter().any(|other| {
+            other.class_id == candidate.class_id && other.bbox.iou(&candidate.bbox) >= threshold
+        });
+        if !overlaps {
+            candidates.swap(kept, i);
+            kept += 1;
+        }
+    }
+    candidates.truncate(kept);
+}
+
+// 카메라 한 대의 원본에서 물체를 찾는다. input 의 filter(가림 영역)를 칠한 프레임을 검사하므로
+// 가린 곳의 사람은 찾지 않는다. frame_size 는 filter 를 거친 프레임 크기, started 는 녹화 파일의 시작 시각
+pub fn detect(
+    config: &DetectionConfig,
+    camera: u32,
+    input: &CompositeInput,
+    frame_size: (u32, u32),
+    started: DateTime<Local>,
+) -> Result<Vec<Detection>> {
+    info!(
+        "camera {}: detecting objects at {} fps with {:?}...",
+        camera, config.fps, config.model
+    );
+    let labels = config.labels()?;
+    let size = config.input_size as usize;
+    let mut model = backend::load(&expand(&config.model), size)?;
+
+    let mut command = Command::new(FFMPEG);
+    command.arg("-loglevel").arg("error");
+    if let Some(offset) = input.seek() {
+        command.arg("-ss").arg(format!("{:.3}", offset));
+    }
+    if input.fps > 0.0 {
+        command.arg("-r").arg(format!("{:.3}", input.fps));
+    }
+    let scaling = Scaling::new(
+        config.scale_mode,
+        frame_size,
+        config.input_size,
+        config.input_size,
+    );
+    let mut filter = format!("fps={},{},format=rgb24", config.fps, scaling.filter());
+    if let Some(before) = input.full_filter() {
+        filter = format!("{},{}", before, filter);
+    }
+    let mut decoder = command
+        .arg("-i")
+        .arg(&input.path)
+        .arg("-vf")
+        .arg(filter)
+        .arg("-f")
+        .arg("rawvideo")
+        .arg("pipe:1")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("Failed to start ffmpeg to decode frames for detection")?;
+    let mut stdout = decoder.stdout.take().context("ffmpeg stdout unavailable")?;
+
+    let result = (|| {
+        // 프레임과 후보 버퍼는 한 번만 만들고 다시 쓴다.
+        let mut frame = vec![0u8; size * size * 3];
+        let mut candidates = Vec::new();
+        let mut detections = Vec::new();
+        let mut index = 0u64;
+        while stdout.read_exact(&mut frame).is_ok() {
+            let (dims, values) = model.forward(&frame)?;
+            decode(
+                dims,
+                values,
+                labels.len(),
+                (size as f64, config.confidence),
+                &mut candidates,
+            )?;
+            suppress(&mut candidates, config.nms_threshold);
+            let time_ms = (index as f64 * 1000.0 / config.fps).round() as u64;
+            let wall_clock = (started + chrono::Duration::milliseconds(time_ms as i64))
+                .to_rfc3339_opts(SecondsFormat::Millis, false);
+            for candidate in candidates.drain(..) {
+                // 채운 여백에만 걸친 상자는 버린다.
+                let Some(bbox) = candidate.bbox.in_source(&scaling) else {
+                    continue;
+                };
+                let class = labels[candidate.class_id].clone();
+                if !config.classes.is_empty() && !config.classes.contains(&class) {
+                    continue;
+                }
+                detections.push(Detection {
+                    camera,
+                    time_ms,
+                    wall_clock: wall_clock.clone(),
+                    class,
+                    class_id: candidate.class_id,
+                    confidence: candidate.confidence,
+                    bbox,
+                });
+            }
+            index += 1;
+        }
+        if index == 0 {
+            bail!("No frames decoded for detection");
+        }
+        info!(
+            "camera {}: {} detection(s) in {} frame(s).",
+            camera,
+            detections.len(),
+            index
+        );
+        Ok(detections)
+    })();
+    let _ = decoder.kill();
+    let _ = decoder.wait();
+    result
+}
+
+// 녹화 파일 옆에 남기는 감지 결과
+pub fn sidecar_path(video: &Path) -> PathBuf {
+    video.with_extension("detections.jsonl")
+}
+
+// 한 줄에 감지 하나 (시각 순)
+pub fn write_sidecar(video: &Path, detections: &[Detection]) -> Result<()> {
+    let path = sidecar_path(video);
+    let file = File::create(&path).with_context(|| format!("Failed to create {:?}", path))?;
+    let mut writer = BufWriter::new(file);
+    for detection in detections {
+        serde_json::to_writer(&mut writer, detection)?;
+        writeln!(writer)?;
+    }
+    writer
+        .flush()
+        .with_context(|| format!("Failed to write {:?}", path))
+}
+
+pub fn read_sidecar(video: &Path) -> Result<Vec<Detection>> {
+    let path = sidecar_path(video);
+    let file = File::open(&path).with_context(|| format!("Failed to open {:?}", path))?;
+    let mut detections = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line.with_context(|| format!("Failed to read {:?}", path))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        detections.push(
+            serde_json::from_str(&line).with_context(|| format!("Invalid line in {:?}", path))?,
+        );
+    }
+    Ok(detections)
+}
+
+// 상자를 ASS 자막으로 그려 둔다 (상자 수가 많아도 필터 하나로 그릴 수 있다).
+// (width, height) 는 카메라 원래 해상도, interval 은 상자 하나를 보여 줄 시간(초)
+pub fn write_boxes(
+    detections: &[Detection],
+    (width, height): (u32, u32),
+    interval: f64,
+    path: &Path,
+) -> Result<()> {
+    let font_size = (height / 30).max(12);
+    let mut script = format!(
+        "[Script Info]\nScriptType: v4.00+\nPlayResX: {width}\nPlayResY: {height}\n\
+         ScaledBorderAndShadow: yes\n\n\
+         [V4+ Styles]\nFormat: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, \
+         OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, \
+         Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n\
+         Style: Box,Sans,{font_size},&H0000FF00,&H0000FF00,&H0000FF00,&H00000000,\
+         0,0,0,0,100,100,0,0,1,2,0,7,0,0,0,1\n\n\
+         [Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n"
+    );
+    for detection in detections {
+        let start = detection.time_ms as f64 / 1000.0;
+        let (start, end) = (ass_time(start), ass_time(start + interval));
+        let bbox = &detection.bbox;
+        let left = (bbox.x * width as f64).round();
+        let top = (bbox.y * height as f64).round();
+        let right = ((bbox.x + bbox.width) * width as f64).round();
+        let bottom = ((bbox.y + bbox.height) * height as f64).round();
+        // 채우기는 투명하게 두고 테두리만 그린다.
+        let _ = writeln!(
+            script,
+            "Dialogue: 0,{start},{end},Box,,0,0,0,,{{\\pos(0,0)\\1a&HFF&\\p1}}\
+             m {left} {top} l {right} {top} {right} {bottom} {left} {bottom}{{\\p0}}"
+        );
+        let _ = writeln!(
+            script,
+            "Dialogue: 1,{start},{end},Box,,0,0,0,,{{\\an1\\pos({left},{top})\\3c&H000000&}}{} {:.0}%",
+            detection.class,
+            detection.confidence * 100.0
+        );
+    }
+    fs::write(path, script).with_context(|| format!("Failed to write {:?}", path))
+}
+
+// H:MM:SS.cc
+fn ass_time(seconds: f64) -> String {
+    let centis = (seconds * 100.0).round() as u64;
+    format!(
+        "{}:{:02}:{:02}.{:02}",
+        centis / 360_000,
+        centis / 6000 % 60,
+        centis / 100 % 60,
+        centis % 100
+    )
+}
+
+// write_boxes 로 만든 파일을 그리는 필터
+pub fn boxes_filter(path: &Path) -> String {
+    format!(
+        "subtitles=filename='{}'",
+        overlay::escape_option(&path.to_string_lossy())
+    )
+}
+
+#[cfg(feature = "detection")]
+mod backend {
+    use anyhow::{Context, Result};
+    use opencv::{
+        core::{CV_32F, Mat, Scalar},
+        dnn::{self, Net},
+        prelude::*,
+    };
+
+    // 입력 blob 은 한 번만 만들어 프레임마다 채운다.
+    pub struct Model {
+        net: Net,
+        size: usize,
+        blob: Mat,
+        output: Mat,
+        dims: Vec<i32>,
+    }
+
+    pub fn load(model: &str, size: usize) -> Result<Model> {
+        let net = dnn::read_net_from_onnx(model)
+            .with_context(|| format!("Failed to load the detection model {:?}", model))?;
+        let side = size as i32;
+        let blob = Mat::new_nd_with_default(&[1, 3, side, side], CV_32F, Scalar::all(0.0))?;
+        Ok(Model {
+            net,
+            size,
+            blob,
+            output: Mat::default(),
+            dims: Vec::new(),
+        })
+    }
+
+    impl Model {
+        // size x size RGB 프레임 하나를 넣고 (출력 모양, 값) 을 돌려준다.
+        // 값은 네트워크의 출력 버퍼를 그대로 빌려준다 (다음 forward 전까지 유효).
+        pub fn forward(&mut self, frame: &[u8]) -> Result<(&[i32], &[f32])> {
+            // HWC u8 을 NCHW 0.0 ~ 1.0 으로
+            let plane = self.size * self.size;
+            let data = self.blob.data_typed_mut::<f32>()?;
+            for (i, pixel) in frame.chunks_exact(3).enumerate() {
+                for (channel, &value) in pixel.iter().enumerate() {
+                    data[channel * plane + i] = value as f32 / 255.0;
+                }
+            }
+            self.net.set_input_def(&self.blob)?;
+            self.output = self
+                .net
+                .forward_single_def()
+                .context("Detection model inference failed")?;
+            self.dims.clear();
+            self.dims.extend_from_slice(&self.output.mat_size());
+            Ok((&self.dims, self.output.data_typed::<f32>()?))
+        }
+    }
+}
+
+#[cfg(not(feature = "detection"))]
+mod backend {
+    use anyhow::{Result, bail};
+
+    pub struct Model;
+
+    pub fn load(_model: &str, _size: usize) -> Result<Model> {
+        bail!("Object detection requires a build with `--features detection`")
+    }
+
+    impl Model {
+        pub fn forward(&mut self, _frame: &[u8]) -> Result<(&[i32], &[f32])> {
+            bail!("Object detection requires a build with `--features detection`")
+        }
+    }
+}
@@ -0,0 +1,378 @@
+// src/dry_run.rs
+use crate::{
+    ApiError, AppState, StartQuery, StartRequest,
+    camera_handler::{self, OutputMode, RecordingConfig},
+    compositor::Layout,
+    config::Config,
+    encoder::Encoder,
+    filename::FileNames,
+    health::{self, Check},
+    selftest::{self, CodecCheck},
+    sink::{self, Backend, DEFAULT_HARDWARE_KBPS},
+};
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+use chrono::Local;
+use serde::Serialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Instant,
+};
+use uuid::Uuid;
+
+// 비트레이트를 정하지 않은 H.264 (libcamera-vid, libx264 CRF) 를 어림하는 값 (프레임의 픽셀당 비트)
+const BITS_PER_PIXEL: f64 = 0.1;
+
+#[derive(Debug, Serialize)]
+pub struct CameraPlan {
+    pub camera: u32,
+    // "libcamera", "network", "device" 또는 "test_pattern"
+    pub kind: &'static str,
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+    #[serde(flatten)]
+    pub check: Check,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OutputPlan {
+    // 마무리가 끝난 파일이 놓일 자리 (분할 녹화면 첫 세그먼트의 이름)
+    pub path: PathBuf,
+    pub cameras: Vec<u32>,
+    // true 면 카메라 스트림을 다시 인코딩하고, false 면 그대로 옮겨 담는다.
+    pub reencoded: bool,
+    pub bitrate_kbps: u64,
+    // 한 시간 녹화했을 때의 크기
+    pub bytes_per_hour: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ValidateResponse {
+    // problems 가 없으면 true
+    pub ok: bool,
+    // POST /start 가 거절하거나 녹화가 시작하지 못할 이유
+    pub problems: Vec<String>,
+    pub cameras: Vec<CameraPlan>,
+    // 다시 인코딩하는 출력이 있을 때 실제로 쓸 방식 (실패하면 다음 방식으로 넘어가는 순서대로 시험한다)
+    pub encoding: Option<CodecCheck>,
+    pub disk_space: Check,
+    pub outputs: Vec<OutputPlan>,
+    // 남는 파일의 시간당 크기 (비트레이트를 정하지 않았으면 어림값)
+    pub bytes_per_hour: u64,
+    // 마무리하는 동안 카메라별 임시 스트림까지 함께 차지하는 시간당 크기
+    pub peak_bytes_per_hour: u64,
+    // min_free_space_mb 에 닿기 전까지 녹화할 수 있는 시간
+    pub hours_available: Option<f64>,
+    // max_duration 만큼 녹화했을 때의 크기
+    pub total_bytes: Option<u64>,
+    pub config: RecordingConfig,
+}
+
+fn estimated_kbps(pixels: u64, fps: f64) -> u64 {
+    (pixels as f64 * fps * BITS_PER_PIXEL / 1000.0).round() as u64
+}
+
+fn bytes_per_hour(kbps: u64, seconds: f64) -> u64 {
+    (kbps as f64 * 1000.0 / 8.0 * seconds).round() as u64
+}
+
+// 녹화 한 시간이 완성본에서 차지하는 길이 (초)
+fn playback_seconds(config: &RecordingConfig) -> f64 {
+    if config.timelapse.enabled {
+        3600.0 / config.timelapse.interval_secs / config.timelapse.playback_fps as f64
+    } else {
+        config.slow_motion.factor(config.fps).unwrap_or(1.0) * 3600.0
+    }
+}
+
+// 합성본 한 프레임의 픽셀 수 (배치마다 대략)
+fn composite_pixels(config: &RecordingConfig, cameras: &[u32]) -> u64 {
+    let frame = config.width as u64 * config.height as u64;
+    match config.layout {
+        Layout::PictureInPicture | Layout::Switch => frame,
+        _ => frame * cameras.len() as u64,
+    }
+}
+
+// 카메라 한 대만 녹화할 때 스트림을 그대로 옮겨 담지 못하고 다시 인코딩하는지
+fn single_reencoded(config: &RecordingConfig, camera: u32) -> bool {
+    config.timelapse.enabled
+        || config.watermark.enabled
+        || config.camera_filter(camera).is_some()
+        || config.overlay_for(camera).is_some()
+}
+
+fn check_cameras(config: &RecordingConfig, cameras: &[u32]) -> Vec<CameraPlan> {
+    // libcamera 카메라가 있을 때 한 번만 목록을 읽는다.
+    let mut attached = None;
+    cameras
+        .iter()
+        .map(|&camera| {
+            let format = config.format_for(camera);
+            let (kind, check) = if config.network_camera(camera).is_some() {
+                (
+                    "network",
+                    Check::pass("connected when the recording starts"),
+                )
+            } else if config.device_camera(camera).is_some() {
+                ("device", Check::pass("opened when the recording starts"))
+            } else if config.test_patterns.contains(&camera) {
+                ("test_pattern", Check::pass("drawn by ffmpeg"))
+            } else {
+                let check = match attached.get_or_insert_with(camera_handler::sensor_sizes) {
+                    Ok(sensors) => match sensors.get(&camera) {
+                        Some(Some((width, height)))
+                            if format.width > *width || format.height > *height =>
+                        {
+                            Check::fail(format!(
+                                "{}x{} is larger than the {}x{} sensor",
+                                format.width, format.height, width, height
+                            ))
+                        }
+                        Some(_) => Check::pass("attached"),
+                        None => Check::fail(format!(
+                            "not attached (available: {:?})",
+                            sensors.keys().collect::<Vec<_>>()
+                        )),
+                    },
+                    Err(e) => Check::fail(format!("{:#}", e)),
+                };
+                ("libcamera", check)
+            };
+            CameraPlan {
+                camera,
+                kind,
+                width: format.width,
+                height: format.height,
+                fps: format.fps,
+                check,
+            }
+        })
+        .collect()
+}
+
+// 다시 인코딩할 방식 중 처음으로 시험 영상을 써 낸 것 (모두 실패하면 마지막 것)
+fn check_encoding(config: &RecordingConfig) -> Option<(CodecCheck, Encoder)> {
+    // 검사가 동시에 돌 수 있으므로 호출마다 따로 쓰고 지운다.
+    let scratch = std::env::temp_dir().join(format!("server-validate-{}", Uuid::new_v4()));
+    if let Err(e) = fs::create_dir_all(&scratch) {
+        return Some((
+            CodecCheck {
+                sink: "ffmpeg".to_string(),
+                duration_ms: 0,
+                check: Check::fail(format!("Failed to create {:?}: {}", scratch, e)),
+            },
+            Encoder::Software,
+        ));
+    }
+    let mut last = None;
+    for sink in sink::candidates(&config.encoding()) {
+        let started = Instant::now();
+        let check = match selftest::encode_test(sink.as_ref(), &scratch) {
+            Ok(()) => Check::pass("wrote a test video"),
+            Err(e) => Check::fail(format!("{:#}", e)),
+        };
+        let ok = check.ok;
+        last = Some((
+            CodecCheck {
+                sink: sink.describe(),
+                duration_ms: started.elapsed().as_millis() as u64,
+                check,
+            },
+            sink.encoder(),
+        ));
+        if ok {
+            break;
+        }
+    }
+    let _ = fs::remove_dir_all(&scratch);
+    last
+}
+
+// 남은 공간을 잴 디렉토리. 아직 없으면 녹화를 시작할 때 만들므로 있는 상위 디렉토리를 잰다.
+fn existing(dir: &Path) -> &Path {
+    dir.ancestors()
+        .find(|dir| dir.exists())
+        .unwrap_or(Path::new("."))
+}
+
+fn check_disk(config: &Config, work_dir: &Path) -> (Check, Option<u64>) {
+    let save_dir = config.save_dir();
+    let mut check = health::check_disk_space(existing(&save_dir), config.min_free_space_mb);
+    if work_dir != save_dir {
+        let staging = health::check_disk_space(existing(work_dir), config.min_free_space_mb);
+        check = Check {
+            ok: check.ok && staging.ok,
+            detail: format!(
+                "save_dir: {}; staging_dir: {}",
+                check.detail, staging.detail
+            ),
+        };
+    }
+    let free = fs4::available_space(existing(work_dir)).ok();
+    (check, free)
+}
+
+fn plan(
+    server_config: &Config,
+    config: RecordingConfig,
+    file_tag: String,
+    mut problems: Vec<String>,
+) -> ValidateResponse {
+    let cameras = camera_handler::resolve_cameras(&config).unwrap_or_else(|e| {
+        problems.push(format!("{:#}", e));
+        config.cameras.clone()
+    });
+    if config.slow_motion.enabled
+        && let Err(e) = camera_handler::check_sensor_modes(&config, &cameras)
+    {
+        problems.push(format!("{:#}", e));
+    }
+    let camera_plans = check_cameras(&config, &cameras);
+    for plan in camera_plans.iter().filter(|plan| !plan.check.ok) {
+        problems.push(format!("camera {}: {}", plan.camera, plan.check.detail));
+    }
+
+    // run_recording_blocking 과 같은 이름. {session_id} 는 시작할 때 정해진다.
+    let names = FileNames::planned(&config, "{session_id}", &file_tag, &cameras);
+    let stem = names.stem(Local::now());
+    let save_dir = server_config.save_dir();
+    let work_dir = server_config
+        .staging_dir()
+        .unwrap_or_else(|| save_dir.clone());
+    let path = |suffix: String| save_dir.join(format!("{}{}.{}", stem, suffix, config.extension()));
+
+    let raw_kbps = |camera: u32| {
+        let format = config.format_for(camera);
+        estimated_kbps(
+            format.width as u64 * format.height as u64,
+            format.fps as f64,
+        )
+    };
+    let single = cameras.len() == 1;
+    let composite = single || config.output_mode != OutputMode::Separate;
+    let reencoded = if single {
+        single_reencoded(&config, cameras[0])
+    } else {
+        config.output_mode != OutputMode::Separate
+    };
+    let encoding = reencoded.then(|| check_encoding(&config)).flatten();
+    let encoder = encoding.as_ref().map(|(_, encoder)| *encoder);
+    let encoding = encoding.map(|(check, _)| check);
+    if let Some(encoding) = encoding.as_ref().filter(|encoding| !encoding.check.ok) {
+        problems.push(format!(
+            "encoding with {}: {}",
+            encoding.sink, encoding.check.detail
+        ));
+    }
+
+    let mut outputs = Vec::new();
+    if composite && !cameras.is_empty() {
+        let (kbps, seconds) = if reencoded {
+            let sink = &config.sink;
+            let kbps = match (sink.bitrate_kbps, sink.backend, encoder) {
+                (Some(kbps), _, _) => kbps as u64,
+                (None, Backend::Null, _) => 0,
+                (None, Backend::Ffmpeg | Backend::Gstreamer, Some(encoder))
+                    if encoder != Encoder::Software =>
+                {
+                    DEFAULT_HARDWARE_KBPS as u64
+                }
+                _ => estimated_kbps(
+                    composite_pixels(&config, &cameras),
+                    config.output_fps() as f64,
+                ),
+            };
+            (kbps, playback_seconds(&config))
+        } else {
+            // 옮겨 담으면 찍은 프레임이 모두 남는다 (슬로 모션은 길이만 늘어난다).
+            (raw_kbps(cameras[0]), 3600.0)
+        };
+        outputs.push(OutputPlan {
+            path: path(String::new()),
+            cameras: cameras.clone(),
+            reencoded,
+            bitrate_kbps: kbps,
+            bytes_per_hour: bytes_per_hour(kbps, seconds),
+        });
+    }
+    if !single && config.output_mode != OutputMode::Composite {
+        for &camera in &cameras {
+            let kbps = raw_kbps(camera);
+            outputs.push(OutputPlan {
+                path: path(format!("_cam{}", camera)),
+                cameras: vec![camera],
+                reencoded: config.camera_filter(camera).is_some(),
+                bitrate_kbps: kbps,
+                bytes_per_hour: bytes_per_hour(kbps, 3600.0),
+            });
+        }
+    }
+
+    let kept: u64 = outputs.iter().map(|output| output.bytes_per_hour).sum();
+    let raw: u64 = cameras
+        .iter()
+        .map(|&camera| bytes_per_hour(raw_kbps(camera), 3600.0))
+        .sum();
+    let peak = kept + raw;
+    let (disk_space, free) = check_disk(server_config, &work_dir);
+    if !disk_space.ok {
+        problems.push(format!("disk space: {}", disk_space.detail));
+    }
+    let min_free = server_config.min_free_space_mb * 1024 * 1024;
+    let hours_available = free
+        .filter(|_| peak > 0)
+        .map(|free| free.saturating_sub(min_free) as f64 / peak as f64);
+    let total_bytes = config
+        .max_duration
+        .map(|secs| (kept as f64 * secs as f64 / 3600.0).round() as u64);
+
+    ValidateResponse {
+        ok: problems.is_empty(),
+        problems,
+        cameras: camera_plans,
+        encoding,
+        disk_space,
+        outputs,
+        bytes_per_hour: kept,
+        peak_bytes_per_hour: peak,
+        hours_available,
+        total_bytes,
+        config,
+    }
+}
+
+// POST /start/validate - POST /start 와 같은 요청을 받아 녹화를 시작하지 않고 무엇을 할지 알려 준다.
+// 요청 자체가 잘못되었으면 POST /start 와 같은 오류로, 카메라나 공간 문제는 problems 로 답한다.
+pub async fn handle_validate(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<StartQuery>,
+    body: Option<Json<StartRequest>>,
+) -> Result<Json<ValidateResponse>, ApiError> {
+    let mut request = body.map(|Json(request)| request).unwrap_or_default();
+    if query.profile.is_some() {
+        request.profile = query.profile;
+    }
+    let config = crate::resolve_start(&state, request).await?;
+    let mut problems = Vec::new();
+    let file_tag = state
+        .sessions
+        .lock()
+        .unwrap()
+        .file_tag(&config)
+        .unwrap_or_else(|e| {
+            problems.push(e.to_string());
+            String::new()
+        });
+    let server_config = state.config.clone();
+    let response =
+        tokio::task::spawn_blocking(move || plan(&server_config, config, file_tag, problems))
+            .await
+            .map_err(|e| ApiError::internal(e.to_string()))?;
+    Ok(Json(response))
+}
@@ -0,0 +1,381 @@
+// src/email.rs
+use crate::{
+    AppState, compositor,
+    events::{Event, EventKind},
+    metadata,
+};
+use anyhow::{Context, Result, bail};
+use lettre::{
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+    message::{Attachment, Mailbox, MultiPart, SinglePart, header::ContentType},
+    transport::smtp::authentication::Credentials,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+// 첨부하는 미리보기 이미지의 가로 크기
+const THUMBNAIL_WIDTH: u32 = 640;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    // 평문으로 접속한 뒤 STARTTLS 로 올린다 (기본 포트 587).
+    #[default]
+    Starttls,
+    // 처음부터 TLS 로 접속한다 (기본 포트 465).
+    Tls,
+    // 암호화하지 않는다 (기본 포트 25). 같은 네트워크의 릴레이에만 쓴다.
+    None,
+}
+
+// 사람이 지켜보지 않는 장비에서 녹화가 끝나거나 실패하면 메일로 알린다.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmailConfig {
+    pub enabled: bool,
+    pub smtp_host: String,
+    // 비워 두면 security 의 기본 포트
+    pub smtp_port: Option<u16>,
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    // /config 에는 노출하지 않는다.
+    #[serde(skip_serializing)]
+    pub password: Option<String>,
+    // "이름 <주소>" 또는 "주소"
+    pub from: String,
+    pub to: Vec<String>,
+    // 여러 장비의 메일을 구분하도록 제목 앞에 붙인다.
+    pub subject_prefix: String,
+    pub on_finished: bool,
+    pub on_failed: bool,
+    // 끝난 녹화의 첫 프레임을 JPEG 로 첨부한다 (ffmpeg 필요).
+    pub attach_thumbnail: bool,
+    pub timeout_secs: u64,
+}
+
+impl Default for EmailConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            smtp_host: String::new(),
+            smtp_port: None,
+            security: SmtpSecurity::default(),
+            username: None,
+            password: None,
+            from: String::new(),
+            to: Vec::new(),
+            subject_prefix: "[recorder]".to_string(),
+            on_finished: true,
+            on_failed: true,
+            attach_thumbnail: true,
+            timeout_secs: 30,
+        }
+    }
+}
+
+impl EmailConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        if self.smtp_host.trim().is_empty() {
+            bail!("email.smtp_host must not be empty");
+        }
+        if self.to.is_empty() {
+            bail!("email.to must list at least one recipient");
+        }
+        self.mailboxes()?;
+        if self.username.is_some() != self.password.is_some() {
+            bail!("email.username and email.password must be set together");
+        }
+        if self.timeout_secs == 0 {
+            bail!("email.timeout_secs must be non-zero");
+        }
+        Ok(())
+    }
+
+    // 보내는 주소와 받는 주소들
+    fn mailboxes(&self) -> Result<(Mailbox, Vec<Mailbox>)> {
+        let from = self
+            .from
+            .parse()
+            .with_context(|| format!("Invalid email.from address: {:?}", self.from))?;
+        let to = self
+            .to
+            .iter()
+            .map(|to| {
+                to.parse()
+                    .with_context(|| format!("Invalid email.to address: {:?}", to))
+            })
+            .collect::<Result<_>>()?;
+        Ok((from, to))
+    }
+}
+
+struct Mailer {
+    config: EmailConfig,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+}
+
+impl Mailer {
+    fn new(config: &EmailConfig) -> Result<Self> {
+        let host = &config.smtp_host;
+        let mut builder = match config.security {
+            SmtpSecurity::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+                .with_context(|| format!("Invalid SMTP host {:?}", host))?,
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)
+                .with_context(|| format!("Invalid SMTP host {:?}", host))?,
+            SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+        };
+        if let Some(port) = config.smtp_port {
+            builder = builder.port(port);
+        }
+        if let (Some(username), Some(password)) = (&config.username, &config.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+        let (from, to) = config.mailboxes()?;
+        Ok(Self {
+            config: config.clone(),
+            transport: builder
+                .timeout(Some(Duration::from_secs(config.timeout_secs)))
+                .build(),
+            from,
+            to,
+        })
+    }
+
+    async fn send(&self, subject: String, body: String, attachments: Vec<SinglePart>) {
+        let mut builder = Message::builder()
+            .from(self.from.clone())
+            .subject(format!("{} {}", self.config.subject_prefix, subject).trim_start());
+        for to in &self.to {
+            builder = builder.to(to.clone());
+        }
+        let message = if attachments.is_empty() {
+            builder.body(body)
+        } else {
+            let parts = attachments.into_iter().fold(
+                MultiPart::mixed().singlepart(SinglePart::plain(body)),
+                |parts, part| parts.singlepart(part),
+            );
+            builder.multipart(parts)
+        };
+        let result = match message {
+            Ok(message) => self
+                .transport
+                .send(message)
+                .await
+                .map(|_| ())
+                .map_err(anyhow::Error::from),
+            Err(e) => Err(e.into()),
+        };
+        match result {
+            Ok(()) => info!("Emailed {:?} to {} recipient(s).", subject, self.to.len()),
+            Err(e) => warn!("Failed to email {:?}: {:#}", subject, e),
+        }
+    }
+}
+
+// 녹화 세션에 대해 아는 것을 본문 앞에 적는다.
+fn describe_session(state: &AppState, event: &Event, body: &mut String) {
+    let _ = writeln!(body, "Session:  {}", event.session_id);
+    let Some(session) = state.sessions.lock().unwrap().get(event.session_id) else {
+        return;
+    };
+    let stats = session.stats.lock().unwrap().clone();
+    let cameras: Vec<String> = session.config.cameras.iter().map(u32::to_string).collect();
+    let _ = writeln!(
+        body,
+        "Started:  {}",
+        session.started_at.format("%Y-%m-%d %H:%M:%S")
+    );
+    let _ = writeln!(body, "Duration: {:.1} s", stats.elapsed_seconds);
+    let _ = writeln!(body, "Cameras:  {}", cameras.join(", "));
+    let _ = writeln!(
+        body,
+        "Frames:   {} captured, {} dropped",
+        stats.frames_captured, stats.dropped_frames
+    );
+    if let Some(project) = &session.config.project {
+        let _ = writeln!(body, "Project:  {}", project);
+    }
+}
+
+fn content_type(mime: &str) -> ContentType {
+    ContentType::parse(mime).expect("a valid MIME type")
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map_or_else(|| path.to_string_lossy(), |name| name.to_string_lossy())
+        .into_owned()
+}
+
+// 파일 목록과 사이드카 요약을 적고, 첨부할 사이드카와 미리보기 이미지를 모은다.
+fn describe_outputs(outputs: &[PathBuf], thumbnail: bool, body: &mut String) -> Vec<SinglePart> {
+    let mut attachments = Vec::new();
+    let _ = writeln!(body, "\nFiles:");
+    for output in outputs {
+        let size = fs::metadata(output).map_or(0, |metadata| metadata.len());
+        let _ = writeln!(
+            body,
+            "  {} ({:.1} MB)",
+            file_name(output),
+            size as f64 / 1_000_000.0
+        );
+        if let Some(metadata) = metadata::read(output) {
+            let _ = writeln!(
+                body,
+                "    {} to {}, {} frames at {:.2} fps ({} dropped), {}x{} {}",
+                metadata.started_at.format("%H:%M:%S"),
+                metadata.ended_at.format("%H:%M:%S"),
+                metadata.frame_count,
+                metadata.actual_fps,
+                metadata.dropped_frames,
+                metadata.width.unwrap_or(metadata.camera_width),
+                metadata.height.unwrap_or(metadata.camera_height),
+                metadata.codec.as_deref().unwrap_or("unknown codec")
+            );
+        }
+        let sidecar = metadata::sidecar_path(output);
+        if let Ok(contents) = fs::read(&sidecar) {
+            attachments.push(
+                Attachment::new(file_name(&sidecar))
+                    .body(contents, content_type("application/json")),
+            );
+        }
+    }
+    if thumbnail && let Some(output) = outputs.first() {
+        match first_frame(output) {
+            Ok(image) => attachments.push(
+                Attachment::new(format!("{}.jpg", file_name(&output.with_extension(""))))
+                    .body(image, content_type("image/jpeg")),
+            ),
+            Err(e) => warn!("No thumbnail for {:?}: {:#}", output, e),
+        }
+    }
+    attachments
+}
+
+// 녹화 파일의 첫 프레임을 THUMBNAIL_WIDTH 로 줄인 JPEG
+fn first_frame(video: &Path) -> Result<Vec<u8>> {
+    let output = Command::new(compositor::FFMPEG)
+        .arg("-loglevel")
+        .arg("error")
+        .arg("-i")
+        .arg(video)
+        .arg("-frames:v")
+        .arg("1")
+        .arg("-vf")
+        .arg(format!("scale={}:-2", THUMBNAIL_WIDTH))
+        .arg("-f")
+        .arg("image2")
+        .arg("-c:v")
+        .arg("mjpeg")
+        .arg("pipe:1")
+        .stdin(Stdio::null())
+        .output()
+        .context("Failed to run ffmpeg")?;
+    if !output.status.success() || output.stdout.is_empty() {
+        bail!(
+            "ffmpeg failed to decode a frame: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output.stdout)
+}
+
+async fn notify(state: Arc<AppState>, mailer: Arc<Mailer>, event: Event) {
+    let EventKind::RecordingStopped { outputs, error } = &event.kind else {
+        return;
+    };
+    let mut body = String::new();
+    match error {
+        Some(error) => {
+            if !mailer.config.on_failed {
+                return;
+            }
+            let _ = writeln!(body, "The recording failed: {}\n", error);
+            describe_session(&state, &event, &mut body);
+            mailer
+                .send(
+                    format!("Recording failed: {}", event.session_id),
+                    body,
+                    Vec::new(),
+                )
+                .await;
+        }
+        None => {
+            if !mailer.config.on_finished {
+                return;
+            }
+            let _ = writeln!(body, "The recording finished.\n");
+            describe_session(&state, &event, &mut body);
+            let outputs = outputs.clone();
+            let thumbnail = mailer.config.attach_thumbnail;
+            let (body, attachments) = match tokio::task::spawn_blocking(move || {
+                let attachments = describe_outputs(&outputs, thumbnail, &mut body);
+                (body, attachments)
+            })
+            .await
+            {
+                Ok(described) => described,
+                Err(e) => {
+                    warn!("Failed to describe the recording for email: {}", e);
+                    return;
+                }
+            };
+            mailer
+                .send(
+                    format!("Recording finished: {}", event.session_id),
+                    body,
+                    attachments,
+                )
+                .await;
+        }
+    }
+}
+
+async fn run(state: Arc<AppState>, mailer: Arc<Mailer>) {
+    let mut receiver = state.events.subscribe();
+    loop {
+        match receiver.recv().await {
+            // 보내는 동안 다음 이벤트를 놓치지 않게 따로 보낸다.
+            Ok(event) if matches!(event.kind, EventKind::RecordingStopped { .. }) => {
+                tokio::spawn(notify(state.clone(), mailer.clone(), event));
+            }
+            Ok(_) => {}
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                warn!("Email notifier fell behind; {} event(s) skipped.", skipped);
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        }
+    }
+}
+
+pub fn spawn(state: Arc<AppState>) -> Result<()> {
+    let config = &state.config.email;
+    if !config.enabled {
+        return Ok(());
+    }
+    let mailer = Arc::new(Mailer::new(config)?);
+    info!(
+        "Emailing recording results to {} via {}.",
+        config.to.join(", "),
+        config.smtp_host
+    );
+    let tasks = state.tasks.clone();
+    tasks.spawn_service("email", move || run(state.clone(), mailer.clone()));
+    Ok(())
+}
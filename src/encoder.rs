@@ -0,0 +1,118 @@
+// src/encoder.rs
+use serde::{Deserialize, Serialize};
+use std::{
+    process::{Command, Stdio},
+    sync::OnceLock,
+};
+use tracing::info;
+
+// VAAPI 를 쓸 때 여는 DRM 렌더 노드
+const VAAPI_DEVICE: &str = "/dev/dri/renderD128";
+
+// 하드웨어 인코더가 요구하는 비트레이트 (libx264 는 CRF 기본값을 쓴다)
+const HARDWARE_BITRATE: &str = "8M";
+
+// ffmpeg 로 다시 인코딩할 때 (합성, 오버레이, re-encode) 쓰는 H.264 인코더
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Encoder {
+    // 처음 쓸 때 하드웨어 인코더를 순서대로 시험해 보고 되는 것을 쓴다.
+    #[default]
+    Auto,
+    Software,
+    // Raspberry Pi 등 V4L2 M2M 하드웨어 인코더
+    V4l2m2m,
+    Vaapi,
+    Nvenc,
+}
+
+// Auto 일 때 시험하는 순서
+const HARDWARE: [Encoder; 3] = [Encoder::V4l2m2m, Encoder::Vaapi, Encoder::Nvenc];
+
+impl Encoder {
+    fn codec(self) -> &'static str {
+        match self {
+            Self::Auto | Self::Software => "libx264",
+            Self::V4l2m2m => "h264_v4l2m2m",
+            Self::Vaapi => "h264_vaapi",
+            Self::Nvenc => "h264_nvenc",
+        }
+    }
+
+    // 입력(-i)보다 앞에 와야 하는 옵션
+    pub fn input_args(self) -> Vec<&'static str> {
+        match self {
+            Self::Vaapi => vec!["-vaapi_device", VAAPI_DEVICE],
+            _ => Vec::new(),
+        }
+    }
+
+    // 필터 그래프 마지막에 붙여야 하는 필터 (VAAPI 는 프레임을 GPU 메모리로 올려야 한다)
+    fn upload_filter(self) -> Option<&'static str> {
+        match self {
+            Self::Vaapi => Some("format=nv12,hwupload"),
+            _ => None,
+        }
+    }
+
+    pub fn output_args(self) -> Vec<&'static str> {
+        let mut args = vec!["-c:v", self.codec()];
+        match self {
+            Self::Auto | Self::Software => args.extend(["-preset", "veryfast"]),
+            Self::V4l2m2m => args.extend(["-pix_fmt", "yuv420p", "-b:v", HARDWARE_BITRATE]),
+            Self::Vaapi => args.extend(["-b:v", HARDWARE_BITRATE]),
+            Self::Nvenc => args.extend(["-preset", "p4", "-b:v", HARDWARE_BITRATE]),
+        }
+        args
+    }
+
+    // filter 뒤에 upload_filter 를 붙인 필터 문자열
+    pub fn with_upload(self, filter: &str) -> String {
+        match self.upload_filter() {
+            Some(upload) => format!("{},{}", filter, upload),
+            None => filter.to_string(),
+        }
+    }
+}
+
+// Auto 를 실제 인코더로 바꾼다. 시험 결과는 프로세스가 끝날 때까지 재사용한다.
+pub fn resolve(requested: Encoder) -> Encoder {
+    if requested != Encoder::Auto {
+        return requested;
+    }
+    static DETECTED: OnceLock<Encoder> = OnceLock::new();
+    *DETECTED.get_or_init(|| {
+        let detected = HARDWARE
+            .into_iter()
+            .find(|&encoder| probe(encoder))
+            .unwrap_or(Encoder::Software);
+        info!(
+            "Using the {:?} H.264 encoder ({}).",
+            detected,
+            detected.codec()
+        );
+        detected
+    })
+}
+
+// 짧은 테스트 영상을 인코딩해 보아 드라이버와 장치까지 실제로 쓸 수 있는지 확인한다.
+fn probe(encoder: Encoder) -> bool {
+    let mut command = Command::new("ffmpeg");
+    command
+        .arg("-loglevel")
+        .arg("error")
+        .args(encoder.input_args())
+        .arg("-f")
+        .arg("lavfi")
+        .arg("-i")
+        .arg("color=c=black:s=256x144:r=24:d=0.5")
+        .arg("-vf")
+        .arg(encoder.with_upload("null"))
+        .args(encoder.output_args())
+        .arg("-f")
+        .arg("null")
+        .arg("-")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    command.status().is_ok_and(|status| status.success())
+}
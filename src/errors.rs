@@ -0,0 +1,50 @@
+// src/errors.rs
+use crate::AppState;
+use axum::{Json, extract::State};
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+use uuid::Uuid;
+
+// GET /errors 와 /status 에서 보여 주는 최근 오류 개수
+const ERROR_HISTORY: usize = 20;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordedError {
+    pub timestamp: DateTime<Local>,
+    // 녹화 세션과 관계없는 오류(예: 예약 녹화 시작 실패)면 None
+    pub session_id: Option<Uuid>,
+    pub message: String,
+}
+
+// 백그라운드 작업에서 난 오류. 로그만 보지 않고도 클라이언트가 실패 이유를 알 수 있게 한다.
+#[derive(Default)]
+pub struct ErrorLog {
+    entries: Mutex<VecDeque<RecordedError>>,
+}
+
+impl ErrorLog {
+    pub fn record(&self, session_id: Option<Uuid>, message: impl Into<String>) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() == ERROR_HISTORY {
+            entries.pop_front();
+        }
+        entries.push_back(RecordedError {
+            timestamp: Local::now(),
+            session_id,
+            message: message.into(),
+        });
+    }
+
+    // 최신순
+    pub fn recent(&self) -> Vec<RecordedError> {
+        self.entries.lock().unwrap().iter().rev().cloned().collect()
+    }
+}
+
+pub async fn handle_list(State(state): State<Arc<AppState>>) -> Json<Vec<RecordedError>> {
+    Json(state.errors.recent())
+}
@@ -0,0 +1,177 @@
+// src/events.rs
+use crate::{
+    AppState,
+    session::{SessionManager, SessionState},
+};
+use axum::{
+    extract::{
+        State,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+    },
+    response::Response,
+};
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::{path::PathBuf, sync::Arc};
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+// 느린 구독자가 이만큼 뒤처지면 오래된 이벤트부터 버려진다.
+const EVENT_BUFFER: usize = 256;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventKind {
+    RecordingStarted {
+        cameras: Vec<u32>,
+    },
+    RecordingStopped {
+        outputs: Vec<PathBuf>,
+        error: Option<String>,
+    },
+    // 세션 상태가 바뀔 때마다 (Starting 은 recording_started 와 함께 시작한다)
+    StateChanged {
+        previous: SessionState,
+        state: SessionState,
+    },
+    FrameDrop {
+        camera: u32,
+        // 지난 보고 이후 빠진 프레임 수
+        dropped: u64,
+        total_dropped: u64,
+    },
+    DiskLow {
+        free_bytes: u64,
+        min_free_bytes: u64,
+    },
+    CameraDisconnected {
+        camera: u32,
+        reason: String,
+    },
+    CameraReconnected {
+        camera: u32,
+        outage_seconds: f64,
+    },
+    // Layout::Switch 녹화에서 화면에 나오는 카메라를 바꿨을 때
+    CameraSwitched {
+        camera: u32,
+    },
+    // 움직임 감지로 녹화를 시작했을 때
+    MotionDetected {
+        camera: u32,
+        changed_percent: f64,
+    },
+    // POST /recordings/current/marker 로 표시를 남겼을 때 (elapsed_secs 는 세션 시작 기준)
+    BookmarkAdded {
+        label: String,
+        elapsed_secs: f64,
+    },
+    // 정리 요청이나 보존 정책으로 녹화 파일을 지웠을 때 (세션을 모르면 nil)
+    RecordingDeleted {
+        name: String,
+        reason: String,
+    },
+    // 설정 파일을 다시 읽어 적용했을 때 (session_id 는 nil). 다시 시작해야 바뀌는 항목은 따로 적는다.
+    ConfigReloaded {
+        changed: Vec<String>,
+        restart_required: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+    pub session_id: Uuid,
+    pub timestamp: DateTime<Local>,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+impl Event {
+    // 녹화 시작/종료 이벤트면 그 뒤에 녹화 중인 세션이 남아 있는지, 다른 이벤트면 None.
+    // 시작은 요청을 받았을 때가 아니라 모든 카메라에서 첫 프레임이 와 Recording 이 되었을 때로 본다.
+    pub fn recording_after(&self, sessions: &SessionManager) -> Option<bool> {
+        match self.kind {
+            EventKind::StateChanged {
+                previous: SessionState::Starting,
+                state: SessionState::Recording,
+            } => Some(true),
+            // 끝난 세션은 이 이벤트를 보낸 다음에 종료 상태가 되므로 나머지 세션만 본다.
+            EventKind::RecordingStopped { .. } => Some(
+                sessions
+                    .running()
+                    .iter()
+                    .any(|session| session.id != self.session_id),
+            ),
+            _ => None,
+        }
+    }
+}
+
+// 녹화 스레드와 핸들러가 이벤트를 보내고, /events 구독자가 받는다.
+pub struct EventBus {
+    sender: broadcast::Sender<Event>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(EVENT_BUFFER).0,
+        }
+    }
+}
+
+impl EventBus {
+    // 구독자가 없어도 실패로 보지 않는다.
+    pub fn publish(&self, session_id: Uuid, kind: EventKind) {
+        let _ = self.sender.send(Event {
+            session_id,
+            timestamp: Local::now(),
+            kind,
+        });
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+}
+
+pub async fn handle_events(
+    State(state): State<Arc<AppState>>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    let receiver = state.events.subscribe();
+    upgrade.on_upgrade(move |socket| stream_events(socket, receiver))
+}
+
+async fn stream_events(mut socket: WebSocket, mut receiver: broadcast::Receiver<Event>) {
+    info!("Event stream client connected.");
+    loop {
+        tokio::select! {
+            event = receiver.recv() => match event {
+                Ok(event) => {
+                    let text = match serde_json::to_string(&event) {
+                        Ok(text) => text,
+                        Err(e) => {
+                            warn!("Failed to serialize event: {}", e);
+                            continue;
+                        }
+                    };
+                    if socket.send(Message::Text(text)).await.is_err() {
+                        break;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("Event stream client fell behind; {} event(s) skipped.", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            // 클라이언트가 보내는 메시지는 무시하고, 연결이 끊기면 종료
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+    info!("Event stream client disconnected.");
+}
@@ -0,0 +1,628 @@
+// src/feed.rs
+use crate::{
+    camera_handler::RecordingConfig,
+    compositor::{self, FFMPEG},
+    encoder::{self, Encoder},
+    stream,
+};
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    process::{Child, Command},
+    sync::{
+        Arc, Condvar, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+// 카메라 파일 끝에서 새 데이터를 기다리는 간격
+const TAIL_INTERVAL: Duration = Duration::from_millis(50);
+
+// 카메라 쪽을 읽는 스레드와 인코더 (ffmpeg) 에 쓰는 스레드 사이의 카메라별 프레임 대기열.
+// 인코더가 뒤처져도 읽기가 멈추지 않으므로, 카메라가 FIFO 에 바로 쓰는 미리보기에서도 촬영이 밀리지 않는다.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WriteQueueConfig {
+    // 카메라마다 쌓아 둘 수 있는 프레임 수
+    pub capacity_frames: usize,
+    pub drop_policy: DropPolicy,
+}
+
+impl Default for WriteQueueConfig {
+    fn default() -> Self {
+        Self {
+            capacity_frames: 120,
+            drop_policy: DropPolicy::default(),
+        }
+    }
+}
+
+impl WriteQueueConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.capacity_frames == 0 {
+            bail!("write_queue.capacity_frames must be non-zero");
+        }
+        Ok(())
+    }
+}
+
+// 대기열이 가득 찼을 때
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DropPolicy {
+    // 가장 오래된 GOP 부터 버리고 다음 키프레임부터 다시 보낸다 (시청자는 잠깐 건너뛴다).
+    #[default]
+    DropOldest,
+    // 자리가 날 때까지 읽기를 멈춘다. 프레임은 빠지지 않지만 카메라가 FIFO 에 쓰다 막힐 수 있다.
+    Block,
+}
+
+// 카메라 한 대의 대기열 상태 (/status 에서 사용)
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct QueueStats {
+    pub depth: usize,
+    pub capacity: usize,
+    // DropOldest 로 버린 프레임 수 (누적)
+    pub dropped_frames: u64,
+}
+
+// Annex B 로 이어 붙인 프레임 하나
+struct Frame {
+    data: Vec<u8>,
+    keyframe: bool,
+}
+
+#[derive(Default)]
+struct QueueState {
+    frames: VecDeque<Frame>,
+    closed: bool,
+    dropped: u64,
+    // 앞 프레임을 모두 버려 다음 키프레임을 기다리는 중
+    resync: bool,
+}
+
+impl QueueState {
+    // 맨 앞 프레임과 그 GOP 의 나머지 (다음 키프레임 전까지) 를 버린다.
+    fn drop_oldest(&mut self) {
+        self.frames.pop_front();
+        self.dropped += 1;
+        while self.frames.front().is_some_and(|frame| !frame.keyframe) {
+            self.frames.pop_front();
+            self.dropped += 1;
+        }
+        if self.frames.is_empty() {
+            self.resync = true;
+        }
+    }
+}
+
+struct FrameQueue {
+    config: WriteQueueConfig,
+    state: Mutex<QueueState>,
+    changed: Condvar,
+}
+
+impl FrameQueue {
+    fn new(config: &WriteQueueConfig) -> Self {
+        Self {
+            config: config.clone(),
+            state: Mutex::new(QueueState::default()),
+            changed: Condvar::new(),
+        }
+    }
+
+    // 닫혔으면 false (쓰는 쪽이 끝났다)
+    fn push(&self, frame: Frame) -> bool {
+        let mut state = self.state.lock().unwrap();
+        while state.frames.len() >= self.config.capacity_frames && !state.closed {
+            match self.config.drop_policy {
+                DropPolicy::DropOldest => state.drop_oldest(),
+                DropPolicy::Block => state = self.changed.wait(state).unwrap(),
+            }
+        }
+        if state.closed {
+            return false;
+        }
+        // 참조할 프레임이 빠졌으므로 키프레임이 올 때까지는 디코딩할 수 없다.
+        if state.resync && !frame.keyframe {
+            state.dropped += 1;
+            return true;
+        }
+        state.resync = false;
+        state.frames.push_back(frame);
+        self.changed.notify_all();
+        true
+    }
+
+    // 닫힌 뒤에도 남은 프레임은 마저 꺼낸다. 비어 있고 닫혔으면 None
+    fn pop(&self) -> Option<Frame> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(frame) = state.frames.pop_front() {
+                self.changed.notify_all();
+                return Some(frame);
+            }
+            if state.closed {
+                return None;
+            }
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+
+    fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.changed.notify_all();
+    }
+
+    fn stats(&self) -> QueueStats {
+        let state = self.state.lock().unwrap();
+        QueueStats {
+            depth: state.frames.len(),
+            capacity: self.config.capacity_frames,
+            dropped_frames: state.dropped,
+        }
+    }
+}
+
+// 읽은 바이트를 NAL 단위로 자르고 프레임별로 묶는다.
+#[derive(Default)]
+struct Framer {
+    pending: Vec<u8>,
+    unit: Vec<Vec<u8>>,
+}
+
+impl Framer {
+    fn push(&mut self, bytes: &[u8]) -> Vec<Frame> {
+        self.pending.extend_from_slice(bytes);
+        let mut frames = Vec::new();
+        for nal in stream::split_nals(&mut self.pending) {
+            if stream::starts_access_unit(&nal) && self.unit.iter().any(|nal| stream::is_vcl(nal)) {
+                frames.push(frame(std::mem::take(&mut self.unit)));
+            }
+            self.unit.push(nal);
+        }
+        frames
+    }
+
+    // 끝을 알 수 없어 남겨 둔 마지막 NAL 까지 내보낸다.
+    fn finish(&mut self) -> Option<Frame> {
+        let mut last = frame(std::mem::take(&mut self.unit));
+        last.data.append(&mut self.pending);
+        (!last.data.is_empty()).then_some(last)
+    }
+}
+
+fn frame(nals: Vec<Vec<u8>>) -> Frame {
+    let keyframe = nals.iter().any(|nal| stream::is_keyframe(nal));
+    let mut data = Vec::with_capacity(nals.iter().map(|nal| nal.len() + 4).sum());
+    for nal in &nals {
+        data.extend_from_slice(&[0, 0, 0, 1]);
+        data.extend_from_slice(nal);
+    }
+    Frame { data, keyframe }
+}
+
+// 녹화 중인 카메라 파일 (또는 카메라가 쓰는 FIFO) 을 따라 읽어 카메라별 FIFO 로 넘긴다.
+// 녹화 파일 쓰기와는 독립적이라 여기서 실패해도 녹화는 계속된다 (HLS, RTSP 에서 사용).
+pub struct Feeds {
+    dir: PathBuf,
+    stop: Arc<AtomicBool>,
+    // 카메라별로 지금 따라 읽는 파일 (재연결하면 바뀐다)
+    sources: Vec<(u32, Arc<Mutex<PathBuf>>)>,
+    // sources 와 같은 순서
+    queues: Vec<Arc<FrameQueue>>,
+    // 카메라마다 읽는 스레드와 쓰는 스레드
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl Feeds {
+    // sources 는 (카메라 번호, libcamera-vid 가 쓰는 파일 또는 %04d 패턴).
+    // dir 에 FIFO 를 만들고, finish 할 때 dir 을 통째로 지운다.
+    pub fn start(
+        dir: PathBuf,
+        sources: &[(u32, PathBuf)],
+        queue: &WriteQueueConfig,
+    ) -> Result<Self> {
+        let cameras: Vec<u32> = sources.iter().map(|(camera, _)| *camera).collect();
+        create_fifos(&dir, &cameras)?;
+
+        let mut feeds = Self {
+            dir,
+            stop: Arc::new(AtomicBool::new(false)),
+            sources: Vec::new(),
+            queues: Vec::new(),
+            threads: Vec::new(),
+        };
+        for &(camera, ref source) in sources {
+            let current = Arc::new(Mutex::new(source.clone()));
+            let queue = Arc::new(FrameQueue::new(queue));
+            let tail = {
+                let current = current.clone();
+                let queue = queue.clone();
+                let stop = feeds.stop.clone();
+                thread::Builder::new()
+                    .name(format!("feed-cam{}", camera))
+                    .spawn(move || {
+                        if let Err(e) = tail(&current, &queue, &stop) {
+                            warn!("camera {}: stream input stopped: {:#}", camera, e);
+                        }
+                        queue.close();
+                    })
+                    .context("Failed to start stream feed thread")?
+            };
+            let writer = {
+                let queue = queue.clone();
+                let fifo = fifo_path(&feeds.dir, camera);
+                thread::Builder::new()
+                    .name(format!("feed-write-cam{}", camera))
+                    .spawn(move || {
+                        if let Err(e) = write_frames(&queue, &fifo) {
+                            warn!("camera {}: stream output stopped: {:#}", camera, e);
+                        }
+                        // 읽는 쪽이 기다리고 있으면 풀어 준다.
+                        queue.close();
+                    })
+                    .context("Failed to start stream feed thread")?
+            };
+            feeds.sources.push((camera, current));
+            feeds.queues.push(queue);
+            feeds.threads.extend([tail, writer]);
+        }
+        Ok(feeds)
+    }
+
+    pub fn fifos(&self) -> Vec<PathBuf> {
+        self.sources
+            .iter()
+            .map(|(camera, _)| fifo_path(&self.dir, *camera))
+            .collect()
+    }
+
+    // 재연결 등으로 카메라가 새 파일에 쓰기 시작하면 그 파일을 따라간다.
+    pub fn follow(&self, camera: u32, path: &Path) {
+        if let Some((_, current)) = self.sources.iter().find(|(index, _)| *index == camera) {
+            let mut current = current.lock().unwrap();
+            if *current != path {
+                *current = path.to_path_buf();
+            }
+        }
+    }
+
+    // 남은 데이터를 넘기고 FIFO 를 닫도록 한다. 읽는 쪽은 EOF 를 받고 끝난다.
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+
+    // 읽는 쪽(ffmpeg)이 끝난 뒤에 호출한다.
+    pub fn finish(mut self) {
+        self.release();
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+        if let Err(e) = fs::remove_dir_all(&self.dir) {
+            warn!("Failed to remove {:?}: {}", self.dir, e);
+        }
+    }
+
+    pub fn cameras(&self) -> Vec<u32> {
+        self.sources.iter().map(|(camera, _)| *camera).collect()
+    }
+
+    pub fn queue_stats(&self) -> BTreeMap<u32, QueueStats> {
+        self.sources
+            .iter()
+            .zip(&self.queues)
+            .map(|((camera, _), queue)| (*camera, queue.stats()))
+            .collect()
+    }
+
+    // 막혀 있을 수 있는 스레드를 모두 풀어 끝나게 한다.
+    fn release(&self) {
+        self.stop();
+        for queue in &self.queues {
+            queue.close();
+        }
+        unblock_writers(&self.dir, &self.cameras());
+        for (_, current) in &self.sources {
+            unblock_reader(&current.lock().unwrap());
+        }
+    }
+}
+
+impl Drop for Feeds {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+pub fn fifo_path(dir: &Path, camera: u32) -> PathBuf {
+    dir.join(format!("cam{}.fifo", camera))
+}
+
+// dir 을 만들고 카메라마다 FIFO 하나씩
+pub fn create_fifos(dir: &Path, cameras: &[u32]) -> Result<()> {
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    for &camera in cameras {
+        make_fifo(&fifo_path(dir, camera))?;
+    }
+    Ok(())
+}
+
+// 읽는 쪽이 FIFO 를 열기 전에 끝났으면 쓰기 쪽을 여는 스레드가 막혀 있으므로,
+// 읽기 쪽을 잠깐 열어 풀어 준다 (이후 쓰기는 EPIPE 로 끝난다).
+pub fn unblock_writers(dir: &Path, cameras: &[u32]) {
+    for &camera in cameras {
+        drop(open_reader(&fifo_path(dir, camera)));
+    }
+}
+
+// 카메라가 FIFO 를 열기 전에 끝났으면 그 FIFO 를 여는 스레드가 막혀 있으므로,
+// 쓰기 쪽을 잠깐 열어 풀어 준다 (이후 읽기는 EOF 를 받는다).
+fn unblock_reader(source: &Path) {
+    if is_fifo(source) {
+        drop(open_writer(source));
+    }
+}
+
+// FIFO 들을 raw H.264 입력으로 받는 ffmpeg 명령 (출력은 호출하는 쪽에서 붙인다).
+// cameras 는 fifos 와 같은 순서의 카메라 번호
+pub fn ffmpeg_inputs(
+    fifos: &[PathBuf],
+    cameras: &[u32],
+    config: &RecordingConfig,
+    encoder: Encoder,
+) -> Command {
+    let mut command = Command::new(FFMPEG);
+    command.arg("-y").arg("-loglevel").arg("error");
+    // 합성하거나 돌리거나 가림 영역을 칠할 때만 다시 인코딩한다.
+    if fifos.len() > 1
+        || cameras
+            .iter()
+            .any(|&camera| config.camera_filter(camera).is_some())
+    {
+        command.args(encoder.input_args());
+    }
+    for (fifo, &camera) in fifos.iter().zip(cameras) {
+        command
+            .arg("-f")
+            .arg("h264")
+            .arg("-r")
+            .arg(config.format_for(camera).fps.to_string())
+            .arg("-i")
+            .arg(fifo);
+    }
+    command
+}
+
+// 여러 카메라를 녹화와 같은 배치로 합성하는 필터 (돌리고 가림 영역을 칠하고, 해상도가 다른 카메라는 크기를 맞춘다)
+pub fn composite_filter(cameras: &[u32], config: &RecordingConfig) -> String {
+    let filters: Vec<Option<String>> = cameras
+        .iter()
+        .map(
+            |&camera| match (config.camera_filter(camera), config.fit_filter(camera)) {
+                (Some(camera), Some(fit)) => Some(format!("{},{}", camera, fit)),
+                (camera, fit) => camera.or(fit),
+            },
+        )
+        .collect();
+    compositor::filter_graph(&config.arrangement(), &filters)
+}
+
+// 카메라 한 대를 내보내는 출력 옵션: 그대로 복사하고, 돌리거나 가림 영역을 칠해야 하면 다시 인코딩한다.
+pub fn camera_output_args(camera: u32, config: &RecordingConfig, encoder: Encoder) -> Vec<String> {
+    match config.camera_filter(camera) {
+        Some(filter) => {
+            let mut args = vec!["-vf".to_string(), encoder.with_upload(&filter)];
+            args.extend(encoder.output_args().into_iter().map(String::from));
+            args
+        }
+        None => vec!["-c:v".to_string(), "copy".to_string()],
+    }
+}
+
+// 한 대면 그대로 (가림 영역이 있으면 칠해서), 여러 대면 녹화와 같은 배치로 합성해 인코딩하는
+// ffmpeg 명령. 출력 형식과 경로는 호출하는 쪽에서 붙인다.
+pub fn ffmpeg_command(fifos: &[PathBuf], cameras: &[u32], config: &RecordingConfig) -> Command {
+    let encoder = encoder::resolve(config.encoder);
+    let mut command = ffmpeg_inputs(fifos, cameras, config, encoder);
+    if fifos.len() > 1 {
+        command
+            .arg("-filter_complex")
+            .arg(encoder.with_upload(&composite_filter(cameras, config)))
+            .args(encoder.output_args());
+    } else {
+        command.args(camera_output_args(cameras[0], config, encoder));
+    }
+    command
+}
+
+// 입력이 닫힌 뒤 스스로 끝나기를 grace 만큼 기다리고, 그래도 안 끝나면 죽인다.
+pub fn wait_or_kill(child: &mut Child, grace: Duration) {
+    let deadline = Instant::now() + grace;
+    loop {
+        match child.try_wait() {
+            Ok(Some(_)) => break,
+            Ok(None) if Instant::now() < deadline => thread::sleep(TAIL_INTERVAL),
+            // 입력을 기다리며 멈춰 있으면 죽인다 (막혀 있던 쓰기도 풀린다).
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+pub fn make_fifo(path: &Path) -> Result<()> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).context("Invalid FIFO path")?;
+    // SAFETY: c_path 는 호출 동안 유효한 NUL 종료 문자열이다.
+    let result = unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) };
+    if result != 0 {
+        bail!(
+            "Failed to create FIFO {:?}: {}",
+            path,
+            io::Error::last_os_error()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn make_fifo(_path: &Path) -> Result<()> {
+    bail!("Streaming is only supported on Unix")
+}
+
+#[cfg(unix)]
+pub fn open_reader(path: &Path) -> Option<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    File::options()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path)
+        .ok()
+}
+
+#[cfg(not(unix))]
+pub fn open_reader(_path: &Path) -> Option<File> {
+    None
+}
+
+// 출력 FIFO 를 읽으려고 기다리는 스레드를 풀어 줄 때 쓴다 (닫으면 EOF 를 받는다).
+#[cfg(unix)]
+pub fn open_writer(path: &Path) -> Option<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    File::options()
+        .write(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path)
+        .ok()
+}
+
+#[cfg(not(unix))]
+pub fn open_writer(_path: &Path) -> Option<File> {
+    None
+}
+
+#[cfg(unix)]
+fn is_fifo(path: &Path) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+
+    fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_fifo())
+}
+
+#[cfg(not(unix))]
+fn is_fifo(_path: &Path) -> bool {
+    false
+}
+
+// 카메라 파일을 처음부터 따라 읽으며 프레임 단위로 대기열에 넣는다. 분할 녹화(%04d)면 다음 세그먼트로 넘어간다.
+fn tail(current: &Mutex<PathBuf>, queue: &FrameQueue, stop: &AtomicBool) -> Result<()> {
+    let mut pattern = current.lock().unwrap().clone();
+    let mut number = 0u32;
+    let mut input: Option<File> = None;
+    let mut framer = Framer::default();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        // 종료 요청 전에 쓰인 데이터까지는 넘기도록 먼저 확인해 둔다.
+        let stopping = stop.load(Ordering::SeqCst);
+
+        if input.is_none() {
+            // libcamera-vid 가 파일을 만들기 전일 수 있다 (FIFO 면 카메라가 열 때까지 기다린다).
+            input = File::open(segment_path(&pattern, number)).ok();
+        }
+        if let Some(file) = input.as_mut()
+            && !read_frames(file, &mut framer, &mut buffer, queue)?
+        {
+            // 쓰는 쪽이 끝나 대기열이 닫혔다.
+            return Ok(());
+        }
+
+        if stopping {
+            if let Some(frame) = framer.finish() {
+                queue.push(frame);
+            }
+            return Ok(());
+        }
+
+        let latest = current.lock().unwrap().clone();
+        if latest != pattern {
+            pattern = latest;
+            number = 0;
+            input = None;
+            continue;
+        }
+        // libcamera-vid 는 이전 세그먼트를 닫은 뒤 다음 파일을 만들므로,
+        // 다음 파일이 보이면 지금 파일을 끝까지 한 번 더 읽고 넘어간다.
+        let next = segment_path(&pattern, number + 1);
+        let advance = next != segment_path(&pattern, number) && next.exists();
+        if let Some(file) = input.as_mut().filter(|_| advance) {
+            if !read_frames(file, &mut framer, &mut buffer, queue)? {
+                return Ok(());
+            }
+            number += 1;
+            input = None;
+            continue;
+        }
+        thread::sleep(TAIL_INTERVAL);
+    }
+}
+
+// 지금 읽을 수 있는 만큼 읽어 대기열에 넣는다. 대기열이 닫혔으면 false
+fn read_frames(
+    file: &mut File,
+    framer: &mut Framer,
+    buffer: &mut [u8],
+    queue: &FrameQueue,
+) -> Result<bool> {
+    loop {
+        let read = file.read(buffer)?;
+        if read == 0 {
+            return Ok(true);
+        }
+        for frame in framer.push(&buffer[..read]) {
+            if !queue.push(frame) {
+                return Ok(false);
+            }
+        }
+    }
+}
+
+// 대기열의 프레임을 FIFO 에 쓴다. 대기열이 닫히고 비면 FIFO 를 닫아 읽는 쪽이 EOF 를 받게 한다.
+fn write_frames(queue: &FrameQueue, fifo: &Path) -> Result<()> {
+    // ffmpeg 가 읽기 쪽을 열 때까지 여기서 기다린다.
+    let mut output = File::options()
+        .write(true)
+        .open(fifo)
+        .with_context(|| format!("Failed to open {:?}", fifo))?;
+    while let Some(frame) = queue.pop() {
+        if let Err(e) = output.write_all(&frame.data) {
+            // ffmpeg 가 끝나 읽기 쪽이 닫혔다.
+            if e.kind() == io::ErrorKind::BrokenPipe {
+                return Ok(());
+            }
+            return Err(e.into());
+        }
+    }
+    Ok(())
+}
+
+fn segment_path(pattern: &Path, number: u32) -> PathBuf {
+    let pattern = pattern.to_string_lossy();
+    if pattern.contains("%04d") {
+        PathBuf::from(pattern.replace("%04d", &format!("{:04}", number)))
+    } else {
+        PathBuf::from(pattern.into_owned())
+    }
+}
@@ -0,0 +1,161 @@
+// src/filename.rs
+use crate::{camera_handler::RecordingConfig, session::RecordingSession};
+use anyhow::{Result, bail};
+use chrono::{DateTime, Local};
+
+// 지금까지와 같은 <시작 시각>.mp4
+pub const DEFAULT_TEMPLATE: &str = "{start_time}";
+
+const PLACEHOLDERS: &[&str] = &["date", "time", "start_time", "session_id", "camera_set"];
+
+// Windows 에서 파일 이름에 쓸 수 없는 문자
+const WINDOWS_RESERVED: &[char] = &['<', '>', ':', '"', '|', '?', '*'];
+
+// 확장자는 sink.container 를 따르므로 틀 끝에 붙인 것은 무시한다.
+const IGNORED_EXTENSIONS: &[&str] = &[".mp4", ".mkv"];
+
+// 틀에서 {이름} 을 찾아 replace 로 바꾼다. 모르는 이름이나 짝이 맞지 않는 괄호는 오류
+fn expand(template: &str, mut replace: impl FnMut(&str) -> String) -> Result<String> {
+    let mut output = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find(['{', '}']) {
+        if rest[open..].starts_with('}') {
+            bail!("filename has an unmatched '}}': {:?}", template);
+        }
+        output.push_str(&rest[..open]);
+        let Some(close) = rest[open..].find('}') else {
+            bail!("filename has an unmatched '{{': {:?}", template);
+        };
+        let name = &rest[open + 1..open + close];
+        if !PLACEHOLDERS.contains(&name) {
+            bail!(
+                "filename has an unknown placeholder {{{}}} (expected one of {:?})",
+                name,
+                PLACEHOLDERS
+            );
+        }
+        output.push_str(&replace(name));
+        rest = &rest[open + close + 1..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+fn strip_extension(template: &str) -> &str {
+    IGNORED_EXTENSIONS
+        .iter()
+        .find_map(|extension| template.strip_suffix(extension))
+        .unwrap_or(template)
+}
+
+// 저장 디렉토리 기준 상대 경로여야 하고, '/' 로 나눈 각 부분이 비어 있으면 안 된다.
+pub fn validate(template: &str) -> Result<()> {
+    let expanded = expand(strip_extension(template), |name| name.to_string())?;
+    validate_relative("filename", template, &expanded)
+}
+
+// output_dir: 저장 디렉토리 아래의 하위 디렉토리. 저장 디렉토리 밖을 가리킬 수 없다.
+pub fn validate_output_dir(output_dir: &str) -> Result<()> {
+    if output_dir.starts_with('/') {
+        bail!("output_dir must be relative to save_dir: {:?}", output_dir);
+    }
+    validate_relative("output_dir", output_dir, output_dir.trim_end_matches('/'))
+}
+
+fn validate_relative(field: &str, original: &str, path: &str) -> Result<()> {
+    if path.trim().is_empty() {
+        bail!("{} must not be empty", field);
+    }
+    if path.contains('\\') {
+        bail!("{} must use '/' to separate directories", field);
+    }
+    if path.contains(char::is_control) {
+        bail!(
+            "{} must not contain control characters: {:?}",
+            field,
+            original
+        );
+    }
+    for part in path.split('/') {
+        if part.is_empty() || part == "." || part == ".." {
+            bail!(
+                "{} must be a relative path without empty, '.' or '..' parts: {:?}",
+                field,
+                original
+            );
+        }
+        if cfg!(windows) && (part.contains(WINDOWS_RESERVED) || part.ends_with(['.', ' '])) {
+            bail!(
+                "{} parts must not contain any of <>:\"|?* or end with '.' or a space on Windows: {:?}",
+                field,
+                original
+            );
+        }
+    }
+    Ok(())
+}
+
+// 한 녹화 세션의 파일 이름. 분할 녹화면 세그먼트마다 그 시작 시각으로 만든다.
+pub struct FileNames {
+    template: String,
+    // 모든 파일 앞에 붙는 하위 디렉토리 (뒤에 '/' 가 붙어 있다)
+    output_dir: String,
+    session_id: String,
+    camera_set: String,
+    // 동시에 녹화하는 세션끼리 이름이 겹치지 않게 붙이는 꼬리표.
+    // {session_id} 나 {camera_set} 이 들어 있으면 이미 겹치지 않으므로 붙이지 않는다.
+    tag: String,
+}
+
+impl FileNames {
+    pub fn new(session: &RecordingSession, cameras: &[u32]) -> Self {
+        Self::planned(
+            &session.config,
+            &session.id.to_string(),
+            &session.file_tag,
+            cameras,
+        )
+    }
+
+    // 아직 시작하지 않은 세션의 이름 (POST /start/validate 는 session_id 자리에 "{session_id}" 를 넣는다)
+    pub fn planned(
+        config: &RecordingConfig,
+        session_id: &str,
+        file_tag: &str,
+        cameras: &[u32],
+    ) -> Self {
+        let template = strip_extension(&config.filename).to_string();
+        let unique = template.contains("{session_id}") || template.contains("{camera_set}");
+        let cameras: Vec<String> = cameras.iter().map(u32::to_string).collect();
+        Self {
+            tag: if unique {
+                String::new()
+            } else {
+                file_tag.to_string()
+            },
+            template,
+            output_dir: config
+                .output_dir
+                .as_deref()
+                .or(config.project.as_deref())
+                .map(|dir| format!("{}/", dir.trim_end_matches('/')))
+                .unwrap_or_default(),
+            session_id: session_id.to_string(),
+            camera_set: format!("cam{}", cameras.join("-")),
+        }
+    }
+
+    // 확장자를 뺀 상대 경로 (예: projectX/2025-01-31/cam0-1_20250131_093000)
+    pub fn stem(&self, start: DateTime<Local>) -> String {
+        let expanded = expand(&self.template, |name| match name {
+            "date" => start.format("%Y-%m-%d").to_string(),
+            "time" => start.format("%H%M%S").to_string(),
+            "start_time" => start.format("%Y%m%d_%H%M%S").to_string(),
+            "session_id" => self.session_id.clone(),
+            _ => self.camera_set.clone(),
+        })
+        // validate 를 거친 틀이므로 실패하지 않는다.
+        .unwrap_or_else(|_| start.format("%Y%m%d_%H%M%S").to_string());
+        format!("{}{}{}", self.output_dir, expanded, self.tag)
+    }
+}
@@ -0,0 +1,101 @@
+// src/frame_log.rs
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, SecondsFormat};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+// 녹화 파일 옆에 남기는 프레임 시각 목록
+pub fn sidecar_path(video: &Path) -> PathBuf {
+    video.with_extension("frames.csv")
+}
+
+// 녹화 중 모든 카메라의 프레임마다 한 줄씩 남기는 CSV.
+// camera,frame,monotonic_ms,pts_ms,wall_clock
+//  - frame: 카메라별 0 부터의 번호 (재연결해도 이어짐, 녹화 전 영상은 포함하지 않음)
+//  - monotonic_ms: 녹화 시작 기준 경과 시간 (카메라끼리 비교할 수 있음)
+//  - pts_ms: libcamera-vid 가 기록한 값 (재연결하면 0 부터 다시 시작)
+//  - wall_clock: 프레임이 찍힌 벽시계 시각 (RFC 3339, 밀리초)
+pub struct FrameLog {
+    path: PathBuf,
+    started: Instant,
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    writer: BufWriter<File>,
+    frames: BTreeMap<u32, u64>,
+    failed: bool,
+}
+
+// pts 파일 하나의 첫 프레임 기준점. 이후 프레임 시각은 pts 차이로 구해 읽기 지연에 흔들리지 않는다.
+#[derive(Default)]
+pub struct FileClock {
+    first: Option<(f64, Instant, DateTime<Local>)>,
+}
+
+impl FrameLog {
+    pub fn create(path: PathBuf, started: Instant) -> Result<Self> {
+        let file = File::create(&path).with_context(|| format!("Failed to create {:?}", path))?;
+        let mut writer = BufWriter::new(file);
+        writeln!(writer, "camera,frame,monotonic_ms,pts_ms,wall_clock")
+            .with_context(|| format!("Failed to write {:?}", path))?;
+        Ok(Self {
+            path,
+            started,
+            inner: Mutex::new(Inner {
+                writer,
+                frames: BTreeMap::new(),
+                failed: false,
+            }),
+        })
+    }
+
+    // pts 읽기 스레드가 프레임을 읽을 때마다 부른다.
+    pub fn record(&self, clock: &mut FileClock, camera: u32, pts_ms: f64, arrived: Instant) {
+        let (first_pts, first_instant, first_wall) = *clock
+            .first
+            .get_or_insert_with(|| (pts_ms, arrived, Local::now()));
+        let since_first = Duration::from_secs_f64((pts_ms - first_pts).max(0.0) / 1000.0);
+        let monotonic = (first_instant + since_first).saturating_duration_since(self.started);
+        let wall = first_wall
+            + chrono::Duration::from_std(since_first).unwrap_or(chrono::Duration::zero());
+
+        let mut inner = self.inner.lock().unwrap();
+        if inner.failed {
+            return;
+        }
+        let frame = inner.frames.entry(camera).or_insert(0);
+        let number = *frame;
+        *frame += 1;
+        let result = writeln!(
+            inner.writer,
+            "{},{},{:.3},{:.3},{}",
+            camera,
+            number,
+            monotonic.as_secs_f64() * 1000.0,
+            pts_ms,
+            wall.to_rfc3339_opts(SecondsFormat::Millis, false)
+        );
+        if let Err(e) = result {
+            warn!(
+                "Failed to write {:?}: {}. No more frames will be logged.",
+                self.path, e
+            );
+            inner.failed = true;
+        }
+    }
+
+    pub fn finish(&self) -> PathBuf {
+        if let Err(e) = self.inner.lock().unwrap().writer.flush() {
+            warn!("Failed to write {:?}: {}", self.path, e);
+        }
+        self.path.clone()
+    }
+}
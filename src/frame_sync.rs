@@ -0,0 +1,209 @@
+// src/frame_sync.rs
+use crate::frame_log::{FileClock, FrameLog};
+use anyhow::{Context, Result};
+use std::{
+    collections::{BTreeMap, btree_map::Entry},
+    fs::File,
+    io::{BufRead, BufReader},
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, SyncSender},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+// 녹화 루프가 잠시 밀려도 읽기 스레드가 기다리도록 하는 채널 크기
+const CHANNEL_CAPACITY: usize = 256;
+
+// pts 파일 끝에서 새 줄을 기다리는 간격
+const READ_INTERVAL: Duration = Duration::from_millis(10);
+
+// 카메라 한 대에서 프레임 하나가 기록됨
+pub struct FrameEvent {
+    pub camera: u32,
+    pub pts_ms: f64,
+    pub arrived: Instant,
+}
+
+pub fn channel() -> (SyncSender<FrameEvent>, Receiver<FrameEvent>) {
+    mpsc::sync_channel(CHANNEL_CAPACITY)
+}
+
+// 카메라별 pts 읽기 스레드. 버려지면 스레드에 종료를 알린다.
+pub struct PtsReaders {
+    stop: Arc<AtomicBool>,
+    handles: Vec<JoinHandle<()>>,
+    // 있으면 읽은 프레임마다 시각을 기록한다.
+    log: Option<Arc<FrameLog>>,
+}
+
+impl PtsReaders {
+    pub fn new(log: Option<Arc<FrameLog>>) -> Self {
+        Self {
+            stop: Arc::new(AtomicBool::new(false)),
+            handles: Vec::new(),
+            log,
+        }
+    }
+
+    // libcamera-vid 가 --save-pts 로 쓰는 파일을 따라 읽는다.
+    pub fn spawn(
+        &mut self,
+        camera: u32,
+        path: PathBuf,
+        sender: SyncSender<FrameEvent>,
+    ) -> Result<()> {
+        let stop = self.stop.clone();
+        let log = self.log.clone();
+        let handle = thread::Builder::new()
+            .name(format!("pts-cam{}", camera))
+            .spawn(move || read_pts(camera, &path, &sender, &stop, log.as_deref()))
+            .context("Failed to start pts reader thread")?;
+        self.handles.push(handle);
+        Ok(())
+    }
+
+    // 남은 줄을 모두 읽고 끝내도록 한 뒤 기다린다. 수신 측은 그동안 채널을 비워야 한다.
+    pub fn finish(mut self, receiver: &Receiver<FrameEvent>, mut on_event: impl FnMut(FrameEvent)) {
+        self.stop.store(true, Ordering::SeqCst);
+        // 모든 스레드가 끝나 송신 측이 모두 사라지면 iter() 가 끝난다.
+        for event in receiver.iter() {
+            on_event(event);
+        }
+        for handle in self.handles.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for PtsReaders {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
+
+fn read_pts(
+    camera: u32,
+    path: &std::path::Path,
+    sender: &SyncSender<FrameEvent>,
+    stop: &AtomicBool,
+    log: Option<&FrameLog>,
+) {
+    let mut reader: Option<BufReader<File>> = None;
+    let mut pending = String::new();
+    let mut clock = FileClock::default();
+    loop {
+        // 종료 요청 전에 쓰인 줄까지는 읽고 끝나도록 먼저 확인해 둔다.
+        let stopping = stop.load(Ordering::SeqCst);
+
+        if reader.is_none() {
+            // libcamera-vid 가 첫 프레임을 쓰기 전에는 파일이 없을 수 있다.
+            reader = File::open(path).ok().map(BufReader::new);
+        }
+        if let Some(reader) = reader.as_mut() {
+            loop {
+                match reader.read_line(&mut pending) {
+                    // 아직 쓰는 중인 줄은 다음에 이어서 읽는다.
+                    Ok(0) => break,
+                    Ok(_) if !pending.ends_with('\n') => break,
+                    Ok(_) => {}
+                    Err(e) => {
+                        warn!(
+                            "camera {}: failed to read pts file {:?}: {}",
+                            camera, path, e
+                        );
+                        return;
+                    }
+                }
+                // 헤더("# timecode format v2")나 빈 줄은 파싱에 실패하므로 건너뛴다.
+                if let Ok(pts_ms) = pending.trim().parse::<f64>() {
+                    let event = FrameEvent {
+                        camera,
+                        pts_ms,
+                        arrived: Instant::now(),
+                    };
+                    if let Some(log) = log {
+                        log.record(&mut clock, camera, pts_ms, event.arrived);
+                    }
+                    if sender.send(event).is_err() {
+                        return;
+                    }
+                }
+                pending.clear();
+            }
+        }
+
+        if stopping {
+            return;
+        }
+        thread::sleep(READ_INTERVAL);
+    }
+}
+
+// 카메라마다 첫 프레임이 찍힌 시각을 추정해 카메라 간 시작 차이를 구한다.
+#[derive(Default)]
+pub struct FrameSync {
+    // 카메라별 (첫 pts, 첫 프레임 시각 추정값)
+    starts: BTreeMap<u32, (f64, Instant)>,
+    // 찍힌 뒤 pts 가 도착하기까지 알려진 지연. 네트워크 카메라는 로컬 카메라보다 늦게 도착하므로
+    // 도착 시각에서 이만큼 빼야 실제로 찍힌 시각에 맞춰진다.
+    latencies: BTreeMap<u32, Duration>,
+}
+
+impl FrameSync {
+    pub fn set_latency(&mut self, camera: u32, latency: Duration) {
+        self.latencies.insert(camera, latency);
+    }
+
+    pub fn observe(&mut self, event: &FrameEvent) {
+        match self.starts.entry(event.camera) {
+            Entry::Vacant(entry) => {
+                entry.insert((event.pts_ms, event.arrived));
+            }
+            Entry::Occupied(mut entry) => {
+                // pts 는 버퍼링되어 여러 줄이 한꺼번에 도착하므로,
+                // (도착 시각 - 첫 프레임 이후 경과) 의 최솟값을 첫 프레임 시각으로 본다.
+                let (first_pts, start) = entry.get_mut();
+                let since_first =
+                    Duration::from_secs_f64((event.pts_ms - *first_pts).max(0.0) / 1000.0);
+                if let Some(candidate) = event
+                    .arrived
+                    .checked_sub(since_first)
+                    .filter(|candidate| candidate < start)
+                {
+                    *start = candidate;
+                }
+            }
+        }
+    }
+
+    // 가장 늦게 시작한 카메라에 맞추려면 각 카메라 앞부분을 몇 초 잘라내야 하는지
+    pub fn start_offsets(&self) -> BTreeMap<u32, f64> {
+        let captured: BTreeMap<u32, Instant> = self
+            .starts
+            .iter()
+            .map(|(&camera, &(_, start))| {
+                let latency = self.latencies.get(&camera).copied().unwrap_or_default();
+                (camera, start.checked_sub(latency).unwrap_or(start))
+            })
+            .collect();
+        let Some(latest) = captured.values().max().copied() else {
+            return BTreeMap::new();
+        };
+        captured
+            .into_iter()
+            .map(|(camera, start)| (camera, (latest - start).as_secs_f64()))
+            .collect()
+    }
+
+    // 카메라 간 시작 시각의 최대 차이 (밀리초)
+    pub fn skew_ms(&self) -> f64 {
+        self.start_offsets()
+            .values()
+            .fold(0.0, |max, offset| f64::max(max, offset * 1000.0))
+    }
+}
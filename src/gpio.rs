@@ -0,0 +1,215 @@
+// src/gpio.rs
+use crate::AppState;
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+// 40핀 헤더에 나와 있는 BCM 번호의 최댓값
+const MAX_PIN: u8 = 27;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GpioConfig {
+    // true 면 버튼으로 녹화를 시작/정지하고 LED 로 녹화 상태를 보여 준다 (gpio 기능으로 빌드해야 한다).
+    pub enabled: bool,
+    // 누를 때마다 녹화 중이 아니면 [recording] 기본값으로 시작하고, 녹화 중이면 정지 (BCM 번호)
+    pub button_pin: Option<u8>,
+    // true 면 내부 풀업을 켜고 핀이 GND 로 떨어질 때 눌린 것으로 본다.
+    // false 면 내부 풀다운을 켜고 3.3V 가 걸릴 때 눌린 것으로 본다.
+    pub button_active_low: bool,
+    // 이 시간 동안 같은 값이 유지되어야 눌림/뗌으로 인정한다 (접점 떨림 방지)
+    pub debounce_ms: u64,
+    // 녹화 중이면 켜지는 LED (BCM 번호)
+    pub led_pin: Option<u8>,
+}
+
+impl Default for GpioConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            button_pin: None,
+            button_active_low: true,
+            debounce_ms: 50,
+            led_pin: None,
+        }
+    }
+}
+
+impl GpioConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        if !cfg!(feature = "gpio") {
+            bail!("gpio.enabled requires a build with `--features gpio`");
+        }
+        if self.button_pin.is_none() && self.led_pin.is_none() {
+            bail!("gpio.enabled needs a button_pin, a led_pin or both");
+        }
+        for pin in self.button_pin.iter().chain(&self.led_pin) {
+            if *pin > MAX_PIN {
+                bail!("gpio pin {} is not a BCM GPIO number (0-{})", pin, MAX_PIN);
+            }
+        }
+        if self.button_pin.is_some() && self.button_pin == self.led_pin {
+            bail!("gpio.button_pin and gpio.led_pin must be different pins");
+        }
+        Ok(())
+    }
+}
+
+// 버튼을 읽는 스레드와 LED 를 켜고 끄는 작업을 시작한다 (설정에서 켠 경우에만).
+#[cfg(feature = "gpio")]
+pub fn spawn(state: Arc<AppState>) -> Result<()> {
+    if !state.config.gpio.enabled {
+        return Ok(());
+    }
+    pins::spawn(state)
+}
+
+#[cfg(not(feature = "gpio"))]
+pub fn spawn(_state: Arc<AppState>) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(feature = "gpio")]
+mod pins {
+    use super::GpioConfig;
+    use crate::{AppState, SessionTarget, StartRequest, audit, start_recording, stop_recording};
+    use anyhow::{Context, Result};
+    use rppal::gpio::{Gpio, InputPin, OutputPin};
+    use std::{
+        sync::Arc,
+        thread,
+        time::{Duration, Instant},
+    };
+    use tokio::{runtime::Handle, sync::broadcast};
+    use tracing::{info, warn};
+
+    // 버튼을 읽는 간격. 사람이 누르는 시간보다 충분히 짧으면 된다.
+    const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+    pub fn spawn(state: Arc<AppState>) -> Result<()> {
+        let config = state.config.gpio.clone();
+        let gpio = Gpio::new().context("Failed to open the GPIO controller")?;
+
+        if let Some(pin) = config.led_pin {
+            let led = gpio
+                .get(pin)
+                .with_context(|| format!("Failed to claim GPIO {} for the LED", pin))?
+                .into_output_low();
+            info!("Recording LED on GPIO {}.", pin);
+            tokio::spawn(drive_led(state.clone(), led));
+        }
+
+        if let Some(number) = config.button_pin {
+            let pin = gpio
+                .get(number)
+                .with_context(|| format!("Failed to claim GPIO {} for the button", number))?;
+            let button = if config.button_active_low {
+                pin.into_input_pullup()
+            } else {
+                pin.into_input_pulldown()
+            };
+            let runtime = Handle::current();
+            thread::Builder::new()
+                .name("gpio-button".to_string())
+                .spawn(move || watch_button(&runtime, &state, &config, &button))
+                .context("Failed to start the GPIO button thread")?;
+            info!("Recording button on GPIO {}.", number);
+        }
+        Ok(())
+    }
+
+    fn watch_button(
+        runtime: &Handle,
+        state: &Arc<AppState>,
+        config: &GpioConfig,
+        button: &InputPin,
+    ) {
+        let debounce = Duration::from_millis(config.debounce_ms);
+        let is_pressed = || button.is_low() == config.button_active_low;
+        let mut pressed = is_pressed();
+        let mut changed_since: Option<Instant> = None;
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            if is_pressed() == pressed {
+                changed_since = None;
+                continue;
+            }
+            let since = *changed_since.get_or_insert_with(Instant::now);
+            if since.elapsed() < debounce {
+                continue;
+            }
+            pressed = !pressed;
+            changed_since = None;
+            if pressed {
+                runtime.spawn(toggle(state.clone()));
+            }
+        }
+    }
+
+    // 녹화 중이면 정지 (여러 세션이면 모두), 아니면 [recording] 기본값으로 시작
+    async fn toggle(state: Arc<AppState>) {
+        let running = state.sessions.lock().unwrap().running();
+        let result = if running.is_empty() {
+            info!("GPIO button pressed. Starting a recording.");
+            let started = start_recording(state.clone(), StartRequest::default()).await;
+            audit::record_command(
+                &state,
+                "gpio",
+                "start",
+                started.as_ref().map(|response| Some(response.0.session_id)),
+            );
+            started.map(|_| ())
+        } else {
+            info!("GPIO button pressed. Stopping the recording.");
+            let mut result = Ok(());
+            for session in running {
+                let target = SessionTarget {
+                    session_id: Some(session.id),
+                };
+                let stopped = stop_recording(state.clone(), target, false).await;
+                audit::record_command(
+                    &state,
+                    "gpio",
+                    "stop",
+                    stopped.as_ref().map(|_| Some(session.id)),
+                );
+                if let Err(e) = stopped {
+                    result = Err(e);
+                }
+            }
+            result
+        };
+        if let Err(e) = result {
+            let message = format!("GPIO button command failed: {}", e.message);
+            warn!("{}", message);
+            state.errors.record(None, message);
+        }
+    }
+
+    async fn drive_led(state: Arc<AppState>, mut led: OutputPin) {
+        let mut events = state.events.subscribe();
+        if state.sessions.lock().unwrap().any_running() {
+            led.set_high();
+        }
+        loop {
+            match events.recv().await {
+                Ok(event) => match event.recording_after(&state.sessions.lock().unwrap()) {
+                    Some(true) => led.set_high(),
+                    Some(false) => led.set_low(),
+                    None => {}
+                },
+                Err(broadcast::error::RecvError::Lagged(_)) => {
+                    if state.sessions.lock().unwrap().any_running() {
+                        led.set_high();
+                    } else {
+                        led.set_low();
+                    }
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            }
+        }
+    }
+}
@@ -0,0 +1,144 @@
+// src/health.rs
+use crate::{AppState, camera_handler};
+use axum::{Json, extract::State, http::StatusCode};
+use serde::Serialize;
+use std::{fs, path::Path, sync::Arc};
+
+// 쓰기 가능한지 확인할 때 잠깐 만들었다 지우는 파일
+const PROBE_FILE: &str = ".readyz-probe";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub ok: bool,
+    pub detail: String,
+}
+
+impl Check {
+    pub fn pass(detail: impl Into<String>) -> Self {
+        Self {
+            ok: true,
+            detail: detail.into(),
+        }
+    }
+
+    pub fn fail(detail: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            detail: detail.into(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    pub cameras: Check,
+    pub save_dir: Check,
+    pub disk_space: Check,
+}
+
+// GET /healthz - 프로세스가 요청에 응답할 수 있는지만 본다.
+pub async fn handle_healthz() -> &'static str {
+    "ok"
+}
+
+// GET /readyz - 지금 녹화를 시작할 수 있는지. 하나라도 실패하면 503
+pub async fn handle_readyz(State(state): State<Arc<AppState>>) -> (StatusCode, Json<Readiness>) {
+    let readiness = tokio::task::spawn_blocking(move || check(&state))
+        .await
+        .unwrap_or_else(|e| Readiness {
+            ready: false,
+            cameras: Check::fail(e.to_string()),
+            save_dir: Check::fail(e.to_string()),
+            disk_space: Check::fail(e.to_string()),
+        });
+    let status = if readiness.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(readiness))
+}
+
+fn check(state: &AppState) -> Readiness {
+    let save_dir = state.config.save_dir();
+    let cameras = check_cameras(state);
+    let writable = check_writable(&save_dir);
+    let disk_space = check_disk_space(&save_dir, state.config.min_free_space_mb);
+    Readiness {
+        ready: cameras.ok && writable.ok && disk_space.ok,
+        cameras,
+        save_dir: writable,
+        disk_space,
+    }
+}
+
+// recording 기본 설정의 카메라가 붙어 있는지 (allow_missing_cameras 면 한 대 이상)
+fn check_cameras(state: &AppState) -> Check {
+    let config = &state.config.recording;
+    // 네트워크 카메라와 테스트 패턴은 녹화를 시작할 때 열어 보므로 여기서는 붙어 있는 것으로 친다.
+    let local: Vec<u32> = config
+        .cameras
+        .iter()
+        .copied()
+        .filter(|&camera| config.is_local(camera))
+        .collect();
+    let available = if local.is_empty() {
+        Vec::new()
+    } else {
+        match camera_handler::list_cameras() {
+            Ok(available) => available,
+            Err(e) => return Check::fail(format!("{:#}", e)),
+        }
+    };
+    let missing: Vec<u32> = local
+        .into_iter()
+        .filter(|camera| !available.contains(camera))
+        .collect();
+    if missing.is_empty() {
+        Check::pass(format!("cameras {:?} attached", config.cameras))
+    } else if config.allow_missing_cameras && missing.len() < config.cameras.len() {
+        Check::pass(format!(
+            "cameras {:?} missing, recording from the rest",
+            missing
+        ))
+    } else {
+        Check::fail(format!(
+            "cameras {:?} missing (available: {:?})",
+            missing, available
+        ))
+    }
+}
+
+// 녹화를 시작할 때처럼 디렉토리를 만들고, 파일을 하나 써 본다.
+pub fn check_writable(save_dir: &Path) -> Check {
+    let probe = save_dir.join(PROBE_FILE);
+    let result = fs::create_dir_all(save_dir)
+        .and_then(|()| fs::write(&probe, b""))
+        .and_then(|()| fs::remove_file(&probe));
+    match result {
+        Ok(()) => Check::pass(format!("{:?} is writable", save_dir)),
+        Err(e) => Check::fail(format!("{:?} is not writable: {}", save_dir, e)),
+    }
+}
+
+pub fn check_disk_space(save_dir: &Path, min_free_space_mb: u64) -> Check {
+    match fs4::available_space(save_dir) {
+        Ok(free) => {
+            let free_mb = free / 1024 / 1024;
+            let detail = format!(
+                "{} MB available, {} MB required",
+                free_mb, min_free_space_mb
+            );
+            if free_mb >= min_free_space_mb {
+                Check::pass(detail)
+            } else {
+                Check::fail(detail)
+            }
+        }
+        Err(e) => Check::fail(format!(
+            "Failed to check free space of {:?}: {}",
+            save_dir, e
+        )),
+    }
+}
@@ -0,0 +1,292 @@
+// src/keep_alive.rs
+use crate::{
+    ApiError, AppState,
+    camera_handler::{self, RecordingConfig},
+    camera_settings::CameraControls,
+    config::Config,
+    settings::LiveSettings,
+    sink,
+};
+use anyhow::{Context, Result, bail};
+use axum::{Json, extract::State};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    process::{Child, Command},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+
+// 열어 둔 카메라가 끝났거나 오래 쓰이지 않았는지 확인하는 간격
+const REAP_INTERVAL: Duration = Duration::from_secs(5);
+
+// 녹화 사이에도 카메라를 열어 두어 /start 가 libcamera-vid 를 새로 띄우는 시간을 기다리지 않게 한다.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeepAliveConfig {
+    // true 면 녹화가 끝난 뒤와 POST /cameras/warmup 때 [recording] 기본 카메라를 일시정지 상태로 열어 두고,
+    // /start 는 설정이 같은 카메라의 프로세스를 그대로 녹화로 전환한다.
+    pub enabled: bool,
+    // 마지막으로 열거나 녹화에 넘긴 뒤 이 시간 (초) 동안 쓰지 않으면 닫는다.
+    pub idle_timeout_secs: u64,
+}
+
+impl Default for KeepAliveConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            idle_timeout_secs: 300,
+        }
+    }
+}
+
+impl KeepAliveConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.enabled && self.idle_timeout_secs == 0 {
+            bail!("keep_alive.idle_timeout_secs must be non-zero");
+        }
+        Ok(())
+    }
+}
+
+// --signal --initial pause 로 띄워 아무것도 쓰지 않고 기다리는 libcamera-vid
+struct WarmCamera {
+    // 출력 경로를 뺀 libcamera-vid 인자. 녹화 설정으로 만든 것과 같아야 넘겨준다.
+    args: Vec<String>,
+    child: Child,
+}
+
+pub struct WarmCameras {
+    config: KeepAliveConfig,
+    // 출력 링크와 pts 파일을 두는 곳. pts 파일을 옮겨야 하므로 녹화 파일과 같은 파일 시스템에 둔다.
+    dir: PathBuf,
+    // POST /config 로 바뀐 fps 도 따르도록 열 때마다 [recording] 기본값을 여기서 읽는다.
+    settings: Arc<LiveSettings>,
+    controls: Arc<CameraControls>,
+    cameras: Mutex<BTreeMap<u32, WarmCamera>>,
+    last_used: Mutex<Instant>,
+}
+
+impl WarmCameras {
+    pub fn new(
+        config: &Config,
+        settings: Arc<LiveSettings>,
+        controls: Arc<CameraControls>,
+    ) -> Self {
+        let work_dir = config.staging_dir().unwrap_or_else(|| config.save_dir());
+        Self {
+            config: config.keep_alive.clone(),
+            dir: work_dir.join(".warm"),
+            settings,
+            controls,
+            cameras: Mutex::new(BTreeMap::new()),
+            last_used: Mutex::new(Instant::now()),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    // 열어 둔 카메라가 있는지
+    pub fn is_open(&self) -> bool {
+        !self.cameras.lock().unwrap().is_empty()
+    }
+
+    // libcamera-vid 가 쓸 출력 링크와 pts 파일
+    fn paths(&self, index: u32) -> (PathBuf, PathBuf) {
+        (
+            self.dir.join(format!("cam{}.h264", index)),
+            self.dir.join(format!("cam{}.pts", index)),
+        )
+    }
+
+    // 기본 카메라 중 열려 있지 않은 libcamera 카메라를 일시정지 상태로 연다.
+    // 카메라 설정이 바뀐 것은 다시 연다. 열려 있는 카메라 목록을 돌려준다.
+    pub fn warm(&self) -> Result<Vec<u32>> {
+        // 분할 녹화는 출력이 파일 이름 패턴이라 넘겨줄 수 없으므로 나누지 않는 설정으로 연다.
+        let defaults = RecordingConfig {
+            camera_settings: self.controls.all(),
+            segment_duration: None,
+            ..self.settings.recording()
+        };
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {:?}", self.dir))?;
+        let mut cameras = self.cameras.lock().unwrap();
+        *self.last_used.lock().unwrap() = Instant::now();
+        for &index in defaults.cameras.iter() {
+            if !defaults.is_local(index) {
+                continue;
+            }
+            let args = camera_handler::libcamera_args(index, &defaults);
+            let reusable = cameras
+                .get_mut(&index)
+                .is_some_and(|warm| warm.args == args && matches!(warm.child.try_wait(), Ok(None)));
+            if reusable {
+                continue;
+            }
+            if let Some(old) = cameras.remove(&index) {
+                stop(old);
+            }
+            let (link, pts) = self.paths(index);
+            let _ = fs::remove_file(&pts);
+            // 넘겨주기 전까지 쓰는 것은 버린다.
+            point_link(&link, Path::new(sink::NULL_DEVICE))?;
+            let child = Command::new("libcamera-vid")
+                .args(&args)
+                .arg("--initial")
+                .arg("pause")
+                .arg("--save-pts")
+                .arg(&pts)
+                .arg("--output")
+                .arg(&link)
+                .spawn()
+                .with_context(|| format!("Failed to start libcamera-vid for camera {}", index))?;
+            info!(
+                "camera {}: opened and paused for the next recording.",
+                index
+            );
+            cameras.insert(index, WarmCamera { args, child });
+        }
+        Ok(cameras.keys().copied().collect())
+    }
+
+    // 열어 둔 카메라를 녹화로 넘긴다: 출력 링크를 output 으로 돌리고 pts 파일을 pts_path 로 옮긴 뒤
+    // 일시정지를 푼다 (libcamera-vid 는 다음 키프레임부터 링크를 열어 쓴다).
+    // 설정이 달라 넘길 수 없으면 녹화가 새로 열 수 있게 닫고 None
+    pub fn take(
+        &self,
+        index: u32,
+        config: &RecordingConfig,
+        output: &Path,
+        pts_path: &Path,
+    ) -> Option<Child> {
+        let mut warm = self.cameras.lock().unwrap().remove(&index)?;
+        *self.last_used.lock().unwrap() = Instant::now();
+        if warm.args != camera_handler::libcamera_args(index, config)
+            || !matches!(warm.child.try_wait(), Ok(None))
+        {
+            info!(
+                "camera {}: the recording needs different camera settings; reopening it.",
+                index
+            );
+            stop(warm);
+            return None;
+        }
+        let (link, pts) = self.paths(index);
+        let handed = point_link(&link, output)
+            .and_then(|()| {
+                fs::rename(&pts, pts_path)
+                    .with_context(|| format!("Failed to move {:?} to {:?}", pts, pts_path))
+            })
+            .and_then(|()| camera_handler::toggle_pause(&warm.child));
+        match handed {
+            Ok(()) => {
+                info!("camera {}: recording from the kept-open camera.", index);
+                Some(warm.child)
+            }
+            Err(e) => {
+                warn!("camera {}: {:#}. Reopening it.", index, e);
+                stop(warm);
+                None
+            }
+        }
+    }
+
+    // 열어 둔 카메라를 모두 닫는다.
+    pub fn release(&self) {
+        let cameras = std::mem::take(&mut *self.cameras.lock().unwrap());
+        if cameras.is_empty() {
+            return;
+        }
+        info!("Closing {} kept-open camera(s).", cameras.len());
+        for (index, warm) in cameras {
+            stop(warm);
+            let (link, pts) = self.paths(index);
+            let _ = fs::remove_file(link);
+            let _ = fs::remove_file(pts);
+        }
+    }
+
+    // 스스로 끝난 프로세스를 치우고, idle_timeout_secs 동안 쓰지 않았으면 모두 닫는다.
+    fn reap(&self) {
+        self.cameras.lock().unwrap().retain(|index, warm| {
+            let exited = !matches!(warm.child.try_wait(), Ok(None));
+            if exited {
+                warn!("camera {}: the kept-open libcamera-vid exited.", index);
+            }
+            !exited
+        });
+        let idle = self.last_used.lock().unwrap().elapsed();
+        if idle >= Duration::from_secs(self.config.idle_timeout_secs) {
+            self.release();
+        }
+    }
+}
+
+fn stop(mut warm: WarmCamera) {
+    let _ = warm.child.kill();
+    let _ = warm.child.wait();
+}
+
+// 새 링크를 만들어 덮어써서, libcamera-vid 가 링크를 여는 순간 옛 대상이나 빈 자리를 보지 않게 한다.
+#[cfg(unix)]
+fn point_link(link: &Path, target: &Path) -> Result<()> {
+    let target =
+        std::path::absolute(target).with_context(|| format!("Failed to resolve {:?}", target))?;
+    let next = link.with_extension("next");
+    let _ = fs::remove_file(&next);
+    std::os::unix::fs::symlink(&target, &next)
+        .with_context(|| format!("Failed to link {:?} to {:?}", next, target))?;
+    fs::rename(&next, link).with_context(|| format!("Failed to move {:?} to {:?}", next, link))
+}
+
+#[cfg(not(unix))]
+fn point_link(_link: &Path, _target: &Path) -> Result<()> {
+    bail!("Keeping cameras open is only supported on Unix")
+}
+
+// 서버가 멈출 때까지 열어 둔 카메라를 지켜본다.
+pub async fn run(state: Arc<AppState>) {
+    if !state.warm_cameras.enabled() {
+        return;
+    }
+    let mut interval = tokio::time::interval(REAP_INTERVAL);
+    loop {
+        interval.tick().await;
+        state.warm_cameras.reap();
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct WarmupResponse {
+    pub message: String,
+    // 열려 있는 카메라
+    pub cameras: Vec<u32>,
+}
+
+// POST /cameras/warmup: 다음 /start 전에 카메라를 미리 열어 둔다.
+pub async fn handle_warmup(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<WarmupResponse>, ApiError> {
+    if !state.warm_cameras.enabled() {
+        return Err(ApiError::conflict(
+            "keep_alive is disabled in the server config.",
+        ));
+    }
+    if state.sessions.lock().unwrap().any_running() {
+        return Err(ApiError::conflict("The cameras are in use by a recording."));
+    }
+    let warm_cameras = state.warm_cameras.clone();
+    let cameras = tokio::task::spawn_blocking(move || warm_cameras.warm())
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))?
+        .map_err(|e| ApiError::internal(format!("{:#}", e)))?;
+    Ok(Json(WarmupResponse {
+        message: format!("{} camera(s) are open and paused.", cameras.len()),
+        cameras,
+    }))
+}
@@ -0,0 +1,261 @@
+// src/lens.rs
+use crate::{config, overlay};
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    io::Write,
+    path::{Path, PathBuf},
+};
+use tracing::warn;
+
+// 맵 파일에서 프레임 밖을 가리키는 값 (remap 이 검게 채운다)
+const OUTSIDE: u16 = u16::MAX;
+
+// 카메라 한 대의 렌즈 왜곡 보정. 다른 처리보다 먼저, 촬영한 해상도 그대로 펴 준다.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LensCorrection {
+    pub camera: u32,
+    pub enabled: bool,
+    // OpenCV FileStorage (YAML 또는 XML) 보정 파일 ('~' 확장). camera_matrix 와
+    // distortion_coefficients, 있으면 image_width/image_height 를 읽는다.
+    pub calibration: String,
+}
+
+impl LensCorrection {
+    pub fn validate(&self) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        if self.calibration.trim().is_empty() {
+            bail!(
+                "camera {} lens correction requires a calibration file",
+                self.camera
+            );
+        }
+        Calibration::load(&self.expanded_path())
+            .with_context(|| format!("camera {} lens calibration", self.camera))?;
+        Ok(())
+    }
+
+    fn expanded_path(&self) -> String {
+        config::expand_path(&self.calibration)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    // width x height 프레임을 펴는 필터 (입력 하나, 출력 하나). 꺼져 있거나 맵을 만들지
+    // 못하면 None 이고, 그때는 보정하지 않은 채 녹화한다.
+    pub fn filter(&self, width: u32, height: u32) -> Option<String> {
+        if !self.enabled {
+            return None;
+        }
+        let maps = Calibration::load(&self.expanded_path())
+            .and_then(|calibration| calibration.write_maps(width, height));
+        let (xmap, ymap) = match maps {
+            Ok(maps) => maps,
+            Err(e) => {
+                warn!("camera {}: lens correction skipped: {:#}", self.camera, e);
+                return None;
+            }
+        };
+        let label = format!("lens{}", self.camera);
+        Some(format!(
+            "null[{label}_in];\
+             movie=filename='{}'[{label}_x];\
+             movie=filename='{}'[{label}_y];\
+             [{label}_in][{label}_x][{label}_y]remap",
+            overlay::escape_option(&xmap.to_string_lossy()),
+            overlay::escape_option(&ymap.to_string_lossy()),
+        ))
+    }
+}
+
+// 핀홀 카메라 행렬과 왜곡 계수 (OpenCV 순서: k1, k2, p1, p2[, k3[, k4, k5, k6]])
+#[derive(Debug, Clone, PartialEq)]
+struct Calibration {
+    fx: f64,
+    fy: f64,
+    cx: f64,
+    cy: f64,
+    // 8 개로 채운 계수
+    distortion: [f64; 8],
+    // 보정한 해상도. 없으면 녹화 해상도로 본다.
+    size: Option<(u32, u32)>,
+}
+
+impl Calibration {
+    fn load(path: &str) -> Result<Self> {
+        let text =
+            fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+        let matrix = matrix(&text, "camera_matrix")?;
+        if matrix.len() != 9 {
+            bail!("camera_matrix must be 3x3");
+        }
+        let coefficients = matrix_or_empty(&text, "distortion_coefficients")?;
+        if ![4, 5, 8].contains(&coefficients.len()) {
+            bail!("distortion_coefficients must have 4, 5 or 8 values");
+        }
+        let mut distortion = [0.0; 8];
+        distortion[..coefficients.len()].copy_from_slice(&coefficients);
+        let (fx, fy) = (matrix[0], matrix[4]);
+        if !(fx > 0.0 && fy > 0.0) {
+            bail!("camera_matrix focal lengths must be positive");
+        }
+        let size = match (scalar(&text, "image_width"), scalar(&text, "image_height")) {
+            (Some(width), Some(height)) if width > 0 && height > 0 => Some((width, height)),
+            _ => None,
+        };
+        Ok(Self {
+            fx,
+            fy,
+            cx: matrix[2],
+            cy: matrix[5],
+            distortion,
+            size,
+        })
+    }
+
+    // 다른 해상도로 보정했으면 행렬을 그 비율만큼 늘이거나 줄인다.
+    fn scaled(&self, width: u32, height: u32) -> Self {
+        let (sx, sy) = match self.size {
+            Some((w, h)) => (width as f64 / w as f64, height as f64 / h as f64),
+            None => (1.0, 1.0),
+        };
+        Self {
+            fx: self.fx * sx,
+            fy: self.fy * sy,
+            cx: self.cx * sx,
+            cy: self.cy * sy,
+            size: Some((width, height)),
+            ..self.clone()
+        }
+    }
+
+    // 보정된 정규 좌표 (x, y) 가 원래 (왜곡된) 영상에서 놓이는 정규 좌표
+    fn distort(&self, x: f64, y: f64) -> (f64, f64) {
+        let [k1, k2, p1, p2, k3, k4, k5, k6] = self.distortion;
+        let r2 = x * x + y * y;
+        let r4 = r2 * r2;
+        let r6 = r4 * r2;
+        let radial = (1.0 + k1 * r2 + k2 * r4 + k3 * r6) / (1.0 + k4 * r2 + k5 * r4 + k6 * r6);
+        (
+            x * radial + 2.0 * p1 * x * y + p2 * (r2 + 2.0 * x * x),
+            y * radial + p1 * (r2 + 2.0 * y * y) + 2.0 * p2 * x * y,
+        )
+    }
+
+    // OpenCV initUndistortRectifyMap (R = I, 새 카메라 행렬 = 원래 행렬) 과 같은 맵을
+    // ffmpeg remap 이 읽는 16 비트 PGM 두 장 (x, y) 으로 쓴다. 같은 보정과 해상도면 다시 쓴다.
+    fn write_maps(&self, width: u32, height: u32) -> Result<(PathBuf, PathBuf)> {
+        let calibration = self.scaled(width, height);
+        let mut hasher = DefaultHasher::new();
+        for value in [
+            calibration.fx,
+            calibration.fy,
+            calibration.cx,
+            calibration.cy,
+        ]
+        .iter()
+        .chain(&calibration.distortion)
+        {
+            value.to_bits().hash(&mut hasher);
+        }
+        let dir = std::env::temp_dir().join("server_lens");
+        let name = format!("{}x{}_{:016x}", width, height, hasher.finish());
+        let xmap = dir.join(format!("{}_x.pgm", name));
+        let ymap = dir.join(format!("{}_y.pgm", name));
+        if xmap.exists() && ymap.exists() {
+            return Ok((xmap, ymap));
+        }
+
+        let pixels = (width * height) as usize;
+        let mut xs = Vec::with_capacity(pixels);
+        let mut ys = Vec::with_capacity(pixels);
+        for v in 0..height {
+            for u in 0..width {
+                let x = (u as f64 - calibration.cx) / calibration.fx;
+                let y = (v as f64 - calibration.cy) / calibration.fy;
+                let (xd, yd) = calibration.distort(x, y);
+                let source_x = (calibration.fx * xd + calibration.cx).round();
+                let source_y = (calibration.fy * yd + calibration.cy).round();
+                if (0.0..width as f64).contains(&source_x)
+                    && (0.0..height as f64).contains(&source_y)
+                {
+                    xs.push(source_x as u16);
+                    ys.push(source_y as u16);
+                } else {
+                    xs.push(OUTSIDE);
+                    ys.push(OUTSIDE);
+                }
+            }
+        }
+        fs::create_dir_all(&dir).with_context(|| format!("Failed to create {:?}", dir))?;
+        write_pgm(&xmap, width, height, &xs)?;
+        write_pgm(&ymap, width, height, &ys)?;
+        Ok((xmap, ymap))
+    }
+}
+
+fn write_pgm(path: &Path, width: u32, height: u32, values: &[u16]) -> Result<()> {
+    let mut bytes = format!("P5\n{} {}\n65535\n", width, height).into_bytes();
+    for value in values {
+        bytes.extend_from_slice(&value.to_be_bytes());
+    }
+    // 다른 녹화가 읽는 중일 수 있으므로 다 쓴 뒤 이름을 바꾼다.
+    let partial = path.with_extension("pgm.partial");
+    fs::File::create(&partial)
+        .and_then(|mut file| file.write_all(&bytes))
+        .with_context(|| format!("Failed to write {:?}", partial))?;
+    fs::rename(&partial, path).with_context(|| format!("Failed to move {:?}", partial))
+}
+
+// FileStorage 의 행렬 값. YAML 은 `key: !!opencv-matrix ... data: [ ... ]`,
+// XML 은 `<key type_id="opencv-matrix">...<data> ... </data></key>` 로 쓴다.
+fn matrix(text: &str, key: &str) -> Result<Vec<f64>> {
+    let start = text
+        .find(key)
+        .with_context(|| format!("{} not found", key))?;
+    let rest = &text[start..];
+    let data = rest
+        .find("data")
+        .with_context(|| format!("{} has no data", key))?;
+    let rest = &rest[data + "data".len()..];
+    let (open, close) = if rest.trim_start_matches([':', ' ']).starts_with('[') {
+        ('[', ']')
+    } else {
+        ('>', '<')
+    };
+    let body = rest
+        .find(open)
+        .map(|at| &rest[at + 1..])
+        .and_then(|body| body.find(close).map(|end| &body[..end]))
+        .with_context(|| format!("{} data is not closed", key))?;
+    body.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|value| !value.is_empty())
+        .map(|value| {
+            value
+                .parse::<f64>()
+                .with_context(|| format!("{} has an invalid value {:?}", key, value))
+        })
+        .collect()
+}
+
+// 왜곡 계수가 없는 파일은 왜곡 없는 렌즈로 본다.
+fn matrix_or_empty(text: &str, key: &str) -> Result<Vec<f64>> {
+    if text.contains(key) {
+        matrix(text, key)
+    } else {
+        Ok(vec![0.0; 5])
+    }
+}
+
+// `key: 1280` 또는 `<key>1280</key>`
+fn scalar(text: &str, key: &str) -> Option<u32> {
+    let start = text.find(key)? + key.len();
+    let rest = text[start..].trim_start_matches([':', '>', ' ', '"']);
+    let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+    digits.parse().ok()
+}
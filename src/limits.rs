@@ -0,0 +1,217 @@
+// src/limits.rs
+use crate::{ApiError, AppState};
+use anyhow::{Result, bail};
+use axum::{
+    body::{Body, Bytes, HttpBody},
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderValue, Method, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+// 이만큼 요청이 없던 IP 의 버킷은 가득 찬 것과 같으므로 지운다.
+const IDLE_BUCKET: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LimitsConfig {
+    // 클라이언트 IP 마다 초당 허용하는 요청 수 (토큰 버킷). 0 이면 제한하지 않는다.
+    pub requests_per_second: f64,
+    // 잠깐 몰아서 보낼 수 있는 요청 수 (버킷 크기)
+    pub burst: u32,
+    // 미리보기(WebRTC), 스냅샷, 녹화 다운로드를 동시에 처리하는 최대 수 (모든 클라이언트 합계).
+    // 넘치면 기다리게 하지 않고 바로 503 으로 돌려보낸다. 0 이면 제한하지 않는다.
+    pub max_concurrent_expensive: usize,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            requests_per_second: 20.0,
+            burst: 40,
+            max_concurrent_expensive: 4,
+        }
+    }
+}
+
+impl LimitsConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !self.requests_per_second.is_finite() || self.requests_per_second < 0.0 {
+            bail!("limits.requests_per_second must be zero or positive");
+        }
+        if self.requests_per_second > 0.0 && self.burst == 0 {
+            bail!("limits.burst must be at least 1 when requests_per_second is set");
+        }
+        Ok(())
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+pub struct Limiter {
+    config: LimitsConfig,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+    last_sweep: Mutex<Instant>,
+    expensive: Arc<Semaphore>,
+}
+
+impl Limiter {
+    pub fn new(config: LimitsConfig) -> Self {
+        Self {
+            expensive: Arc::new(Semaphore::new(config.max_concurrent_expensive)),
+            config,
+            buckets: Mutex::new(HashMap::new()),
+            last_sweep: Mutex::new(Instant::now()),
+        }
+    }
+
+    // 요청을 받아도 되면 None, 아니면 토큰이 다시 생길 때까지의 시간
+    fn take(&self, ip: IpAddr) -> Option<Duration> {
+        let rate = self.config.requests_per_second;
+        if rate <= 0.0 {
+            return None;
+        }
+        let burst = self.config.burst as f64;
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        self.sweep(&mut buckets, now);
+
+        let bucket = buckets.entry(ip).or_insert(Bucket {
+            tokens: burst,
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            None
+        } else {
+            Some(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+        }
+    }
+
+    fn sweep(&self, buckets: &mut HashMap<IpAddr, Bucket>, now: Instant) {
+        let mut last_sweep = self.last_sweep.lock().unwrap();
+        if now.duration_since(*last_sweep) < IDLE_BUCKET {
+            return;
+        }
+        *last_sweep = now;
+        buckets.retain(|_, bucket| now.duration_since(bucket.updated) < IDLE_BUCKET);
+    }
+
+    // 자리가 없으면 Err. 제한이 꺼져 있으면 Ok(None)
+    fn acquire_expensive(&self) -> Result<Option<OwnedSemaphorePermit>, ()> {
+        if self.config.max_concurrent_expensive == 0 {
+            return Ok(None);
+        }
+        self.expensive
+            .clone()
+            .try_acquire_owned()
+            .map(Some)
+            .map_err(|_| ())
+    }
+}
+
+// 캡처 스레드와 CPU/디스크를 오래 붙잡는 요청
+fn is_expensive(method: &Method, path: &str) -> bool {
+    match *method {
+        Method::POST => path == "/webrtc/offer",
+        Method::GET => {
+            path == "/snapshot"
+                || path
+                    .strip_prefix("/recordings/")
+                    .is_some_and(|name| !name.is_empty())
+        }
+        _ => false,
+    }
+}
+
+fn retry_after(mut response: Response, wait: Duration) -> Response {
+    let secs = wait.as_secs_f64().ceil().max(1.0) as u64;
+    if let Ok(value) = HeaderValue::from_str(&secs.to_string()) {
+        response.headers_mut().insert(header::RETRY_AFTER, value);
+    }
+    response
+}
+
+// 인증보다 먼저 적용해 잘못된 키로 계속 두드리는 클라이언트도 막는다.
+pub async fn limit(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let limiter = &state.limiter;
+    let ip = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    if let Some(ip) = ip
+        && let Some(wait) = limiter.take(ip)
+    {
+        let response =
+            ApiError::new(StatusCode::TOO_MANY_REQUESTS, "Too many requests").into_response();
+        return retry_after(response, wait);
+    }
+
+    if !is_expensive(request.method(), request.uri().path()) {
+        return next.run(request).await;
+    }
+    let permit = match limiter.acquire_expensive() {
+        Ok(permit) => permit,
+        Err(()) => {
+            let response = ApiError::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Too many previews, snapshots or downloads in progress",
+            )
+            .into_response();
+            return retry_after(response, Duration::from_secs(1));
+        }
+    };
+    let response = next.run(request).await;
+    match permit {
+        // 다운로드는 응답 본문을 다 보낼 때까지 자리를 차지한다.
+        Some(permit) => response.map(|body| {
+            Body::new(PermitBody {
+                body,
+                _permit: permit,
+            })
+        }),
+        None => response,
+    }
+}
+
+// 본문이 끝나거나 연결이 끊겨 버려질 때 자리를 돌려준다.
+struct PermitBody {
+    body: Body,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl HttpBody for PermitBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<http_body::Frame<Self::Data>, Self::Error>>> {
+        Pin::new(&mut self.body).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.body.size_hint()
+    }
+}
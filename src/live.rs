@@ -0,0 +1,201 @@
+// src/live.rs
+use crate::{
+    ApiError, AppState, auth,
+    camera_handler::RecordingConfig,
+    feed::{self, Feeds, QueueStats, WriteQueueConfig},
+};
+use anyhow::{Context, Result, bail};
+use axum::{
+    body::Body,
+    extract::{Path, Request, State},
+    http::{HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path as FsPath, PathBuf},
+    process::{Child, Stdio},
+    sync::Arc,
+    time::Duration,
+};
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
+use tracing::info;
+use uuid::Uuid;
+
+const PLAYLIST: &str = "index.m3u8";
+
+// 입력이 모두 닫힌 뒤 ffmpeg 가 재생 목록을 마무리하기를 기다리는 시간
+const FINISH_GRACE_PERIOD: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LiveConfig {
+    // true 면 녹화 중인 영상을 /live/<session_id>/index.m3u8 로 내보낸다.
+    pub enabled: bool,
+    // HLS 세그먼트 길이 (초)
+    pub segment_secs: u32,
+    // 재생 목록에 남겨 두는 세그먼트 수 (오래된 세그먼트는 지운다)
+    pub playlist_size: u32,
+}
+
+impl Default for LiveConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            segment_secs: 2,
+            playlist_size: 6,
+        }
+    }
+}
+
+impl LiveConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.segment_secs == 0 {
+            bail!("live.segment_secs must be non-zero");
+        }
+        if self.playlist_size == 0 {
+            bail!("live.playlist_size must be non-zero");
+        }
+        Ok(())
+    }
+}
+
+// 세션별 HLS 출력 디렉토리 (임시 디렉토리 아래)
+fn session_dir(session_id: Uuid) -> PathBuf {
+    std::env::temp_dir()
+        .join("server-live")
+        .join(session_id.to_string())
+}
+
+pub fn playlist_url(session_id: Uuid) -> String {
+    format!("/live/{}/{}", session_id, PLAYLIST)
+}
+
+// 카메라별 원본 파일을 FIFO 로 ffmpeg 에 넘기고, ffmpeg 가 HLS 로 자른다.
+pub struct LiveStream {
+    ffmpeg: Child,
+    feeds: Feeds,
+}
+
+impl LiveStream {
+    // sources 는 (카메라 번호, libcamera-vid 가 쓰는 파일 또는 %04d 패턴)
+    pub fn start(
+        session_id: Uuid,
+        live: &LiveConfig,
+        queue: &WriteQueueConfig,
+        config: &RecordingConfig,
+        sources: &[(u32, PathBuf)],
+    ) -> Result<Self> {
+        let dir = session_dir(session_id);
+        let feeds = Feeds::start(dir.clone(), sources, queue)?;
+        let ffmpeg = feed::ffmpeg_command(&feeds.fifos(), &feeds.cameras(), config)
+            .arg("-f")
+            .arg("hls")
+            .arg("-hls_time")
+            .arg(live.segment_secs.to_string())
+            .arg("-hls_list_size")
+            .arg(live.playlist_size.to_string())
+            .arg("-hls_flags")
+            .arg("delete_segments+independent_segments")
+            .arg("-hls_segment_filename")
+            .arg(dir.join("segment%05d.ts"))
+            .arg(dir.join(PLAYLIST))
+            .stdin(Stdio::null())
+            .spawn()
+            .context("Failed to start ffmpeg for live streaming")?;
+
+        info!("Live stream available at {}", playlist_url(session_id));
+        Ok(Self { ffmpeg, feeds })
+    }
+
+    pub fn follow(&self, camera: u32, path: &FsPath) {
+        self.feeds.follow(camera, path);
+    }
+
+    pub fn queue_stats(&self) -> BTreeMap<u32, QueueStats> {
+        self.feeds.queue_stats()
+    }
+
+    // 남은 데이터를 넘기고 ffmpeg 를 끝낸 뒤 출력 디렉토리를 지운다.
+    // libcamera-vid 가 모두 종료된 뒤에 호출해야 한다.
+    pub fn finish(mut self) {
+        self.feeds.stop();
+        feed::wait_or_kill(&mut self.ffmpeg, FINISH_GRACE_PERIOD);
+        self.feeds.finish();
+    }
+}
+
+// GET /live/:session_id/:file - 재생 목록과 세그먼트
+pub async fn handle_file(
+    State(state): State<Arc<AppState>>,
+    Path((session_id, file)): Path<(Uuid, String)>,
+    request: Request,
+) -> Result<Response, ApiError> {
+    if !state.config.live.enabled {
+        return Err(ApiError::not_found("Live streaming is disabled"));
+    }
+    let valid = (file.ends_with(".m3u8") || file.ends_with(".ts"))
+        && file
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_');
+    if !valid {
+        return Err(ApiError::bad_request(format!(
+            "Invalid live file: {}",
+            file
+        )));
+    }
+
+    let path = session_dir(session_id).join(&file);
+    // 접근 토큰으로 받은 재생 목록이면 플레이어가 세그먼트도 같은 토큰으로 받게 붙여 준다.
+    let token = auth::query_value(&request, auth::ACCESS_TOKEN_QUERY).map(str::to_string);
+    if let (Some(token), true) = (token, file.ends_with(".m3u8")) {
+        let playlist = tokio::fs::read_to_string(&path).await.map_err(|_| {
+            ApiError::not_found(format!("No live stream for session {}", session_id))
+        })?;
+        let playlist: String = playlist
+            .lines()
+            .map(|line| {
+                if line.is_empty() || line.starts_with('#') {
+                    format!("{}\n", line)
+                } else {
+                    format!("{}?{}={}\n", line, auth::ACCESS_TOKEN_QUERY, token)
+                }
+            })
+            .collect();
+        return Ok((
+            [
+                (header::CONTENT_TYPE, "application/vnd.apple.mpegurl"),
+                (header::CACHE_CONTROL, "no-cache"),
+            ],
+            playlist,
+        )
+            .into_response());
+    }
+    let mut response = ServeFile::new(&path)
+        .oneshot(request)
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))?;
+    if response.status() == StatusCode::NOT_FOUND {
+        return Err(ApiError::not_found(format!(
+            "No live stream for session {}",
+            session_id
+        )));
+    }
+    let content_type = if file.ends_with(".m3u8") {
+        "application/vnd.apple.mpegurl"
+    } else {
+        "video/mp2t"
+    };
+    response
+        .headers_mut()
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    // 재생 목록은 계속 바뀌므로 캐시하지 않게 한다.
+    if file.ends_with(".m3u8") {
+        response
+            .headers_mut()
+            .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    }
+    Ok(response.map(Body::new).into_response())
+}
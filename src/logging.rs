@@ -0,0 +1,66 @@
+// src/logging.rs
+use crate::config::Config;
+use anyhow::{Context, Result};
+use std::{env, sync::OnceLock};
+use tracing_subscriber::{
+    EnvFilter, Registry, filter::Directive, fmt, layer::SubscriberExt, reload,
+    util::SubscriberInitExt,
+};
+
+// webrtc-rs 는 연결을 시도하는 동안 경고를 계속 남기므로 오류만 보이게 한다.
+const QUIET_TARGETS: [&str; 1] = ["webrtc=error"];
+
+// 실행 중에 log_level 을 바꿀 때 쓴다.
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+// RUST_LOG 가 설정되어 있으면 config.log_level 보다 우선한다.
+pub fn init(config: &Config) {
+    let filter = EnvFilter::try_from_default_env()
+        .or_else(|_| filter(&config.log_level))
+        .unwrap_or_else(|e| {
+            eprintln!(
+                "Invalid log level {:?} ({:#}). Falling back to info.",
+                config.log_level, e
+            );
+            EnvFilter::new("info")
+        });
+
+    let (filter, handle) = reload::Layer::new(filter);
+    let _ = FILTER.set(handle);
+    let registry = tracing_subscriber::registry().with(filter);
+    if config.log_json {
+        registry
+            .with(fmt::layer().json().with_current_span(true))
+            .init();
+    } else {
+        registry.with(fmt::layer()).init();
+    }
+}
+
+// config.log_level 형식의 필터
+pub fn filter(level: &str) -> Result<EnvFilter> {
+    EnvFilter::try_new(level)
+        .map(quiet_dependencies)
+        .with_context(|| format!("Invalid log_level {:?}", level))
+}
+
+// RUST_LOG 로 정한 필터는 그대로 둔다 (바꾸지 않았으면 false).
+pub fn set_filter(filter: EnvFilter) -> Result<bool> {
+    if env::var_os("RUST_LOG").is_some() {
+        return Ok(false);
+    }
+    let Some(handle) = FILTER.get() else {
+        return Ok(false);
+    };
+    handle
+        .reload(filter)
+        .context("Failed to change the log level")?;
+    Ok(true)
+}
+
+fn quiet_dependencies(filter: EnvFilter) -> EnvFilter {
+    QUIET_TARGETS
+        .iter()
+        .filter_map(|target| target.parse::<Directive>().ok())
+        .fold(filter, EnvFilter::add_directive)
+}
@@ -1,123 +1,1307 @@
 // src/main.rs
 
-use axum::{Router, extract::State, http::StatusCode, routing::get, serve};
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+};
+use serde::{Deserialize, Serialize};
 use std::{
-    env, // Add this to import the env module
+    collections::BTreeMap,
     net::SocketAddr,
     path::PathBuf,
-    sync::{
-        Arc,
-        atomic::{AtomicBool, Ordering},
-    },
+    sync::{Arc, Mutex},
+    time::Duration,
 };
-use tokio::net::TcpListener;
+use tracing::{Instrument, error, info, info_span, warn};
 
+mod audit;
+mod auth;
+mod bookmarks;
 mod camera_handler;
+mod camera_settings;
+mod chapters;
+mod chat;
+mod cli;
+mod clips;
+mod color_match;
+mod compositor;
+mod config;
+mod config_watch;
+mod cors;
+mod daemon;
+mod depth;
+mod detection;
+mod dry_run;
+mod email;
+mod encoder;
+mod errors;
+mod events;
+mod feed;
+mod filename;
+mod frame_log;
+mod frame_sync;
+mod gpio;
+mod health;
+mod keep_alive;
+mod lens;
+mod limits;
+mod live;
+mod logging;
+mod masks;
+mod metadata;
+mod motion;
+mod mqtt;
+mod onvif;
+mod openapi;
+mod overlay;
+mod panorama;
+mod preroll;
+mod profiles;
+mod projects;
+mod reconnect;
+mod recordings;
+mod recovery;
+mod renditions;
+mod retention;
+mod rtsp;
+mod scaling;
+mod scheduler;
+mod selftest;
+mod session;
+mod settings;
+mod sink;
+mod slow_motion;
+mod snapshot;
+mod source;
+mod stats;
+mod storage;
+mod stream;
+mod supervisor;
+mod systemd;
+mod timelapse;
+mod tls;
+mod upload;
+mod watermark;
+mod webhooks;
+mod webrtc_preview;
+
+use auth::AccessTokens;
+use camera_handler::{CameraFormat, OutputMode, RecordingConfig};
+use camera_settings::CameraControls;
+use chapters::ChapterMode;
+use clap::Parser;
+use cli::Cli;
+use color_match::ColorMatchConfig;
+use compositor::{Layout, PipConfig};
+use config::Config;
+use depth::DepthConfig;
+use detection::DetectionConfig;
+use encoder::Encoder;
+use errors::{ErrorLog, RecordedError};
+use events::{EventBus, EventKind};
+use feed::QueueStats;
+use keep_alive::WarmCameras;
+use limits::Limiter;
+use masks::PrivacyMasks;
+use overlay::OverlayConfig;
+use panorama::PanoramaConfig;
+use preroll::PreRoll;
+use profiles::ProfileStore;
+use reconnect::ReconnectConfig;
+use renditions::Renditions;
+use rtsp::RtspServer;
+use scheduler::ScheduleStore;
+use selftest::SelfTest;
+use session::{RecordingSession, SessionManager, SessionState, SessionSummary};
+use settings::LiveSettings;
+use sink::SinkConfig;
+use slow_motion::SlowMotionConfig;
+use stats::Counters;
+use storage::Catalog;
+use stream::StreamHub;
+use supervisor::TaskSupervisor;
+use timelapse::TimelapseConfig;
+use upload::Uploader;
+use uuid::Uuid;
+use watermark::WatermarkConfig;
+use webhooks::{WebhookEvent, Webhooks};
 
 #[derive(Clone)]
 struct AppState {
-    recording_active: Arc<AtomicBool>,
-    stop_requested: Arc<AtomicBool>,
+    config: Arc<Config>,
+    sessions: Arc<Mutex<SessionManager>>,
+    schedules: Arc<Mutex<ScheduleStore>>,
+    profiles: Arc<Mutex<ProfileStore>>,
+    catalog: Arc<Catalog>,
+    uploader: Arc<Uploader>,
+    webhooks: Arc<Webhooks>,
+    events: Arc<EventBus>,
+    errors: Arc<ErrorLog>,
+    streams: Arc<StreamHub>,
+    pre_roll: Arc<PreRoll>,
+    rtsp: Arc<RtspServer>,
+    camera_controls: Arc<CameraControls>,
+    warm_cameras: Arc<WarmCameras>,
+    privacy_masks: Arc<PrivacyMasks>,
+    limiter: Arc<Limiter>,
+    renditions: Arc<Renditions>,
+    tasks: Arc<TaskSupervisor>,
+    self_test: Arc<SelfTest>,
+    counters: Arc<Counters>,
+    // Settings POST /config can change while the server runs
+    settings: Arc<LiveSettings>,
+    // Signs the query tokens POST /auth/token hands out
+    access_tokens: Arc<AccessTokens>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+struct StartRequest {
+    // Name of a [profiles] entry (or one added through /profiles) applied on top of the
+    // [recording] defaults; the other fields here still override it
+    profile: Option<String>,
+    // Shorthand for a single-camera recording; `cameras` takes precedence
+    camera: Option<u32>,
+    cameras: Option<Vec<u32>>,
+    allow_missing_cameras: Option<bool>,
+    layout: Option<Layout>,
+    grid_columns: Option<u32>,
+    pip: Option<PipConfig>,
+    panorama: Option<PanoramaConfig>,
+    width: Option<u32>,
+    height: Option<u32>,
+    fps: Option<u32>,
+    camera_formats: Option<Vec<CameraFormat>>,
+    #[serde(alias = "duration_limit")]
+    max_duration: Option<u64>,
+    segment_duration: Option<u64>,
+    overlays: Option<Vec<OverlayConfig>>,
+    reconnect: Option<ReconnectConfig>,
+    constant_frame_rate: Option<bool>,
+    output_mode: Option<OutputMode>,
+    frame_timestamps: Option<bool>,
+    depth: Option<DepthConfig>,
+    encoder: Option<Encoder>,
+    sink: Option<SinkConfig>,
+    timelapse: Option<TimelapseConfig>,
+    slow_motion: Option<SlowMotionConfig>,
+    watermark: Option<WatermarkConfig>,
+    color_match: Option<ColorMatchConfig>,
+    detection: Option<DetectionConfig>,
+    chapters: Option<ChapterMode>,
+    filename: Option<String>,
+    // Subdirectory of save_dir for this recording's files
+    output_dir: Option<String>,
+    // Project (see /projects) the recording belongs to; also its directory unless output_dir is set
+    project: Option<String>,
+}
+
+impl StartRequest {
+    fn into_config(self, defaults: &RecordingConfig) -> Result<RecordingConfig, ApiError> {
+        let mut config = defaults.clone();
+        self.apply(&mut config);
+        config
+            .validate()
+            .map_err(|e| ApiError::bad_request(e.to_string()))?;
+        Ok(config)
+    }
+
+    // Overwrites the fields this request sets, without validating the result
+    fn apply(self, config: &mut RecordingConfig) {
+        if let Some(cameras) = self.cameras {
+            config.cameras = cameras;
+        } else if let Some(camera) = self.camera {
+            config.cameras = vec![camera];
+        }
+        if let Some(allow_missing_cameras) = self.allow_missing_cameras {
+            config.allow_missing_cameras = allow_missing_cameras;
+        }
+        if let Some(layout) = self.layout {
+            config.layout = layout;
+        }
+        if let Some(grid_columns) = self.grid_columns {
+            config.grid_columns = Some(grid_columns);
+        }
+        if let Some(pip) = self.pip {
+            config.pip = pip;
+        }
+        if let Some(panorama) = self.panorama {
+            config.panorama = panorama;
+        }
+        if let Some(width) = self.width {
+            config.width = width;
+        }
+        if let Some(height) = self.height {
+            config.height = height;
+        }
+        if let Some(fps) = self.fps {
+            config.fps = fps;
+        }
+        if let Some(camera_formats) = self.camera_formats {
+            config.camera_formats = camera_formats;
+        }
+        if let Some(max_duration) = self.max_duration {
+            config.max_duration = Some(max_duration);
+        }
+        if let Some(segment_duration) = self.segment_duration {
+            config.segment_duration = Some(segment_duration);
+        }
+        if let Some(overlays) = self.overlays {
+            config.overlays = overlays;
+        }
+        if let Some(reconnect) = self.reconnect {
+            config.reconnect = reconnect;
+        }
+        if let Some(constant_frame_rate) = self.constant_frame_rate {
+            config.constant_frame_rate = constant_frame_rate;
+        }
+        if let Some(output_mode) = self.output_mode {
+            config.output_mode = output_mode;
+        }
+        if let Some(frame_timestamps) = self.frame_timestamps {
+            config.frame_timestamps = frame_timestamps;
+        }
+        if let Some(depth) = self.depth {
+            config.depth = depth;
+        }
+        if let Some(encoder) = self.encoder {
+            config.encoder = encoder;
+        }
+        if let Some(sink) = self.sink {
+            config.sink = sink;
+        }
+        if let Some(timelapse) = self.timelapse {
+            config.timelapse = timelapse;
+        }
+        if let Some(slow_motion) = self.slow_motion {
+            config.slow_motion = slow_motion;
+        }
+        if let Some(watermark) = self.watermark {
+            config.watermark = watermark;
+        }
+        if let Some(color_match) = self.color_match {
+            config.color_match = color_match;
+        }
+        if let Some(detection) = self.detection {
+            config.detection = detection;
+        }
+        if let Some(chapters) = self.chapters {
+            config.chapters = chapters;
+        }
+        if let Some(filename) = self.filename {
+            config.filename = filename;
+        }
+        if let Some(output_dir) = self.output_dir {
+            config.output_dir = Some(output_dir);
+        }
+        if let Some(project) = self.project {
+            config.project = Some(project);
+        }
+    }
+}
+
+// Query of POST /start
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct StartQuery {
+    profile: Option<String>,
+}
+
+// Query of POST /stop (and legacy GET /stop)
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct StopQuery {
+    // Skip finalizing; the raw camera streams are kept for recovery
+    force: bool,
+}
+
+#[derive(Serialize)]
+struct StartResponse {
+    message: &'static str,
+    session_id: Uuid,
+    config: RecordingConfig,
+}
+
+// Body of /stop, /pause and /resume; without a session_id the active session is used
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct SessionTarget {
+    session_id: Option<Uuid>,
+}
+
+#[derive(Serialize)]
+struct MessageResponse {
+    message: &'static str,
+}
+
+#[derive(Serialize)]
+struct StatusResponse {
+    recording_active: bool,
+    // State of the running session (the newest one if several), or idle
+    recording_state: SessionState,
+    // Newest first; see GET /errors
+    recent_errors: Vec<RecordedError>,
+    // Per-camera write queues of the RTSP/WebRTC stream source (empty while it is closed)
+    stream_queues: BTreeMap<u32, QueueStats>,
+    #[serde(flatten)]
+    session: Option<SessionSummary>,
+}
+
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+
+    fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    fn internal(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, message)
+    }
+
+    fn unauthorized(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, message)
+    }
+
+    fn forbidden(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::FORBIDDEN, message)
+    }
+
+    fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, message)
+    }
+
+    fn conflict(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::CONFLICT, message)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = Json(serde_json::json!({ "error": self.message }));
+        (self.status, body).into_response()
+    }
 }
 
 async fn hello_world() -> &'static str {
     "Hello, World!"
 }
 
-async fn handle_start_recording(
-    State(state): State<Arc<AppState>>,
-) -> Result<String, (StatusCode, String)> {
-    if state
-        .recording_active
-        .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
-        .is_err()
-    {
-        return Err((
-            StatusCode::CONFLICT,
-            "Recording is already in progress.".to_string(),
-        ));
+// The settings a start request records with: the request over its profile over the defaults
+async fn resolve_start(
+    state: &AppState,
+    mut request: StartRequest,
+) -> Result<RecordingConfig, ApiError> {
+    let mut defaults = state.settings.recording();
+    if let Some(name) = request.profile.take() {
+        let profile = state
+            .profiles
+            .lock()
+            .unwrap()
+            .get(&name)
+            .ok_or_else(|| ApiError::not_found(format!("Profile {:?} not found", name)))?;
+        info!("Using recording profile {}.", name);
+        profile.settings.apply(&mut defaults);
     }
+    let mut config = request.into_config(&defaults)?;
+    if let Some(project) = &config.project {
+        projects::ensure_exists(state, project).await?;
+    }
+    // Tuned via PUT /cameras/:id/settings, so these always come from the live controls
+    config.camera_settings = state.camera_controls.all();
+    // Likewise managed via PUT /cameras/:id/masks
+    config.masks = state.privacy_masks.all();
+    Ok(config)
+}
 
-    println!("Received request to start recording...");
+async fn start_recording(
+    state: Arc<AppState>,
+    request: StartRequest,
+) -> Result<Json<StartResponse>, ApiError> {
+    let config = resolve_start(&state, request).await?;
+    let (session, ready) = state
+        .sessions
+        .lock()
+        .unwrap()
+        .begin(config.clone())
+        .map_err(|e| ApiError::conflict(e.to_string()))?;
 
-    state.stop_requested.store(false, Ordering::SeqCst);
-    println!("Stop request flag reset to false.");
+    let span = info_span!("recording", session_id = %session.id);
+    info!(parent: &span, cameras = ?config.cameras, "Received request to start recording...");
 
-    let state_clone = state.clone();
+    state.events.publish(
+        session.id,
+        EventKind::RecordingStarted {
+            cameras: config.cameras.clone(),
+        },
+    );
+    state.webhooks.notify(WebhookEvent::Started {
+        session_id: session.id,
+        cameras: config.cameras.clone(),
+    });
 
-    tokio::spawn(async move {
-        let stop_flag_clone = state_clone.stop_requested.clone();
-        let result: Result<Result<PathBuf, anyhow::Error>, tokio::task::JoinError> =
-            tokio::task::spawn_blocking(move || {
-                camera_handler::run_recording_blocking(stop_flag_clone)
-            })
-            .await;
+    let server_config = state.config.clone();
+    let webhooks = state.webhooks.clone();
+    let events = state.events.clone();
+    let streams = state.streams.clone();
+    let pre_roll = state.pre_roll.clone();
+    let catalog = state.catalog.clone();
+    let errors = state.errors.clone();
+    let uploader = state.uploader.clone();
+    let warm_cameras = state.warm_cameras.clone();
+    let sessions = state.sessions.clone();
+    let task_session = session.clone();
+    let task_span = span.clone();
 
-        match result {
-            Ok(Ok(final_path)) => {
-                println!(
-                    "Background recording task finished successfully. Video saved to: {:?}",
-                    final_path
-                );
-            }
-            Ok(Err(e)) => {
-                eprintln!("Background recording task failed: {}", e);
+    state.tasks.spawn_job(
+        session.id,
+        async move {
+            let blocking_session = task_session.clone();
+            let blocking_events = events.clone();
+            let result: Result<Result<Vec<PathBuf>, anyhow::Error>, tokio::task::JoinError> =
+                tokio::task::spawn_blocking(move || {
+                    let _guard = task_span.enter();
+                    // Take the pre-roll before the preview lets go of the cameras
+                    let clips = pre_roll.take(&blocking_session.config, &server_config.recording);
+                    // The RTSP/WebRTC preview may be holding the cameras open
+                    streams.release_cameras();
+                    let save_dir = server_config.save_dir();
+                    let discards_video = blocking_session.config.discards_video();
+                    let session_id = blocking_session.id;
+                    let outputs = camera_handler::run_recording_blocking(
+                        server_config,
+                        blocking_session,
+                        blocking_events,
+                        clips,
+                        &warm_cameras,
+                    );
+                    // Reopen the cameras for the next recording whether or not this one succeeded,
+                    // unless another recording still holds them (the last one to finish reopens them)
+                    let others_running = sessions
+                        .lock()
+                        .unwrap()
+                        .running()
+                        .iter()
+                        .any(|other| other.id != session_id);
+                    if warm_cameras.enabled()
+                        && !others_running
+                        && let Err(e) = warm_cameras.warm()
+                    {
+                        warn!("Failed to keep the cameras open: {:#}", e);
+                    }
+                    let outputs = outputs?;
+                    // A null sink leaves frame summaries, not recordings
+                    if discards_video {
+                        return Ok(outputs);
+                    }
+                    if let Err(e) = catalog.add(&save_dir, &outputs) {
+                        warn!("Failed to add the recording to the catalog: {:#}", e);
+                    }
+                    uploader.enqueue(&outputs);
+                    Ok(outputs)
+                })
+                .await;
+
+            let error = match result {
+                Ok(Ok(outputs)) => {
+                    info!(
+                        "Background recording task finished successfully. Video saved to: {:?}",
+                        outputs
+                    );
+                    let stats = task_session.stats.lock().unwrap().clone();
+                    webhooks.notify(WebhookEvent::Finished {
+                        session_id: task_session.id,
+                        outputs,
+                        duration_seconds: stats.elapsed_seconds,
+                        frames_captured: stats.frames_captured,
+                    });
+                    None
+                }
+                Ok(Err(e)) => {
+                    error!("Background recording task failed: {:#}", e);
+                    Some(format!("{:#}", e))
+                }
+                Err(e) => {
+                    error!("Background recording task panicked or was cancelled: {}", e);
+                    Some(e.to_string())
+                }
+            };
+
+            if let Some(error) = &error {
+                errors.record(Some(task_session.id), error.clone());
+                webhooks.notify(WebhookEvent::Failed {
+                    session_id: task_session.id,
+                    error: error.clone(),
+                });
             }
-            Err(e) => {
-                eprintln!("Background recording task panicked or was cancelled: {}", e);
+            events.publish(
+                task_session.id,
+                EventKind::RecordingStopped {
+                    outputs: if error.is_none() {
+                        task_session.outputs()
+                    } else {
+                        Vec::new()
+                    },
+                    error: error.clone(),
+                },
+            );
+            task_session.finish(error, &events);
+            info!("Recording session marked as {:?}.", task_session.state());
+        }
+        .instrument(span),
+    );
+
+    // Answer only once every camera delivers frames, so a failure reaches the caller
+    let timeout = Duration::from_secs(state.config.start_timeout_secs);
+    match tokio::time::timeout(timeout, ready).await {
+        Ok(Ok(Ok(()))) => Ok(Json(StartResponse {
+            message: "Recording started.",
+            session_id: session.id,
+            config,
+        })),
+        Ok(Ok(Err(error))) => Err(ApiError::new(
+            StatusCode::BAD_GATEWAY,
+            format!("Recording failed to start: {}", error),
+        )),
+        Ok(Err(_)) => Err(ApiError::internal(
+            "Recording task ended without reporting whether capture began",
+        )),
+        Err(_) => {
+            warn!(
+                session_id = %session.id,
+                "Cameras did not start capturing within {}s. Stopping the recording.",
+                timeout.as_secs()
+            );
+            let _ = session.request_stop();
+            Err(ApiError::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                format!(
+                    "Cameras did not start capturing within {}s",
+                    timeout.as_secs()
+                ),
+            ))
+        }
+    }
+}
+
+// The requested session, or the running one when no id is given and only one is running
+fn find_session(
+    state: &AppState,
+    session_id: Option<Uuid>,
+) -> Result<Arc<RecordingSession>, ApiError> {
+    let sessions = state.sessions.lock().unwrap();
+    match session_id {
+        Some(id) => sessions
+            .get(id)
+            .ok_or_else(|| ApiError::not_found(format!("Session {} not found", id))),
+        None => {
+            let mut running = sessions.running();
+            match running.len() {
+                0 => Err(ApiError::conflict("Recording is not currently active.")),
+                1 => Ok(running.remove(0)),
+                _ => Err(ApiError::bad_request(
+                    "Several sessions are recording; specify a session_id.",
+                )),
             }
         }
+    }
+}
 
-        state_clone.recording_active.store(false, Ordering::SeqCst);
-        println!("Recording active flag reset to false.");
-    });
+async fn stop_recording(
+    state: Arc<AppState>,
+    target: SessionTarget,
+    force: bool,
+) -> Result<Json<MessageResponse>, ApiError> {
+    let session = find_session(&state, target.session_id)?;
+    if force {
+        session
+            .request_cancel()
+            .map_err(|e| ApiError::conflict(e.to_string()))?;
+        warn!(session_id = %session.id, "Forced stop requested; skipping finalization.");
+        return Ok(Json(MessageResponse {
+            message: "Recording cancelled. The camera streams are kept and recovered on the next start.",
+        }));
+    }
+    session
+        .request_stop()
+        .map_err(|e| ApiError::conflict(e.to_string()))?;
+    info!(session_id = %session.id, "Stop request signal sent.");
 
-    Ok("Recording started in the background.".to_string())
+    Ok(Json(MessageResponse {
+        message: "Stop request sent. Recording will finalize shortly.",
+    }))
 }
 
-async fn handle_stop_recording(
+async fn set_paused(
+    state: &AppState,
+    target: SessionTarget,
+    paused: bool,
+) -> Result<Json<MessageResponse>, ApiError> {
+    let session = find_session(state, target.session_id)?;
+    if !session.is_capturing() {
+        return Err(ApiError::conflict("Recording is not currently active."));
+    }
+    // ffmpeg has no equivalent of libcamera-vid's SIGUSR1 pause toggle
+    let cameras = session.stats.lock().unwrap().cameras.clone();
+    if let Some(camera) = cameras
+        .iter()
+        .find(|&&camera| !session.config.is_local(camera))
+    {
+        return Err(ApiError::conflict(format!(
+            "Camera {} is not a libcamera camera; recordings with network or test-pattern cameras cannot be paused.",
+            camera
+        )));
+    }
+    session
+        .request_pause(paused)
+        .map_err(|e| ApiError::conflict(e.to_string()))?;
+
+    Ok(Json(MessageResponse {
+        message: if paused {
+            "Pause request sent."
+        } else {
+            "Resume request sent."
+        },
+    }))
+}
+
+// Body of /switch
+#[derive(Debug, Deserialize)]
+struct SwitchRequest {
+    session_id: Option<Uuid>,
+    camera: u32,
+}
+
+// Only for recordings with the switch layout; takes effect on the next loop iteration.
+async fn handle_switch_camera(
     State(state): State<Arc<AppState>>,
-) -> Result<String, (StatusCode, String)> {
-    if !state.recording_active.load(Ordering::SeqCst) {
-        return Err((
-            StatusCode::CONFLICT,
-            "Recording is not currently active or has already finished.".to_string(),
+    Json(request): Json<SwitchRequest>,
+) -> Result<Json<MessageResponse>, ApiError> {
+    let session = find_session(&state, request.session_id)?;
+    if !session.is_capturing() {
+        return Err(ApiError::conflict("Recording is not currently active."));
+    }
+    if session.config.layout != Layout::Switch {
+        return Err(ApiError::conflict(
+            "Recording does not use the switch layout.",
         ));
     }
+    let cameras = session.stats.lock().unwrap().cameras.clone();
+    if !cameras.contains(&request.camera) {
+        return Err(ApiError::bad_request(format!(
+            "Camera {} is not part of this recording (cameras: {:?})",
+            request.camera, cameras
+        )));
+    }
+    *session.switch_requested.lock().unwrap() = Some(request.camera);
+    Ok(Json(MessageResponse {
+        message: "Switch request sent.",
+    }))
+}
+
+async fn handle_pause_recording(
+    State(state): State<Arc<AppState>>,
+    body: Option<Json<SessionTarget>>,
+) -> Result<Json<MessageResponse>, ApiError> {
+    let target = body.map(|Json(target)| target).unwrap_or_default();
+    set_paused(&state, target, true).await
+}
+
+async fn handle_resume_recording(
+    State(state): State<Arc<AppState>>,
+    body: Option<Json<SessionTarget>>,
+) -> Result<Json<MessageResponse>, ApiError> {
+    let target = body.map(|Json(target)| target).unwrap_or_default();
+    set_paused(&state, target, false).await
+}
+
+// A missing or non-JSON body falls back to the defaults.
+async fn handle_start_recording(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<StartQuery>,
+    body: Option<Json<StartRequest>>,
+) -> Result<Json<StartResponse>, ApiError> {
+    let mut request = body.map(|Json(request)| request).unwrap_or_default();
+    // ?profile= wins over a "profile" field in the body
+    if query.profile.is_some() {
+        request.profile = query.profile;
+    }
+    start_recording(state, request).await
+}
+
+async fn handle_stop_recording(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<StopQuery>,
+    body: Option<Json<SessionTarget>>,
+) -> Result<Json<MessageResponse>, ApiError> {
+    let target = body.map(|Json(target)| target).unwrap_or_default();
+    stop_recording(state, target, query.force).await
+}
+
+// Legacy GET /stop?session_id=...
+async fn handle_legacy_stop_recording(
+    State(state): State<Arc<AppState>>,
+    Query(target): Query<SessionTarget>,
+    Query(query): Query<StopQuery>,
+) -> Result<Json<MessageResponse>, ApiError> {
+    stop_recording(state, target, query.force).await
+}
+
+// Legacy GET /start?camera=...; only routed when legacy_get_routes is enabled.
+async fn handle_legacy_start_recording(
+    State(state): State<Arc<AppState>>,
+    Query(request): Query<StartRequest>,
+) -> Result<Json<StartResponse>, ApiError> {
+    start_recording(state, request).await
+}
+
+async fn handle_config(State(state): State<Arc<AppState>>) -> Json<Config> {
+    Json(settings::effective(&state))
+}
 
-    state.stop_requested.store(true, Ordering::SeqCst);
-    println!("Stop request signal sent.");
+// Without a session_id this reports the running session (the newest one if several),
+// or the most recent one
+async fn handle_status(
+    State(state): State<Arc<AppState>>,
+    Query(target): Query<SessionTarget>,
+) -> Result<Json<StatusResponse>, ApiError> {
+    let session = match target.session_id {
+        Some(id) => Some(find_session(&state, Some(id))?),
+        None => {
+            let sessions = state.sessions.lock().unwrap();
+            sessions
+                .running()
+                .into_iter()
+                .next()
+                .or_else(|| sessions.latest())
+        }
+    };
+    let running = state.sessions.lock().unwrap().running();
+    let recording_state = running
+        .first()
+        .map_or(SessionState::Idle, |session| session.state());
+
+    Ok(Json(StatusResponse {
+        recording_active: !running.is_empty(),
+        recording_state,
+        recent_errors: state.errors.recent(),
+        stream_queues: state.streams.queue_stats(),
+        session: session.map(|session| session.summary()),
+    }))
+}
 
-    Ok("Stop request sent. Recording will finalize shortly.".to_string())
+async fn handle_list_sessions(State(state): State<Arc<AppState>>) -> Json<Vec<SessionSummary>> {
+    let sessions = state.sessions.lock().unwrap().list();
+    Json(sessions.iter().map(|session| session.summary()).collect())
+}
+
+async fn handle_get_session(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<SessionSummary>, ApiError> {
+    Ok(Json(find_session(&state, Some(id))?.summary()))
 }
 
-#[tokio::main]
-async fn main() {
+fn main() {
+    let cli = Cli::parse();
+    let config = Config::load(cli.config.as_deref())
+        .and_then(|mut config| {
+            cli.apply(&mut config);
+            config.validate()?;
+            Ok(config)
+        })
+        .unwrap_or_else(|e| {
+            eprintln!("Failed to load configuration: {:#}", e);
+            std::process::exit(1);
+        });
+    // Detach before the runtime starts its threads; fork only carries the calling thread
+    let output = if cli.daemon && !cli.generate_cert {
+        daemon::detach(config.log_file().as_deref())
+    } else {
+        config
+            .log_file()
+            .map_or(Ok(()), |log_file| daemon::redirect_output(&log_file))
+    };
+    if let Err(e) = output {
+        eprintln!("{:#}", e);
+        std::process::exit(1);
+    }
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("Failed to start the Tokio runtime")
+        .block_on(run(cli, config));
+}
+
+async fn run(cli: Cli, config: Config) {
+    logging::init(&config);
+    match &config.source {
+        Some(path) => info!("Loaded configuration from {:?}", path),
+        None => info!("No config file found. Using defaults."),
+    }
+    if cli.generate_cert {
+        if let Err(e) = tls::generate_self_signed(&config.tls) {
+            error!("{:#}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    let pid_file = config.pid_file().map(|path| {
+        daemon::PidFile::create(path).unwrap_or_else(|e| {
+            error!("{:#}", e);
+            std::process::exit(1);
+        })
+    });
+
+    let schedules = ScheduleStore::load(config.schedules_file()).unwrap_or_else(|e| {
+        error!("Failed to load schedules: {:#}", e);
+        std::process::exit(1);
+    });
+    info!("Loaded {} schedule(s).", schedules.len());
+    let profiles = ProfileStore::load(config.profiles_file(), config.profiles.clone())
+        .unwrap_or_else(|e| {
+            error!("Failed to load profiles: {:#}", e);
+            std::process::exit(1);
+        });
+    info!("Loaded {} recording profile(s).", profiles.len());
+    let catalog = Catalog::open(&config.catalog_file()).unwrap_or_else(|e| {
+        error!("{:#}", e);
+        std::process::exit(1);
+    });
+    if config.recover_interrupted {
+        // Recordings interrupted in the staging directory are recovered there and then moved
+        let save_dir = config.save_dir();
+        let recovered = match config.staging_dir() {
+            Some(staging_dir) => recovery::recover(&staging_dir)
+                .and_then(|recovered| recordings::publish(&staging_dir, &save_dir, &recovered)),
+            None => Ok(Vec::new()),
+        }
+        .and_then(|mut recovered| {
+            recovered.extend(recovery::recover(&save_dir)?);
+            Ok(recovered)
+        });
+        match recovered {
+            Ok(recovered) if !recovered.is_empty() => {
+                info!(
+                    "Recovered {} interrupted recording file(s).",
+                    recovered.len()
+                );
+                if let Err(e) = catalog.add(&config.save_dir(), &recovered) {
+                    warn!("Failed to add recovered recordings to the catalog: {:#}", e);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => error!("Failed to recover interrupted recordings: {:#}", e),
+        }
+    }
+    if let Err(e) = catalog.reconcile(&config.save_dir()) {
+        error!("Failed to reconcile the recording catalog: {:#}", e);
+        std::process::exit(1);
+    }
+    let catalog = Arc::new(catalog);
+    let uploader = Uploader::new(&config.upload, catalog.clone(), config.save_dir());
+    let legacy_get_routes = config.legacy_get_routes;
+    let rtsp_config = config.rtsp.clone();
+    let auth_config = config.auth.clone();
+    let pre_roll_config = config.pre_roll.clone();
+    let camera_settings = config.recording.camera_settings.clone();
+    let masks = config.recording.masks.clone();
+    let limits_config = config.limits.clone();
+    let renditions = Arc::new(Renditions::new(&config.renditions));
+    let tasks = Arc::new(TaskSupervisor::new(&config.supervisor));
+    let streams = Arc::new(StreamHub::default());
+    let camera_controls = Arc::new(CameraControls::new(&camera_settings));
+    let settings = Arc::new(LiveSettings::new(&config));
+    let warm_cameras = Arc::new(WarmCameras::new(
+        &config,
+        settings.clone(),
+        camera_controls.clone(),
+    ));
+    let webhooks = Webhooks::new(&config.webhooks).unwrap_or_else(|e| {
+        error!("Failed to set up webhooks: {:#}", e);
+        std::process::exit(1);
+    });
+
     let shared_state = Arc::new(AppState {
-        recording_active: Arc::new(AtomicBool::new(false)),
-        stop_requested: Arc::new(AtomicBool::new(false)),
+        config: Arc::new(config),
+        sessions: Arc::new(Mutex::new(SessionManager::default())),
+        schedules: Arc::new(Mutex::new(schedules)),
+        profiles: Arc::new(Mutex::new(profiles)),
+        catalog,
+        uploader: Arc::new(uploader),
+        webhooks: Arc::new(webhooks),
+        events: Arc::new(EventBus::default()),
+        errors: Arc::new(ErrorLog::default()),
+        streams: streams.clone(),
+        pre_roll: Arc::new(PreRoll::new(&pre_roll_config)),
+        rtsp: Arc::new(RtspServer::new(&rtsp_config, &auth_config, streams)),
+        camera_controls,
+        warm_cameras,
+        privacy_masks: Arc::new(PrivacyMasks::new(&masks)),
+        limiter: Arc::new(Limiter::new(limits_config)),
+        renditions,
+        tasks,
+        self_test: Arc::new(SelfTest::default()),
+        counters: Arc::new(Counters::default()),
+        settings,
+        access_tokens: Arc::new(AccessTokens::default()),
     });
 
-    let app = Router::new()
+    shared_state.tasks.spawn_service("scheduler", {
+        let state = shared_state.clone();
+        move || scheduler::run(state.clone())
+    });
+    shared_state.tasks.spawn_service("retention", {
+        let state = shared_state.clone();
+        move || retention::run(state.clone())
+    });
+    shared_state.tasks.spawn_service("stats", {
+        let state = shared_state.clone();
+        move || stats::run(state.clone())
+    });
+    shared_state.tasks.spawn_service("keep-alive", {
+        let state = shared_state.clone();
+        move || keep_alive::run(state.clone())
+    });
+    if let Err(e) = preroll::spawn(
+        shared_state.pre_roll.clone(),
+        shared_state.streams.clone(),
+        &shared_state.config.recording.cameras,
+    ) {
+        error!("Failed to start the pre-roll buffer: {:#}", e);
+        std::process::exit(1);
+    }
+    if let Err(e) = motion::spawn(shared_state.clone()) {
+        error!("Failed to start motion detection: {:#}", e);
+        std::process::exit(1);
+    }
+    if let Err(e) = stream::spawn(
+        shared_state.streams.clone(),
+        shared_state.config.clone(),
+        shared_state.sessions.clone(),
+        shared_state.camera_controls.clone(),
+        shared_state.privacy_masks.clone(),
+    ) {
+        error!("Failed to start the stream source: {:#}", e);
+        std::process::exit(1);
+    }
+    rtsp::spawn(shared_state.rtsp.clone());
+    mqtt::spawn(shared_state.clone());
+    config_watch::spawn(shared_state.clone(), cli.clone());
+    if let Err(e) = chat::spawn(shared_state.clone()) {
+        error!("Failed to set up chat notifications: {:#}", e);
+        std::process::exit(1);
+    }
+    if let Err(e) = email::spawn(shared_state.clone()) {
+        error!("Failed to set up email notifications: {:#}", e);
+        std::process::exit(1);
+    }
+    if let Err(e) = gpio::spawn(shared_state.clone()) {
+        error!("Failed to set up GPIO: {:#}", e);
+        std::process::exit(1);
+    }
+    if let Err(e) = upload::spawn(shared_state.uploader.clone()) {
+        error!("Failed to start the uploader: {:#}", e);
+        std::process::exit(1);
+    }
+
+    shared_state
+        .tasks
+        .spawn_service("watchdog", systemd::run_watchdog);
+
+    // Check the cameras, encoders and directories before the first recording
+    let self_test_config = shared_state.config.self_test.clone();
+    let mut self_test_result = None;
+    if self_test_config.on_startup {
+        let state = shared_state.clone();
+        let profiles = selftest::profiles(&state);
+        let self_test = async move { state.self_test.run(state.config.clone(), profiles).await.ok };
+        if self_test_config.exit_on_failure {
+            if !self_test.await {
+                error!("Self-test failed; exiting (self_test.exit_on_failure).");
+                std::process::exit(1);
+            }
+        } else {
+            self_test_result = Some(tokio::spawn(self_test));
+        }
+    }
+
+    let mut app = Router::new()
         .route("/", get(hello_world))
-        .route("/start", get(handle_start_recording))
-        .route("/stop", get(handle_stop_recording))
-        .with_state(shared_state);
-
-    // Read the port from the environment variable or default to 8000
-    let port: u16 = env::var("PORT")
-        .unwrap_or_else(|_| "8000".to_string())
-        .parse()
-        .expect("PORT must be a valid u16");
-
-    let addr = SocketAddr::from(([127, 0, 0, 1], port));
-    println!("Listening on {}", addr);
-
-    let listener = TcpListener::bind(addr)
-        .await
-        .expect("Failed to bind address");
-
-    serve(listener, app.into_make_service())
-        .await
-        .expect("Server failed");
+        .route("/healthz", get(health::handle_healthz))
+        .route("/readyz", get(health::handle_readyz))
+        .route("/selftest", get(selftest::handle_selftest))
+        .route("/openapi.json", get(openapi::handle_spec))
+        .route("/docs", get(openapi::handle_docs))
+        .route("/start", post(handle_start_recording))
+        .route("/start/validate", post(dry_run::handle_validate))
+        .route("/stop", post(handle_stop_recording))
+        .route("/pause", post(handle_pause_recording))
+        .route("/resume", post(handle_resume_recording))
+        .route("/switch", post(handle_switch_camera))
+        .route("/status", get(handle_status))
+        .route("/errors", get(errors::handle_list))
+        .route("/stats", get(stats::handle_stats))
+        .route("/audit", get(audit::handle_list))
+        .route("/sessions", get(handle_list_sessions))
+        .route("/sessions/:id", get(handle_get_session))
+        .route("/events", get(events::handle_events))
+        .route("/live/:session_id/:file", get(live::handle_file))
+        .route("/webrtc/offer", post(webrtc_preview::handle_offer))
+        .route("/auth/token", post(auth::handle_token))
+        .route("/config", get(handle_config).post(settings::handle_update))
+        .route("/snapshot", get(snapshot::handle_snapshot))
+        .route("/cameras", get(source::handle_list))
+        .route("/cameras/warmup", post(keep_alive::handle_warmup))
+        .route(
+            "/cameras/:id/settings",
+            get(camera_settings::handle_get).put(camera_settings::handle_put),
+        )
+        .route(
+            "/cameras/:id/masks",
+            get(masks::handle_get).put(masks::handle_put),
+        )
+        .route("/recordings", get(recordings::handle_list))
+        .route("/recordings/cleanup", post(recordings::handle_cleanup))
+        .route("/recordings/current/marker", post(bookmarks::handle_add))
+        .route(
+            "/recordings/*name",
+            get(recordings::handle_download)
+                .post(clips::handle_create)
+                .delete(recordings::handle_delete),
+        )
+        .route(
+            "/webhooks",
+            get(webhooks::handle_list).post(webhooks::handle_create),
+        )
+        .route("/webhooks/:id", delete(webhooks::handle_delete))
+        .route(
+            "/schedules",
+            get(scheduler::handle_list).post(scheduler::handle_create),
+        )
+        .route(
+            "/schedules/:id",
+            get(scheduler::handle_get)
+                .put(scheduler::handle_update)
+                .delete(scheduler::handle_delete),
+        )
+        .route(
+            "/projects",
+            get(projects::handle_list).post(projects::handle_create),
+        )
+        .route(
+            "/projects/:name",
+            get(projects::handle_get).delete(projects::handle_delete),
+        )
+        .route(
+            "/projects/:name/recordings",
+            get(projects::handle_recordings),
+        )
+        .route("/projects/:name/manifest", get(projects::handle_manifest))
+        .route("/profiles", get(profiles::handle_list))
+        .route(
+            "/profiles/:name",
+            get(profiles::handle_get)
+                .put(profiles::handle_put)
+                .delete(profiles::handle_delete),
+        );
+
+    // Keep the old GET side-effect routes for existing clients when asked to
+    if legacy_get_routes {
+        info!("Legacy GET /start and /stop routes enabled.");
+        app = app
+            .route("/start", get(handle_legacy_start_recording))
+            .route("/stop", get(handle_legacy_stop_recording));
+    }
+
+    if shared_state.config.auth.enabled() {
+        info!(
+            "API key authentication enabled ({} user(s) with roles).",
+            shared_state.config.auth.users.len()
+        );
+    } else {
+        warn!("No API keys configured; every route is open to the network.");
+    }
+    let app = app
+        .route_layer(middleware::from_fn_with_state(
+            shared_state.clone(),
+            auth::require,
+        ))
+        // Outside authentication so rejected requests are recorded too
+        .route_layer(middleware::from_fn_with_state(
+            shared_state.clone(),
+            audit::record,
+        ))
+        // Every request counts against the client's rate limit, unknown routes included
+        .layer(middleware::from_fn_with_state(
+            shared_state.clone(),
+            limits::limit,
+        ))
+        .with_state(shared_state.clone());
+    // Outermost so preflight requests and 401 responses carry the CORS headers too
+    let app = match shared_state.config.cors.layer() {
+        Ok(Some(cors)) => {
+            info!(
+                "CORS enabled for {:?}.",
+                shared_state.config.cors.allowed_origins
+            );
+            app.layer(cors)
+        }
+        Ok(None) => app,
+        Err(e) => {
+            error!("{:#}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let addresses = shared_state.config.bind_addresses().unwrap_or_else(|e| {
+        error!("{:#}", e);
+        std::process::exit(1);
+    });
+    let tls_config = &shared_state.config.tls;
+    let rustls = if tls_config.enabled {
+        Some(tls::load(tls_config).await.unwrap_or_else(|e| {
+            error!("{:#}", e);
+            std::process::exit(1);
+        }))
+    } else {
+        None
+    };
+
+    // One server per address; a failure to bind any of them stops the process
+    let mut handles = Vec::with_capacity(addresses.len());
+    let mut servers = Vec::with_capacity(addresses.len());
+    for addr in addresses {
+        let handle = axum_server::Handle::new();
+        handles.push(handle.clone());
+        let service = app
+            .clone()
+            .into_make_service_with_connect_info::<SocketAddr>();
+        let scheme = if rustls.is_some() { "https" } else { "http" };
+        let server = match &rustls {
+            Some(rustls) => tokio::spawn(
+                axum_server::bind_rustls(addr, rustls.clone())
+                    .handle(handle)
+                    .serve(service),
+            ),
+            None => tokio::spawn(axum_server::bind(addr).handle(handle).serve(service)),
+        };
+        info!("Listening on {}://{}", scheme, addr);
+        servers.push(tokio::spawn(async move {
+            if let Ok(Err(e)) = server.await {
+                error!("Failed to serve on {}: {}", addr, e);
+                std::process::exit(1);
+            }
+        }));
+    }
+    // systemd only counts the service as started once the self-test has passed
+    tokio::spawn(async move {
+        let passed = match self_test_result {
+            Some(result) => result.await.unwrap_or(false),
+            None => true,
+        };
+        daemon::started();
+        if passed {
+            systemd::ready("Ready to record");
+        } else {
+            systemd::status("Self-test failed; see GET /selftest");
+        }
+    });
+    tokio::spawn({
+        let state = shared_state.clone();
+        async move {
+            shutdown_signal(state).await;
+            for handle in handles {
+                handle.graceful_shutdown(None);
+            }
+        }
+    });
+    for server in servers {
+        let _ = server.await;
+    }
+
+    if let Some(pid_file) = &pid_file {
+        pid_file.remove();
+    }
+    let unfinished = shared_state.tasks.running_jobs();
+    if !unfinished.is_empty() {
+        // The blocking tasks would keep the runtime alive; exit without them.
+        warn!(
+            "Exiting before {} recording(s) finished finalizing: {:?}",
+            unfinished.len(),
+            unfinished
+        );
+        std::process::exit(1);
+    }
+    info!("Shutdown complete.");
+}
+
+async fn wait_for_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+// Stop every active recording and give them time to finalize before the server exits
+async fn shutdown_signal(state: Arc<AppState>) {
+    wait_for_signal().await;
+    info!("Shutdown signal received.");
+    state.warm_cameras.release();
+
+    let running = state.sessions.lock().unwrap().running();
+    let timeout = Duration::from_secs(state.config.shutdown_timeout_secs);
+    if running.is_empty() {
+        systemd::stopping("Shutting down", Duration::ZERO);
+        return;
+    }
+    systemd::stopping(
+        &format!("Finalizing {} recording(s)", running.len()),
+        timeout,
+    );
+
+    for session in &running {
+        // Sessions already finalizing just need to be waited for
+        let _ = session.request_stop();
+    }
+    info!(
+        "Waiting up to {}s for {} active recording(s) to finalize...",
+        timeout.as_secs(),
+        running.len()
+    );
+
+    if !state.tasks.join_jobs(timeout).await {
+        warn!("Timed out waiting for the recordings to finalize.");
+    }
+    // A recording that ended meanwhile may have reopened its cameras
+    state.warm_cameras.release();
 }
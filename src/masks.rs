@@ -0,0 +1,208 @@
+// src/masks.rs
+use crate::{ApiError, AppState};
+use anyhow::{Result, bail};
+use axum::{
+    Json,
+    extract::{Path, State},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+use tracing::info;
+use uuid::Uuid;
+
+// 흐리게 가리는 영역의 가우시안 블러 세기 (얼굴, 번호판을 알아볼 수 없을 만큼)
+const BLUR_SIGMA: u32 = 30;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaskStyle {
+    // 검은 사각형으로 채운다.
+    #[default]
+    Black,
+    // 그 영역만 흐리게 한다.
+    Blur,
+}
+
+// 가릴 사각형 하나. 해상도가 달라도 같은 곳을 가리도록 프레임 크기에 대한 비율(0.0 ~ 1.0)로 쓴다.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MaskZone {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    #[serde(default)]
+    pub style: MaskStyle,
+}
+
+impl MaskZone {
+    fn validate(&self, camera: u32) -> Result<()> {
+        let fraction = |value: f64| value.is_finite() && (0.0..=1.0).contains(&value);
+        if ![self.x, self.y, self.width, self.height]
+            .into_iter()
+            .all(fraction)
+        {
+            bail!(
+                "camera {} mask x, y, width and height must be between 0.0 and 1.0",
+                camera
+            );
+        }
+        if self.width == 0.0 || self.height == 0.0 {
+            bail!("camera {} mask width and height must be non-zero", camera);
+        }
+        if self.x + self.width > 1.0 || self.y + self.height > 1.0 {
+            bail!("camera {} mask must lie inside the frame", camera);
+        }
+        Ok(())
+    }
+
+    // label 은 그래프 안에서 겹치지 않도록 카메라와 영역 번호로 만든다.
+    fn filter(&self, label: &str) -> String {
+        let (x, y, w, h) = (self.x, self.y, self.width, self.height);
+        match self.style {
+            MaskStyle::Black => format!(
+                "drawbox=x=iw*{:.4}:y=ih*{:.4}:w=iw*{:.4}:h=ih*{:.4}:color=black:t=fill",
+                x, y, w, h
+            ),
+            // 영역을 잘라 흐리게 한 뒤 제자리에 겹친다.
+            MaskStyle::Blur => format!(
+                "split[{label}_base][{label}_area];\
+                 [{label}_area]crop=w=iw*{w:.4}:h=ih*{h:.4}:x=iw*{x:.4}:y=ih*{y:.4},\
+                 gblur=sigma={sigma}[{label}_blur];\
+                 [{label}_base][{label}_blur]overlay=x=main_w*{x:.4}:y=main_h*{y:.4}",
+                sigma = BLUR_SIGMA
+            ),
+        }
+    }
+}
+
+// 카메라 한 대에서 가릴 영역들. 녹화 파일과 모든 스트림, 스냅숏에 적용한다.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraMasks {
+    pub camera: u32,
+    pub zones: Vec<MaskZone>,
+}
+
+impl CameraMasks {
+    pub fn validate(&self) -> Result<()> {
+        for zone in &self.zones {
+            zone.validate(self.camera)?;
+        }
+        Ok(())
+    }
+
+    // 카메라 원래 해상도의 프레임에 거는 필터 (영역이 없으면 None). 입력 하나, 출력 하나인
+    // 체인이라 다른 입력 필터 앞에 ',' 로 이어 붙일 수 있다.
+    pub fn filter(&self) -> Option<String> {
+        if self.zones.is_empty() {
+            return None;
+        }
+        let chain: Vec<String> = self
+            .zones
+            .iter()
+            .enumerate()
+            .map(|(i, zone)| zone.filter(&format!("mask{}_{}", self.camera, i)))
+            .collect();
+        Some(chain.join(","))
+    }
+}
+
+// 지금 적용할 카메라별 가림 영역. recording.masks 로 시작하고 PUT 으로 바꾼다.
+// 새로 시작하는 녹화와 미리보기, 스냅숏은 항상 이 값을 쓴다.
+pub struct PrivacyMasks {
+    masks: Mutex<BTreeMap<u32, CameraMasks>>,
+}
+
+impl PrivacyMasks {
+    pub fn new(initial: &[CameraMasks]) -> Self {
+        Self {
+            masks: Mutex::new(
+                initial
+                    .iter()
+                    .map(|masks| (masks.camera, masks.clone()))
+                    .collect(),
+            ),
+        }
+    }
+
+    pub fn get(&self, camera: u32) -> CameraMasks {
+        self.masks
+            .lock()
+            .unwrap()
+            .get(&camera)
+            .cloned()
+            .unwrap_or(CameraMasks {
+                camera,
+                ..Default::default()
+            })
+    }
+
+    // 값이 바뀌었으면 true
+    fn set(&self, masks: CameraMasks) -> bool {
+        let mut all = self.masks.lock().unwrap();
+        let previous = if masks.zones.is_empty() {
+            all.remove(&masks.camera)
+        } else {
+            all.insert(masks.camera, masks.clone())
+        };
+        previous.map_or(Vec::new(), |previous| previous.zones) != masks.zones
+    }
+
+    pub fn all(&self) -> Vec<CameraMasks> {
+        self.masks.lock().unwrap().values().cloned().collect()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct MasksResponse {
+    #[serde(flatten)]
+    pub masks: CameraMasks,
+    // 시작할 때의 영역으로 끝까지 녹화하는 세션 (새 영역은 다음 녹화부터)
+    pub pending_sessions: Vec<Uuid>,
+}
+
+// GET /cameras/:id/masks
+pub async fn handle_get(
+    State(state): State<Arc<AppState>>,
+    Path(camera): Path<u32>,
+) -> Json<CameraMasks> {
+    Json(state.privacy_masks.get(camera))
+}
+
+// PUT /cameras/:id/masks - 영역 목록 전체를 바꾼다 (빈 목록이면 가리지 않는다).
+pub async fn handle_put(
+    State(state): State<Arc<AppState>>,
+    Path(camera): Path<u32>,
+    Json(zones): Json<Vec<MaskZone>>,
+) -> Result<Json<MasksResponse>, ApiError> {
+    let masks = CameraMasks { camera, zones };
+    masks
+        .validate()
+        .map_err(|e| ApiError::bad_request(e.to_string()))?;
+    let changed = state.privacy_masks.set(masks.clone());
+
+    let mut pending_sessions = Vec::new();
+    if changed {
+        info!(
+            "Camera {} privacy masks changed: {} zone(s)",
+            camera,
+            masks.zones.len()
+        );
+        let running = state.sessions.lock().unwrap().running();
+        for session in running {
+            if session.stats.lock().unwrap().cameras.contains(&camera) {
+                pending_sessions.push(session.id);
+            }
+        }
+        // 미리보기가 카메라를 직접 열고 있으면 닫아서 새 영역으로 다시 열게 한다.
+        state.streams.release_cameras();
+    }
+
+    Ok(Json(MasksResponse {
+        masks,
+        pending_sessions,
+    }))
+}
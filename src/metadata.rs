@@ -0,0 +1,233 @@
+// src/metadata.rs
+use crate::{
+    bookmarks::{self, Bookmark},
+    camera_handler::{CaptureFormat, RecordingStats},
+    recovery::Marker,
+    session::RecordingSession,
+};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+use uuid::Uuid;
+
+// 녹화 파일마다 같은 이름의 .json 으로 남기는 정보 (GET /recordings 에도 포함)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordingMetadata {
+    pub session_id: Uuid,
+    // 녹화를 시작할 때 정한 프로젝트
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    // 파일 첫 프레임과 마지막 프레임의 시각 (녹화 전 영상을 붙였으면 그만큼 앞당겨진다)
+    pub started_at: DateTime<Local>,
+    pub ended_at: DateTime<Local>,
+    pub cameras: Vec<u32>,
+    pub requested_fps: u32,
+    // 타임랩스로 마무리한 파일에서 프레임을 고른 간격 (초)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timelapse_interval_secs: Option<f64>,
+    // 타임랩스나 슬로 모션으로 마무리한 파일의 재생 FPS (requested_fps 는 촬영 FPS)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub playback_fps: Option<u32>,
+    // 아래 네 값은 세션 전체 기준 (분할 녹화면 모든 세그먼트의 합)
+    pub actual_fps: f64,
+    pub frame_count: u64,
+    pub dropped_frames: u64,
+    // constant_frame_rate 로 녹화했으면 빠진 칸을 채운 프레임 수
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duplicated_frames: Option<u64>,
+    // 카메라 한 대의 촬영 해상도
+    pub camera_width: u32,
+    pub camera_height: u32,
+    // 위 기본값과 다른 해상도/FPS 로 촬영한 카메라
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub camera_formats: BTreeMap<u32, CaptureFormat>,
+    // 파일의 해상도와 코덱. ffprobe 로 읽지 못하면 None
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub codec: Option<String>,
+    pub software_version: String,
+    // 서버가 녹화 중에 죽어 다음 시작 때 남은 스트림으로 살린 파일
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub recovered: bool,
+    // POST /recordings/{name}/clip 으로 잘라 낸 파일이면 원래 녹화의 이름
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clip_of: Option<String>,
+    // 이 파일 구간에 남긴 표시 (POST /recordings/current/marker)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bookmarks: Vec<Bookmark>,
+}
+
+impl RecordingMetadata {
+    pub fn new(
+        session: &RecordingSession,
+        stats: &RecordingStats,
+        path: &Path,
+        (started_at, ended_at): (DateTime<Local>, DateTime<Local>),
+    ) -> Self {
+        let probed = probe_video(path);
+        let timelapse = Some(&session.config.timelapse).filter(|timelapse| timelapse.enabled);
+        Self {
+            session_id: session.id,
+            project: session.config.project.clone(),
+            started_at,
+            ended_at,
+            cameras: stats.cameras.clone(),
+            requested_fps: session.config.fps,
+            timelapse_interval_secs: timelapse.map(|timelapse| timelapse.interval_secs),
+            playback_fps: session.config.playback_fps(),
+            actual_fps: stats.measured_fps,
+            frame_count: stats.frames_captured,
+            dropped_frames: stats.dropped_frames,
+            duplicated_frames: session
+                .config
+                .constant_frame_rate
+                .then_some(stats.duplicated_frames),
+            camera_width: session.config.width,
+            camera_height: session.config.height,
+            camera_formats: session
+                .config
+                .camera_formats
+                .iter()
+                .filter(|format| stats.cameras.contains(&format.camera))
+                .map(|format| (format.camera, session.config.format_for(format.camera)))
+                .collect(),
+            width: probed.as_ref().and_then(|video| video.width),
+            height: probed.as_ref().and_then(|video| video.height),
+            codec: probed.and_then(|video| video.codec_name),
+            software_version: env!("CARGO_PKG_VERSION").to_string(),
+            recovered: false,
+            clip_of: None,
+            bookmarks: bookmarks::in_file(
+                &session.bookmarks.lock().unwrap(),
+                (started_at, ended_at),
+            ),
+        }
+    }
+
+    // 중단된 녹화에서 살린 카메라 한 대의 파일. 세션 통계가 없으므로 프레임 수와 FPS 는
+    // 타임스탬프 파일에서 센 값이고, 빠진 프레임은 알 수 없어 0 이다.
+    pub fn recovered(
+        marker: &Marker,
+        camera: u32,
+        (frame_count, actual_fps): (u64, f64),
+        path: &Path,
+        (started_at, ended_at): (DateTime<Local>, DateTime<Local>),
+    ) -> Self {
+        let probed = probe_video(path);
+        let default_format = CaptureFormat {
+            width: marker.camera_width,
+            height: marker.camera_height,
+            fps: marker.requested_fps,
+        };
+        Self {
+            session_id: marker.session_id,
+            project: marker.project.clone(),
+            started_at,
+            ended_at,
+            cameras: vec![camera],
+            requested_fps: marker.requested_fps,
+            timelapse_interval_secs: None,
+            playback_fps: None,
+            actual_fps,
+            frame_count,
+            dropped_frames: 0,
+            duplicated_frames: None,
+            camera_width: marker.camera_width,
+            camera_height: marker.camera_height,
+            camera_formats: marker
+                .formats
+                .get(&camera)
+                .filter(|format| **format != default_format)
+                .map(|format| (camera, *format))
+                .into_iter()
+                .collect(),
+            width: probed.as_ref().and_then(|video| video.width),
+            height: probed.as_ref().and_then(|video| video.height),
+            codec: probed.and_then(|video| video.codec_name),
+            software_version: env!("CARGO_PKG_VERSION").to_string(),
+            recovered: true,
+            clip_of: None,
+            bookmarks: Vec::new(),
+        }
+    }
+
+    // name 의 start ~ end 초를 잘라 낸 path 의 사이드카. 프레임 수 같은 세션 통계는 원래 녹화의 값이다.
+    pub fn clip(&self, name: &str, (start, end): (f64, f64), path: &Path) -> Self {
+        let at = |secs: f64| {
+            self.started_at + chrono::Duration::milliseconds((secs * 1000.0).round() as i64)
+        };
+        let (started_at, ended_at) = (at(start), at(end).min(self.ended_at));
+        let probed = probe_video(path);
+        Self {
+            started_at,
+            ended_at,
+            width: probed.as_ref().and_then(|video| video.width).or(self.width),
+            height: probed
+                .as_ref()
+                .and_then(|video| video.height)
+                .or(self.height),
+            codec: probed
+                .and_then(|video| video.codec_name)
+                .or_else(|| self.codec.clone()),
+            clip_of: Some(name.to_string()),
+            bookmarks: bookmarks::in_file(&self.bookmarks, (started_at, ended_at)),
+            ..self.clone()
+        }
+    }
+}
+
+pub fn sidecar_path(video: &Path) -> PathBuf {
+    video.with_extension("json")
+}
+
+pub fn write(video: &Path, metadata: &RecordingMetadata) -> Result<()> {
+    let path = sidecar_path(video);
+    let json = serde_json::to_vec_pretty(metadata)?;
+    fs::write(&path, json).with_context(|| format!("Failed to write {:?}", path))
+}
+
+// 사이드카가 없거나 읽을 수 없으면 None
+pub fn read(video: &Path) -> Option<RecordingMetadata> {
+    let contents = fs::read(sidecar_path(video)).ok()?;
+    serde_json::from_slice(&contents).ok()
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbedStream {
+    codec_name: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeOutput {
+    #[serde(default)]
+    streams: Vec<ProbedStream>,
+}
+
+// ffprobe 로 첫 영상 스트림의 코덱과 해상도를 읽는다.
+fn probe_video(path: &Path) -> Option<ProbedStream> {
+    let output = Command::new("ffprobe")
+        .arg("-v")
+        .arg("error")
+        .arg("-select_streams")
+        .arg("v:0")
+        .arg("-show_entries")
+        .arg("stream=codec_name,width,height")
+        .arg("-of")
+        .arg("json")
+        .arg(path)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let probe: ProbeOutput = serde_json::from_slice(&output.stdout).ok()?;
+    probe.streams.into_iter().next()
+}
@@ -0,0 +1,461 @@
+// src/motion.rs
+use crate::{
+    AppState, StartRequest, audit,
+    compositor::FFMPEG,
+    events::EventKind,
+    scaling::Scaling,
+    session::RecordingSession,
+    start_recording,
+    stream::{StreamHub, Subscription, View},
+};
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    process::{Child, ChildStdin, Stdio},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+use tokio::sync::{broadcast, mpsc};
+use tracing::{info, warn};
+
+// 분석용 ffmpeg 가 끝났을 때 다시 시작하기까지 기다리는 시간
+const DECODER_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+// 움직임이 없어도 이 주기로 녹화를 멈출 때가 되었는지 확인한다.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+// 프레임 안의 사각형 영역 (프레임 크기에 대한 비율, 0.0 - 1.0)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Region {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Region {
+    fn validate(&self) -> Result<()> {
+        let valid = [self.x, self.y, self.width, self.height]
+            .iter()
+            .all(|value| (0.0..=1.0).contains(value))
+            && self.width > 0.0
+            && self.height > 0.0
+            && self.x + self.width <= 1.0
+            && self.y + self.height <= 1.0;
+        if !valid {
+            bail!("motion region must lie within the frame (fractions between 0 and 1)");
+        }
+        Ok(())
+    }
+
+    fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+// 카메라 한 대의 감도와 감시 영역
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MotionCameraConfig {
+    pub camera: u32,
+    // 배경과 밝기(0-255)가 이보다 많이 다른 화소를 움직인 화소로 본다.
+    pub pixel_threshold: u8,
+    // 감시 영역에서 움직인 화소가 이 비율(%)을 넘으면 움직임으로 본다. 낮을수록 민감하다.
+    pub min_area_percent: f64,
+    // 이 영역 안만 본다 (비어 있으면 프레임 전체)
+    pub regions: Vec<Region>,
+    // 이 영역은 보지 않는다 (나뭇가지, 시계 등)
+    pub ignore_regions: Vec<Region>,
+}
+
+impl Default for MotionCameraConfig {
+    fn default() -> Self {
+        Self {
+            camera: 0,
+            pixel_threshold: 25,
+            min_area_percent: 1.0,
+            regions: Vec::new(),
+            ignore_regions: Vec::new(),
+        }
+    }
+}
+
+impl MotionCameraConfig {
+    fn validate(&self) -> Result<()> {
+        if !self.min_area_percent.is_finite()
+            || self.min_area_percent <= 0.0
+            || self.min_area_percent > 100.0
+        {
+            bail!("motion min_area_percent must be between 0 and 100");
+        }
+        for region in self.regions.iter().chain(&self.ignore_regions) {
+            region.validate()?;
+        }
+        Ok(())
+    }
+
+    // 분석 프레임의 화소마다 감시 대상인지
+    fn mask(&self, width: u32, height: u32) -> Vec<bool> {
+        let mut mask = Vec::with_capacity((width * height) as usize);
+        for row in 0..height {
+            for column in 0..width {
+                let x = (column as f64 + 0.5) / width as f64;
+                let y = (row as f64 + 0.5) / height as f64;
+                let included = self.regions.is_empty()
+                    || self.regions.iter().any(|region| region.contains(x, y));
+                let ignored = self
+                    .ignore_regions
+                    .iter()
+                    .any(|region| region.contains(x, y));
+                mask.push(included && !ignored);
+            }
+        }
+        mask
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MotionConfig {
+    // true 면 움직임이 감지될 때 기본 설정으로 녹화를 시작한다.
+    pub enabled: bool,
+    // 분석용 미리보기의 프레임 속도와 크기 (녹화 화질과는 무관)
+    pub fps: u32,
+    pub width: u32,
+    pub height: u32,
+    // 연속으로 이만큼의 분석 프레임에서 움직임이 보여야 녹화를 시작한다 (순간적인 잡음 무시).
+    pub trigger_frames: u32,
+    // 움직임이 멈춘 뒤 이 시간(초) 동안 조용하면 녹화를 멈춘다.
+    pub stop_after_secs: u64,
+    // 배경이 현재 프레임을 따라가는 속도 (0 - 1, 클수록 천천히 움직이는 물체를 놓친다)
+    pub learning_rate: f64,
+    // 감시할 카메라별 설정 (비어 있으면 recording.cameras 전체를 기본값으로 감시)
+    pub cameras: Vec<MotionCameraConfig>,
+}
+
+impl Default for MotionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            fps: 5,
+            width: 160,
+            height: 120,
+            trigger_frames: 2,
+            stop_after_secs: 10,
+            learning_rate: 0.05,
+            cameras: Vec::new(),
+        }
+    }
+}
+
+impl MotionConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.fps == 0 {
+            bail!("motion.fps must be non-zero");
+        }
+        if self.width == 0 || self.height == 0 {
+            bail!("motion.width and motion.height must be non-zero");
+        }
+        if self.trigger_frames == 0 {
+            bail!("motion.trigger_frames must be non-zero");
+        }
+        if !(self.learning_rate > 0.0 && self.learning_rate <= 1.0) {
+            bail!("motion.learning_rate must be in (0, 1]");
+        }
+        let mut cameras: Vec<u32> = self.cameras.iter().map(|camera| camera.camera).collect();
+        cameras.sort_unstable();
+        cameras.dedup();
+        if cameras.len() != self.cameras.len() {
+            bail!("motion.cameras must not contain duplicates");
+        }
+        for camera in &self.cameras {
+            camera.validate()?;
+        }
+        Ok(())
+    }
+
+    fn watched(&self, defaults: &[u32]) -> Vec<MotionCameraConfig> {
+        if !self.cameras.is_empty() {
+            return self.cameras.clone();
+        }
+        defaults
+            .iter()
+            .map(|&camera| MotionCameraConfig {
+                camera,
+                ..Default::default()
+            })
+            .collect()
+    }
+}
+
+// 분석 프레임 하나의 결과
+struct Sample {
+    camera: u32,
+    changed_percent: f64,
+    moving: bool,
+}
+
+// 화소마다 천천히 따라가는 평균을 배경으로 두고 현재 프레임과 비교한다.
+struct BackgroundModel {
+    background: Vec<f32>,
+    mask: Vec<bool>,
+    watched: usize,
+    pixel_threshold: f32,
+    learning_rate: f32,
+}
+
+impl BackgroundModel {
+    fn new(config: &MotionCameraConfig, width: u32, height: u32, learning_rate: f64) -> Self {
+        let mask = config.mask(width, height);
+        Self {
+            background: Vec::new(),
+            watched: mask.iter().filter(|&&watched| watched).count(),
+            mask,
+            pixel_threshold: config.pixel_threshold as f32,
+            learning_rate: learning_rate as f32,
+        }
+    }
+
+    // 감시 영역에서 움직인 화소의 비율 (%)
+    fn update(&mut self, frame: &[u8]) -> f64 {
+        if self.background.len() != frame.len() {
+            self.background = frame.iter().map(|&value| value as f32).collect();
+            return 0.0;
+        }
+        let mut changed = 0usize;
+        for ((background, &value), &watched) in
+            self.background.iter_mut().zip(frame).zip(&self.mask)
+        {
+            let value = value as f32;
+            if watched && (value - *background).abs() > self.pixel_threshold {
+                changed += 1;
+            }
+            *background += (value - *background) * self.learning_rate;
+        }
+        if self.watched == 0 {
+            return 0.0;
+        }
+        changed as f64 * 100.0 / self.watched as f64
+    }
+}
+
+// 감시를 시작한다 (설정에서 켠 경우에만). 카메라마다 분석 스레드를 두고,
+// 결과를 모아 녹화를 시작/정지하는 작업은 tokio 에서 돈다.
+pub fn spawn(state: Arc<AppState>) -> Result<()> {
+    let config = &state.config.motion;
+    if !config.enabled {
+        return Ok(());
+    }
+    let (sender, receiver) = mpsc::channel(64);
+    for camera in config.watched(&state.config.recording.cameras) {
+        let hub = state.streams.clone();
+        let config = config.clone();
+        let sender = sender.clone();
+        thread::Builder::new()
+            .name(format!("motion-cam{}", camera.camera))
+            .spawn(move || watch_camera(&hub, &config, &camera, &sender))
+            .context("Failed to start motion detection thread")?;
+    }
+    tokio::spawn(control(state, receiver));
+    Ok(())
+}
+
+fn watch_camera(
+    hub: &StreamHub,
+    config: &MotionConfig,
+    camera: &MotionCameraConfig,
+    sender: &mpsc::Sender<Sample>,
+) {
+    info!(
+        "Watching camera {} for motion ({:.1}% of the watched area).",
+        camera.camera, camera.min_area_percent
+    );
+    loop {
+        if let Err(e) = analyze(hub, config, camera, sender) {
+            warn!(
+                "Motion detection for camera {} stopped: {:#}",
+                camera.camera, e
+            );
+        }
+        if sender.is_closed() {
+            return;
+        }
+        thread::sleep(DECODER_RETRY_DELAY);
+    }
+}
+
+// 카메라 원본 스트림을 ffmpeg 로 작은 흑백 프레임으로 풀어 한 장씩 비교한다.
+fn analyze(
+    hub: &StreamHub,
+    config: &MotionConfig,
+    camera: &MotionCameraConfig,
+    sender: &mpsc::Sender<Sample>,
+) -> Result<()> {
+    let mut decoder = std::process::Command::new(FFMPEG)
+        .arg("-loglevel")
+        .arg("error")
+        .arg("-f")
+        .arg("h264")
+        .arg("-i")
+        .arg("pipe:0")
+        .arg("-vf")
+        .arg(format!(
+            "fps={},{},format=gray",
+            config.fps,
+            Scaling::stretch(config.width, config.height).filter()
+        ))
+        .arg("-f")
+        .arg("rawvideo")
+        .arg("pipe:1")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("Failed to start ffmpeg for motion detection")?;
+    let stdin = decoder.stdin.take().context("ffmpeg stdin unavailable")?;
+    let mut stdout = decoder.stdout.take().context("ffmpeg stdout unavailable")?;
+
+    let subscription = hub.subscribe(View::Camera(camera.camera));
+    thread::Builder::new()
+        .name(format!("motion-feed{}", camera.camera))
+        .spawn(move || feed_decoder(subscription, stdin))
+        .context("Failed to start motion feed thread")?;
+
+    let mut model = BackgroundModel::new(camera, config.width, config.height, config.learning_rate);
+    let mut frame = vec![0u8; (config.width * config.height) as usize];
+    let result = loop {
+        if let Err(e) = stdout.read_exact(&mut frame) {
+            break Err(e).context("Motion decoder ended");
+        }
+        let changed_percent = model.update(&frame);
+        let sample = Sample {
+            camera: camera.camera,
+            changed_percent,
+            moving: changed_percent >= camera.min_area_percent,
+        };
+        if sender.blocking_send(sample).is_err() {
+            break Ok(());
+        }
+    };
+    kill(&mut decoder);
+    result
+}
+
+// 녹화 세션이 바뀌어도 같은 구독으로 계속 받는다. ffmpeg 가 끝나면 쓰기가 실패해 멈춘다.
+fn feed_decoder(mut subscription: Subscription, mut stdin: ChildStdin) {
+    let mut waiting_for_keyframe = true;
+    loop {
+        let unit = match subscription.units.blocking_recv() {
+            Ok(unit) => unit,
+            Err(broadcast::error::RecvError::Lagged(_)) => {
+                waiting_for_keyframe = true;
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        if waiting_for_keyframe {
+            if !unit.keyframe {
+                continue;
+            }
+            waiting_for_keyframe = false;
+        }
+        for nal in &unit.nals {
+            if stdin
+                .write_all(&[0, 0, 0, 1])
+                .and_then(|_| stdin.write_all(nal))
+                .is_err()
+            {
+                return;
+            }
+        }
+    }
+}
+
+fn kill(child: &mut Child) {
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+// 움직임이 시작되면 녹화를 시작하고, stop_after_secs 동안 조용하면 멈춘다.
+// 직접 시작한 녹화만 멈춘다 (/start 나 예약으로 시작한 녹화는 건드리지 않는다).
+async fn control(state: Arc<AppState>, mut samples: mpsc::Receiver<Sample>) {
+    let config = &state.config.motion;
+    let stop_after = Duration::from_secs(config.stop_after_secs);
+    let mut streaks: HashMap<u32, u32> = HashMap::new();
+    let mut recording: Option<Arc<RecordingSession>> = None;
+    let mut last_motion: Option<Instant> = None;
+
+    loop {
+        let sample = match tokio::time::timeout(CHECK_INTERVAL, samples.recv()).await {
+            Ok(Some(sample)) => Some(sample),
+            Ok(None) => return,
+            Err(_) => None,
+        };
+
+        if recording
+            .as_ref()
+            .is_some_and(|session| !session.is_running())
+        {
+            recording = None;
+        }
+
+        if let Some(sample) = sample {
+            let streak = streaks.entry(sample.camera).or_default();
+            *streak = if sample.moving { *streak + 1 } else { 0 };
+            if *streak >= config.trigger_frames {
+                last_motion = Some(Instant::now());
+                if recording.is_none() && !state.sessions.lock().unwrap().any_running() {
+                    recording = start(&state, &sample).await;
+                }
+            }
+        }
+
+        if let Some(session) = &recording {
+            let still = last_motion.is_none_or(|at| at.elapsed() >= stop_after);
+            if still {
+                info!(
+                    session_id = %session.id,
+                    "No motion for {}s. Stopping the recording.", config.stop_after_secs
+                );
+                // 이미 마무리 중이면 그대로 둔다.
+                let _ = session.request_stop();
+                recording = None;
+            }
+        }
+    }
+}
+
+async fn start(state: &Arc<AppState>, sample: &Sample) -> Option<Arc<RecordingSession>> {
+    info!(
+        "Motion on camera {} ({:.1}% changed). Starting a recording.",
+        sample.camera, sample.changed_percent
+    );
+    let started = start_recording(state.clone(), StartRequest::default()).await;
+    audit::record_command(
+        state,
+        "motion",
+        format!("start (camera {})", sample.camera),
+        started.as_ref().map(|response| Some(response.0.session_id)),
+    );
+    match started {
+        Ok(response) => {
+            let session_id = response.0.session_id;
+            state.events.publish(
+                session_id,
+                EventKind::MotionDetected {
+                    camera: sample.camera,
+                    changed_percent: sample.changed_percent,
+                },
+            );
+            state.sessions.lock().unwrap().get(session_id)
+        }
+        Err(e) => {
+            let message = format!("Motion-triggered recording could not start: {}", e.message);
+            warn!("{}", message);
+            state.errors.record(None, message);
+            None
+        }
+    }
+}
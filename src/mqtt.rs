@@ -0,0 +1,476 @@
+// src/mqtt.rs
+use crate::{
+    AppState, SessionTarget, StartRequest, audit, auth, events::Event, start_recording,
+    stop_recording,
+};
+use anyhow::{Context, Result, bail};
+use rustls::pki_types::{CertificateDer, ServerName, pem::PemObject};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{sync::Arc, time::Duration};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf},
+    net::TcpStream,
+    sync::{broadcast, mpsc},
+};
+use tokio_rustls::TlsConnector;
+use tracing::{info, warn};
+
+// MQTT 3.1.1 고정 헤더의 패킷 종류 (상위 4비트)
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+const SUBSCRIBE: u8 = 0x82;
+const PINGREQ: u8 = 0xc0;
+
+// 명령 페이로드는 /start 요청 본문 정도이므로 이보다 큰 패킷은 연결 오류로 본다.
+const MAX_PACKET: usize = 1024 * 1024;
+
+// 브로커와의 연결 (평문 TCP 또는 TLS)
+trait Connection: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Connection for T {}
+
+type Reader = ReadHalf<Box<dyn Connection>>;
+type Writer = WriteHalf<Box<dyn Connection>>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MqttConfig {
+    // true 면 브로커에 접속해 명령을 받고 상태와 이벤트를 보낸다.
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    pub client_id: String,
+    pub username: Option<String>,
+    // /config 에는 노출하지 않는다.
+    #[serde(skip_serializing)]
+    pub password: Option<String>,
+    // true 면 TLS 로 접속한다 (보통 port = 8883).
+    pub tls: bool,
+    // 브로커 인증서를 확인할 CA (PEM). 없으면 공개 루트 인증서로 확인한다.
+    pub ca_file: Option<String>,
+    // start, stop 명령 페이로드의 "secret" 이 이것과 같아야 한다. /config 에는 노출하지 않는다.
+    // 없으면 tls 와 username 으로 브로커에 인증한 연결에서만 명령을 받는다.
+    #[serde(skip_serializing)]
+    pub command_secret: Option<String>,
+    // <prefix>/start, <prefix>/stop 을 구독하고 <prefix>/state, <prefix>/availability,
+    // <prefix>/events 로 보낸다.
+    pub topic_prefix: String,
+    pub keep_alive_secs: u16,
+    // 연결이 끊기면 이만큼 기다렸다가 다시 접속한다.
+    pub reconnect_secs: u64,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: "localhost".to_string(),
+            port: 1883,
+            client_id: "recorder".to_string(),
+            username: None,
+            password: None,
+            tls: false,
+            ca_file: None,
+            command_secret: None,
+            topic_prefix: "recorder".to_string(),
+            keep_alive_secs: 30,
+            reconnect_secs: 5,
+        }
+    }
+}
+
+impl MqttConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        if self.host.trim().is_empty() {
+            bail!("mqtt.host must not be empty");
+        }
+        if self.client_id.trim().is_empty() {
+            bail!("mqtt.client_id must not be empty");
+        }
+        let prefix = &self.topic_prefix;
+        if prefix.is_empty() || prefix.ends_with('/') || prefix.contains(['+', '#']) {
+            bail!(
+                "mqtt.topic_prefix {:?} must be a non-empty topic without wildcards or a trailing '/'",
+                prefix
+            );
+        }
+        if self.password.is_some() && self.username.is_none() {
+            bail!("mqtt.password requires mqtt.username");
+        }
+        if self.ca_file.is_some() && !self.tls {
+            bail!("mqtt.ca_file requires mqtt.tls");
+        }
+        if self
+            .command_secret
+            .as_ref()
+            .is_some_and(|secret| secret.trim().is_empty())
+        {
+            bail!("mqtt.command_secret must not be empty");
+        }
+        if self.keep_alive_secs == 0 {
+            bail!("mqtt.keep_alive_secs must be at least 1");
+        }
+        if self.reconnect_secs == 0 {
+            bail!("mqtt.reconnect_secs must be at least 1");
+        }
+        Ok(())
+    }
+
+    fn topic(&self, name: &str) -> String {
+        format!("{}/{}", self.topic_prefix, name)
+    }
+
+    // 아무나 브로커에 보낼 수 있는 연결 (평문이거나 익명) 이면 명령을 받지 않는다.
+    fn accepts_commands(&self) -> bool {
+        self.command_secret.is_some() || (self.tls && self.username.is_some())
+    }
+}
+
+// 브로커 연결을 유지하는 작업을 시작한다 (설정에서 켠 경우에만).
+pub fn spawn(state: Arc<AppState>) {
+    if !state.config.mqtt.enabled {
+        return;
+    }
+    let tasks = state.tasks.clone();
+    tasks.spawn_service("mqtt", move || maintain(state.clone()));
+}
+
+// 접속해 있지 않은 동안의 이벤트는 버리고, 다시 접속하면 현재 상태부터 보낸다.
+async fn maintain(state: Arc<AppState>) {
+    let config = &state.config.mqtt;
+    loop {
+        match run(&state).await {
+            Ok(()) => info!("MQTT broker closed the connection."),
+            Err(e) => warn!(
+                "MQTT connection to {}:{}: {:#}",
+                config.host, config.port, e
+            ),
+        }
+        tokio::time::sleep(Duration::from_secs(config.reconnect_secs)).await;
+    }
+}
+
+async fn run(state: &Arc<AppState>) -> Result<()> {
+    let config = &state.config.mqtt;
+    let address = format!("{}:{}", config.host, config.port);
+    let (mut read, mut write) = tokio::io::split(connect(config, &address).await?);
+
+    write.write_all(&connect_packet(config)?).await?;
+    let (kind, body) = read_packet(&mut read).await?;
+    if kind & 0xf0 != CONNACK || body.len() < 2 {
+        bail!("Expected CONNACK from the broker");
+    }
+    if body[1] != 0 {
+        bail!("Broker refused the connection (return code {})", body[1]);
+    }
+    info!("Connected to MQTT broker {}.", address);
+
+    let start_topic = config.topic("start");
+    let stop_topic = config.topic("stop");
+    if config.accepts_commands() {
+        write
+            .write_all(&subscribe_packet(&[&start_topic, &stop_topic])?)
+            .await?;
+    } else {
+        warn!(
+            "MQTT start/stop commands are off; set mqtt.command_secret, or mqtt.tls with a username."
+        );
+    }
+    publish(&mut write, &config.topic("availability"), b"online", true).await?;
+    let recording = state.sessions.lock().unwrap().any_running();
+    publish_state(&mut write, config, recording).await?;
+
+    let mut events = state.events.subscribe();
+    let (sender, mut packets) = mpsc::channel(16);
+    let reader = tokio::spawn(read_packets(read, sender));
+    let keep_alive = Duration::from_secs(config.keep_alive_secs as u64);
+    let mut ping = tokio::time::interval_at(tokio::time::Instant::now() + keep_alive, keep_alive);
+
+    let result = loop {
+        tokio::select! {
+            packet = packets.recv() => match packet {
+                Some(Ok((topic, payload))) => {
+                    if topic == start_topic {
+                        tokio::spawn(handle_start(state.clone(), payload));
+                    } else if topic == stop_topic {
+                        tokio::spawn(handle_stop(state.clone(), payload));
+                    }
+                }
+                Some(Err(e)) => break Err(e),
+                None => break Ok(()),
+            },
+            event = events.recv() => match event {
+                Ok(event) => {
+                    if let Err(e) = publish_event(&mut write, state, &event).await {
+                        break Err(e);
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("MQTT publisher fell behind; {} events were dropped.", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => break Ok(()),
+            },
+            _ = ping.tick() => {
+                if let Err(e) = write.write_all(&[PINGREQ, 0]).await {
+                    break Err(e.into());
+                }
+            }
+        }
+    };
+    reader.abort();
+    result
+}
+
+async fn connect(config: &MqttConfig, address: &str) -> Result<Box<dyn Connection>> {
+    let stream = TcpStream::connect(address)
+        .await
+        .with_context(|| format!("Failed to connect to {}", address))?;
+    if !config.tls {
+        return Ok(Box::new(stream));
+    }
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    let mut roots = rustls::RootCertStore::empty();
+    match &config.ca_file {
+        Some(ca_file) => {
+            let ca_file = crate::config::expand_path(ca_file);
+            for cert in CertificateDer::pem_file_iter(&ca_file)
+                .with_context(|| format!("Failed to read {:?}", ca_file))?
+            {
+                roots
+                    .add(cert.with_context(|| format!("Invalid certificate in {:?}", ca_file))?)?;
+            }
+        }
+        None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+    }
+    let tls = rustls::ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let name = ServerName::try_from(config.host.clone())
+        .with_context(|| format!("mqtt.host {:?} is not a valid TLS name", config.host))?;
+    let stream = TlsConnector::from(Arc::new(tls))
+        .connect(name, stream)
+        .await
+        .with_context(|| format!("TLS handshake with {} failed", address))?;
+    Ok(Box::new(stream))
+}
+
+// 명령 페이로드 (JSON 객체, 비어 있으면 본문 없이 부른 것과 같다) 에서 "secret" 을 확인하고 떼어 낸다.
+fn command_body(config: &MqttConfig, payload: &[u8]) -> Result<Value, String> {
+    let mut body = if payload.iter().all(u8::is_ascii_whitespace) {
+        Value::Object(Default::default())
+    } else {
+        serde_json::from_slice(payload).map_err(|e| format!("Invalid payload: {}", e))?
+    };
+    let secret = body.as_object_mut().and_then(|body| body.remove("secret"));
+    if let Some(expected) = &config.command_secret {
+        let given = secret.as_ref().and_then(Value::as_str).unwrap_or_default();
+        if auth::position(std::iter::once(expected.as_str()), given).is_none() {
+            return Err("missing or wrong secret".to_string());
+        }
+    }
+    Ok(body)
+}
+
+async fn handle_start(state: Arc<AppState>, payload: Vec<u8>) {
+    let request = command_body(&state.config.mqtt, &payload).and_then(|body| {
+        serde_json::from_value::<StartRequest>(body)
+            .map_err(|e| format!("Invalid start payload: {}", e))
+    });
+    let result = match request {
+        Ok(request) => {
+            let started = start_recording(state.clone(), request).await;
+            audit::record_command(
+                &state,
+                "mqtt",
+                "start",
+                started.as_ref().map(|response| Some(response.0.session_id)),
+            );
+            started
+                .map(|response| {
+                    info!(
+                        "MQTT start command began session {}.",
+                        response.0.session_id
+                    )
+                })
+                .map_err(|e| e.message)
+        }
+        Err(e) => Err(e),
+    };
+    if let Err(message) = result {
+        let message = format!("MQTT start command failed: {}", message);
+        warn!("{}", message);
+        state.errors.record(None, message);
+    }
+}
+
+async fn handle_stop(state: Arc<AppState>, payload: Vec<u8>) {
+    let target = command_body(&state.config.mqtt, &payload).and_then(|body| {
+        serde_json::from_value::<SessionTarget>(body)
+            .map_err(|e| format!("Invalid stop payload: {}", e))
+    });
+    let result = match target {
+        Ok(target) => {
+            let session_id = target.session_id;
+            let stopped = stop_recording(state.clone(), target, false).await;
+            audit::record_command(&state, "mqtt", "stop", stopped.as_ref().map(|_| session_id));
+            stopped
+                .map(|_| info!("MQTT stop command sent."))
+                .map_err(|e| e.message)
+        }
+        Err(e) => Err(e),
+    };
+    if let Err(message) = result {
+        let message = format!("MQTT stop command failed: {}", message);
+        warn!("{}", message);
+        state.errors.record(None, message);
+    }
+}
+
+// 모든 이벤트는 <prefix>/events 로, 녹화 시작/종료는 <prefix>/state 에도 반영한다.
+async fn publish_event(write: &mut Writer, state: &AppState, event: &Event) -> Result<()> {
+    let config = &state.config.mqtt;
+    let json = serde_json::to_vec(event)?;
+    publish(write, &config.topic("events"), &json, false).await?;
+    let recording = event.recording_after(&state.sessions.lock().unwrap());
+    match recording {
+        Some(recording) => publish_state(write, config, recording).await,
+        None => Ok(()),
+    }
+}
+
+async fn publish_state(write: &mut Writer, config: &MqttConfig, recording: bool) -> Result<()> {
+    let state: &[u8] = if recording { b"recording" } else { b"idle" };
+    publish(write, &config.topic("state"), state, true).await
+}
+
+async fn publish(write: &mut Writer, topic: &str, payload: &[u8], retain: bool) -> Result<()> {
+    let mut body = Vec::with_capacity(topic.len() + payload.len() + 2);
+    put_string(&mut body, topic.as_bytes())?;
+    body.extend_from_slice(payload);
+    let kind = if retain { PUBLISH | 0x01 } else { PUBLISH };
+    write.write_all(&packet(kind, &body)).await?;
+    Ok(())
+}
+
+// 깨끗한 세션으로 접속하고, 연결이 끊기면 브로커가 availability 에 offline 을 남기게 한다.
+fn connect_packet(config: &MqttConfig) -> Result<Vec<u8>> {
+    let mut flags = 0x02 | 0x04 | 0x20;
+    if config.username.is_some() {
+        flags |= 0x80;
+    }
+    if config.password.is_some() {
+        flags |= 0x40;
+    }
+    let mut body = Vec::new();
+    put_string(&mut body, b"MQTT")?;
+    body.push(4);
+    body.push(flags);
+    body.extend_from_slice(&config.keep_alive_secs.to_be_bytes());
+    put_string(&mut body, config.client_id.as_bytes())?;
+    put_string(&mut body, config.topic("availability").as_bytes())?;
+    put_string(&mut body, b"offline")?;
+    if let Some(username) = &config.username {
+        put_string(&mut body, username.as_bytes())?;
+    }
+    if let Some(password) = &config.password {
+        put_string(&mut body, password.as_bytes())?;
+    }
+    Ok(packet(CONNECT, &body))
+}
+
+fn subscribe_packet(topics: &[&str]) -> Result<Vec<u8>> {
+    let mut body = vec![0, 1];
+    for topic in topics {
+        put_string(&mut body, topic.as_bytes())?;
+        // QoS 0
+        body.push(0);
+    }
+    Ok(packet(SUBSCRIBE, &body))
+}
+
+fn packet(kind: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![kind];
+    let mut remaining = body.len();
+    loop {
+        let mut byte = (remaining % 128) as u8;
+        remaining /= 128;
+        if remaining > 0 {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if remaining == 0 {
+            break;
+        }
+    }
+    packet.extend_from_slice(body);
+    packet
+}
+
+fn put_string(body: &mut Vec<u8>, value: &[u8]) -> Result<()> {
+    let length = u16::try_from(value.len()).context("MQTT string is longer than 65535 bytes")?;
+    body.extend_from_slice(&length.to_be_bytes());
+    body.extend_from_slice(value);
+    Ok(())
+}
+
+async fn read_packet(read: &mut Reader) -> Result<(u8, Vec<u8>)> {
+    let kind = read.read_u8().await.context("Connection closed")?;
+    let mut length = 0usize;
+    for shift in 0..4 {
+        let byte = read.read_u8().await?;
+        length |= ((byte & 0x7f) as usize) << (7 * shift);
+        if byte & 0x80 == 0 {
+            break;
+        }
+        if shift == 3 {
+            bail!("Malformed MQTT packet length");
+        }
+    }
+    if length > MAX_PACKET {
+        bail!("MQTT packet of {} bytes is too large", length);
+    }
+    let mut body = vec![0u8; length];
+    read.read_exact(&mut body).await?;
+    Ok((kind, body))
+}
+
+// 브로커가 보내는 패킷 중 구독한 PUBLISH 만 (토픽, 페이로드) 로 넘긴다.
+// SUBACK, PINGRESP 같은 나머지는 버린다. 보관된 (RETAIN) 명령은 접속할 때마다 다시 오므로 버린다.
+async fn read_packets(mut read: Reader, packets: mpsc::Sender<Result<(String, Vec<u8>)>>) {
+    loop {
+        let message = match read_packet(&mut read).await {
+            Ok((kind, _)) if kind & 0xf0 == PUBLISH && kind & 0x01 != 0 => continue,
+            Ok((kind, body)) if kind & 0xf0 == PUBLISH => parse_publish(kind, &body),
+            Ok(_) => continue,
+            Err(e) => {
+                let _ = packets.send(Err(e)).await;
+                return;
+            }
+        };
+        if packets.send(message).await.is_err() {
+            return;
+        }
+    }
+}
+
+fn parse_publish(kind: u8, body: &[u8]) -> Result<(String, Vec<u8>)> {
+    if body.len() < 2 {
+        bail!("Malformed MQTT PUBLISH");
+    }
+    let length = u16::from_be_bytes([body[0], body[1]]) as usize;
+    let Some(topic) = body.get(2..2 + length) else {
+        bail!("Malformed MQTT PUBLISH");
+    };
+    let topic = String::from_utf8_lossy(topic).into_owned();
+    // QoS 0 으로 구독했으므로 보통은 없지만, QoS 가 있으면 패킷 번호를 건너뛴다.
+    let mut offset = 2 + length;
+    if (kind >> 1) & 0x03 > 0 {
+        offset += 2;
+    }
+    Ok((topic, body.get(offset..).unwrap_or_default().to_vec()))
+}
@@ -0,0 +1,47 @@
+// src/openapi.rs
+use axum::{
+    Json,
+    response::{Html, IntoResponse, Response},
+};
+use serde_json::Value;
+use std::sync::OnceLock;
+
+// 라우트나 요청/응답 형식을 바꾸면 이 문서도 함께 고친다.
+const SPEC: &str = include_str!("openapi.json");
+
+// Swagger UI 는 CDN 에서 받아 온다 (브라우저가 인터넷에 닿아야 한다).
+const DOCS_PAGE: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+  <meta charset="utf-8">
+  <title>API documentation</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>
+    window.ui = SwaggerUIBundle({ url: "openapi.json", dom_id: "#swagger-ui" });
+  </script>
+</body>
+</html>
+"##;
+
+fn spec() -> &'static Value {
+    static SPEC_VALUE: OnceLock<Value> = OnceLock::new();
+    SPEC_VALUE.get_or_init(|| {
+        let mut spec: Value = serde_json::from_str(SPEC).expect("openapi.json is valid JSON");
+        spec["info"]["version"] = Value::from(env!("CARGO_PKG_VERSION"));
+        spec
+    })
+}
+
+// GET /openapi.json - OpenAPI 3 문서 (인증 없이 열림)
+pub async fn handle_spec() -> Response {
+    Json(spec()).into_response()
+}
+
+// GET /docs - openapi.json 을 보여 주는 Swagger UI (인증 없이 열림, 요청을 보낼 때는 Authorize 로 키를 넣는다)
+pub async fn handle_docs() -> Html<&'static str> {
+    Html(DOCS_PAGE)
+}
@@ -0,0 +1,149 @@
+// src/overlay.rs
+use anyhow::{Result, bail};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+// 화면 가장자리와 글자 사이 여백 (픽셀)
+const MARGIN: u32 = 10;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverlayPosition {
+    #[default]
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+// 카메라 한 대의 프레임에 그릴 글자 (ffmpeg drawtext 로 마무리 단계에서 그린다)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OverlayConfig {
+    pub camera: u32,
+    // 녹화 시작 시각 기준 벽시계 시각
+    pub timestamp: bool,
+    // 시각을 밀리초까지 그린다 (frame_timestamps 의 .frames.csv 와 맞춰 볼 때).
+    pub milliseconds: bool,
+    pub label: Option<String>,
+    pub frame_number: bool,
+    pub position: OverlayPosition,
+    // 1.0 이면 프레임 높이의 1/30 크기
+    pub font_scale: f64,
+    // ffmpeg 색 이름이나 #RRGGBB
+    pub color: String,
+    // fontconfig 가 없는 ffmpeg 빌드에서는 글꼴 파일을 직접 지정해야 한다.
+    pub font_file: Option<String>,
+}
+
+impl Default for OverlayConfig {
+    fn default() -> Self {
+        Self {
+            camera: 0,
+            timestamp: true,
+            milliseconds: false,
+            label: None,
+            frame_number: false,
+            position: OverlayPosition::TopLeft,
+            font_scale: 1.0,
+            color: "white".to_string(),
+            font_file: None,
+        }
+    }
+}
+
+impl OverlayConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !self.font_scale.is_finite() || self.font_scale <= 0.0 {
+            bail!("overlay font_scale must be positive");
+        }
+        let valid_color = !self.color.is_empty()
+            && self
+                .color
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '#' || c == '@' || c == '.');
+        if !valid_color {
+            bail!("overlay color is invalid: {}", self.color);
+        }
+        if !self.timestamp && !self.frame_number && self.label.is_none() {
+            bail!("overlay for camera {} has nothing to draw", self.camera);
+        }
+        Ok(())
+    }
+
+    // start 는 입력 영상의 첫 프레임 시각, height 는 프레임 높이
+    pub fn filter(&self, start: DateTime<Local>, height: u32) -> String {
+        let mut parts = Vec::new();
+        if let Some(label) = &self.label {
+            parts.push(escape_text(label));
+        }
+        if self.timestamp && self.milliseconds {
+            // 시작 시각의 밀리초까지 더해야 초가 넘어가는 프레임이 맞는다.
+            let start = start.timestamp_millis() as f64 / 1000.0;
+            parts.push(format!(
+                "%{{pts\\:localtime\\:{:.3}\\:%Y-%m-%d %H\\\\\\:%M\\\\\\:%S}}\
+                 .%{{eif\\:mod(({:.3}+t)*1000,1000)\\:d\\:3}}",
+                start, start
+            ));
+        } else if self.timestamp {
+            parts.push(format!(
+                "%{{pts\\:localtime\\:{}\\:%Y-%m-%d %H\\\\\\:%M\\\\\\:%S}}",
+                start.timestamp()
+            ));
+        }
+        if self.frame_number {
+            parts.push("#%{frame_num}".to_string());
+        }
+
+        let (x, y) = match self.position {
+            OverlayPosition::TopLeft => (format!("{}", MARGIN), format!("{}", MARGIN)),
+            OverlayPosition::TopRight => (format!("w-tw-{}", MARGIN), format!("{}", MARGIN)),
+            OverlayPosition::BottomLeft => (format!("{}", MARGIN), format!("h-th-{}", MARGIN)),
+            OverlayPosition::BottomRight => {
+                (format!("w-tw-{}", MARGIN), format!("h-th-{}", MARGIN))
+            }
+        };
+        let font_size = ((height as f64 / 30.0) * self.font_scale).round().max(1.0);
+
+        let mut filter = format!(
+            "drawtext=text='{}':x={}:y={}:fontsize={}:fontcolor={}:box=1:boxcolor=black@0.4",
+            parts.join("  "),
+            x,
+            y,
+            font_size,
+            self.color
+        );
+        if let Some(font_file) = &self.font_file {
+            filter.push_str(&format!(":fontfile='{}'", escape_option(font_file)));
+        }
+        filter
+    }
+}
+
+// 필터 그래프에서 값은 '...' 로 감싸므로, 그 안에서는 옵션 구분자(:)와
+// 역슬래시, 작은따옴표만 이스케이프하면 된다.
+pub fn escape_option(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ':' => escaped.push_str("\\:"),
+            // 따옴표를 닫고, 이스케이프한 따옴표를 넣고, 다시 연다.
+            '\'' => escaped.push_str("'\\\\\\''"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// drawtext 는 text 안의 % 와 \ 를 한 번 더 해석한다.
+pub fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c == '\\' || c == '%' {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escape_option(&escaped)
+}
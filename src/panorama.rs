@@ -0,0 +1,234 @@
+// src/panorama.rs
+use crate::overlay;
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    io::Write,
+    path::{Path, PathBuf},
+};
+use tracing::warn;
+
+// 이어 붙인 화면이 이보다 크면 호모그래피가 잘못된 것으로 본다.
+const MAX_CANVAS: f64 = 8192.0;
+
+// Layout::Panorama: 두 번째 카메라를 호모그래피로 첫 카메라의 평면에 옮겨, 겹치는 곳을
+// 가장자리에서의 거리에 비례해 섞는다 (OpenCV Stitcher 의 FeatherBlender 와 같은 방식).
+// 두 카메라 모두 width x height 로 맞춘 뒤 이어 붙인다.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PanoramaConfig {
+    // 두 번째 카메라의 픽셀 (x, y, 1) 을 첫 카메라의 픽셀로 옮기는 3x3 행렬 (행 우선).
+    // width x height 기준이며, 한 번 OpenCV findHomography 등으로 구해 둔다.
+    pub homography: [f64; 9],
+    // false 면 섞지 않고 겹치는 곳은 두 번째 카메라로 덮는다.
+    pub blend: bool,
+}
+
+impl Default for PanoramaConfig {
+    fn default() -> Self {
+        Self {
+            homography: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+            blend: true,
+        }
+    }
+}
+
+impl PanoramaConfig {
+    pub fn validate(&self, width: u32, height: u32) -> Result<()> {
+        if !self.homography.iter().all(|value| value.is_finite()) {
+            bail!("panorama.homography must contain finite values");
+        }
+        Stitch::new(&self.homography, width, height).map(|_| ())
+    }
+
+    // 두 입력 레이블 (이미 width x height) 을 한 장으로 이어 붙이는 체인들. 마지막 체인은 출력
+    // 레이블이 없다.
+    pub fn chains(&self, left: &str, right: &str, width: u32, height: u32) -> Vec<String> {
+        let stitched = Stitch::new(&self.homography, width, height)
+            .and_then(|stitch| Ok((stitch.write_weights(self.blend)?, stitch)));
+        let (weights, stitch) = match stitched {
+            Ok(stitched) => stitched,
+            // 설정은 validate 를 거쳤으므로 맵을 쓰지 못한 경우다.
+            Err(e) => {
+                warn!(
+                    "Panorama disabled, placing the cameras side by side: {:#}",
+                    e
+                );
+                return vec![format!("[{}][{}]hstack=inputs=2", left, right)];
+            }
+        };
+        let (canvas_w, canvas_h) = stitch.canvas;
+        let (tx, ty) = stitch.offset;
+        // 출력 모서리가 두 번째 카메라에서 오는 자리 (perspective 의 sense=source)
+        let corners: Vec<String> = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)]
+            .iter()
+            .enumerate()
+            .map(|(i, &(u, v))| {
+                let (x, y) = stitch.to_right(u * canvas_w as f64, v * canvas_h as f64);
+                format!("x{i}={x:.3}:y{i}={y:.3}")
+            })
+            .collect();
+        vec![
+            format!(
+                "[{}]pad={}:{}:{}:{}:black[pano_left]",
+                left, canvas_w, canvas_h, tx, ty
+            ),
+            format!(
+                "[{}]pad={}:{}:0:0:black,perspective={}:sense=source:interpolation=linear[pano_warp]",
+                right,
+                canvas_w,
+                canvas_h,
+                corners.join(":")
+            ),
+            // 두 번째 카메라의 불투명도로 겹칠 곳을 섞는다.
+            format!(
+                "movie=filename='{}',format=gray[pano_weight]",
+                overlay::escape_option(&weights.to_string_lossy())
+            ),
+            "[pano_warp][pano_weight]alphamerge[pano_right]".to_string(),
+            "[pano_left][pano_right]overlay=format=auto".to_string(),
+        ]
+    }
+}
+
+// 호모그래피로 정한 이어 붙인 화면의 크기와 첫 카메라가 놓이는 위치
+struct Stitch {
+    // 이어 붙인 화면 좌표 -> 두 번째 카메라 좌표 (첫 카메라 좌표로 옮긴 뒤 역행렬)
+    inverse: [f64; 9],
+    offset: (u32, u32),
+    canvas: (u32, u32),
+    size: (u32, u32),
+}
+
+impl Stitch {
+    fn new(homography: &[f64; 9], width: u32, height: u32) -> Result<Self> {
+        let (w, h) = (width as f64, height as f64);
+        // 행렬에 음수를 곱해도 같은 변환이므로, 두 번째 카메라 화면에서 w 가 양수가 되게 맞춘다.
+        // 그러면 역행렬도 첫 카메라 쪽에서 w 가 양수다.
+        let homography = &if homography[6] * w / 2.0 + homography[7] * h / 2.0 + homography[8] < 0.0
+        {
+            homography.map(|value| -value)
+        } else {
+            *homography
+        };
+        let inverse = invert(homography).context("panorama.homography is not invertible")?;
+        let mut min = (0.0f64, 0.0f64);
+        let mut max = (w, h);
+        for (x, y) in [(0.0, 0.0), (w, 0.0), (0.0, h), (w, h)] {
+            let (x, y) = project(homography, x, y)
+                .context("panorama.homography maps the second camera past the horizon")?;
+            min = (min.0.min(x), min.1.min(y));
+            max = (max.0.max(x), max.1.max(y));
+        }
+        let (canvas_w, canvas_h) = (max.0 - min.0, max.1 - min.1);
+        if canvas_w > MAX_CANVAS || canvas_h > MAX_CANVAS {
+            bail!(
+                "panorama.homography gives a {:.0}x{:.0} canvas (at most {:.0} per side)",
+                canvas_w,
+                canvas_h,
+                MAX_CANVAS
+            );
+        }
+        // 인코더가 받도록 짝수로 올린다.
+        let even = |size: f64| ((size.ceil() as u32) + 1) & !1;
+        Ok(Self {
+            inverse,
+            offset: ((-min.0).floor() as u32, (-min.1).floor() as u32),
+            canvas: (even(canvas_w), even(canvas_h)),
+            size: (width, height),
+        })
+    }
+
+    fn to_left(&self, x: f64, y: f64) -> (f64, f64) {
+        (x - self.offset.0 as f64, y - self.offset.1 as f64)
+    }
+
+    fn to_right(&self, x: f64, y: f64) -> (f64, f64) {
+        let (x, y) = self.to_left(x, y);
+        project(&self.inverse, x, y).unwrap_or((-1.0, -1.0))
+    }
+
+    // 카메라 화면 안이면 가장 가까운 가장자리까지의 거리, 밖이면 0
+    fn edge_distance(&self, (x, y): (f64, f64)) -> f64 {
+        let (w, h) = (self.size.0 as f64, self.size.1 as f64);
+        if (0.0..w).contains(&x) && (0.0..h).contains(&y) {
+            x.min(w - x).min(y).min(h - y) + 1.0
+        } else {
+            0.0
+        }
+    }
+
+    // 두 번째 카메라의 불투명도 (0 ~ 255) 를 담은 8 비트 PGM. 같은 배치면 다시 쓴다.
+    fn write_weights(&self, blend: bool) -> Result<PathBuf> {
+        let mut hasher = DefaultHasher::new();
+        for value in &self.inverse {
+            value.to_bits().hash(&mut hasher);
+        }
+        (self.offset, self.canvas, self.size, blend).hash(&mut hasher);
+        let dir = std::env::temp_dir().join("server_panorama");
+        let path = dir.join(format!("{:016x}.pgm", hasher.finish()));
+        if path.exists() {
+            return Ok(path);
+        }
+
+        let (canvas_w, canvas_h) = self.canvas;
+        let mut bytes = format!("P5\n{} {}\n255\n", canvas_w, canvas_h).into_bytes();
+        for v in 0..canvas_h {
+            for u in 0..canvas_w {
+                let (x, y) = (u as f64 + 0.5, v as f64 + 0.5);
+                let right = self.edge_distance(self.to_right(x, y));
+                let left = self.edge_distance(self.to_left(x, y));
+                let weight = match (left > 0.0, right > 0.0) {
+                    (_, false) => 0.0,
+                    (true, true) if blend => right / (left + right),
+                    _ => 1.0,
+                };
+                bytes.push((weight * 255.0).round() as u8);
+            }
+        }
+        fs::create_dir_all(&dir).with_context(|| format!("Failed to create {:?}", dir))?;
+        write_atomic(&path, &bytes)?;
+        Ok(path)
+    }
+}
+
+// 다른 녹화가 읽는 중일 수 있으므로 다 쓴 뒤 이름을 바꾼다.
+fn write_atomic(path: &Path, bytes: &[u8]) -> Result<()> {
+    let partial = path.with_extension("pgm.partial");
+    fs::File::create(&partial)
+        .and_then(|mut file| file.write_all(bytes))
+        .with_context(|| format!("Failed to write {:?}", partial))?;
+    fs::rename(&partial, path).with_context(|| format!("Failed to move {:?}", partial))
+}
+
+fn project(m: &[f64; 9], x: f64, y: f64) -> Option<(f64, f64)> {
+    let w = m[6] * x + m[7] * y + m[8];
+    (w > f64::EPSILON).then(|| {
+        (
+            (m[0] * x + m[1] * y + m[2]) / w,
+            (m[3] * x + m[4] * y + m[5]) / w,
+        )
+    })
+}
+
+fn invert(m: &[f64; 9]) -> Option<[f64; 9]> {
+    let det = m[0] * (m[4] * m[8] - m[5] * m[7]) - m[1] * (m[3] * m[8] - m[5] * m[6])
+        + m[2] * (m[3] * m[7] - m[4] * m[6]);
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let adjugate = [
+        m[4] * m[8] - m[5] * m[7],
+        m[2] * m[7] - m[1] * m[8],
+        m[1] * m[5] - m[2] * m[4],
+        m[5] * m[6] - m[3] * m[8],
+        m[0] * m[8] - m[2] * m[6],
+        m[2] * m[3] - m[0] * m[5],
+        m[3] * m[7] - m[4] * m[6],
+        m[1] * m[6] - m[0] * m[7],
+        m[0] * m[4] - m[1] * m[3],
+    ];
+    Some(adjugate.map(|value| value / det))
+}
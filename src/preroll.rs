@@ -0,0 +1,226 @@
+// src/preroll.rs
+use crate::{
+    camera_handler::RecordingConfig,
+    stream::{AccessUnit, StreamHub, View},
+};
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fs::{self, File},
+    io::{self, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+use tokio::sync::broadcast;
+use tracing::info;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PreRollConfig {
+    // true 면 녹화하지 않는 동안에도 카메라를 열어 두고 최근 영상을 메모리에 모아 둔다.
+    pub enabled: bool,
+    // 녹화 파일 앞에 붙일 시간 (초). 키프레임 단위로 자르므로 조금 더 길어질 수 있다.
+    pub seconds: u64,
+    // 카메라 한 대가 버퍼에 쓸 수 있는 최대 메모리 (MB)
+    pub max_megabytes: u64,
+}
+
+impl Default for PreRollConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            seconds: 5,
+            max_megabytes: 64,
+        }
+    }
+}
+
+impl PreRollConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.seconds == 0 {
+            bail!("pre_roll.seconds must be non-zero");
+        }
+        if self.max_megabytes == 0 {
+            bail!("pre_roll.max_megabytes must be non-zero");
+        }
+        Ok(())
+    }
+}
+
+// 녹화 시작 때 꺼낸 카메라 한 대의 앞부분 (키프레임으로 시작하는 Annex B H.264)
+pub struct Clip {
+    pub data: Vec<u8>,
+    // 첫 프레임부터 녹화 시작까지의 시간 (초)
+    pub duration: f64,
+    pub frames: u64,
+}
+
+#[derive(Default)]
+struct Buffer {
+    units: VecDeque<(Instant, Arc<AccessUnit>)>,
+    bytes: usize,
+}
+
+impl Buffer {
+    fn push(&mut self, unit: Arc<AccessUnit>, keep: Duration, max_bytes: usize) {
+        self.bytes += unit_size(&unit);
+        self.units.push_back((Instant::now(), unit));
+
+        // 다음 키프레임이 보관 시간보다 오래되었으면 그 앞 GOP 는 필요 없다.
+        // 메모리 한도를 넘으면 마지막 GOP 만 남을 때까지 앞에서부터 버린다.
+        let cutoff = Instant::now().checked_sub(keep);
+        while let Some(next) = self.next_keyframe() {
+            let expired = cutoff.is_some_and(|cutoff| self.units[next].0 <= cutoff);
+            if !expired && self.bytes <= max_bytes {
+                break;
+            }
+            for (_, unit) in self.units.drain(..next) {
+                self.bytes -= unit_size(&unit);
+            }
+        }
+    }
+
+    // 맨 앞 다음에 오는 첫 키프레임의 위치 (맨 앞은 GOP 의 시작)
+    fn next_keyframe(&self) -> Option<usize> {
+        self.units
+            .iter()
+            .skip(1)
+            .position(|(_, unit)| unit.keyframe)
+            .map(|position| position + 1)
+    }
+
+    fn take(&mut self) -> Option<Clip> {
+        let units = std::mem::take(&mut self.units);
+        self.bytes = 0;
+        let start = units.iter().position(|(_, unit)| unit.keyframe)?;
+        let first = units[start].0;
+        let mut data = Vec::new();
+        for (_, unit) in units.iter().skip(start) {
+            for nal in &unit.nals {
+                data.extend_from_slice(&[0, 0, 0, 1]);
+                data.extend_from_slice(nal);
+            }
+        }
+        Some(Clip {
+            data,
+            duration: first.elapsed().as_secs_f64(),
+            frames: (units.len() - start) as u64,
+        })
+    }
+}
+
+fn unit_size(unit: &AccessUnit) -> usize {
+    unit.nals.iter().map(|nal| nal.len() + 4).sum()
+}
+
+// 카메라별 최근 영상. 스트림 소스(StreamHub)의 카메라 원본을 구독해 채운다.
+pub struct PreRoll {
+    config: PreRollConfig,
+    buffers: Mutex<HashMap<u32, Buffer>>,
+}
+
+impl PreRoll {
+    pub fn new(config: &PreRollConfig) -> Self {
+        Self {
+            config: config.clone(),
+            buffers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn push(&self, camera: u32, unit: Arc<AccessUnit>) {
+        let keep = Duration::from_secs(self.config.seconds);
+        let max_bytes = (self.config.max_megabytes * 1024 * 1024) as usize;
+        self.buffers
+            .lock()
+            .unwrap()
+            .entry(camera)
+            .or_default()
+            .push(unit, keep, max_bytes);
+    }
+
+    fn clear(&self, camera: u32) {
+        self.buffers.lock().unwrap().remove(&camera);
+    }
+
+    // 녹화를 시작할 때 호출한다. 버퍼는 비워진다.
+    // 미리보기는 recording 기본 설정으로 촬영하므로 해상도나 FPS 가 다른 카메라에는 붙이지 않는다.
+    pub fn take(
+        &self,
+        config: &RecordingConfig,
+        defaults: &RecordingConfig,
+    ) -> BTreeMap<u32, Clip> {
+        if !self.config.enabled {
+            return BTreeMap::new();
+        }
+        let mut buffers = self.buffers.lock().unwrap();
+        config
+            .cameras
+            .iter()
+            .filter_map(|&camera| {
+                if config.format_for(camera) != defaults.format_for(camera) {
+                    info!(
+                        "Skipping the pre-roll of camera {}: the recording format differs from the preview.",
+                        camera
+                    );
+                    return None;
+                }
+                let clip = buffers.get_mut(&camera)?.take()?;
+                Some((camera, clip))
+            })
+            .collect()
+    }
+}
+
+// 카메라마다 버퍼를 채우는 스레드를 시작한다 (설정에서 켠 경우에만).
+pub fn spawn(preroll: Arc<PreRoll>, hub: Arc<StreamHub>, cameras: &[u32]) -> Result<()> {
+    if !preroll.config.enabled {
+        return Ok(());
+    }
+    for &camera in cameras {
+        let preroll = preroll.clone();
+        let mut subscription = hub.subscribe(View::Camera(camera));
+        thread::Builder::new()
+            .name(format!("preroll-cam{}", camera))
+            .spawn(move || {
+                loop {
+                    match subscription.units.blocking_recv() {
+                        Ok(unit) => preroll.push(camera, unit),
+                        // 빠진 프레임이 있으면 다음 키프레임부터 다시 모은다.
+                        Err(broadcast::error::RecvError::Lagged(_)) => preroll.clear(camera),
+                        Err(broadcast::error::RecvError::Closed) => return,
+                    }
+                }
+            })
+            .context("Failed to start pre-roll thread")?;
+    }
+    info!(
+        "Keeping {}s of pre-roll for cameras {:?}.",
+        preroll.config.seconds, cameras
+    );
+    Ok(())
+}
+
+// 카메라 파일 앞에 clip 을 붙인다.
+pub fn prepend(clip: &Clip, target: &Path) -> Result<()> {
+    let joined = target.with_extension("preroll.h264");
+    let result = write_joined(clip, target, &joined).and_then(|()| {
+        fs::rename(&joined, target)
+            .with_context(|| format!("Failed to move {:?} to {:?}", joined, target))
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&joined);
+    }
+    result
+}
+
+fn write_joined(clip: &Clip, target: &Path, joined: &Path) -> Result<()> {
+    let mut output =
+        File::create(joined).with_context(|| format!("Failed to create {:?}", joined))?;
+    output.write_all(&clip.data)?;
+    let mut input = File::open(target).with_context(|| format!("Failed to open {:?}", target))?;
+    io::copy(&mut input, &mut output)?;
+    Ok(())
+}
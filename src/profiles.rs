@@ -0,0 +1,198 @@
+// src/profiles.rs
+use crate::{ApiError, AppState, StartRequest, camera_handler::RecordingConfig};
+use anyhow::{Context, Result, anyhow, bail};
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use serde::Serialize;
+use std::{collections::BTreeMap, fs, path::PathBuf, sync::Arc};
+use tracing::info;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProfileSource {
+    // 설정 파일의 [profiles] (API 로 바꾸거나 지울 수 없다)
+    Config,
+    // API 로 만든 프로필 (profiles_file 에 저장된다)
+    Api,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Profile {
+    pub name: String,
+    pub source: ProfileSource,
+    // /start 요청과 같은 형식. 생략한 값은 [recording] 기본값을 따른다.
+    pub settings: StartRequest,
+}
+
+// 이름은 URL 과 쿼리에 그대로 쓰이므로 영문, 숫자, '_', '-' 만 허용한다.
+fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty() {
+        bail!("profile name must not be empty");
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        bail!(
+            "profile name {:?} may only contain letters, digits, '_' and '-'",
+            name
+        );
+    }
+    Ok(())
+}
+
+// 프로필 하나가 기본값 위에서 올바른 녹화 설정이 되는지 확인한다.
+pub fn validate(name: &str, settings: &StartRequest, defaults: &RecordingConfig) -> Result<()> {
+    validate_name(name)?;
+    if settings.profile.is_some() {
+        bail!("profile {:?} must not refer to another profile", name);
+    }
+    settings
+        .clone()
+        .into_config(defaults)
+        .map_err(|e| anyhow!("profile {:?} is invalid: {}", name, e.message))?;
+    Ok(())
+}
+
+// 설정 파일의 프로필과 API 로 만든 프로필 (디스크에 JSON 으로 저장)
+pub struct ProfileStore {
+    path: PathBuf,
+    config: BTreeMap<String, StartRequest>,
+    saved: BTreeMap<String, StartRequest>,
+}
+
+impl ProfileStore {
+    pub fn load(path: PathBuf, config: BTreeMap<String, StartRequest>) -> Result<Self> {
+        let saved: BTreeMap<String, StartRequest> = if path.exists() {
+            let contents = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read profiles file: {:?}", path))?;
+            serde_json::from_str(&contents)
+                .with_context(|| format!("Invalid profiles file: {:?}", path))?
+        } else {
+            BTreeMap::new()
+        };
+        if let Some(name) = saved.keys().find(|name| config.contains_key(*name)) {
+            bail!(
+                "Profile {:?} in {:?} is also defined in the config file",
+                name,
+                path
+            );
+        }
+        Ok(Self {
+            path,
+            config,
+            saved,
+        })
+    }
+
+    fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {:?}", parent))?;
+        }
+        let contents = serde_json::to_string_pretty(&self.saved)?;
+        fs::write(&self.path, contents)
+            .with_context(|| format!("Failed to write profiles file: {:?}", self.path))
+    }
+
+    pub fn len(&self) -> usize {
+        self.config.len() + self.saved.len()
+    }
+
+    pub fn get(&self, name: &str) -> Option<Profile> {
+        let (source, settings) = match (self.config.get(name), self.saved.get(name)) {
+            (Some(settings), _) => (ProfileSource::Config, settings),
+            (None, Some(settings)) => (ProfileSource::Api, settings),
+            (None, None) => return None,
+        };
+        Some(Profile {
+            name: name.to_string(),
+            source,
+            settings: settings.clone(),
+        })
+    }
+
+    pub fn list(&self) -> Vec<Profile> {
+        let mut names: Vec<&String> = self.config.keys().chain(self.saved.keys()).collect();
+        names.sort();
+        names.iter().filter_map(|name| self.get(name)).collect()
+    }
+}
+
+fn store_error(e: anyhow::Error) -> ApiError {
+    ApiError::internal(format!("{:#}", e))
+}
+
+fn not_found(name: &str) -> ApiError {
+    ApiError::not_found(format!("Profile {:?} not found", name))
+}
+
+fn read_only(name: &str) -> ApiError {
+    ApiError::conflict(format!(
+        "Profile {:?} is defined in the config file and cannot be changed through the API",
+        name
+    ))
+}
+
+pub async fn handle_list(State(state): State<Arc<AppState>>) -> Json<Vec<Profile>> {
+    Json(state.profiles.lock().unwrap().list())
+}
+
+pub async fn handle_get(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Result<Json<Profile>, ApiError> {
+    state
+        .profiles
+        .lock()
+        .unwrap()
+        .get(&name)
+        .map(Json)
+        .ok_or_else(|| not_found(&name))
+}
+
+// 없으면 만들고 (201), 있으면 바꾼다 (200).
+pub async fn handle_put(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Json(settings): Json<StartRequest>,
+) -> Result<(StatusCode, Json<Profile>), ApiError> {
+    validate(&name, &settings, &state.settings.recording())
+        .map_err(|e| ApiError::bad_request(e.to_string()))?;
+
+    let mut store = state.profiles.lock().unwrap();
+    if store.config.contains_key(&name) {
+        return Err(read_only(&name));
+    }
+    let created = store.saved.insert(name.clone(), settings).is_none();
+    store.save().map_err(store_error)?;
+    let profile = store.get(&name).ok_or_else(|| not_found(&name))?;
+
+    if created {
+        info!("Profile {} created.", name);
+        Ok((StatusCode::CREATED, Json(profile)))
+    } else {
+        info!("Profile {} updated.", name);
+        Ok((StatusCode::OK, Json(profile)))
+    }
+}
+
+pub async fn handle_delete(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let mut store = state.profiles.lock().unwrap();
+    if store.config.contains_key(&name) {
+        return Err(read_only(&name));
+    }
+    if store.saved.remove(&name).is_none() {
+        return Err(not_found(&name));
+    }
+    store.save().map_err(store_error)?;
+
+    info!("Profile {} deleted.", name);
+    Ok(StatusCode::NO_CONTENT)
+}
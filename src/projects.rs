@@ -0,0 +1,195 @@
+// src/projects.rs
+use crate::{
+    ApiError, AppState,
+    recordings::RecordingEntry,
+    storage::{Catalog, CatalogQuery},
+};
+use anyhow::{Result, bail};
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::info;
+
+const MAX_NAME_LEN: usize = 64;
+
+// 여러 세션의 녹화를 묶는 실험/프로젝트. 카탈로그에 보관하고, 녹화는 사이드카의 project 로 묶인다.
+#[derive(Debug, Clone, Serialize)]
+pub struct Project {
+    pub name: String,
+    pub description: String,
+    pub created: DateTime<Local>,
+    // 카탈로그에 있는 이 프로젝트의 녹화
+    pub recordings: u64,
+    pub total_size: u64,
+    pub total_duration_seconds: f64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProjectRequest {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+// 분석 파이프라인에 넘기는 프로젝트의 녹화 목록 (오래된 것부터)
+#[derive(Debug, Serialize)]
+pub struct ProjectManifest {
+    pub project: Project,
+    pub exported_at: DateTime<Local>,
+    // 녹화의 name 은 이 디렉토리 기준 상대 경로
+    pub save_dir: String,
+    pub recordings: Vec<RecordingEntry>,
+}
+
+// 디렉토리 이름과 URL, 내려받는 파일 이름에 그대로 쓰므로 ASCII 글자, 숫자, '-', '_', '.', 공백만 받는다.
+pub fn validate_name(name: &str) -> Result<()> {
+    if name.trim().is_empty() || name.len() > MAX_NAME_LEN {
+        bail!(
+            "project name must be 1 to {} characters long: {:?}",
+            MAX_NAME_LEN,
+            name
+        );
+    }
+    if name.starts_with(['.', ' ']) || name.ends_with(['.', ' ']) {
+        bail!(
+            "project name must not start or end with '.' or a space: {:?}",
+            name
+        );
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ' '))
+    {
+        bail!(
+            "project name may only contain ASCII letters, digits, '-', '_', '.' and spaces: {:?}",
+            name
+        );
+    }
+    Ok(())
+}
+
+async fn with_catalog<T: Send + 'static>(
+    state: &AppState,
+    job: impl FnOnce(&Catalog) -> Result<T> + Send + 'static,
+) -> Result<T, ApiError> {
+    let catalog = state.catalog.clone();
+    tokio::task::spawn_blocking(move || job(&catalog))
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))?
+        .map_err(|e| ApiError::internal(format!("{:#}", e)))
+}
+
+async fn find(state: &AppState, name: String) -> Result<Project, ApiError> {
+    let lookup = name.clone();
+    with_catalog(state, move |catalog| catalog.project(&lookup))
+        .await?
+        .ok_or_else(|| ApiError::not_found(format!("Project {:?} not found", name)))
+}
+
+// POST /start 에서 project 를 정했으면 있는 프로젝트여야 한다.
+pub async fn ensure_exists(state: &AppState, name: &str) -> Result<(), ApiError> {
+    find(state, name.to_string()).await.map(|_| ())
+}
+
+pub async fn handle_list(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<Project>>, ApiError> {
+    Ok(Json(
+        with_catalog(&state, |catalog| catalog.projects(None)).await?,
+    ))
+}
+
+pub async fn handle_create(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ProjectRequest>,
+) -> Result<(StatusCode, Json<Project>), ApiError> {
+    validate_name(&request.name).map_err(|e| ApiError::bad_request(e.to_string()))?;
+    let name = request.name.clone();
+    let created = with_catalog(&state, move |catalog| {
+        catalog.create_project(&request.name, &request.description)
+    })
+    .await?;
+    if !created {
+        return Err(ApiError::conflict(format!(
+            "Project {:?} already exists",
+            name
+        )));
+    }
+    info!("Project {:?} created.", name);
+    Ok((StatusCode::CREATED, Json(find(&state, name).await?)))
+}
+
+pub async fn handle_get(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Result<Json<Project>, ApiError> {
+    Ok(Json(find(&state, name).await?))
+}
+
+// 녹화가 남아 있거나 녹화 중인 프로젝트는 지우지 않는다.
+pub async fn handle_delete(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let project = find(&state, name.clone()).await?;
+    let recording = state
+        .sessions
+        .lock()
+        .unwrap()
+        .running()
+        .iter()
+        .any(|session| session.config.project.as_deref() == Some(name.as_str()));
+    if recording || project.recordings > 0 {
+        return Err(ApiError::conflict(format!(
+            "Project {:?} still has recordings",
+            name
+        )));
+    }
+    let lookup = name.clone();
+    if !with_catalog(&state, move |catalog| catalog.delete_project(&lookup)).await? {
+        return Err(ApiError::not_found(format!("Project {:?} not found", name)));
+    }
+    info!("Project {:?} deleted.", name);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn recordings(state: &AppState, name: String) -> Result<Vec<RecordingEntry>, ApiError> {
+    let query = CatalogQuery {
+        project: Some(name),
+        ..CatalogQuery::default()
+    };
+    with_catalog(state, move |catalog| catalog.query(&query)).await
+}
+
+// GET /projects/:name/recordings - 최신순
+pub async fn handle_recordings(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Result<Json<Vec<RecordingEntry>>, ApiError> {
+    find(&state, name.clone()).await?;
+    Ok(Json(recordings(&state, name).await?))
+}
+
+// GET /projects/:name/manifest - <name>.manifest.json 으로 내려받는다.
+pub async fn handle_manifest(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Result<Response, ApiError> {
+    let project = find(&state, name.clone()).await?;
+    let mut recordings = recordings(&state, name.clone()).await?;
+    recordings.reverse();
+    let manifest = ProjectManifest {
+        project,
+        exported_at: Local::now(),
+        save_dir: state.config.save_dir().to_string_lossy().into_owned(),
+        recordings,
+    };
+    let disposition = format!("attachment; filename=\"{}.manifest.json\"", name);
+    Ok(([(header::CONTENT_DISPOSITION, disposition)], Json(manifest)).into_response())
+}
@@ -0,0 +1,130 @@
+// src/reconnect.rs
+use crate::{compositor, sink::Encoding};
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tracing::warn;
+
+// 녹화 중 카메라가 빠졌을 때의 재연결 설정
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReconnectConfig {
+    // false 면 카메라 하나가 끊겨도 녹화 전체를 실패로 끝낸다 (분할 녹화는 항상 그렇다).
+    pub enabled: bool,
+    pub max_attempts: u32,
+    // 재시도 간격은 1초부터 두 배씩 늘어나며 이 값을 넘지 않는다.
+    pub max_backoff_secs: u64,
+    // 이 시간 동안 프레임이 없으면 libcamera-vid 가 멈춘 것으로 보고 다시 시작한다.
+    pub stall_timeout_secs: u64,
+    // 끊긴 구간을 "NO SIGNAL" 화면으로 채워 길이를 유지한다 (없으면 이어 붙이기만 한다).
+    pub placeholder: bool,
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_attempts: 5,
+            max_backoff_secs: 30,
+            stall_timeout_secs: 10,
+            placeholder: false,
+        }
+    }
+}
+
+impl ReconnectConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.enabled && self.max_attempts == 0 {
+            bail!("reconnect.max_attempts must be non-zero");
+        }
+        if self.max_backoff_secs == 0 {
+            bail!("reconnect.max_backoff_secs must be non-zero");
+        }
+        if self.stall_timeout_secs == 0 {
+            bail!("reconnect.stall_timeout_secs must be non-zero");
+        }
+        Ok(())
+    }
+
+    // attempt 번째 (1부터) 실패 후 다음 시도까지 기다릴 시간
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let secs = 1u64
+            .checked_shl(attempt.saturating_sub(1))
+            .unwrap_or(u64::MAX)
+            .min(self.max_backoff_secs);
+        Duration::from_secs(secs)
+    }
+}
+
+// 재연결 전까지 쓰던 파일 하나와 그 뒤로 끊겨 있던 시간
+pub struct Part {
+    pub output: PathBuf,
+    pub pts_path: PathBuf,
+    pub gap: Duration,
+}
+
+// NO SIGNAL 화면을 만들 때 필요한 정보
+pub struct Placeholder {
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+    pub encoding: Encoding,
+}
+
+// --inline 으로 녹화한 H.264 스트림은 매 I 프레임마다 헤더가 있으므로
+// 파일을 그대로 이어 붙여도 하나의 스트림으로 재생된다.
+pub fn join_parts(
+    parts: &[Part],
+    last: &Path,
+    placeholder: Option<&Placeholder>,
+    output: &Path,
+) -> Result<()> {
+    let mut joined =
+        File::create(output).with_context(|| format!("Failed to create {:?}", output))?;
+
+    for part in parts {
+        append(&mut joined, &part.output)?;
+        if let Some(placeholder) = placeholder.filter(|_| !part.gap.is_zero()) {
+            let clip = output.with_extension("gap.h264");
+            match compositor::no_signal_clip(
+                placeholder.width,
+                placeholder.height,
+                placeholder.fps,
+                part.gap,
+                &placeholder.encoding,
+                &clip,
+            ) {
+                Ok(()) => append(&mut joined, &clip)?,
+                Err(e) => warn!("{:#}. Leaving the outage out of the recording.", e),
+            }
+            let _ = fs::remove_file(&clip);
+        }
+    }
+    append(&mut joined, last)?;
+
+    for part in parts {
+        for path in [&part.output, &part.pts_path] {
+            if let Err(e) = fs::remove_file(path) {
+                warn!("Failed to remove {:?}: {}", path, e);
+            }
+        }
+    }
+    if let Err(e) = fs::remove_file(last) {
+        warn!("Failed to remove {:?}: {}", last, e);
+    }
+    Ok(())
+}
+
+fn append(joined: &mut File, path: &Path) -> Result<()> {
+    // 프레임을 하나도 쓰기 전에 끊기면 파일이 없을 수 있다.
+    let Ok(mut part) = File::open(path) else {
+        return Ok(());
+    };
+    io::copy(&mut part, joined).with_context(|| format!("Failed to append {:?}", path))?;
+    Ok(())
+}
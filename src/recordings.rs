@@ -0,0 +1,425 @@
+// src/recordings.rs
+use crate::{
+    ApiError, AppState, detection,
+    events::EventKind,
+    frame_log,
+    metadata::{self, RecordingMetadata},
+    renditions::RenditionQuery,
+    sink,
+    storage::{CatalogQuery, UploadStatus},
+};
+use anyhow::{Context, Result};
+use axum::{
+    Json,
+    body::Body,
+    extract::{Path, Query, Request, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Component, Path as FsPath, PathBuf},
+    process::Command,
+    sync::Arc,
+};
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+const VIDEO_EXTENSIONS: &[&str] = &["mp4", "mkv", "avi", "h264", "ts"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordingEntry {
+    // 저장 디렉토리 기준 상대 경로 ('/' 구분)
+    pub name: String,
+    pub size: u64,
+    pub duration_seconds: Option<f64>,
+    pub created: DateTime<Local>,
+    // 사이드카(.json) 가 있으면 그 내용
+    pub metadata: Option<RecordingMetadata>,
+    pub upload_status: UploadStatus,
+    // 업로드한 객체의 키 (버킷 기준)
+    pub remote_key: Option<String>,
+}
+
+pub fn is_video(path: &FsPath) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| VIDEO_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+// 저장 디렉토리 아래의 모든 영상 파일 (하위 디렉토리 포함).
+// with_duration 이 false 면 ffprobe 호출을 생략한다.
+pub fn scan(save_dir: &FsPath, with_duration: bool) -> Result<Vec<RecordingEntry>> {
+    let mut entries = Vec::new();
+    if save_dir.exists() {
+        scan_dir(save_dir, save_dir, with_duration, &mut entries)?;
+    }
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.created));
+    Ok(entries)
+}
+
+fn scan_dir(
+    root: &FsPath,
+    dir: &FsPath,
+    with_duration: bool,
+    entries: &mut Vec<RecordingEntry>,
+) -> Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {:?}", dir))? {
+        let entry = entry?;
+        let path = entry.path();
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            scan_dir(root, &path, with_duration, entries)?;
+            continue;
+        }
+        if !metadata.is_file() || !is_video(&path) {
+            continue;
+        }
+        entries.push(load_entry(root, &path, &metadata, with_duration)?);
+    }
+    Ok(())
+}
+
+// 저장 디렉토리 기준 상대 경로 ('/' 구분)
+pub fn relative_name(root: &FsPath, path: &FsPath) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+pub fn load_entry(
+    root: &FsPath,
+    path: &FsPath,
+    file: &fs::Metadata,
+    with_duration: bool,
+) -> Result<RecordingEntry> {
+    let created = file.created().or_else(|_| file.modified())?;
+    Ok(RecordingEntry {
+        name: relative_name(root, path),
+        size: file.len(),
+        duration_seconds: if with_duration {
+            probe_duration(path)
+        } else {
+            None
+        },
+        created: created.into(),
+        metadata: metadata::read(path),
+        upload_status: UploadStatus::Local,
+        remote_key: None,
+    })
+}
+
+// ffprobe 로 컨테이너 길이를 읽는다. 알 수 없으면 None.
+pub fn probe_duration(path: &FsPath) -> Option<f64> {
+    let output = Command::new("ffprobe")
+        .arg("-v")
+        .arg("error")
+        .arg("-show_entries")
+        .arg("format=duration")
+        .arg("-of")
+        .arg("default=noprint_wrappers=1:nokey=1")
+        .arg(path)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+}
+
+// 요청된 이름을 저장 디렉토리 안의 경로로 변환 ('..' 나 절대 경로는 거부)
+pub fn resolve(save_dir: &FsPath, name: &str) -> Result<PathBuf, ApiError> {
+    let relative = FsPath::new(name);
+    let safe = relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+    if name.is_empty() || !safe {
+        return Err(ApiError::bad_request(format!(
+            "Invalid recording name: {}",
+            name
+        )));
+    }
+    Ok(save_dir.join(relative))
+}
+
+// 목록은 파일 시스템 대신 카탈로그(SQLite)에서 읽는다.
+pub async fn handle_list(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CatalogQuery>,
+) -> Result<Json<Vec<RecordingEntry>>, ApiError> {
+    let catalog = state.catalog.clone();
+    let entries = tokio::task::spawn_blocking(move || catalog.query(&query))
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))?
+        .map_err(|e| ApiError::internal(format!("{:#}", e)))?;
+    Ok(Json(entries))
+}
+
+// Range 요청을 지원하도록 tower-http 의 ServeFile 에 위임
+pub async fn handle_download(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Query(query): Query<RenditionQuery>,
+    request: Request,
+) -> Result<Response, ApiError> {
+    let mut path = resolve(&state.config.save_dir(), &name)?;
+    if !path.is_file() || !is_video(&path) {
+        return Err(ApiError::not_found(format!("Recording {} not found", name)));
+    }
+    // ?format=mp4&height=480 이면 브라우저에서 바로 재생할 수 있는 변환본을 보낸다.
+    if query.is_requested() {
+        if let Some(height) = query.height
+            && !(16..=state.renditions.max_height()).contains(&height)
+        {
+            return Err(ApiError::bad_request(format!(
+                "height must be between 16 and {}",
+                state.renditions.max_height()
+            )));
+        }
+        if is_active_output(&state, &path) {
+            return Err(ApiError::conflict(format!(
+                "Recording {} is still being written",
+                name
+            )));
+        }
+        path = state
+            .renditions
+            .get(&path, query)
+            .await
+            .map_err(|e| ApiError::internal(format!("{:#}", e)))?;
+    }
+
+    let response = ServeFile::new(&path)
+        .oneshot(request)
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))?;
+    if response.status() == StatusCode::NOT_FOUND {
+        return Err(ApiError::not_found(format!("Recording {} not found", name)));
+    }
+    Ok(response.map(Body::new).into_response())
+}
+
+// 녹화 중인 파일은 삭제하지 않는다.
+pub fn is_active_output(state: &AppState, path: &FsPath) -> bool {
+    state.sessions.lock().unwrap().is_writing(path)
+}
+
+// 영상과 같은 이름의 사이드카(.pts, .json, .frames.csv, .detections.jsonl)
+fn sidecars(path: &FsPath) -> [PathBuf; 5] {
+    [
+        path.with_extension("pts"),
+        metadata::sidecar_path(path),
+        frame_log::sidecar_path(path),
+        detection::sidecar_path(path),
+        sink::summary_path(path),
+    ]
+}
+
+// 사이드카도 함께 지운다.
+pub fn remove_recording(path: &FsPath) -> Result<()> {
+    fs::remove_file(path).with_context(|| format!("Failed to delete {:?}", path))?;
+    for sidecar in sidecars(path) {
+        if sidecar.exists() {
+            fs::remove_file(&sidecar).with_context(|| format!("Failed to delete {:?}", sidecar))?;
+        }
+    }
+    Ok(())
+}
+
+// staging_dir 에서 마무리한 파일들을 save_dir 의 같은 상대 경로로 옮기고 옮긴 경로를 돌려준다.
+// 사이드카를 먼저 옮기고 영상을 마지막에 옮겨, 목록에는 다 쓴 영상만 나타난다.
+pub fn publish(staging_dir: &FsPath, save_dir: &FsPath, files: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    files
+        .iter()
+        .map(|file| {
+            let relative = file
+                .strip_prefix(staging_dir)
+                .with_context(|| format!("{:?} is not in {:?}", file, staging_dir))?;
+            let target = save_dir.join(relative);
+            if let Some(dir) = target.parent() {
+                fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+            }
+            for (from, to) in sidecars(file).into_iter().zip(sidecars(&target)) {
+                if from.exists() {
+                    move_file(&from, &to)?;
+                }
+            }
+            move_file(file, &target)?;
+            info!("Moved {:?} into {:?}.", file, target);
+            Ok(target)
+        })
+        .collect()
+}
+
+// 같은 파일 시스템이면 rename 한다. 아니면 대상 디렉토리에 숨은 이름 (.<이름>.partial) 으로
+// 복사해 디스크에 쓴 뒤 rename 하므로, 대상 이름으로는 다 쓴 파일만 보인다.
+fn move_file(from: &FsPath, to: &FsPath) -> Result<()> {
+    match fs::rename(from, to) {
+        Ok(()) => return Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {}
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to move {:?} to {:?}", from, to));
+        }
+    }
+    let name = to.file_name().unwrap_or_default().to_string_lossy();
+    let partial = to.with_file_name(format!(".{}.partial", name));
+    let copied = fs::copy(from, &partial)
+        .and_then(|_| fs::File::open(&partial)?.sync_all())
+        .and_then(|()| fs::rename(&partial, to));
+    if let Err(e) = copied {
+        let _ = fs::remove_file(&partial);
+        return Err(e).with_context(|| format!("Failed to copy {:?} to {:?}", from, to));
+    }
+    fs::remove_file(from).with_context(|| format!("Failed to remove {:?}", from))
+}
+
+pub async fn handle_delete(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let path = resolve(&state.config.save_dir(), &name)?;
+    if !path.is_file() || !is_video(&path) {
+        return Err(ApiError::not_found(format!("Recording {} not found", name)));
+    }
+    if is_active_output(&state, &path) {
+        return Err(ApiError::conflict(format!(
+            "Recording {} is still being written",
+            name
+        )));
+    }
+
+    remove_recording(&path).map_err(|e| ApiError::internal(format!("{:#}", e)))?;
+    if let Err(e) = state.catalog.remove(&name) {
+        warn!("{:#}", e);
+    }
+    info!("Deleted recording {}", name);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct CleanupRequest {
+    // 이보다 오래된 파일 삭제
+    pub older_than_days: Option<u64>,
+    // 최신 파일부터 합산해 이 용량을 넘는 나머지 삭제
+    pub keep_newest_gb: Option<f64>,
+    // 최신 파일 이 개수만 남기고 나머지 삭제
+    pub keep_newest_count: Option<usize>,
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CleanupResponse {
+    pub dry_run: bool,
+    pub deleted: Vec<String>,
+    pub freed_bytes: u64,
+}
+
+// 삭제 대상 선택 (entries 는 최신순)
+fn select_for_cleanup(entries: &[RecordingEntry], request: &CleanupRequest) -> Vec<RecordingEntry> {
+    let cutoff = request
+        .older_than_days
+        .map(|days| Local::now() - chrono::Duration::days(days as i64));
+    let budget = request
+        .keep_newest_gb
+        .map(|gb| (gb * 1024.0 * 1024.0 * 1024.0) as u64);
+
+    let mut kept_bytes = 0u64;
+    entries
+        .iter()
+        .enumerate()
+        .filter(|(index, entry)| {
+            let too_old = cutoff.is_some_and(|cutoff| entry.created < cutoff);
+            let too_many = request
+                .keep_newest_count
+                .is_some_and(|count| *index >= count);
+            let over_budget = budget.is_some_and(|budget| {
+                if kept_bytes + entry.size > budget {
+                    true
+                } else {
+                    kept_bytes += entry.size;
+                    false
+                }
+            });
+            too_old || too_many || over_budget
+        })
+        .map(|(_, entry)| entry.clone())
+        .collect()
+}
+
+// 조건에 맞는 녹화를 지운다 (dry_run 이면 대상만 돌려준다). reason 은 로그와 이벤트에 남는다.
+// 녹화 중인 파일과 업로드한 뒤 로컬 사본을 지운 항목은 건너뛴다.
+pub fn clean_up(
+    state: &AppState,
+    request: &CleanupRequest,
+    reason: &str,
+) -> Result<CleanupResponse> {
+    let save_dir = state.config.save_dir();
+    let mut entries = state.catalog.query(&CatalogQuery::default())?;
+    entries.retain(|entry| save_dir.join(&entry.name).is_file());
+
+    let mut response = CleanupResponse {
+        dry_run: request.dry_run,
+        deleted: Vec::new(),
+        freed_bytes: 0,
+    };
+    for entry in select_for_cleanup(&entries, request) {
+        let path = save_dir.join(&entry.name);
+        if is_active_output(state, &path) {
+            continue;
+        }
+        if !request.dry_run {
+            if let Err(e) = remove_recording(&path) {
+                warn!("{}: {:#}", reason, e);
+                continue;
+            }
+            if let Err(e) = state.catalog.remove(&entry.name) {
+                warn!("{}: {:#}", reason, e);
+            }
+            info!("{}: deleted {}", reason, entry.name);
+            state.events.publish(
+                entry
+                    .metadata
+                    .as_ref()
+                    .map_or(Uuid::nil(), |metadata| metadata.session_id),
+                EventKind::RecordingDeleted {
+                    name: entry.name.clone(),
+                    reason: reason.to_string(),
+                },
+            );
+        }
+        response.freed_bytes += entry.size;
+        response.deleted.push(entry.name);
+    }
+    Ok(response)
+}
+
+pub async fn handle_cleanup(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<CleanupRequest>,
+) -> Result<Json<CleanupResponse>, ApiError> {
+    if request.older_than_days.is_none()
+        && request.keep_newest_gb.is_none()
+        && request.keep_newest_count.is_none()
+    {
+        return Err(ApiError::bad_request(
+            "older_than_days, keep_newest_gb or keep_newest_count is required",
+        ));
+    }
+    if request.keep_newest_gb.is_some_and(|gb| gb < 0.0) {
+        return Err(ApiError::bad_request("keep_newest_gb must not be negative"));
+    }
+
+    let response = tokio::task::spawn_blocking(move || clean_up(&state, &request, "Cleanup"))
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))?
+        .map_err(|e| ApiError::internal(format!("{:#}", e)))?;
+    Ok(Json(response))
+}
@@ -0,0 +1,382 @@
+// src/recovery.rs
+use crate::{
+    camera_handler::CaptureFormat,
+    compositor::{self, CompositeInput},
+    encoder::Encoder,
+    lens::LensCorrection,
+    metadata::{self, RecordingMetadata},
+    session::RecordingSession,
+    sink::{Container, Encoding, SinkConfig},
+};
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+const MARKER_EXTENSION: &str = "recording";
+
+// 녹화 중인 파일 옆에 두는 표시. 녹화가 끝까지 확정되면 지우므로, 서버를 시작할 때 남아 있으면
+// 그 녹화는 중간에 끊긴 것이다.
+pub fn marker_path(video: &Path) -> PathBuf {
+    video.with_extension(MARKER_EXTENSION)
+}
+
+// 끊긴 녹화를 살릴 때 필요한 정보 (녹화 시작 시점의 값)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Marker {
+    pub session_id: Uuid,
+    pub started_at: DateTime<Local>,
+    pub cameras: Vec<u32>,
+    pub formats: BTreeMap<u32, CaptureFormat>,
+    pub requested_fps: u32,
+    pub camera_width: u32,
+    pub camera_height: u32,
+    pub container: Container,
+    // 가림 영역이 있는 카메라의 필터. 복구할 때도 칠하지 않은 원본을 남기지 않는다.
+    #[serde(default)]
+    pub mask_filters: BTreeMap<u32, String>,
+    // 90/270 도로 돌려 녹화하던 카메라의 회전 필터
+    #[serde(default)]
+    pub rotate_filters: BTreeMap<u32, String>,
+    // 렌즈 왜곡을 펴던 카메라 (맵 파일은 임시 디렉토리에 있어 복구할 때 다시 만든다)
+    #[serde(default)]
+    pub lens_corrections: BTreeMap<u32, LensCorrection>,
+    #[serde(default)]
+    pub project: Option<String>,
+}
+
+impl Marker {
+    pub fn new(session: &RecordingSession, cameras: &[u32], started_at: DateTime<Local>) -> Self {
+        let config = &session.config;
+        Self {
+            session_id: session.id,
+            started_at,
+            cameras: cameras.to_vec(),
+            formats: cameras
+                .iter()
+                .map(|&camera| (camera, config.format_for(camera)))
+                .collect(),
+            requested_fps: config.fps,
+            camera_width: config.width,
+            camera_height: config.height,
+            container: config.sink.container,
+            mask_filters: cameras
+                .iter()
+                .filter_map(|&camera| Some((camera, config.mask_filter(camera)?)))
+                .collect(),
+            rotate_filters: cameras
+                .iter()
+                .filter_map(|&camera| Some((camera, config.rotate_filter(camera)?)))
+                .collect(),
+            lens_corrections: config
+                .lens_corrections
+                .iter()
+                .filter(|lens| lens.enabled && cameras.contains(&lens.camera))
+                .map(|lens| (lens.camera, lens.clone()))
+                .collect(),
+            project: config.project.clone(),
+        }
+    }
+}
+
+// 녹화를 시작할 때 부른다.
+pub fn begin(video: &Path, marker: &Marker) -> Result<()> {
+    let path = marker_path(video);
+    let json = serde_json::to_vec_pretty(marker)?;
+    fs::write(&path, json).with_context(|| format!("Failed to write {:?}", path))
+}
+
+// 녹화 파일을 모두 확정한 뒤 부른다.
+pub fn finish(video: &Path) {
+    let path = marker_path(video);
+    if let Err(e) = fs::remove_file(&path) {
+        warn!("Failed to remove {:?}: {}", path, e);
+    }
+}
+
+// 저장 디렉토리에 남은 표시마다 카메라별 임시 .h264 (재연결 구간, 분할 세그먼트 포함) 를
+// 카메라 한 대당 한 파일로 이어 붙여 container 에 담는다. 합성이나 오버레이는 다시 하지 않는다.
+// 살린 파일 목록을 돌려준다.
+pub fn recover(save_dir: &Path) -> Result<Vec<PathBuf>> {
+    if !save_dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut markers = Vec::new();
+    find_markers(save_dir, &mut markers)?;
+    markers.sort();
+
+    let mut recovered = Vec::new();
+    for path in markers {
+        let marker = fs::read(&path)
+            .with_context(|| format!("Failed to read {:?}", path))
+            .and_then(|json| {
+                serde_json::from_slice::<Marker>(&json)
+                    .with_context(|| format!("Invalid recording marker {:?}", path))
+            });
+        match marker {
+            Ok(marker) => {
+                info!(
+                    "Recording {} was interrupted. Recovering cameras {:?}...",
+                    marker.session_id, marker.cameras
+                );
+                recovered.extend(recover_session(&path, &marker));
+            }
+            Err(e) => warn!("{:#}. Leaving its files as they are.", e),
+        }
+        if let Err(e) = fs::remove_file(&path) {
+            warn!("Failed to remove {:?}: {}", path, e);
+        }
+    }
+    Ok(recovered)
+}
+
+// filename 틀로 하위 디렉토리에 녹화했을 수 있으므로 아래 디렉토리까지 찾는다.
+fn find_markers(dir: &Path, markers: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {:?}", dir))? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            find_markers(&path, markers)?;
+        } else if path.extension().is_some_and(|ext| ext == MARKER_EXTENSION) {
+            markers.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn recover_session(marker_path: &Path, marker: &Marker) -> Vec<PathBuf> {
+    let dir = marker_path.parent().unwrap_or(Path::new("."));
+    let stem = marker_path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    // 녹화가 끝났을 때와 같은 이름: 한 대면 <stem>.<확장자>, 여러 대면 카메라별 파일
+    let single = marker.cameras.len() == 1;
+
+    let _ = fs::remove_file(dir.join(format!("{}_depth.h264", stem)));
+    let mut recovered = Vec::new();
+    for &camera in &marker.cameras {
+        let prefix = format!("{}_cam{}", stem, camera);
+        let output = if single {
+            dir.join(format!("{}.{}", stem, marker.container.extension()))
+        } else {
+            dir.join(format!("{}.{}", prefix, marker.container.extension()))
+        };
+        match recover_camera(dir, &prefix, camera, marker, &output) {
+            Ok(Some(path)) => {
+                info!("camera {}: recovered {:?}.", camera, path);
+                recovered.push(path);
+            }
+            Ok(None) => info!("camera {}: nothing left to recover.", camera),
+            Err(e) => warn!("camera {}: failed to recover: {:#}", camera, e),
+        }
+    }
+    recovered
+}
+
+fn recover_camera(
+    dir: &Path,
+    prefix: &str,
+    camera: u32,
+    marker: &Marker,
+    output: &Path,
+) -> Result<Option<PathBuf>> {
+    // 중간 파일: 녹화 전 영상을 붙이던 중이거나 끊긴 구간을 채우던 중이면 완전하지 않다.
+    for name in [
+        format!("{}.preroll.h264", prefix),
+        format!("{}_0000.preroll.h264", prefix),
+        format!("{}.joined.gap.h264", prefix),
+    ] {
+        let _ = fs::remove_file(dir.join(name));
+    }
+
+    let (pieces, pts_files) = pieces(dir, prefix)?;
+    let joined = dir.join(format!("{}.joined.h264", prefix));
+    let source = match pieces.as_slice() {
+        // 재연결 구간을 합친 뒤 원본을 지우고 끊겼다면 합친 파일만 남는다.
+        [] if joined.exists() => joined.clone(),
+        [] => return Ok(None),
+        [only] => only.clone(),
+        _ => {
+            let mut output =
+                File::create(&joined).with_context(|| format!("Failed to create {:?}", joined))?;
+            for piece in &pieces {
+                let mut input =
+                    File::open(piece).with_context(|| format!("Failed to open {:?}", piece))?;
+                io::copy(&mut input, &mut output)
+                    .with_context(|| format!("Failed to append {:?}", piece))?;
+            }
+            joined.clone()
+        }
+    };
+    if fs::metadata(&source).map(|file| file.len()).unwrap_or(0) == 0 {
+        remove_all(pieces.iter().chain(&pts_files).chain([&joined]));
+        return Ok(None);
+    }
+
+    let (frames, span_ms) = count_frames(&pts_files);
+    let fps = marker
+        .formats
+        .get(&camera)
+        .map_or(marker.requested_fps, |format| format.fps);
+    let measured = if frames > pts_files.len() as u64 && span_ms > 0.0 {
+        (frames - pts_files.len() as u64) as f64 * 1000.0 / span_ms
+    } else {
+        0.0
+    };
+    let mask = marker.mask_filters.get(&camera);
+    let lens = marker.lens_corrections.get(&camera).and_then(|lens| {
+        let format = marker.formats.get(&camera)?;
+        lens.filter(format.width, format.height)
+    });
+    let filter: Vec<&str> = [lens.as_ref(), marker.rotate_filters.get(&camera), mask]
+        .into_iter()
+        .flatten()
+        .map(String::as_str)
+        .collect();
+    let input = CompositeInput {
+        path: source.clone(),
+        fps: measured,
+        filter: (!filter.is_empty()).then(|| filter.join(",")),
+        start_offset: 0.0,
+        retime: None,
+    };
+    let result = match input.filter {
+        // 렌즈 보정, 회전과 가림 영역은 프레임을 바꿔야 하므로 다시 인코딩한다 (녹화 때의 인코더 설정은 남아 있지 않다).
+        Some(_) => {
+            let encoding = Encoding {
+                encoder: Encoder::default(),
+                sink: SinkConfig {
+                    container: marker.container,
+                    ..SinkConfig::default()
+                },
+            };
+            compositor::reencode(&input, fps, &encoding, output)
+        }
+        None => compositor::remux(&input, fps, marker.container, output),
+    };
+    let path = match result {
+        Ok(()) => {
+            remove_all(pieces.iter().chain([&joined]));
+            output.to_path_buf()
+        }
+        Err(e) if mask.is_some() => {
+            remove_all(pieces.iter().chain(&pts_files).chain([&joined]));
+            bail!(
+                "{:#}; camera {} has privacy masks, so its unmasked stream was discarded",
+                e,
+                camera
+            );
+        }
+        Err(e) => {
+            warn!(
+                "camera {}: {:#}. Keeping the raw H.264 stream instead.",
+                camera, e
+            );
+            let raw = output.with_extension("h264");
+            fs::rename(&source, &raw)
+                .with_context(|| format!("Failed to move {:?} to {:?}", source, raw))?;
+            remove_all(pieces.iter().chain([&joined]).filter(|p| **p != raw));
+            raw
+        }
+    };
+
+    // 타임스탬프 파일은 finalize 와 같이 첫 파일만 영상 옆에 남긴다.
+    let mut pts_files = pts_files.into_iter();
+    if let Some(first) = pts_files.next() {
+        let pts = path.with_extension("pts");
+        if let Err(e) = fs::rename(&first, &pts) {
+            warn!("Failed to move {:?} to {:?}: {}", first, pts, e);
+        }
+    }
+    remove_all(pts_files.as_slice());
+
+    let duration = chrono::Duration::milliseconds(span_ms as i64);
+    let metadata = RecordingMetadata::recovered(
+        marker,
+        camera,
+        (frames, measured),
+        &path,
+        (marker.started_at, marker.started_at + duration),
+    );
+    if let Err(e) = metadata::write(&path, &metadata) {
+        warn!("{:#}", e);
+    }
+    Ok(Some(path))
+}
+
+// 카메라 한 대의 임시 파일 (녹화 순서) 과 타임스탬프 파일.
+// <prefix>.h264, 재연결하면 <prefix>_part2.h264 ..., 분할 녹화면 <prefix>_0000.h264 ...
+fn pieces(dir: &Path, prefix: &str) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+    let mut videos = BTreeMap::new();
+    let mut pts = BTreeMap::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {:?}", dir))? {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let Some(rest) = name.strip_prefix(prefix) else {
+            continue;
+        };
+        let (key, extension) = match rest.rsplit_once('.') {
+            Some(("", extension)) => (Some(0), extension),
+            Some((suffix, extension)) => {
+                let key = suffix
+                    .strip_prefix("_part")
+                    .or_else(|| suffix.strip_prefix('_').filter(|s| s.len() == 4))
+                    .filter(|s| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()))
+                    .and_then(|s| s.parse::<u64>().ok());
+                (key, extension)
+            }
+            None => (None, ""),
+        };
+        match (key, extension) {
+            (Some(key), "h264") => {
+                videos.insert(key, path);
+            }
+            (Some(key), "pts") => {
+                pts.insert(key, path);
+            }
+            _ => {}
+        }
+    }
+    Ok((videos.into_values().collect(), pts.into_values().collect()))
+}
+
+// 타임스탬프 파일들의 프레임 수와 (파일마다 첫 프레임부터 마지막 프레임까지) 길이의 합 (밀리초)
+fn count_frames(pts_files: &[PathBuf]) -> (u64, f64) {
+    let mut frames = 0;
+    let mut span_ms = 0.0;
+    for path in pts_files {
+        let Ok(contents) = fs::read_to_string(path) else {
+            continue;
+        };
+        let values: Vec<f64> = contents
+            .lines()
+            .filter_map(|line| line.trim().parse().ok())
+            .collect();
+        frames += values.len() as u64;
+        if let (Some(first), Some(last)) = (values.first(), values.last()) {
+            span_ms += (last - first).max(0.0);
+        }
+    }
+    (frames, span_ms)
+}
+
+fn remove_all<'a>(paths: impl IntoIterator<Item = &'a PathBuf>) {
+    for path in paths {
+        if path.exists()
+            && let Err(e) = fs::remove_file(path)
+        {
+            warn!("Failed to remove {:?}: {}", path, e);
+        }
+    }
+}
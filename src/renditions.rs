@@ -0,0 +1,254 @@
+// src/renditions.rs
+use crate::{compositor::FFMPEG, config, metadata};
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::{info, warn};
+
+// GET /recordings/{name}?format=mp4&height=480 으로 요청한 브라우저용 변환본 (H.264 + AAC MP4)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RenditionConfig {
+    // 변환본을 모아 두는 디렉토리. 비워 두면 임시 디렉토리 아래 server_renditions
+    pub cache_dir: String,
+    // 변환본 크기 합의 상한 (MB). 넘치면 가장 오래 쓰지 않은 것부터 지운다.
+    pub max_cache_mb: u64,
+    // 요청할 수 있는 가장 큰 height
+    pub max_height: u32,
+    // libx264 품질 (낮을수록 좋고 크다)
+    pub crf: u32,
+}
+
+impl Default for RenditionConfig {
+    fn default() -> Self {
+        Self {
+            cache_dir: String::new(),
+            max_cache_mb: 2048,
+            max_height: 1080,
+            crf: 23,
+        }
+    }
+}
+
+impl RenditionConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.max_cache_mb == 0 {
+            bail!("renditions.max_cache_mb must be non-zero");
+        }
+        if self.max_height < 16 {
+            bail!("renditions.max_height must be at least 16");
+        }
+        if self.crf > 51 {
+            bail!("renditions.crf must be between 0 and 51");
+        }
+        Ok(())
+    }
+
+    pub fn cache_dir(&self) -> PathBuf {
+        if self.cache_dir.trim().is_empty() {
+            std::env::temp_dir().join("server_renditions")
+        } else {
+            config::expand_path(&self.cache_dir)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RenditionFormat {
+    Mp4,
+}
+
+// GET /recordings/{name} 의 쿼리. 둘 다 없으면 파일을 그대로 보낸다.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct RenditionQuery {
+    pub format: Option<RenditionFormat>,
+    // 세로 크기. 원본보다 크게 늘리지는 않는다.
+    pub height: Option<u32>,
+}
+
+impl RenditionQuery {
+    pub fn is_requested(&self) -> bool {
+        self.format.is_some() || self.height.is_some()
+    }
+}
+
+pub struct Renditions {
+    config: RenditionConfig,
+    // 만들고 있는 변환본마다 하나. 같은 변환본을 동시에 요청하면 먼저 온 쪽이 끝나기를 기다린다.
+    pending: Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl Renditions {
+    pub fn new(config: &RenditionConfig) -> Self {
+        Self {
+            config: config.clone(),
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn max_height(&self) -> u32 {
+        self.config.max_height
+    }
+
+    // source 의 변환본 경로. 없으면 만든다 (원본이 바뀌면 이름이 달라져 새로 만든다).
+    pub async fn get(&self, source: &Path, query: RenditionQuery) -> Result<PathBuf> {
+        let rendition = self.path_for(source, query)?;
+        let lock = self
+            .pending
+            .lock()
+            .unwrap()
+            .entry(rendition.clone())
+            .or_default()
+            .clone();
+        let guard = lock.lock().await;
+        let result = if rendition.exists() {
+            touch(&rendition);
+            Ok(())
+        } else {
+            let (source, target, crf) = (source.to_path_buf(), rendition.clone(), self.config.crf);
+            tokio::task::spawn_blocking(move || transcode(&source, &target, query, crf))
+                .await
+                .context("Transcoding task failed")
+                .and_then(|result| result)
+        };
+        drop(guard);
+        self.pending.lock().unwrap().remove(&rendition);
+        result?;
+        if let Err(e) = self.evict(&rendition) {
+            warn!("Failed to trim the rendition cache: {:#}", e);
+        }
+        Ok(rendition)
+    }
+
+    fn path_for(&self, source: &Path, query: RenditionQuery) -> Result<PathBuf> {
+        let file = fs::metadata(source).with_context(|| format!("Failed to read {:?}", source))?;
+        let modified = file
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |modified| modified.as_millis());
+        let mut hasher = DefaultHasher::new();
+        source.hash(&mut hasher);
+        file.len().hash(&mut hasher);
+        modified.hash(&mut hasher);
+        query.height.hash(&mut hasher);
+        let stem = source.file_stem().unwrap_or_default().to_string_lossy();
+        let height = query
+            .height
+            .map_or_else(|| "source".to_string(), |height| format!("{}p", height));
+        Ok(self.config.cache_dir().join(format!(
+            "{}_{}_{:016x}.mp4",
+            stem,
+            height,
+            hasher.finish()
+        )))
+    }
+
+    // max_cache_mb 를 넘으면 마지막으로 쓴 시각이 오래된 변환본부터 지운다 (keep 은 남긴다).
+    fn evict(&self, keep: &Path) -> Result<()> {
+        let dir = self.config.cache_dir();
+        let mut files: Vec<(PathBuf, u64, SystemTime)> = fs::read_dir(&dir)
+            .with_context(|| format!("Failed to read {:?}", dir))?
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let file = entry.metadata().ok()?;
+                let path = entry.path();
+                let name = path.file_name()?.to_string_lossy().into_owned();
+                (file.is_file() && name.ends_with(".mp4") && !name.ends_with(".partial.mp4"))
+                    .then(|| (path, file.len(), file.modified().unwrap_or(UNIX_EPOCH)))
+            })
+            .collect();
+        files.sort_by_key(|(_, _, modified)| *modified);
+        let limit = self.config.max_cache_mb * 1024 * 1024;
+        let mut total: u64 = files.iter().map(|(_, size, _)| size).sum();
+        for (path, size, _) in files {
+            if total <= limit {
+                break;
+            }
+            if path == keep || self.pending.lock().unwrap().contains_key(&path) {
+                continue;
+            }
+            fs::remove_file(&path).with_context(|| format!("Failed to delete {:?}", path))?;
+            info!("Removed cached rendition {:?}.", path);
+            total = total.saturating_sub(size);
+        }
+        Ok(())
+    }
+}
+
+// 캐시에서 꺼낼 때마다 수정 시각을 바꿔 evict 가 최근에 쓴 것을 남기게 한다.
+fn touch(path: &Path) {
+    if let Err(e) = fs::File::options()
+        .append(true)
+        .open(path)
+        .and_then(|file| file.set_modified(SystemTime::now()))
+    {
+        warn!("Failed to touch {:?}: {}", path, e);
+    }
+}
+
+// 이미 H.264 이고 크기를 바꾸지 않으면 영상은 복사하고 소리만 AAC 로 바꾼다.
+// 파일 앞에 moov 를 두어 (+faststart) 다 받기 전에 재생하고 건너뛸 수 있게 한다.
+fn transcode(source: &Path, rendition: &Path, query: RenditionQuery, crf: u32) -> Result<()> {
+    let dir = rendition.parent().context("Rendition path has no parent")?;
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    let copy_video = query.height.is_none()
+        && metadata::read(source).is_some_and(|metadata| metadata.codec.as_deref() == Some("h264"));
+    let partial = rendition.with_extension("partial.mp4");
+    let mut command = Command::new(FFMPEG);
+    command
+        .arg("-y")
+        .arg("-loglevel")
+        .arg("error")
+        .arg("-i")
+        .arg(source)
+        .arg("-map")
+        .arg("0:v:0")
+        .arg("-map")
+        .arg("0:a:0?");
+    if copy_video {
+        command.arg("-c:v").arg("copy");
+    } else {
+        command
+            .arg("-c:v")
+            .arg("libx264")
+            .arg("-preset")
+            .arg("veryfast")
+            .arg("-crf")
+            .arg(crf.to_string())
+            .arg("-pix_fmt")
+            .arg("yuv420p");
+        if let Some(height) = query.height {
+            command
+                .arg("-vf")
+                .arg(format!("scale=-2:'min({},ih)'", height));
+        }
+    }
+    let status = command
+        .arg("-c:a")
+        .arg("aac")
+        .arg("-b:a")
+        .arg("128k")
+        .arg("-movflags")
+        .arg("+faststart")
+        .arg(&partial)
+        .status()
+        .context("Failed to run ffmpeg")?;
+    if !status.success() {
+        let _ = fs::remove_file(&partial);
+        bail!("ffmpeg failed to transcode {:?}: {}", source, status);
+    }
+    fs::rename(&partial, rendition)
+        .with_context(|| format!("Failed to move {:?} to {:?}", partial, rendition))?;
+    info!("Transcoded {:?} into {:?}.", source, rendition);
+    Ok(())
+}
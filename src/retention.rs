@@ -0,0 +1,122 @@
+// src/retention.rs
+use crate::{
+    AppState,
+    recordings::{self, CleanupRequest},
+};
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use std::{sync::Arc, time::Duration};
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    // true 면 아래 규칙을 주기적으로 적용해 오래된 녹화부터 지운다.
+    pub enabled: bool,
+    // 규칙을 확인하는 간격 (초). 서버가 시작할 때 한 번 바로 확인한다.
+    pub interval_secs: u64,
+    // 이보다 오래된 녹화 삭제
+    pub max_age_days: Option<u64>,
+    // 최신 녹화부터 합산해 이 용량을 넘는 나머지 삭제
+    pub max_total_gb: Option<f64>,
+    // 최신 녹화 이 개수만 남긴다.
+    pub max_files: Option<usize>,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: 3600,
+            max_age_days: None,
+            max_total_gb: None,
+            max_files: None,
+        }
+    }
+}
+
+impl RetentionConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.interval_secs == 0 {
+            bail!("retention.interval_secs must be non-zero");
+        }
+        if self.max_total_gb.is_some_and(|gb| gb < 0.0) {
+            bail!("retention.max_total_gb must not be negative");
+        }
+        if self.enabled
+            && self.max_age_days.is_none()
+            && self.max_total_gb.is_none()
+            && self.max_files.is_none()
+        {
+            bail!("retention needs max_age_days, max_total_gb or max_files when enabled");
+        }
+        Ok(())
+    }
+
+    fn request(&self) -> CleanupRequest {
+        CleanupRequest {
+            older_than_days: self.max_age_days,
+            keep_newest_gb: self.max_total_gb,
+            keep_newest_count: self.max_files,
+            dry_run: false,
+        }
+    }
+}
+
+// 보존 규칙을 interval_secs 마다 적용한다 (켠 경우에만). POST /config 로 규칙이 바뀌면 새 규칙으로 다시 시작한다.
+pub async fn run(state: Arc<AppState>) {
+    let mut settings = state.settings.retention();
+    loop {
+        let config = settings.borrow_and_update().clone();
+        tokio::select! {
+            () = enforce(&state, &config), if config.enabled => {}
+            changed = settings.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+                if !settings.borrow().enabled {
+                    info!("Retention: disabled.");
+                }
+            }
+        }
+    }
+}
+
+async fn enforce(state: &Arc<AppState>, config: &RetentionConfig) {
+    let mut rules = Vec::new();
+    if let Some(days) = config.max_age_days {
+        rules.push(format!("older than {} day(s)", days));
+    }
+    if let Some(gb) = config.max_total_gb {
+        rules.push(format!("beyond {} GB in total", gb));
+    }
+    if let Some(count) = config.max_files {
+        rules.push(format!("beyond the newest {} file(s)", count));
+    }
+    info!(
+        "Retention: deleting recordings {}, checked every {}s.",
+        rules.join(" or "),
+        config.interval_secs
+    );
+
+    let mut interval = tokio::time::interval(Duration::from_secs(config.interval_secs));
+    loop {
+        interval.tick().await;
+        let task_state = state.clone();
+        let request = config.request();
+        let result = tokio::task::spawn_blocking(move || {
+            recordings::clean_up(&task_state, &request, "Retention")
+        })
+        .await;
+        match result {
+            Ok(Ok(response)) if !response.deleted.is_empty() => info!(
+                "Retention: removed {} recording(s), freeing {} bytes.",
+                response.deleted.len(),
+                response.freed_bytes
+            ),
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => warn!("Retention: {:#}", e),
+            Err(e) => warn!("Retention task failed: {}", e),
+        }
+    }
+}
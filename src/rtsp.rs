@@ -0,0 +1,477 @@
+// src/rtsp.rs
+use crate::{
+    auth::AuthConfig,
+    stream::{AccessUnit, CLOCK_RATE, StreamHub, View},
+};
+use anyhow::{Context, Result, bail};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, net::IpAddr, sync::Arc};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{
+        TcpListener, TcpStream,
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+    },
+    sync::{broadcast, mpsc},
+};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+// RTP 패킷 하나에 담는 최대 페이로드 (TCP 로 보내지만 일반적인 MTU 에 맞춘다)
+const MAX_PAYLOAD: usize = 1400;
+
+const RTP_PAYLOAD_TYPE: u8 = 96;
+const SESSION_TIMEOUT_SECS: u32 = 60;
+
+// 요청 본문의 최대 크기. 여기서 받는 요청에는 본문이 쓰이지 않으므로 넘으면 연결을 끊는다.
+const MAX_BODY: usize = 4096;
+
+const AUTH_REALM: &str = "Camera server";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RtspConfig {
+    // true 면 rtsp://<host>:<port>/<path> 로 카메라 영상을 내보낸다.
+    pub enabled: bool,
+    // 다른 기기의 NVR 에 내보내려면 "0.0.0.0" 등으로 바꾼다 ([auth] 키가 있어야 한다).
+    pub bind: String,
+    pub port: u16,
+    pub path: String,
+}
+
+impl Default for RtspConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind: "127.0.0.1".to_string(),
+            port: 8554,
+            path: "combined".to_string(),
+        }
+    }
+}
+
+impl RtspConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.port == 0 {
+            bail!("rtsp.port must be non-zero");
+        }
+        if self.path.is_empty() || self.path.contains('/') {
+            bail!("rtsp.path must be a single non-empty path segment");
+        }
+        Ok(())
+    }
+
+    // 이 기기에서만 접속할 수 있는 주소에 여는지
+    pub fn is_loopback(&self) -> bool {
+        self.bind == "localhost"
+            || self
+                .bind
+                .parse::<IpAddr>()
+                .is_ok_and(|address| address.is_loopback())
+    }
+}
+
+pub struct RtspServer {
+    config: RtspConfig,
+    // 키가 있으면 DESCRIBE, SETUP, PLAY 에 Basic 인증을 요구한다 (비밀번호가 키).
+    auth: AuthConfig,
+    hub: Arc<StreamHub>,
+}
+
+impl RtspServer {
+    pub fn new(config: &RtspConfig, auth: &AuthConfig, hub: Arc<StreamHub>) -> Self {
+        Self {
+            config: config.clone(),
+            auth: auth.clone(),
+            hub,
+        }
+    }
+
+    // Authorization: Basic base64(<이름>:<키>) 의 키가 [auth] 에 있는지. 이름은 보지 않는다.
+    fn authorized(&self, request: &RtspRequest) -> bool {
+        if !self.auth.enabled() {
+            return true;
+        }
+        request
+            .header("Authorization")
+            .and_then(|value| value.strip_prefix("Basic "))
+            .and_then(|encoded| BASE64.decode(encoded.trim()).ok())
+            .and_then(|decoded| String::from_utf8(decoded).ok())
+            .and_then(|credentials| {
+                let (_, key) = credentials.split_once(':')?;
+                self.auth.user_for_key(key)
+            })
+            .is_some()
+    }
+}
+
+// RTSP 리스너를 시작한다 (설정에서 켠 경우에만).
+pub fn spawn(server: Arc<RtspServer>) {
+    if !server.config.enabled {
+        return;
+    }
+    tokio::spawn(async move {
+        if let Err(e) = listen(server).await {
+            warn!("RTSP server stopped: {:#}", e);
+        }
+    });
+}
+
+async fn listen(server: Arc<RtspServer>) -> Result<()> {
+    let address = format!("{}:{}", server.config.bind, server.config.port);
+    let listener = TcpListener::bind(&address)
+        .await
+        .with_context(|| format!("Failed to bind RTSP address {}", address))?;
+    info!(
+        "RTSP stream available at rtsp://{}/{}",
+        address, server.config.path
+    );
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let server = server.clone();
+        tokio::spawn(async move {
+            info!("RTSP client {} connected.", peer);
+            if let Err(e) = handle_client(&server, stream).await {
+                warn!("RTSP client {}: {:#}", peer, e);
+            }
+            info!("RTSP client {} disconnected.", peer);
+        });
+    }
+}
+
+struct RtspRequest {
+    method: String,
+    url: String,
+    headers: HashMap<String, String>,
+}
+
+impl RtspRequest {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(&name.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+}
+
+// 클라이언트가 보내는 요청을 읽는다. 요청 사이에 끼어 오는 RTCP($ 프레임)는 버린다.
+async fn read_requests(read: OwnedReadHalf, requests: mpsc::Sender<RtspRequest>) -> Result<()> {
+    let mut reader = BufReader::new(read);
+    loop {
+        let buffered = reader.fill_buf().await?;
+        if buffered.is_empty() {
+            return Ok(());
+        }
+        if buffered[0] == b'$' {
+            let mut header = [0u8; 4];
+            reader.read_exact(&mut header).await?;
+            let length = u16::from_be_bytes([header[2], header[3]]) as usize;
+            let mut skipped = vec![0u8; length];
+            reader.read_exact(&mut skipped).await?;
+            continue;
+        }
+
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(());
+        }
+        let mut parts = line.split_whitespace();
+        let (Some(method), Some(url)) = (parts.next(), parts.next()) else {
+            continue;
+        };
+        let mut request = RtspRequest {
+            method: method.to_string(),
+            url: url.to_string(),
+            headers: HashMap::new(),
+        };
+        loop {
+            line.clear();
+            if reader.read_line(&mut line).await? == 0 {
+                return Ok(());
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                request
+                    .headers
+                    .insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+            }
+        }
+        let length: usize = request
+            .header("Content-Length")
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(0);
+        if length > MAX_BODY {
+            bail!("request body of {} bytes is too large", length);
+        }
+        let mut body = vec![0u8; length];
+        reader.read_exact(&mut body).await?;
+
+        if requests.send(request).await.is_err() {
+            return Ok(());
+        }
+    }
+}
+
+// 재생 중인 클라이언트 하나의 RTP 상태
+struct Player {
+    channel: u8,
+    sequence: u16,
+    ssrc: u32,
+    // 처음이나 뒤처진 뒤에는 키프레임부터 보낸다.
+    waiting_for_keyframe: bool,
+}
+
+async fn handle_client(server: &RtspServer, stream: TcpStream) -> Result<()> {
+    let (read, mut write) = stream.into_split();
+    let (sender, mut requests) = mpsc::channel(8);
+    let reader = tokio::spawn(read_requests(read, sender));
+
+    let session_id = Uuid::new_v4().simple().to_string()[..16].to_string();
+    // SETUP 을 받기 전에는 None
+    let mut channel: Option<u8> = None;
+    let mut player: Option<Player> = None;
+    // 연결하는 동안 시청자로 세어 PLAY 전에 소스를 미리 연다.
+    let mut subscription = server.hub.subscribe(View::Composite);
+
+    let result = loop {
+        tokio::select! {
+            request = requests.recv() => {
+                let Some(request) = request else { break Ok(()) };
+                let teardown = request.method == "TEARDOWN";
+                let response = respond(server, &request, &session_id, &mut channel, &mut player);
+                if let Err(e) = write.write_all(response.as_bytes()).await {
+                    break Err(e.into());
+                }
+                if player.is_some() && request.method == "PLAY" {
+                    // 이전에 쌓인 프레임은 버리고 지금부터 보낸다.
+                    subscription = server.hub.subscribe(View::Composite);
+                }
+                if teardown {
+                    break Ok(());
+                }
+            }
+            unit = subscription.units.recv(), if player.is_some() => {
+                let Some(player) = player.as_mut() else { continue };
+                match unit {
+                    Ok(unit) => {
+                        if let Err(e) = send_unit(&mut write, player, &unit).await {
+                            break Err(e.into());
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => player.waiting_for_keyframe = true,
+                    Err(broadcast::error::RecvError::Closed) => break Ok(()),
+                }
+            }
+        }
+    };
+    reader.abort();
+    result
+}
+
+fn respond(
+    server: &RtspServer,
+    request: &RtspRequest,
+    session_id: &str,
+    channel: &mut Option<u8>,
+    player: &mut Option<Player>,
+) -> String {
+    let cseq = request.header("CSeq").unwrap_or("0");
+    let reply = |status: &str, headers: &[String], body: &str| {
+        let mut response = format!("RTSP/1.0 {}\r\nCSeq: {}\r\n", status, cseq);
+        for header in headers {
+            response.push_str(header);
+            response.push_str("\r\n");
+        }
+        if !body.is_empty() {
+            response.push_str(&format!("Content-Length: {}\r\n", body.len()));
+        }
+        response.push_str("\r\n");
+        response.push_str(body);
+        response
+    };
+    let session_header = format!("Session: {};timeout={}", session_id, SESSION_TIMEOUT_SECS);
+    // 클라이언트가 보낸 세션 ("<id>;timeout=..." 의 id)
+    let session = request
+        .header("Session")
+        .map(|value| value.split(';').next().unwrap_or_default().trim());
+    let same_session = session == Some(session_id);
+
+    match request.method.as_str() {
+        "OPTIONS" => reply(
+            "200 OK",
+            &["Public: OPTIONS, DESCRIBE, SETUP, PLAY, TEARDOWN, GET_PARAMETER".to_string()],
+            "",
+        ),
+        "DESCRIBE" | "SETUP" | "PLAY" if !server.authorized(request) => reply(
+            "401 Unauthorized",
+            &[format!("WWW-Authenticate: Basic realm=\"{}\"", AUTH_REALM)],
+            "",
+        ),
+        "DESCRIBE" => {
+            if !matches_path(&request.url, &server.config.path) {
+                return reply("404 Not Found", &[], "");
+            }
+            let sdp = format!(
+                "v=0\r\no=- 0 0 IN IP4 0.0.0.0\r\ns=Camera server\r\nc=IN IP4 0.0.0.0\r\nt=0 0\r\n\
+                 m=video 0 RTP/AVP {pt}\r\na=rtpmap:{pt} H264/{clock}\r\n\
+                 a=fmtp:{pt} packetization-mode=1\r\na=control:trackID=0\r\n",
+                pt = RTP_PAYLOAD_TYPE,
+                clock = CLOCK_RATE
+            );
+            reply(
+                "200 OK",
+                &[
+                    "Content-Type: application/sdp".to_string(),
+                    format!("Content-Base: {}/", request.url.trim_end_matches('/')),
+                ],
+                &sdp,
+            )
+        }
+        "SETUP" => {
+            if !matches_stream(&request.url, &server.config.path) {
+                return reply("404 Not Found", &[], "");
+            }
+            if session.is_some() && !same_session {
+                return reply("454 Session Not Found", &[], "");
+            }
+            // RTP over TCP (interleaved) 만 지원한다. UDP 는 NAT/방화벽 뒤에서 잘 안 되기 때문이다.
+            let transport = request.header("Transport").unwrap_or("");
+            if !transport.contains("RTP/AVP/TCP") && !transport.contains("interleaved") {
+                return reply("461 Unsupported Transport", &[], "");
+            }
+            let interleaved: u8 = transport
+                .split(';')
+                .find_map(|part| part.trim().strip_prefix("interleaved="))
+                .and_then(|range| range.split('-').next())
+                .and_then(|first| first.parse().ok())
+                .unwrap_or(0)
+                .min(u8::MAX - 1);
+            *channel = Some(interleaved);
+            reply(
+                "200 OK",
+                &[
+                    format!(
+                        "Transport: RTP/AVP/TCP;unicast;interleaved={}-{}",
+                        interleaved,
+                        interleaved + 1
+                    ),
+                    session_header,
+                ],
+                "",
+            )
+        }
+        "PLAY" => {
+            if !matches_stream(&request.url, &server.config.path) {
+                return reply("404 Not Found", &[], "");
+            }
+            let Some(channel) = *channel else {
+                return reply("455 Method Not Valid in This State", &[], "");
+            };
+            if !same_session {
+                return reply("454 Session Not Found", &[], "");
+            }
+            *player = Some(Player {
+                channel,
+                sequence: 0,
+                ssrc: rand_u32(),
+                waiting_for_keyframe: true,
+            });
+            reply(
+                "200 OK",
+                &[session_header, "Range: npt=0.000-".to_string()],
+                "",
+            )
+        }
+        "TEARDOWN" => {
+            if !same_session {
+                return reply("454 Session Not Found", &[], "");
+            }
+            *player = None;
+            reply("200 OK", &[session_header], "")
+        }
+        // 클라이언트의 keep-alive
+        "GET_PARAMETER" | "SET_PARAMETER" if session.is_some() && !same_session => {
+            reply("454 Session Not Found", &[], "")
+        }
+        "GET_PARAMETER" | "SET_PARAMETER" => reply("200 OK", &[session_header], ""),
+        _ => reply("501 Not Implemented", &[], ""),
+    }
+}
+
+// rtsp://host:port/<path> 또는 그 아래 (쿼리, 끝의 / 무시)
+fn matches_path(url: &str, path: &str) -> bool {
+    let url = url.split('?').next().unwrap_or(url).trim_end_matches('/');
+    url.rsplit('/').next() == Some(path)
+}
+
+// DESCRIBE 에서 알린 스트림이나 그 트랙 (SDP 의 a=control:trackID=0)
+fn matches_stream(url: &str, path: &str) -> bool {
+    let url = url.split('?').next().unwrap_or(url).trim_end_matches('/');
+    matches_path(url.strip_suffix("/trackID=0").unwrap_or(url), path)
+}
+
+fn rand_u32() -> u32 {
+    let bytes = Uuid::new_v4().into_bytes();
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+// RFC 6184: 작은 NAL 은 그대로, 큰 NAL 은 FU-A 로 나누어 보낸다.
+async fn send_unit(
+    write: &mut OwnedWriteHalf,
+    player: &mut Player,
+    unit: &AccessUnit,
+) -> std::io::Result<()> {
+    if player.waiting_for_keyframe {
+        if !unit.keyframe {
+            return Ok(());
+        }
+        player.waiting_for_keyframe = false;
+    }
+
+    let mut packets: Vec<Vec<u8>> = Vec::new();
+    for nal in &unit.nals {
+        if nal.len() <= MAX_PAYLOAD {
+            packets.push(nal.clone());
+            continue;
+        }
+        let indicator = (nal[0] & 0xe0) | 28;
+        let kind = nal[0] & 0x1f;
+        let chunks: Vec<&[u8]> = nal[1..].chunks(MAX_PAYLOAD - 2).collect();
+        for (i, chunk) in chunks.iter().enumerate() {
+            let mut header = kind;
+            if i == 0 {
+                header |= 0x80;
+            }
+            if i == chunks.len() - 1 {
+                header |= 0x40;
+            }
+            let mut payload = Vec::with_capacity(chunk.len() + 2);
+            payload.push(indicator);
+            payload.push(header);
+            payload.extend_from_slice(chunk);
+            packets.push(payload);
+        }
+    }
+
+    let mut frame = Vec::new();
+    let count = packets.len();
+    for (i, payload) in packets.into_iter().enumerate() {
+        // 프레임의 마지막 패킷에 marker 비트
+        let marker = if i == count - 1 { 0x80 } else { 0 };
+        let length = (12 + payload.len()) as u16;
+        frame.push(b'$');
+        frame.push(player.channel);
+        frame.extend_from_slice(&length.to_be_bytes());
+        frame.push(0x80);
+        frame.push(marker | RTP_PAYLOAD_TYPE);
+        frame.extend_from_slice(&player.sequence.to_be_bytes());
+        frame.extend_from_slice(&unit.timestamp.to_be_bytes());
+        frame.extend_from_slice(&player.ssrc.to_be_bytes());
+        frame.extend_from_slice(&payload);
+        player.sequence = player.sequence.wrapping_add(1);
+    }
+    write.write_all(&frame).await
+}
@@ -0,0 +1,162 @@
+// src/scaling.rs
+use serde::{Deserialize, Serialize};
+
+// 분석 (감지, 깊이, 움직임, 색 맞춤) 용으로 디코딩할 때 프레임을 정해진 크기로 바꾸는 방식
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScaleMode {
+    // 비율을 무시하고 목표 크기로 늘인다.
+    Stretch,
+    // 비율을 유지한 채 목표 크기 안에 가장 크게 넣고 남는 곳을 검게 채운다.
+    #[default]
+    Letterbox,
+}
+
+// 목표 크기 안에서 원본 영상이 놓이는 자리 (픽셀)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placement {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// 원본 프레임을 width x height 로 바꾸는 단계. 디코더 (ffmpeg) 에 넣을 필터와,
+// 바꾼 프레임의 좌표를 원본 기준으로 되돌리는 계산을 함께 들고 있다.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scaling {
+    pub width: u32,
+    pub height: u32,
+    pub placement: Placement,
+}
+
+impl Scaling {
+    // 원본 크기와 상관없이 목표 크기를 가득 채운다.
+    pub fn stretch(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            placement: Placement {
+                x: 0,
+                y: 0,
+                width,
+                height,
+            },
+        }
+    }
+
+    // source 는 필터를 거쳐 디코더에 들어오는 프레임 크기 (RecordingConfig::frame_size)
+    pub fn new(
+        mode: ScaleMode,
+        (source_width, source_height): (u32, u32),
+        width: u32,
+        height: u32,
+    ) -> Self {
+        if mode == ScaleMode::Stretch || source_width == 0 || source_height == 0 {
+            return Self::stretch(width, height);
+        }
+        let scale = (width as f64 / source_width as f64).min(height as f64 / source_height as f64);
+        let fit =
+            |source: u32, target: u32| ((source as f64 * scale).round() as u32).clamp(1, target);
+        let (fit_width, fit_height) = (fit(source_width, width), fit(source_height, height));
+        Self {
+            width,
+            height,
+            placement: Placement {
+                x: (width - fit_width) / 2,
+                y: (height - fit_height) / 2,
+                width: fit_width,
+                height: fit_height,
+            },
+        }
+    }
+
+    // 크기를 바꾸는 ffmpeg 필터. 자리를 직접 계산해 넣으므로 source_point 와 어긋나지 않는다.
+    pub fn filter(&self) -> String {
+        let placement = &self.placement;
+        if placement.width == self.width && placement.height == self.height {
+            return format!("scale={}:{}", self.width, self.height);
+        }
+        format!(
+            "scale={}:{},setsar=1,pad={}:{}:{}:{}",
+            placement.width, placement.height, self.width, self.height, placement.x, placement.y
+        )
+    }
+
+    // 바꾼 프레임 기준 (0.0 ~ 1.0) 의 점을 원본 프레임 기준으로. 채운 여백은 가장자리로 붙인다.
+    pub fn source_point(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let placement = &self.placement;
+        let unmap = |value: f64, size: u32, offset: u32, inner: u32| {
+            ((value * size as f64 - offset as f64) / inner as f64).clamp(0.0, 1.0)
+        };
+        (
+            unmap(x, self.width, placement.x, placement.width),
+            unmap(y, self.height, placement.y, placement.height),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn letterboxes_wider_source() {
+        let scaling = Scaling::new(ScaleMode::Letterbox, (1920, 1080), 640, 640);
+        assert_eq!(
+            scaling.placement,
+            Placement {
+                x: 0,
+                y: 140,
+                width: 640,
+                height: 360
+            }
+        );
+        assert_eq!(scaling.filter(), "scale=640:360,setsar=1,pad=640:640:0:140");
+    }
+
+    #[test]
+    fn letterboxes_taller_source() {
+        let scaling = Scaling::new(ScaleMode::Letterbox, (720, 1280), 640, 640);
+        assert_eq!(
+            scaling.placement,
+            Placement {
+                x: 140,
+                y: 0,
+                width: 360,
+                height: 640
+            }
+        );
+        assert_eq!(scaling.filter(), "scale=360:640,setsar=1,pad=640:640:140:0");
+    }
+
+    #[test]
+    fn same_aspect_scales_without_padding() {
+        let scaling = Scaling::new(ScaleMode::Letterbox, (1280, 720), 640, 360);
+        assert_eq!(scaling, Scaling::stretch(640, 360));
+        assert_eq!(scaling.filter(), "scale=640:360");
+    }
+
+    #[test]
+    fn maps_points_back_to_source() {
+        let scaling = Scaling::new(ScaleMode::Letterbox, (1920, 1080), 640, 640);
+        assert_eq!(scaling.source_point((0.5, 0.5)), (0.5, 0.5));
+        // y = 230 px 는 그림 (140 ~ 500 px) 의 1/4 지점
+        assert_eq!(scaling.source_point((0.25, 230.0 / 640.0)), (0.25, 0.25));
+        // 여백 안의 점은 가장자리로 붙는다.
+        assert_eq!(scaling.source_point((0.0, 0.1)), (0.0, 0.0));
+        assert_eq!(scaling.source_point((1.0, 0.95)), (1.0, 1.0));
+
+        let stretched = Scaling::new(ScaleMode::Stretch, (1920, 1080), 640, 640);
+        assert_eq!(stretched.source_point((0.3, 0.7)), (0.3, 0.7));
+    }
+
+    #[test]
+    fn zero_size_source_stretches() {
+        for source in [(0, 1080), (1920, 0), (0, 0)] {
+            let scaling = Scaling::new(ScaleMode::Letterbox, source, 640, 480);
+            assert_eq!(scaling, Scaling::stretch(640, 480));
+            assert_eq!(scaling.filter(), "scale=640:480");
+        }
+    }
+}
@@ -0,0 +1,252 @@
+// src/scheduler.rs
+use crate::{ApiError, AppState, StartRequest, audit, start_recording};
+use anyhow::{Context, Result, bail};
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use chrono::{
+    Datelike, Duration as ChronoDuration, Local, NaiveDate, NaiveDateTime, NaiveTime, Weekday,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs, path::PathBuf, sync::Arc, time::Duration};
+use tracing::{info, warn};
+
+// 예약 시각을 지나고 이 시간 안에만 시작을 시도한다 (서버가 잠시 멈췄던 경우 대비).
+const START_WINDOW_SECONDS: i64 = 60;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Schedule {
+    #[serde(default)]
+    pub id: u64,
+    #[serde(default)]
+    pub name: String,
+    // 현지 시각, 예: "14:00"
+    pub start_time: NaiveTime,
+    // 녹화 시간 (초)
+    pub duration: u64,
+    // 반복 요일, 비어 있으면 매일 (date 가 있으면 그 날 한 번만)
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    #[serde(default)]
+    pub date: Option<NaiveDate>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    // /start 요청과 같은 형식의 녹화 설정 (max_duration 은 duration 으로 대체)
+    #[serde(default)]
+    pub recording: StartRequest,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl Schedule {
+    fn validate(&self) -> Result<()> {
+        if self.duration == 0 {
+            bail!("duration must be non-zero");
+        }
+        if self.date.is_some() && !self.days.is_empty() {
+            bail!("a schedule can have either a date or repeat days, not both");
+        }
+        Ok(())
+    }
+
+    // 해당 날짜에 실행되어야 한다면 그 시작 시각
+    fn occurrence_on(&self, date: NaiveDate) -> Option<NaiveDateTime> {
+        let runs_today = match self.date {
+            Some(once) => once == date,
+            None => self.days.is_empty() || self.days.contains(&date.weekday()),
+        };
+        runs_today.then(|| date.and_time(self.start_time))
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ScheduleFile {
+    next_id: u64,
+    schedules: Vec<Schedule>,
+}
+
+// 디스크(JSON)에 저장되는 예약 목록
+pub struct ScheduleStore {
+    path: PathBuf,
+    data: ScheduleFile,
+    // 같은 회차가 두 번 시작되지 않도록 마지막 실행 시각을 기록
+    last_fired: HashMap<u64, NaiveDateTime>,
+}
+
+impl ScheduleStore {
+    pub fn load(path: PathBuf) -> Result<Self> {
+        let data = if path.exists() {
+            let contents = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read schedules file: {:?}", path))?;
+            serde_json::from_str(&contents)
+                .with_context(|| format!("Invalid schedules file: {:?}", path))?
+        } else {
+            ScheduleFile {
+                next_id: 1,
+                schedules: Vec::new(),
+            }
+        };
+        Ok(Self {
+            path,
+            data,
+            last_fired: HashMap::new(),
+        })
+    }
+
+    fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {:?}", parent))?;
+        }
+        let contents = serde_json::to_string_pretty(&self.data)?;
+        fs::write(&self.path, contents)
+            .with_context(|| format!("Failed to write schedules file: {:?}", self.path))
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.schedules.len()
+    }
+}
+
+// 1초마다 예약을 확인해 시작 시각이 된 녹화를 시작한다.
+pub async fn run(state: Arc<AppState>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+
+        let now = Local::now().naive_local();
+        let due: Vec<Schedule> = {
+            let mut store = state.schedules.lock().unwrap();
+            let due: Vec<Schedule> = store
+                .data
+                .schedules
+                .iter()
+                .filter(|schedule| schedule.enabled)
+                .filter(|schedule| {
+                    schedule.occurrence_on(now.date()).is_some_and(|at| {
+                        now >= at
+                            && now < at + ChronoDuration::seconds(START_WINDOW_SECONDS)
+                            && store.last_fired.get(&schedule.id) != Some(&at)
+                    })
+                })
+                .cloned()
+                .collect();
+            for schedule in &due {
+                if let Some(at) = schedule.occurrence_on(now.date()) {
+                    store.last_fired.insert(schedule.id, at);
+                }
+            }
+            due
+        };
+
+        for schedule in due {
+            info!(
+                "Schedule {} ({}) is due. Starting a {}s recording.",
+                schedule.id, schedule.name, schedule.duration
+            );
+            let mut request = schedule.recording.clone();
+            request.max_duration = Some(schedule.duration);
+            let started = start_recording(state.clone(), request).await;
+            audit::record_command(
+                &state,
+                "schedule",
+                format!("start (schedule {})", schedule.id),
+                started.as_ref().map(|response| Some(response.0.session_id)),
+            );
+            if let Err(e) = started {
+                let message = format!(
+                    "Scheduled recording {} could not start: {}",
+                    schedule.id, e.message
+                );
+                warn!("{}", message);
+                state.errors.record(None, message);
+            }
+        }
+    }
+}
+
+fn store_error(e: anyhow::Error) -> ApiError {
+    ApiError::internal(format!("{:#}", e))
+}
+
+pub async fn handle_list(State(state): State<Arc<AppState>>) -> Json<Vec<Schedule>> {
+    Json(state.schedules.lock().unwrap().data.schedules.clone())
+}
+
+pub async fn handle_get(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<u64>,
+) -> Result<Json<Schedule>, ApiError> {
+    let store = state.schedules.lock().unwrap();
+    store
+        .data
+        .schedules
+        .iter()
+        .find(|schedule| schedule.id == id)
+        .cloned()
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found(format!("Schedule {} not found", id)))
+}
+
+pub async fn handle_create(
+    State(state): State<Arc<AppState>>,
+    Json(mut schedule): Json<Schedule>,
+) -> Result<(StatusCode, Json<Schedule>), ApiError> {
+    schedule
+        .validate()
+        .map_err(|e| ApiError::bad_request(e.to_string()))?;
+
+    let mut store = state.schedules.lock().unwrap();
+    schedule.id = store.data.next_id;
+    store.data.next_id += 1;
+    store.data.schedules.push(schedule.clone());
+    store.save().map_err(store_error)?;
+
+    info!("Schedule {} created.", schedule.id);
+    Ok((StatusCode::CREATED, Json(schedule)))
+}
+
+pub async fn handle_update(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<u64>,
+    Json(mut schedule): Json<Schedule>,
+) -> Result<Json<Schedule>, ApiError> {
+    schedule
+        .validate()
+        .map_err(|e| ApiError::bad_request(e.to_string()))?;
+    schedule.id = id;
+
+    let mut store = state.schedules.lock().unwrap();
+    let existing = store
+        .data
+        .schedules
+        .iter_mut()
+        .find(|existing| existing.id == id)
+        .ok_or_else(|| ApiError::not_found(format!("Schedule {} not found", id)))?;
+    *existing = schedule.clone();
+    store.save().map_err(store_error)?;
+
+    info!("Schedule {} updated.", id);
+    Ok(Json(schedule))
+}
+
+pub async fn handle_delete(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<u64>,
+) -> Result<StatusCode, ApiError> {
+    let mut store = state.schedules.lock().unwrap();
+    let before = store.data.schedules.len();
+    store.data.schedules.retain(|schedule| schedule.id != id);
+    if store.data.schedules.len() == before {
+        return Err(ApiError::not_found(format!("Schedule {} not found", id)));
+    }
+    store.last_fired.remove(&id);
+    store.save().map_err(store_error)?;
+
+    info!("Schedule {} deleted.", id);
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,358 @@
+// src/selftest.rs
+use crate::{
+    AppState, StartRequest,
+    camera_handler::{self, RecordingConfig},
+    compositor::FFMPEG,
+    config::Config,
+    health::{self, Check},
+    sink::{self, Encoding, Pipeline},
+    source::TestPattern,
+};
+use anyhow::{Result, bail};
+use axum::{Json, extract::State, http::StatusCode};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::Path,
+    process::{Command, Stdio},
+    sync::Arc,
+    time::Instant,
+};
+use tracing::{info, warn};
+
+// 코덱마다 인코딩해 보는 짧은 영상
+const TEST_INPUT: &str = "testsrc=size=256x144:rate=24:duration=0.5";
+
+// 서버가 시작할 때 카메라, 인코더, 저장 디렉토리를 미리 확인해 첫 녹화 전에 잘못된 설정을 찾는다.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SelfTestConfig {
+    // false 면 시작할 때 돌리지 않고 처음 GET /selftest 를 받을 때 돌린다.
+    pub on_startup: bool,
+    // true 면 시작할 때 검사를 마친 뒤 요청을 받고, 실패한 항목이 있으면 서버를 끝낸다.
+    pub exit_on_failure: bool,
+}
+
+impl Default for SelfTestConfig {
+    fn default() -> Self {
+        Self {
+            on_startup: true,
+            exit_on_failure: false,
+        }
+    }
+}
+
+impl SelfTestConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.exit_on_failure && !self.on_startup {
+            bail!("self_test.exit_on_failure requires self_test.on_startup");
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CameraCheck {
+    pub camera: u32,
+    // "libcamera", "network", "device" 또는 "test_pattern"
+    pub kind: &'static str,
+    #[serde(flatten)]
+    pub check: Check,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CodecCheck {
+    // 녹화 설정과 프로필에서 쓰는 인코딩 방식 (실패하면 다음 것으로 넘어가는 순서)
+    pub sink: String,
+    pub duration_ms: u64,
+    #[serde(flatten)]
+    pub check: Check,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SelfTestReport {
+    pub ok: bool,
+    pub started_at: DateTime<Local>,
+    pub duration_ms: u64,
+    pub cameras: Vec<CameraCheck>,
+    pub codecs: Vec<CodecCheck>,
+    pub save_dir: Check,
+    pub staging_dir: Option<Check>,
+    pub disk_space: Check,
+}
+
+impl SelfTestReport {
+    fn log(&self) {
+        let checks = self
+            .cameras
+            .iter()
+            .map(|camera| {
+                (
+                    format!("camera {} ({})", camera.camera, camera.kind),
+                    &camera.check,
+                )
+            })
+            .chain(
+                self.codecs
+                    .iter()
+                    .map(|codec| (format!("encoding with {}", codec.sink), &codec.check)),
+            )
+            .chain([("save_dir".to_string(), &self.save_dir)])
+            .chain(
+                self.staging_dir
+                    .iter()
+                    .map(|check| ("staging_dir".to_string(), check)),
+            )
+            .chain([("disk space".to_string(), &self.disk_space)]);
+        for (name, check) in checks {
+            if check.ok {
+                info!("Self-test: {}: {}", name, check.detail);
+            } else {
+                warn!("Self-test: {} FAILED: {}", name, check.detail);
+            }
+        }
+        if self.ok {
+            info!("Self-test passed in {} ms.", self.duration_ms);
+        } else {
+            warn!(
+                "Self-test found problems in {} ms; see GET /selftest.",
+                self.duration_ms
+            );
+        }
+    }
+}
+
+// 마지막 검사 결과. 검사하는 동안 잠가 두어 GET /selftest 가 끝날 때까지 기다리게 한다.
+#[derive(Default)]
+pub struct SelfTest {
+    report: tokio::sync::Mutex<Option<SelfTestReport>>,
+}
+
+impl SelfTest {
+    // 검사를 돌려 결과를 남기고 로그에 적는다.
+    pub async fn run(&self, config: Arc<Config>, profiles: Vec<StartRequest>) -> SelfTestReport {
+        let mut report = self.report.lock().await;
+        let fresh = tokio::task::spawn_blocking(move || check(&config, &profiles))
+            .await
+            .expect("self-test panicked");
+        fresh.log();
+        *report = Some(fresh.clone());
+        fresh
+    }
+
+    // 시작할 때 돌린 결과, 없으면 지금 돌린 결과
+    async fn report(&self, state: &AppState) -> SelfTestReport {
+        if let Some(report) = self.report.lock().await.clone() {
+            return report;
+        }
+        self.run(state.config.clone(), profiles(state)).await
+    }
+}
+
+// 설정 파일과 API 로 만든 프로필의 설정
+pub fn profiles(state: &AppState) -> Vec<StartRequest> {
+    let profiles = state.profiles.lock().unwrap();
+    profiles
+        .list()
+        .into_iter()
+        .map(|profile| profile.settings)
+        .collect()
+}
+
+fn check(config: &Config, profiles: &[StartRequest]) -> SelfTestReport {
+    let started_at = Local::now();
+    let started = Instant::now();
+    let save_dir = config.save_dir();
+    let staging_dir = config.staging_dir();
+    // 시험 영상과 사진은 녹화 목록에 섞이지 않게 임시 디렉토리에 썼다 지운다.
+    let scratch = std::env::temp_dir().join(format!("server-selftest-{}", std::process::id()));
+    if let Err(e) = fs::create_dir_all(&scratch) {
+        warn!("Failed to create {:?}: {}", scratch, e);
+    }
+
+    let save_check = health::check_writable(&save_dir);
+    let staging_check = staging_dir.as_deref().map(health::check_writable);
+    let mut disk_space = health::check_disk_space(&save_dir, config.min_free_space_mb);
+    if let Some(staging_dir) = &staging_dir {
+        let staging_space = health::check_disk_space(staging_dir, config.min_free_space_mb);
+        disk_space = Check {
+            ok: disk_space.ok && staging_space.ok,
+            detail: format!(
+                "save_dir: {}; staging_dir: {}",
+                disk_space.detail, staging_space.detail
+            ),
+        };
+    }
+
+    let cameras = check_cameras(&config.recording, profiles, &scratch);
+    // 여러 설정에 같은 방식이 나오면 처음 나온 자리에서 한 번만 시험한다.
+    let mut seen = BTreeSet::new();
+    let codecs: Vec<CodecCheck> = encodings(&config.recording, profiles)
+        .iter()
+        .flat_map(sink::candidates)
+        .map(|sink| (sink.describe(), sink))
+        .filter(|(name, _)| seen.insert(name.clone()))
+        .map(|(name, sink)| {
+            let started = Instant::now();
+            let check = match encode_test(sink.as_ref(), &scratch) {
+                Ok(()) => Check::pass("wrote a test video"),
+                Err(e) => Check::fail(format!("{:#}", e)),
+            };
+            CodecCheck {
+                sink: name,
+                duration_ms: started.elapsed().as_millis() as u64,
+                check,
+            }
+        })
+        .collect();
+    let _ = fs::remove_dir_all(&scratch);
+
+    let ok = save_check.ok
+        && staging_check.as_ref().is_none_or(|check| check.ok)
+        && disk_space.ok
+        && cameras.iter().all(|camera| camera.check.ok)
+        && codecs.iter().all(|codec| codec.check.ok);
+    SelfTestReport {
+        ok,
+        started_at,
+        duration_ms: started.elapsed().as_millis() as u64,
+        cameras,
+        codecs,
+        save_dir: save_check,
+        staging_dir: staging_check,
+        disk_space,
+    }
+}
+
+// [recording] 기본 카메라와 프로필이 쓰는 카메라. 네트워크 카메라, 카메라 장치와 테스트 패턴은
+// 한 장 찍어 본다.
+fn check_cameras(
+    defaults: &RecordingConfig,
+    profiles: &[StartRequest],
+    scratch: &Path,
+) -> Vec<CameraCheck> {
+    let mut cameras: BTreeSet<u32> = defaults.cameras.iter().copied().collect();
+    for profile in profiles {
+        cameras.extend(profile.cameras.iter().flatten().chain(&profile.camera));
+    }
+    // libcamera 카메라가 있을 때 한 번만 목록을 읽는다.
+    let mut attached = None;
+    cameras
+        .into_iter()
+        .map(|camera| {
+            let (kind, check) = if let Some(network) = defaults.network_camera(camera) {
+                (
+                    "network",
+                    capture_test(scratch, camera, |image| network.capture_still(image)),
+                )
+            } else if let Some(device) = defaults.device_camera(camera) {
+                (
+                    "device",
+                    capture_test(scratch, camera, |image| {
+                        device.capture_still(defaults.format_for(camera), image)
+                    }),
+                )
+            } else if defaults.test_patterns.contains(&camera) {
+                let pattern = TestPattern { camera };
+                (
+                    "test_pattern",
+                    capture_test(scratch, camera, |image| {
+                        pattern.capture_still(defaults.format_for(camera), image)
+                    }),
+                )
+            } else {
+                let check = match attached.get_or_insert_with(camera_handler::list_cameras) {
+                    Ok(attached) if attached.contains(&camera) => Check::pass("attached"),
+                    Ok(attached) => {
+                        Check::fail(format!("not attached (available: {:?})", attached))
+                    }
+                    Err(e) => Check::fail(format!("{:#}", e)),
+                };
+                ("libcamera", check)
+            };
+            CameraCheck {
+                camera,
+                kind,
+                check,
+            }
+        })
+        .collect()
+}
+
+fn capture_test(scratch: &Path, camera: u32, capture: impl FnOnce(&Path) -> Result<()>) -> Check {
+    let image = scratch.join(format!("cam{}.jpg", camera));
+    let result = capture(&image);
+    let _ = fs::remove_file(&image);
+    match result {
+        Ok(()) => Check::pass("captured a frame"),
+        Err(e) => Check::fail(format!("{:#}", e)),
+    }
+}
+
+// [recording] 기본값과 프로필마다 다시 인코딩할 때의 설정 (같은 것은 한 번)
+fn encodings(defaults: &RecordingConfig, profiles: &[StartRequest]) -> Vec<Encoding> {
+    let mut encodings = vec![defaults.encoding()];
+    for profile in profiles {
+        if profile.encoder.is_none() && profile.sink.is_none() {
+            continue;
+        }
+        let fallback = defaults.encoding();
+        encodings.push(Encoding {
+            encoder: profile.encoder.unwrap_or(fallback.encoder),
+            sink: profile.sink.clone().unwrap_or(fallback.sink),
+        });
+    }
+    encodings
+}
+
+// 짧은 테스트 영상을 sink 로 인코딩해 파일이 써지는지 본다.
+pub fn encode_test(sink: &dyn sink::VideoSink, scratch: &Path) -> Result<()> {
+    let encoder = sink.encoder();
+    let mut command = Command::new(FFMPEG);
+    command
+        .arg("-nostdin")
+        .arg("-y")
+        .arg("-loglevel")
+        .arg("error")
+        .args(encoder.input_args())
+        .arg("-f")
+        .arg("lavfi")
+        .arg("-i")
+        .arg(TEST_INPUT)
+        .arg("-vf")
+        .arg(encoder.with_upload("null"))
+        .stdin(Stdio::null())
+        .stderr(Stdio::null());
+    let output = scratch.join("test.mp4");
+    let summary = sink::summary_path(&output);
+    let status = sink.spawn(command, &output).and_then(Pipeline::wait);
+    let written = [&output, &summary]
+        .iter()
+        .any(|path| fs::metadata(path).is_ok_and(|file| file.len() > 0));
+    for path in [&output, &summary] {
+        let _ = fs::remove_file(path);
+    }
+    let status = status?;
+    if !status.success() {
+        bail!("encoding failed: {}", status);
+    }
+    if !written {
+        bail!("encoding produced no output");
+    }
+    Ok(())
+}
+
+// GET /selftest - 시작할 때 돌린 검사 결과. 실패한 항목이 있으면 503
+pub async fn handle_selftest(
+    State(state): State<Arc<AppState>>,
+) -> (StatusCode, Json<SelfTestReport>) {
+    let report = state.self_test.report(&state).await;
+    let status = if report.ok {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report))
+}
@@ -0,0 +1,397 @@
+// src/session.rs
+use crate::{
+    bookmarks::Bookmark,
+    camera_handler::{RecordingConfig, RecordingStats},
+    camera_settings::CameraSettings,
+    events::{EventBus, EventKind},
+};
+use anyhow::{Result, bail};
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+use tokio::sync::oneshot;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+use uuid::Uuid;
+
+// 캡처가 시작되었는지, 시작하지 못했다면 그 이유
+pub type Readiness = oneshot::Receiver<Result<(), String>>;
+
+// 끝난 세션을 /sessions 에서 조회할 수 있도록 남겨 두는 개수
+const SESSION_HISTORY: usize = 50;
+
+// 세션의 상태. RecordingSession::transition 에서만 바뀐다.
+//   Starting -> Recording <-> Paused -> Finalizing -> Finished
+// 모든 카메라에서 첫 프레임이 와야 Recording 이 되고, 그 전에 멈추면 Starting 에서 바로 Finalizing 으로 간다.
+// 끝나지 않은 상태에서는 언제든 Failed 로 갈 수 있다.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionState {
+    // 실행 중인 세션이 없을 때 (/status 의 recording_state 에만 쓴다)
+    Idle,
+    // 카메라를 여는 중
+    Starting,
+    Recording,
+    Paused,
+    // 캡처를 멈추고 파일을 옮기거나 합성하는 중
+    Finalizing,
+    Finished,
+    Failed,
+}
+
+impl SessionState {
+    // 아직 끝나지 않았는지 (카메라나 파일을 쓰고 있다)
+    pub fn is_active(self) -> bool {
+        matches!(
+            self,
+            Self::Starting | Self::Recording | Self::Paused | Self::Finalizing
+        )
+    }
+
+    fn can_become(self, next: Self) -> bool {
+        use SessionState::*;
+        matches!(
+            (self, next),
+            (Starting, Recording)
+                | (Recording, Paused)
+                | (Paused, Recording)
+                | (Starting | Recording | Paused, Finalizing)
+                | (Finalizing, Finished)
+                | (Starting | Recording | Paused | Finalizing, Failed)
+        )
+    }
+}
+
+#[derive(Debug)]
+struct SessionOutcome {
+    state: SessionState,
+    error: Option<String>,
+    finished_at: Option<DateTime<Local>>,
+}
+
+// /start 한 번에 대응하는 녹화 작업. 녹화 스레드와 API 핸들러가 공유한다.
+#[derive(Debug)]
+pub struct RecordingSession {
+    pub id: Uuid,
+    pub config: RecordingConfig,
+    pub started_at: DateTime<Local>,
+    // 다른 세션과 동시에 녹화할 때 파일 이름이 겹치지 않도록 붙이는 꼬리표 (예: "_cam2-3")
+    pub file_tag: String,
+    // API 에서 받은 요청. 녹화 루프가 읽어 상태를 바꾼다 (request_* 가 상태를 보고 받아들인다).
+    // stop 은 캡처를 끝내고 마무리한다. cancel 의 자식이라 cancel 하면 함께 멈춘다.
+    stop: CancellationToken,
+    // /stop?force=true: 캡처를 끝내고 마무리 (합성, 변환) 도 멈춘다.
+    cancel: CancellationToken,
+    pause_requested: AtomicBool,
+    // Layout::Switch 에서 다음에 보여 줄 카메라 (녹화 루프가 가져간다)
+    pub switch_requested: Mutex<Option<u32>>,
+    // 녹화 중 바뀐 카메라 화질 설정 (녹화 루프가 카메라를 다시 열어 적용한다)
+    pub settings_requested: Mutex<Vec<CameraSettings>>,
+    // 녹화 중에 남긴 표시 (시간순)
+    pub bookmarks: Mutex<Vec<Bookmark>>,
+    pub stats: Mutex<RecordingStats>,
+    outcome: Mutex<SessionOutcome>,
+    // /start 가 기다리는 신호. 모든 카메라에서 첫 프레임이 오거나 세션이 끝나면 한 번 보낸다.
+    ready: Mutex<Option<oneshot::Sender<Result<(), String>>>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionSummary {
+    pub session_id: Uuid,
+    pub state: SessionState,
+    pub error: Option<String>,
+    pub started_at: DateTime<Local>,
+    pub finished_at: Option<DateTime<Local>>,
+    pub config: RecordingConfig,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub bookmarks: Vec<Bookmark>,
+    #[serde(flatten)]
+    pub stats: RecordingStats,
+}
+
+impl RecordingSession {
+    fn new(
+        config: RecordingConfig,
+        file_tag: String,
+        ready: oneshot::Sender<Result<(), String>>,
+    ) -> Self {
+        let cancel = CancellationToken::new();
+        Self {
+            id: Uuid::new_v4(),
+            config,
+            started_at: Local::now(),
+            file_tag,
+            stop: cancel.child_token(),
+            cancel,
+            pause_requested: AtomicBool::new(false),
+            switch_requested: Mutex::new(None),
+            settings_requested: Mutex::new(Vec::new()),
+            bookmarks: Mutex::new(Vec::new()),
+            stats: Mutex::new(RecordingStats::default()),
+            outcome: Mutex::new(SessionOutcome {
+                state: SessionState::Starting,
+                error: None,
+                finished_at: None,
+            }),
+            ready: Mutex::new(Some(ready)),
+        }
+    }
+
+    pub fn state(&self) -> SessionState {
+        self.outcome.lock().unwrap().state
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.state().is_active()
+    }
+
+    // 아직 캡처 중이고 멈추라는 요청도 받지 않았는지
+    pub fn is_capturing(&self) -> bool {
+        matches!(
+            self.state(),
+            SessionState::Starting | SessionState::Recording | SessionState::Paused
+        ) && !self.stop_requested()
+    }
+
+    pub fn stop_requested(&self) -> bool {
+        self.stop.is_cancelled()
+    }
+
+    pub fn cancel_requested(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    pub fn stop_token(&self) -> &CancellationToken {
+        &self.stop
+    }
+
+    pub fn cancel_token(&self) -> &CancellationToken {
+        &self.cancel
+    }
+
+    pub fn pause_requested(&self) -> bool {
+        self.pause_requested.load(Ordering::SeqCst)
+    }
+
+    // 상태를 바꾸고 state_changed 이벤트를 보낸다. 허용하지 않는 전이는 거절한다.
+    pub fn transition(&self, next: SessionState, events: &EventBus) -> Result<()> {
+        let previous = {
+            let mut outcome = self.outcome.lock().unwrap();
+            let previous = outcome.state;
+            if !previous.can_become(next) {
+                bail!("Session cannot go from {:?} to {:?}", previous, next);
+            }
+            outcome.state = next;
+            if !next.is_active() {
+                outcome.finished_at = Some(Local::now());
+            }
+            previous
+        };
+        events.publish(
+            self.id,
+            EventKind::StateChanged {
+                previous,
+                state: next,
+            },
+        );
+        Ok(())
+    }
+
+    // 캡처 중일 때만 받는다 (마무리 중이거나 끝난 세션은 거절).
+    pub fn request_stop(&self) -> Result<()> {
+        match self.state() {
+            SessionState::Starting | SessionState::Recording | SessionState::Paused => {
+                self.stop.cancel();
+                Ok(())
+            }
+            SessionState::Finalizing => bail!("Recording is already finalizing."),
+            _ => bail!("Recording is not currently active or has already finished."),
+        }
+    }
+
+    // 마무리 중이어도 받는다 (돌고 있는 ffmpeg 을 멈춘다).
+    pub fn request_cancel(&self) -> Result<()> {
+        if !self.is_running() {
+            bail!("Recording is not currently active or has already finished.");
+        }
+        self.cancel.cancel();
+        Ok(())
+    }
+
+    // 녹화 루프가 다음 반복에서 카메라 출력을 멈추거나 다시 잇는다.
+    pub fn request_pause(&self, paused: bool) -> Result<()> {
+        if !matches!(self.state(), SessionState::Recording | SessionState::Paused)
+            || self.stop_requested()
+        {
+            bail!("Recording is not currently active.");
+        }
+        if self
+            .pause_requested
+            .compare_exchange(!paused, paused, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            if paused {
+                bail!("Recording is already paused.");
+            }
+            bail!("Recording is not paused.");
+        }
+        Ok(())
+    }
+
+    // 캡처가 시작되었거나 (Ok) 시작하지 못했음을 /start 에 알린다. 두 번째부터는 무시한다.
+    pub fn signal_ready(&self, result: Result<(), String>) {
+        if let Some(ready) = self.ready.lock().unwrap().take() {
+            // /start 가 이미 시간 초과로 떠났으면 받는 쪽이 없다.
+            let _ = ready.send(result);
+        }
+    }
+
+    // 녹화 작업이 끝나면 한 번 호출된다.
+    pub fn finish(&self, error: Option<String>, events: &EventBus) {
+        let next = if error.is_some() {
+            SessionState::Failed
+        } else {
+            SessionState::Finished
+        };
+        self.signal_ready(match &error {
+            Some(error) => Err(error.clone()),
+            None => Err("Recording ended before capture began".to_string()),
+        });
+        self.outcome.lock().unwrap().error = error;
+        if let Err(e) = self.transition(next, events) {
+            // 마무리 단계를 거치지 않고 끝났다면 실패로 남긴다.
+            warn!(session_id = %self.id, "{:#}", e);
+            self.outcome
+                .lock()
+                .unwrap()
+                .error
+                .get_or_insert_with(|| e.to_string());
+            let _ = self.transition(SessionState::Failed, events);
+        }
+    }
+
+    // 이 세션이 만든(또는 만들고 있는) 파일
+    pub fn outputs(&self) -> Vec<PathBuf> {
+        let stats = self.stats.lock().unwrap();
+        if !stats.segments.is_empty() {
+            stats.segments.clone()
+        } else {
+            stats.output_path.iter().cloned().collect()
+        }
+    }
+
+    // 녹화 중에 쓰고 있는 파일인지 (최종 파일이나 카메라별 임시 파일)
+    pub fn is_writing(&self, path: &Path) -> bool {
+        if !self.is_running() {
+            return false;
+        }
+        let stats = self.stats.lock().unwrap();
+        stats.output_path.as_deref() == Some(path)
+            || stats.camera_outputs.values().any(|output| output == path)
+    }
+
+    pub fn summary(&self) -> SessionSummary {
+        let outcome = self.outcome.lock().unwrap();
+        SessionSummary {
+            session_id: self.id,
+            state: outcome.state,
+            error: outcome.error.clone(),
+            started_at: self.started_at,
+            finished_at: outcome.finished_at,
+            config: self.config.clone(),
+            bookmarks: self.bookmarks.lock().unwrap().clone(),
+            stats: self.stats.lock().unwrap().clone(),
+        }
+    }
+}
+
+// 실행 중인 세션과 최근에 끝난 세션 목록 (최신이 앞)
+#[derive(Debug, Default)]
+pub struct SessionManager {
+    sessions: VecDeque<Arc<RecordingSession>>,
+}
+
+impl SessionManager {
+    // 요청한 카메라 중 하나라도 다른 세션이 쓰고 있으면 거절한다.
+    // 받는 쪽은 캡처가 시작되었는지 (RecordingSession::signal_ready) 를 받는다.
+    pub fn begin(&mut self, config: RecordingConfig) -> Result<(Arc<RecordingSession>, Readiness)> {
+        let file_tag = self.file_tag(&config)?;
+        let (ready, receiver) = oneshot::channel();
+        let session = Arc::new(RecordingSession::new(config, file_tag, ready));
+        self.sessions.push_front(session.clone());
+        // 동시에 도는 세션이 있으면 오래 도는 세션이 뒤쪽에 있을 수 있으므로 끝난 세션만 지운다.
+        while self.sessions.len() > SESSION_HISTORY {
+            let Some(oldest) = self.sessions.iter().rposition(|s| !s.is_running()) else {
+                break;
+            };
+            self.sessions.remove(oldest);
+        }
+        Ok((session, receiver))
+    }
+
+    // begin 이 새 세션의 파일 이름에 붙일 꼬리표. 카메라가 다른 세션과 겹치면 거절한다.
+    pub fn file_tag(&self, config: &RecordingConfig) -> Result<String> {
+        let running = self.running();
+        for session in &running {
+            let busy: Vec<u32> = config
+                .cameras
+                .iter()
+                .copied()
+                .filter(|camera| session.config.cameras.contains(camera))
+                .collect();
+            if !busy.is_empty() {
+                bail!(
+                    "Cameras {:?} are already recording in session {}",
+                    busy,
+                    session.id
+                );
+            }
+        }
+
+        // 카메라가 겹치지 않으므로 카메라 목록을 붙이면 동시 세션끼리 파일 이름이 다르다.
+        Ok(if running.is_empty() {
+            String::new()
+        } else {
+            let cameras: Vec<String> = config.cameras.iter().map(u32::to_string).collect();
+            format!("_cam{}", cameras.join("-"))
+        })
+    }
+
+    pub fn running(&self) -> Vec<Arc<RecordingSession>> {
+        self.sessions
+            .iter()
+            .filter(|session| session.is_running())
+            .cloned()
+            .collect()
+    }
+
+    pub fn any_running(&self) -> bool {
+        self.sessions.iter().any(|session| session.is_running())
+    }
+
+    pub fn latest(&self) -> Option<Arc<RecordingSession>> {
+        self.sessions.front().cloned()
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<Arc<RecordingSession>> {
+        self.sessions
+            .iter()
+            .find(|session| session.id == id)
+            .cloned()
+    }
+
+    pub fn list(&self) -> Vec<Arc<RecordingSession>> {
+        self.sessions.iter().cloned().collect()
+    }
+
+    pub fn is_writing(&self, path: &Path) -> bool {
+        self.sessions.iter().any(|session| session.is_writing(path))
+    }
+}
@@ -0,0 +1,236 @@
+// src/settings.rs
+use crate::{
+    ApiError, AppState, camera_handler::RecordingConfig, config::Config, logging,
+    overlay::OverlayConfig, retention::RetentionConfig,
+};
+use anyhow::Result;
+use axum::{Json, extract::State};
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::{Map, Value};
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+// POST /config 로 바꿀 수 있는 설정 (오류 메시지에 그대로 쓴다)
+const LIVE_SETTINGS: &str =
+    "log_level, recording.fps, recording.overlays, retention and webhooks.urls";
+
+// 서버를 다시 시작하지 않고 바꿀 수 있는 설정. 나머지는 시작할 때 읽은 state.config 를 쓴다.
+pub struct LiveSettings {
+    // 로그 필터는 logging 이 바꾸고, 여기에는 /config 에 보일 값을 둔다.
+    log_level: Mutex<String>,
+    // 녹화 요청에 채우는 [recording] 기본값. fps 와 overlays 만 바뀐다 (미리보기는 시작할 때 값).
+    recording: Mutex<RecordingConfig>,
+    // 바뀌면 retention::run 이 새 규칙으로 다시 시작한다.
+    retention: watch::Sender<RetentionConfig>,
+}
+
+impl LiveSettings {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            log_level: Mutex::new(config.log_level.clone()),
+            recording: Mutex::new(config.recording.clone()),
+            retention: watch::Sender::new(config.retention.clone()),
+        }
+    }
+
+    pub fn recording(&self) -> RecordingConfig {
+        self.recording.lock().unwrap().clone()
+    }
+
+    pub fn retention(&self) -> watch::Receiver<RetentionConfig> {
+        self.retention.subscribe()
+    }
+}
+
+// 바꿀 값. None 이면 그대로 둔다.
+#[derive(Debug, Default)]
+pub struct Changes {
+    pub log_level: Option<String>,
+    pub fps: Option<u32>,
+    pub overlays: Option<Vec<OverlayConfig>>,
+    pub retention: Option<RetentionConfig>,
+    pub webhook_urls: Option<Vec<String>>,
+}
+
+impl Changes {
+    fn names(&self) -> Vec<&'static str> {
+        [
+            (self.log_level.is_some(), "log_level"),
+            (self.fps.is_some(), "recording.fps"),
+            (self.overlays.is_some(), "recording.overlays"),
+            (self.retention.is_some(), "retention"),
+            (self.webhook_urls.is_some(), "webhooks.urls"),
+        ]
+        .into_iter()
+        .filter_map(|(changed, name)| changed.then_some(name))
+        .collect()
+    }
+}
+
+// 지금 적용된 설정 (GET /config)
+pub fn effective(state: &AppState) -> Config {
+    let mut config = state.config.as_ref().clone();
+    config.log_level = state.settings.log_level.lock().unwrap().clone();
+    let recording = state.settings.recording();
+    config.recording.fps = recording.fps;
+    config.recording.overlays = recording.overlays;
+    config.retention = state.settings.retention.borrow().clone();
+    config.webhooks.urls = state.webhooks.configured_urls();
+    config
+}
+
+// 바꾼 설정 전체를 시작할 때처럼 확인한 뒤 한꺼번에 적용한다. 적용한 설정의 이름을 돌려준다.
+pub fn apply(state: &AppState, changes: Changes) -> Result<Vec<&'static str>> {
+    let applied = changes.names();
+    let mut candidate = effective(state);
+    let filter = changes
+        .log_level
+        .as_deref()
+        .map(logging::filter)
+        .transpose()?;
+    if let Some(log_level) = &changes.log_level {
+        candidate.log_level = log_level.clone();
+    }
+    if let Some(fps) = changes.fps {
+        candidate.recording.fps = fps;
+    }
+    if let Some(overlays) = changes.overlays {
+        candidate.recording.overlays = overlays;
+    }
+    if let Some(retention) = &changes.retention {
+        candidate.retention = retention.clone();
+    }
+    if let Some(urls) = &changes.webhook_urls {
+        candidate.webhooks.urls = urls.clone();
+    }
+    candidate.validate()?;
+
+    if let Some(filter) = filter {
+        if !logging::set_filter(filter)? {
+            warn!("RUST_LOG is set, so the new log_level only shows in /config.");
+        }
+        *state.settings.log_level.lock().unwrap() = candidate.log_level;
+    }
+    {
+        let mut recording = state.settings.recording.lock().unwrap();
+        recording.fps = candidate.recording.fps;
+        recording.overlays = candidate.recording.overlays;
+    }
+    // 열어 둔 카메라를 새 fps 로 다시 연다. 녹화 중이면 그 녹화가 끝날 때 다시 연다.
+    if changes.fps.is_some()
+        && state.warm_cameras.is_open()
+        && !state.sessions.lock().unwrap().any_running()
+    {
+        let warm_cameras = state.warm_cameras.clone();
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = warm_cameras.warm() {
+                warn!("Failed to reopen the kept-open cameras: {:#}", e);
+            }
+        });
+    }
+    if changes.retention.is_some() {
+        state.settings.retention.send_replace(candidate.retention);
+    }
+    if changes.webhook_urls.is_some() {
+        state.webhooks.replace_configured(&candidate.webhooks.urls);
+    }
+    if !applied.is_empty() {
+        info!("Applied new settings: {}.", applied.join(", "));
+    }
+    Ok(applied)
+}
+
+fn value<T: DeserializeOwned>(name: &str, value: Value) -> Result<T, ApiError> {
+    serde_json::from_value(value).map_err(|e| ApiError::bad_request(format!("{}: {}", name, e)))
+}
+
+fn object(name: &str, value: Value) -> Result<Map<String, Value>, ApiError> {
+    match value {
+        Value::Object(fields) => Ok(fields),
+        _ => Err(ApiError::bad_request(format!("{} must be an object", name))),
+    }
+}
+
+// GET /config 와 같은 모양의 본문에서 바꿀 값을 고른다. 다시 시작해야 바뀌는 항목이 있으면 거절한다.
+fn parse(body: Map<String, Value>, current: &Config) -> Result<Changes, ApiError> {
+    let mut changes = Changes::default();
+    let mut restart = Vec::new();
+    for (key, field) in body {
+        match key.as_str() {
+            "log_level" => changes.log_level = Some(value("log_level", field)?),
+            "recording" => {
+                for (name, field) in object("recording", field)? {
+                    match name.as_str() {
+                        "fps" => changes.fps = Some(value("recording.fps", field)?),
+                        "overlays" => changes.overlays = Some(value("recording.overlays", field)?),
+                        _ => restart.push(format!("recording.{}", name)),
+                    }
+                }
+            }
+            "retention" => {
+                // 빠진 항목은 지금 값을 그대로 쓴다.
+                let mut retention = serde_json::to_value(&current.retention)
+                    .map_err(|e| ApiError::internal(e.to_string()))?;
+                if let Value::Object(merged) = &mut retention {
+                    for (name, field) in object("retention", field)? {
+                        if !merged.contains_key(&name) {
+                            return Err(ApiError::bad_request(format!(
+                                "Unknown setting retention.{}",
+                                name
+                            )));
+                        }
+                        merged.insert(name, field);
+                    }
+                }
+                changes.retention = Some(value("retention", retention)?);
+            }
+            "webhooks" => {
+                for (name, field) in object("webhooks", field)? {
+                    match name.as_str() {
+                        "urls" => changes.webhook_urls = Some(value("webhooks.urls", field)?),
+                        _ => restart.push(format!("webhooks.{}", name)),
+                    }
+                }
+            }
+            _ => restart.push(key),
+        }
+    }
+    if !restart.is_empty() {
+        return Err(ApiError::conflict(format!(
+            "{} cannot change while the server is running; edit the config file and restart it. \
+             POST /config can change {}.",
+            restart.join(", "),
+            LIVE_SETTINGS
+        )));
+    }
+    Ok(changes)
+}
+
+#[derive(Debug, Serialize)]
+pub struct UpdateResponse {
+    pub message: String,
+    pub applied: Vec<&'static str>,
+    // 적용한 뒤의 설정 (GET /config 와 같다)
+    pub config: Config,
+}
+
+// POST /config - 보낸 항목만 바꾼다. 파일에는 쓰지 않으므로 다시 시작하면 파일의 값으로 돌아간다.
+pub async fn handle_update(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<Value>,
+) -> Result<Json<UpdateResponse>, ApiError> {
+    let changes = parse(object("The request body", body)?, &effective(&state))?;
+    if changes.names().is_empty() {
+        return Err(ApiError::bad_request(format!(
+            "Nothing to change; POST /config can change {}",
+            LIVE_SETTINGS
+        )));
+    }
+    let applied = apply(&state, changes).map_err(|e| ApiError::bad_request(format!("{:#}", e)))?;
+    Ok(Json(UpdateResponse {
+        message: format!("Applied {}.", applied.join(", ")),
+        applied,
+        config: effective(&state),
+    }))
+}
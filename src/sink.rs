@@ -0,0 +1,699 @@
+// src/sink.rs
+use crate::{
+    compositor::FFMPEG,
+    encoder::{self, Encoder},
+};
+use anyhow::{Context, Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::RefCell,
+    fs,
+    io::{self, BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, ChildStdout, Command, ExitStatus, Stdio},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+pub const GST_LAUNCH: &str = "gst-launch-1.0";
+
+// 쓴 것을 버리는 경로
+pub const NULL_DEVICE: &str = if cfg!(windows) { "NUL" } else { "/dev/null" };
+
+// Backend::Sidecar 에서 codec 을 생략했을 때
+const SOFTWARE_CODEC: &str = "libx264";
+
+// 조각 MP4 에서 조각 하나의 최대 길이 (GStreamer mp4mux, 밀리초)
+const FRAGMENT_MS: u32 = 1000;
+
+// 비트레이트를 지정하지 않았을 때 하드웨어 인코더에 주는 값 (encoder::HARDWARE_BITRATE 와 같음)
+pub const DEFAULT_HARDWARE_KBPS: u32 = 8000;
+
+// 취소를 확인하는 간격
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+thread_local! {
+    // 이 스레드에서 돌리는 ffmpeg 을 멈출 토큰 (녹화 스레드가 마무리하는 동안 세션의 cancel)
+    static CANCEL: RefCell<Option<CancellationToken>> = const { RefCell::new(None) };
+}
+
+// cancel_scope 가 돌려주는 값. 버리면 앞의 토큰으로 되돌린다.
+pub struct CancelScope(Option<CancellationToken>);
+
+impl Drop for CancelScope {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        CANCEL.with(|cancel| *cancel.borrow_mut() = previous);
+    }
+}
+
+// 돌려받은 값을 버릴 때까지 이 스레드의 인코딩 (Pipeline::wait, run) 은 token 이 취소되면
+// 프로세스를 죽이고 실패한다. 합성 함수마다 토큰을 넘기지 않아도 되게 한다.
+pub fn cancel_scope(token: &CancellationToken) -> CancelScope {
+    CancelScope(CANCEL.with(|cancel| cancel.borrow_mut().replace(token.clone())))
+}
+
+fn current_cancel() -> Option<CancellationToken> {
+    CANCEL.with(|cancel| cancel.borrow().clone())
+}
+
+// cancel_scope 의 토큰이 취소되었는지. 취소된 뒤에는 다른 방식으로 다시 시도하지 않는다.
+pub fn cancelled() -> bool {
+    current_cancel().is_some_and(|cancel| cancel.is_cancelled())
+}
+
+// 취소 토큰이 있으면 주기적으로 확인하며 child 를 기다린다. 취소되면 죽이고 실패한다.
+fn wait_child(child: &mut Child, name: &str) -> Result<ExitStatus> {
+    let Some(cancel) = current_cancel() else {
+        return child
+            .wait()
+            .with_context(|| format!("Failed to wait for {}", name));
+    };
+    loop {
+        if let Some(status) = child
+            .try_wait()
+            .with_context(|| format!("Failed to wait for {}", name))?
+        {
+            return Ok(status);
+        }
+        if cancel.is_cancelled() {
+            let _ = child.kill();
+            let _ = child.wait();
+            bail!("{} was cancelled", name);
+        }
+        thread::sleep(CANCEL_POLL_INTERVAL);
+    }
+}
+
+// Command::status 대신: cancel_scope 안이면 취소할 수 있다.
+pub fn run(command: &mut Command) -> Result<ExitStatus> {
+    let mut child = command.spawn().context("Failed to run ffmpeg")?;
+    wait_child(&mut child, "ffmpeg")
+}
+
+// 다시 인코딩한 영상을 파일로 쓰는 프로그램
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    // ffmpeg 이 필터부터 인코딩, 파일 쓰기까지 한다.
+    #[default]
+    Ffmpeg,
+    // ffmpeg 은 디코딩과 필터만 하고, 결과를 gst-launch-1.0 파이프라인으로 넘겨 인코딩한다.
+    Gstreamer,
+    // 필터를 거친 raw 프레임을 stdin 으로 받는 두 번째 ffmpeg 이 codec, crf, preset 으로 인코딩한다.
+    Sidecar,
+    // 인코딩하지 않고 필터를 거친 프레임의 수와 크기만 <출력>.frames.json 에 남긴다.
+    // 영상 파일은 만들지 않는다 (하드웨어나 인코더 없이 합성, 필터를 시험할 때, 테스트).
+    Null,
+}
+
+// 완성된 녹화 파일의 형식 (remux 한 파일도 따른다)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Container {
+    #[default]
+    Mp4,
+    // 키프레임마다 조각을 쓰는 MP4. 인코딩 도중 프로세스가 죽어도 쓴 데까지는 재생된다.
+    FragmentedMp4,
+    Mkv,
+}
+
+impl Container {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Mp4 | Self::FragmentedMp4 => "mp4",
+            Self::Mkv => "mkv",
+        }
+    }
+
+    // output 에 쓸 때 ffmpeg 출력 옵션 (raw .h264 같은 중간 파일에는 붙이지 않는다)
+    pub fn mux_args(self, output: &Path) -> Vec<&'static str> {
+        match self {
+            Self::FragmentedMp4 if is_mp4(output) => {
+                vec!["-movflags", "+frag_keyframe+empty_moov+default_base_moof"]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+// 합성, 오버레이, re-encode 처럼 파일을 다시 인코딩할 때의 출력 방식 (실시간 스트림에는 쓰지 않음)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SinkConfig {
+    pub backend: Backend,
+    pub container: Container,
+    // 목표 비트레이트 (kbps). 생략하면 libx264 는 화질 고정(CRF 23), 하드웨어 인코더는 8 Mbps
+    pub bitrate_kbps: Option<u32>,
+    // Backend::Gstreamer 전용: raw 영상을 받아 H.264 를 내는 요소들
+    // (예: "videoscale ! video/x-raw,width=1920 ! x264enc bitrate=6000 tune=zerolatency").
+    // 생략하면 encoder 에 맞는 요소를 고른다 (auto 는 x264enc).
+    pub pipeline: Option<String>,
+    // Backend::Sidecar 전용: ffmpeg 코덱 이름 (예: "libx265"). 생략하면 libx264
+    pub codec: Option<String>,
+    // Backend::Sidecar 전용: 화질 고정 값 (bitrate_kbps 와 함께 쓸 수 없음)
+    pub crf: Option<u32>,
+    // Backend::Sidecar 전용: 코덱의 preset. 생략하면 libx264 는 veryfast, 다른 코덱은 코덱 기본값
+    pub preset: Option<String>,
+}
+
+impl SinkConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.bitrate_kbps == Some(0) {
+            bail!("sink.bitrate_kbps must be non-zero");
+        }
+        if self.pipeline.as_ref().is_some_and(|p| p.trim().is_empty()) {
+            bail!("sink.pipeline must not be empty");
+        }
+        if self.pipeline.is_some() && self.backend != Backend::Gstreamer {
+            bail!("sink.pipeline requires backend = \"gstreamer\"");
+        }
+        for (name, value) in [("codec", &self.codec), ("preset", &self.preset)] {
+            if value.as_ref().is_some_and(|v| v.trim().is_empty()) {
+                bail!("sink.{} must not be empty", name);
+            }
+        }
+        if self.crf.is_some_and(|crf| crf > 63) {
+            bail!("sink.crf must be between 0 and 63");
+        }
+        if self.crf.is_some() && self.bitrate_kbps.is_some() {
+            bail!("sink.crf and sink.bitrate_kbps cannot be used together");
+        }
+        if (self.codec.is_some() || self.crf.is_some() || self.preset.is_some())
+            && self.backend != Backend::Sidecar
+        {
+            bail!("sink.codec, sink.crf and sink.preset require backend = \"sidecar\"");
+        }
+        Ok(())
+    }
+}
+
+// 다시 인코딩할 때 필요한 설정을 모은 것
+#[derive(Debug, Clone)]
+pub struct Encoding {
+    pub encoder: Encoder,
+    pub sink: SinkConfig,
+}
+
+// 필터까지 만든 ffmpeg 명령 뒤에 붙어 H.264 로 인코딩해 파일에 쓰는 단계
+pub trait VideoSink {
+    // ffmpeg 단계를 만들 때 쓸 인코더 (입력 옵션과 하드웨어 업로드 필터가 달라진다)
+    fn encoder(&self) -> Encoder;
+    fn describe(&self) -> String;
+    // ffmpeg 의 출력 옵션을 붙이거나 파이프로 이어 시작한다. stdin 은 호출한 쪽 설정을 따른다.
+    fn spawn(&self, ffmpeg: Command, output: &Path) -> Result<Pipeline>;
+}
+
+// 실행 중인 인코딩 프로세스들
+pub struct Pipeline {
+    ffmpeg: Child,
+    // GStreamer 나 sidecar 처럼 ffmpeg 의 출력을 받아 인코딩하는 두 번째 프로세스
+    encoder: Option<Child>,
+    // NullSink 에서 ffmpeg 의 출력을 세는 스레드
+    counter: Option<JoinHandle<Result<()>>>,
+}
+
+impl Pipeline {
+    // ffmpeg 을 stdin 으로 띄웠을 때 (raw 프레임을 직접 넣는 경우)
+    pub fn take_stdin(&mut self) -> Option<ChildStdin> {
+        self.ffmpeg.stdin.take()
+    }
+
+    // 모두 끝나기를 기다린다. 앞 단계가 실패했으면 그 상태를 돌려준다.
+    // cancel_scope 안이면 취소될 때 두 프로세스를 죽인다 (Drop).
+    pub fn wait(mut self) -> Result<ExitStatus> {
+        let ffmpeg = wait_child(&mut self.ffmpeg, "ffmpeg")?;
+        if let Some(counter) = self.counter.take() {
+            counter
+                .join()
+                .map_err(|_| anyhow!("Frame counting thread panicked"))??;
+        }
+        let Some(mut encoder) = self.encoder.take() else {
+            return Ok(ffmpeg);
+        };
+        let encoder = wait_child(&mut encoder, "the encoding process")?;
+        Ok(if ffmpeg.success() { encoder } else { ffmpeg })
+    }
+}
+
+impl Drop for Pipeline {
+    fn drop(&mut self) {
+        // wait 하지 않고 버려지면 (오류로 중간에 빠져나가면) 프로세스를 정리한다.
+        for child in std::iter::once(&mut self.ffmpeg).chain(self.encoder.as_mut()) {
+            if let Ok(None) = child.try_wait() {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+        }
+    }
+}
+
+struct FfmpegSink {
+    encoder: Encoder,
+    bitrate_kbps: Option<u32>,
+    container: Container,
+}
+
+impl VideoSink for FfmpegSink {
+    fn encoder(&self) -> Encoder {
+        self.encoder
+    }
+
+    fn describe(&self) -> String {
+        format!("the {:?} encoder", self.encoder)
+    }
+
+    fn spawn(&self, mut ffmpeg: Command, output: &Path) -> Result<Pipeline> {
+        ffmpeg.args(self.encoder.output_args());
+        if let Some(kbps) = self.bitrate_kbps {
+            // -b:v 는 마지막 값이 쓰이므로 하드웨어 기본값을 덮어쓴다.
+            ffmpeg.arg("-b:v").arg(format!("{}k", kbps));
+        }
+        let ffmpeg = ffmpeg
+            .args(self.container.mux_args(output))
+            .arg(output)
+            .spawn()
+            .context("Failed to run ffmpeg")?;
+        Ok(Pipeline {
+            ffmpeg,
+            encoder: None,
+            counter: None,
+        })
+    }
+}
+
+struct GstreamerSink {
+    elements: String,
+    container: Container,
+}
+
+impl GstreamerSink {
+    fn new(config: &SinkConfig, encoder: Encoder) -> Self {
+        let elements = config
+            .pipeline
+            .clone()
+            .unwrap_or_else(|| default_elements(encoder, config.bitrate_kbps));
+        Self {
+            elements,
+            container: config.container,
+        }
+    }
+
+    // y4m 으로 받은 영상을 elements 로 인코딩해 output 확장자에 맞는 형식으로 쓴다.
+    fn description(&self, output: &Path) -> String {
+        let location = output.to_string_lossy().replace('"', "\\\"");
+        let mux = match output.extension().and_then(|ext| ext.to_str()) {
+            Some("mp4") | Some("mov") if self.container == Container::FragmentedMp4 => {
+                format!("mp4mux fragment-duration={}", FRAGMENT_MS)
+            }
+            Some("mp4") | Some("mov") => "mp4mux".to_string(),
+            Some("mkv") => "matroskamux".to_string(),
+            _ => "video/x-h264,stream-format=byte-stream,alignment=au".to_string(),
+        };
+        format!(
+            "fdsrc fd=0 ! y4mdec ! videoconvert ! {} ! h264parse ! {} ! filesink location=\"{}\"",
+            self.elements, mux, location
+        )
+    }
+}
+
+impl VideoSink for GstreamerSink {
+    // 하드웨어 인코딩은 GStreamer 쪽에서 하므로 ffmpeg 에는 아무것도 올리지 않는다.
+    fn encoder(&self) -> Encoder {
+        Encoder::Software
+    }
+
+    fn describe(&self) -> String {
+        format!("the GStreamer pipeline ({})", self.elements)
+    }
+
+    fn spawn(&self, ffmpeg: Command, output: &Path) -> Result<Pipeline> {
+        let mut encoder = Command::new(GST_LAUNCH);
+        encoder
+            .arg("-q")
+            .arg("-e")
+            .arg(self.description(output))
+            .stdout(Stdio::null());
+        pipe_raw(ffmpeg, encoder, GST_LAUNCH)
+    }
+}
+
+struct SidecarSink {
+    codec: String,
+    crf: Option<u32>,
+    preset: Option<String>,
+    bitrate_kbps: Option<u32>,
+    container: Container,
+}
+
+impl SidecarSink {
+    fn new(config: &SinkConfig) -> Self {
+        Self {
+            codec: config
+                .codec
+                .clone()
+                .unwrap_or_else(|| SOFTWARE_CODEC.to_string()),
+            crf: config.crf,
+            preset: config.preset.clone(),
+            bitrate_kbps: config.bitrate_kbps,
+            container: config.container,
+        }
+    }
+}
+
+impl VideoSink for SidecarSink {
+    fn encoder(&self) -> Encoder {
+        Encoder::Software
+    }
+
+    fn describe(&self) -> String {
+        format!("the ffmpeg sidecar ({})", self.codec)
+    }
+
+    fn spawn(&self, ffmpeg: Command, output: &Path) -> Result<Pipeline> {
+        // 자리 채우기 화면처럼 카메라 스트림에 이어 붙이는 raw .h264 는 코덱을 바꿀 수 없다.
+        let codec = if is_mp4(output) || has_extension(output, "mkv") {
+            self.codec.as_str()
+        } else {
+            SOFTWARE_CODEC
+        };
+        let preset = match &self.preset {
+            Some(preset) if codec == self.codec => Some(preset.as_str()),
+            _ => (codec == SOFTWARE_CODEC).then_some("veryfast"),
+        };
+        let mut encoder = Command::new(FFMPEG);
+        encoder
+            .arg("-y")
+            .arg("-loglevel")
+            .arg("error")
+            .arg("-f")
+            .arg("yuv4mpegpipe")
+            .arg("-i")
+            .arg("pipe:0")
+            .arg("-c:v")
+            .arg(codec);
+        if let Some(preset) = preset {
+            encoder.arg("-preset").arg(preset);
+        }
+        if let Some(crf) = self.crf {
+            encoder.arg("-crf").arg(crf.to_string());
+        }
+        if let Some(kbps) = self.bitrate_kbps {
+            encoder.arg("-b:v").arg(format!("{}k", kbps));
+        }
+        encoder
+            .args(self.container.mux_args(output))
+            .arg(output)
+            .stdout(Stdio::null());
+        pipe_raw(ffmpeg, encoder, "the ffmpeg sidecar")
+    }
+}
+
+// NullSink 가 <출력>.frames.json 에 남기는 기록
+#[derive(Debug, Clone, Default, Serialize)]
+struct FrameSummary {
+    frames: u64,
+    width: u32,
+    height: u32,
+    // 받은 raw 프레임 (yuv420p) 바이트의 합
+    bytes: u64,
+}
+
+// 인코딩 대신 프레임을 세는 sink (Backend::Null). 다른 방식으로 다시 시도하지 않는다.
+struct NullSink;
+
+impl VideoSink for NullSink {
+    fn encoder(&self) -> Encoder {
+        Encoder::Software
+    }
+
+    fn describe(&self) -> String {
+        "the null sink".to_string()
+    }
+
+    fn spawn(&self, ffmpeg: Command, output: &Path) -> Result<Pipeline> {
+        let (ffmpeg, stdout) = spawn_raw(ffmpeg)?;
+        let summary = summary_path(output);
+        let counter = thread::spawn(move || {
+            let frames = count_frames(stdout)?;
+            let json = serde_json::to_string_pretty(&frames)?;
+            fs::write(&summary, json).with_context(|| format!("Failed to write {:?}", summary))?;
+            info!(
+                "Discarded {} frame(s) of {}x{}; wrote {:?}.",
+                frames.frames, frames.width, frames.height, summary
+            );
+            Ok(())
+        });
+        Ok(Pipeline {
+            ffmpeg,
+            encoder: None,
+            counter: Some(counter),
+        })
+    }
+}
+
+// video.mp4 → video.frames.json
+pub fn summary_path(output: &Path) -> PathBuf {
+    output.with_extension("frames.json")
+}
+
+// y4m 스트림 (헤더 한 줄, 프레임마다 "FRAME" 줄과 yuv420p 데이터) 을 끝까지 읽으며 센다.
+fn count_frames(stdout: impl Read) -> Result<FrameSummary> {
+    let mut reader = BufReader::new(stdout);
+    let mut line = Vec::new();
+    reader.read_until(b'\n', &mut line)?;
+    let header = String::from_utf8_lossy(&line).into_owned();
+    if !header.starts_with("YUV4MPEG2") {
+        bail!("ffmpeg did not write a y4m stream");
+    }
+    let mut summary = FrameSummary::default();
+    for token in header.split_whitespace() {
+        if let Some(width) = token.strip_prefix('W') {
+            summary.width = width.parse().unwrap_or(0);
+        } else if let Some(height) = token.strip_prefix('H') {
+            summary.height = height.parse().unwrap_or(0);
+        }
+    }
+    let (width, height) = (summary.width as u64, summary.height as u64);
+    let frame_size = width * height + 2 * (width.div_ceil(2) * height.div_ceil(2));
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(summary);
+        }
+        if !line.starts_with(b"FRAME") {
+            bail!("Unexpected data between y4m frames");
+        }
+        let read = io::copy(&mut reader.by_ref().take(frame_size), &mut io::sink())?;
+        if read < frame_size {
+            // 중간에 끊긴 마지막 프레임은 세지 않는다.
+            return Ok(summary);
+        }
+        summary.frames += 1;
+        summary.bytes += read;
+    }
+}
+
+// ffmpeg 이 필터를 거친 영상을 y4m 으로 stdout 에 쓰게 하고 그 stdout 을 돌려준다.
+fn spawn_raw(mut ffmpeg: Command) -> Result<(Child, ChildStdout)> {
+    let mut ffmpeg = ffmpeg
+        .arg("-pix_fmt")
+        .arg("yuv420p")
+        .arg("-f")
+        .arg("yuv4mpegpipe")
+        .arg("pipe:1")
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("Failed to run ffmpeg")?;
+    let stdout = ffmpeg.stdout.take().context("ffmpeg stdout unavailable")?;
+    Ok((ffmpeg, stdout))
+}
+
+// ffmpeg 이 필터를 거친 영상을 y4m 으로 stdout 에 쓰고, encoder 가 stdin 으로 받아 인코딩한다.
+fn pipe_raw(ffmpeg: Command, mut encoder: Command, name: &str) -> Result<Pipeline> {
+    let (mut ffmpeg, stdout) = spawn_raw(ffmpeg)?;
+    match encoder.stdin(stdout).spawn() {
+        Ok(encoder) => Ok(Pipeline {
+            ffmpeg,
+            encoder: Some(encoder),
+            counter: None,
+        }),
+        Err(e) => {
+            let _ = ffmpeg.kill();
+            let _ = ffmpeg.wait();
+            Err(e).with_context(|| format!("Failed to run {}", name))
+        }
+    }
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension().is_some_and(|ext| ext == extension)
+}
+
+fn is_mp4(path: &Path) -> bool {
+    has_extension(path, "mp4") || has_extension(path, "mov")
+}
+
+// 인코더별 GStreamer 요소 (비트레이트는 kbps)
+fn default_elements(encoder: Encoder, bitrate_kbps: Option<u32>) -> String {
+    let hardware_kbps = bitrate_kbps.unwrap_or(DEFAULT_HARDWARE_KBPS);
+    match encoder {
+        Encoder::Auto | Encoder::Software => match bitrate_kbps {
+            Some(kbps) => format!("x264enc speed-preset=veryfast bitrate={}", kbps),
+            None => "x264enc speed-preset=veryfast pass=qual quantizer=23".to_string(),
+        },
+        Encoder::V4l2m2m => format!(
+            "v4l2h264enc extra-controls=\"controls,video_bitrate={}\" ! video/x-h264,level=(string)4",
+            hardware_kbps * 1000
+        ),
+        Encoder::Vaapi => format!("vaapih264enc bitrate={}", hardware_kbps),
+        Encoder::Nvenc => format!("nvh264enc bitrate={}", hardware_kbps),
+    }
+}
+
+// 시도할 순서: GStreamer 또는 sidecar (설정한 경우), 선택된 ffmpeg 인코더, ffmpeg 소프트웨어 인코더.
+// Backend::Null 은 그것 하나
+pub fn candidates(encoding: &Encoding) -> Vec<Box<dyn VideoSink>> {
+    let mut sinks: Vec<Box<dyn VideoSink>> = Vec::new();
+    match encoding.sink.backend {
+        Backend::Ffmpeg => {}
+        Backend::Gstreamer => sinks.push(Box::new(GstreamerSink::new(
+            &encoding.sink,
+            encoding.encoder,
+        ))),
+        Backend::Sidecar => sinks.push(Box::new(SidecarSink::new(&encoding.sink))),
+        Backend::Null => return vec![Box::new(NullSink)],
+    }
+    let resolved = encoder::resolve(encoding.encoder);
+    let mut encoders = vec![resolved];
+    if resolved != Encoder::Software {
+        encoders.push(Encoder::Software);
+    }
+    for encoder in encoders {
+        sinks.push(Box::new(FfmpegSink {
+            encoder,
+            bitrate_kbps: encoding.sink.bitrate_kbps,
+            container: encoding.sink.container,
+        }));
+    }
+    sinks
+}
+
+// build 로 만든 ffmpeg (입력과 필터까지) 을 인코딩해 output 에 쓴다.
+// 실패하면 다음 방식으로 한 번씩 더 시도하고, 마지막 결과를 돌려준다.
+pub fn encode(
+    encoding: &Encoding,
+    build: impl Fn(Encoder) -> Command,
+    output: &Path,
+) -> Result<ExitStatus> {
+    let sinks = candidates(encoding);
+    let mut last = None;
+    for (i, sink) in sinks.iter().enumerate() {
+        let result = sink
+            .spawn(build(sink.encoder()), output)
+            .and_then(Pipeline::wait);
+        let failure = match &result {
+            Ok(status) if status.success() => return result,
+            Ok(status) => status.to_string(),
+            Err(e) => format!("{:#}", e),
+        };
+        if cancelled() {
+            return result;
+        }
+        if let Some(next) = sinks.get(i + 1) {
+            warn!(
+                "Encoding with {} failed ({}). Retrying with {}.",
+                sink.describe(),
+                failure,
+                next.describe()
+            );
+        }
+        last = Some(result);
+    }
+    last.context("No video sink available")?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 4x2 yuv420p 프레임 하나는 8 + 2 * (2 * 1) = 12 바이트
+    const FRAME_SIZE: usize = 12;
+
+    fn y4m(frames: usize, last: usize) -> Vec<u8> {
+        let mut stream = b"YUV4MPEG2 W4 H2 F25:1 Ip A1:1 C420jpeg\n".to_vec();
+        for _ in 0..frames {
+            stream.extend_from_slice(b"FRAME\n");
+            stream.extend_from_slice(&[0x80; FRAME_SIZE]);
+        }
+        if last > 0 {
+            stream.extend_from_slice(b"FRAME\n");
+            stream.extend_from_slice(&vec![0x80; last]);
+        }
+        stream
+    }
+
+    #[test]
+    fn counts_frames_and_bytes() {
+        let summary = count_frames(y4m(3, 0).as_slice()).unwrap();
+        assert_eq!(summary.frames, 3);
+        assert_eq!((summary.width, summary.height), (4, 2));
+        assert_eq!(summary.bytes, 3 * FRAME_SIZE as u64);
+    }
+
+    #[test]
+    fn skips_truncated_last_frame() {
+        let summary = count_frames(y4m(2, FRAME_SIZE - 1).as_slice()).unwrap();
+        assert_eq!(summary.frames, 2);
+        assert_eq!(summary.bytes, 2 * FRAME_SIZE as u64);
+    }
+
+    #[test]
+    fn rejects_bad_header() {
+        assert!(count_frames(&b"RIFF....WAVE\n"[..]).is_err());
+        assert!(count_frames(&b""[..]).is_err());
+    }
+
+    // ffmpeg 대신 y4m 을 쓰는 셸. spawn_raw 가 덧붙이는 ffmpeg 인자는 $1.. 로 들어가 무시된다.
+    fn fake_ffmpeg(frames: usize) -> Command {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(format!(
+            "printf 'YUV4MPEG2 W4 H2 F25:1 Ip A1:1 C420jpeg\\n'; \\
+             for i in $(seq {}); do printf 'FRAME\\n'; head -c {} /dev/zero; done",
+            frames, FRAME_SIZE
+        ));
+        command.arg("sh");
+        command
+    }
+
+    #[test]
+    fn null_sink_writes_frame_summary() {
+        let dir = std::env::temp_dir().join(format!("server-null-sink-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let output = dir.join("video.mp4");
+        let status = NullSink
+            .spawn(fake_ffmpeg(5), &output)
+            .and_then(Pipeline::wait)
+            .unwrap();
+        assert!(status.success());
+        assert!(!output.exists());
+        let summary: serde_json::Value =
+            serde_json::from_slice(&fs::read(summary_path(&output)).unwrap()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(summary["frames"], 5);
+        assert_eq!(
+            (summary["width"].clone(), summary["height"].clone()),
+            (4.into(), 2.into())
+        );
+        assert_eq!(summary["bytes"], 5 * FRAME_SIZE);
+    }
+
+    #[test]
+    fn rejects_garbage_between_frames() {
+        let mut stream = y4m(1, 0);
+        stream.extend_from_slice(b"JUNK\n");
+        assert!(count_frames(stream.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,72 @@
+// src/slow_motion.rs
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+
+// 높은 FPS 로 촬영해 두고 완성본을 playback_fps 로 써서 느리게 재생되게 한다.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SlowMotionConfig {
+    pub enabled: bool,
+    // 완성본의 FPS. 120 FPS 로 촬영하고 30 이면 4배 느리게 재생된다.
+    pub playback_fps: u32,
+    // 녹화를 시작하고 실제로 들어오는 FPS 가 요청한 FPS 의 이 비율보다 낮으면 녹화를 멈춘다.
+    pub min_rate_ratio: f64,
+}
+
+impl Default for SlowMotionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            playback_fps: 30,
+            min_rate_ratio: 0.9,
+        }
+    }
+}
+
+impl SlowMotionConfig {
+    // fps 는 촬영 FPS
+    pub fn validate(&self, fps: u32) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        if self.playback_fps == 0 {
+            bail!("slow_motion.playback_fps must be non-zero");
+        }
+        if self.playback_fps >= fps {
+            bail!(
+                "slow_motion.playback_fps ({}) must be lower than the capture fps ({})",
+                self.playback_fps,
+                fps
+            );
+        }
+        if !(self.min_rate_ratio > 0.0 && self.min_rate_ratio <= 1.0) {
+            bail!("slow_motion.min_rate_ratio must be in (0, 1]");
+        }
+        Ok(())
+    }
+
+    // fps 로 촬영한 영상이 몇 배 느리게 재생되는지 (꺼져 있으면 None)
+    pub fn factor(&self, fps: u32) -> Option<f64> {
+        self.enabled.then(|| fps as f64 / self.playback_fps as f64)
+    }
+
+    // 배치까지 마친 영상의 시간을 factor 배로 늘린다.
+    pub fn filter(&self, fps: u32) -> Option<String> {
+        self.factor(fps)
+            .map(|factor| format!("setpts={:.6}*PTS", factor))
+    }
+
+    // 녹화 초반에 잰 FPS 가 요청한 만큼 나오는지 확인한다.
+    pub fn check_rate(&self, camera: u32, requested: u32, measured: f64) -> Result<()> {
+        if measured < requested as f64 * self.min_rate_ratio {
+            bail!(
+                "camera {} delivers {:.1} fps instead of the requested {} fps; \
+                 lower the resolution or pick a sensor mode that supports it",
+                camera,
+                measured,
+                requested
+            );
+        }
+        Ok(())
+    }
+}
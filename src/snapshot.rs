@@ -0,0 +1,404 @@
+// src/snapshot.rs
+use crate::{
+    ApiError, AppState,
+    camera_handler::{CaptureFormat, RecordingConfig},
+    camera_settings::CameraSettings,
+    compositor,
+    source::{DeviceCamera, NetworkCamera, TestPattern},
+};
+use anyhow::{Context, Result, bail};
+use axum::{
+    extract::{Query, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::Arc,
+};
+use tracing::info;
+
+// 녹화 중 스냅샷은 파일 끝의 이만큼만 디코딩한다 (1초 간격 I 프레임이 포함될 만큼).
+const TAIL_BYTES: u64 = 4 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    #[default]
+    #[serde(alias = "jpg")]
+    Jpeg,
+    Png,
+}
+
+impl ImageFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Png => "png",
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Png => "image/png",
+        }
+    }
+}
+
+// 이미지 한 장을 만드는 방법: 쉬고 있는 카메라는 직접 촬영, 녹화 중인 카메라는
+// 녹화 파일의 마지막 프레임을 디코딩 (libcamera 카메라는 한 프로세스만 열 수 있다).
+#[derive(Debug, Clone)]
+pub enum FrameSource {
+    Camera {
+        index: u32,
+        width: u32,
+        height: u32,
+        // 녹화와 같은 부분, 같은 방향으로 찍도록 넘기는 camera_settings
+        settings: Option<CameraSettings>,
+    },
+    Network(NetworkCamera),
+    Device(DeviceCamera, CaptureFormat),
+    TestPattern(TestPattern, CaptureFormat),
+    Recording(PathBuf),
+}
+
+fn temp_path(name: &str, format: ImageFormat) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "server_{}_{}_{}.{}",
+        name,
+        std::process::id(),
+        chrono::Local::now().format("%H%M%S%f"),
+        format.extension()
+    ))
+}
+
+// 분할 녹화 중이면 가장 최근 세그먼트 파일을 찾는다.
+fn current_file(path: &Path) -> Option<PathBuf> {
+    let pattern = path.to_string_lossy();
+    if !pattern.contains("%04d") {
+        return path.exists().then(|| path.to_path_buf());
+    }
+    (0..10_000)
+        .map(|number| PathBuf::from(pattern.replace("%04d", &format!("{:04}", number))))
+        .take_while(|segment| segment.exists())
+        .last()
+}
+
+fn capture_still(
+    index: u32,
+    (width, height): (u32, u32),
+    settings: Option<&CameraSettings>,
+    format: ImageFormat,
+) -> Result<Vec<u8>> {
+    let output = Command::new("libcamera-still")
+        .args(settings.map(CameraSettings::args).unwrap_or_default())
+        .arg("--camera")
+        .arg(index.to_string())
+        .arg("--width")
+        .arg(width.to_string())
+        .arg("--height")
+        .arg(height.to_string())
+        .arg("--encoding")
+        .arg(format.extension())
+        .arg("--nopreview")
+        .arg("--timeout")
+        .arg("1")
+        .arg("--output")
+        .arg("-")
+        .stderr(Stdio::null())
+        .output()
+        .context("Failed to run libcamera-still")?;
+    if !output.status.success() || output.stdout.is_empty() {
+        bail!(
+            "libcamera-still failed for camera {}: {}",
+            index,
+            output.status
+        );
+    }
+    Ok(output.stdout)
+}
+
+fn capture_network(network: &NetworkCamera, format: ImageFormat) -> Result<Vec<u8>> {
+    let image = temp_path("network", format);
+    let result = network
+        .capture_still(&image)
+        .and_then(|()| fs::read(&image).context("ffmpeg produced no frame"));
+    let _ = fs::remove_file(&image);
+    result
+}
+
+fn capture_device(
+    device: &DeviceCamera,
+    capture: CaptureFormat,
+    format: ImageFormat,
+) -> Result<Vec<u8>> {
+    let image = temp_path("device", format);
+    let result = device
+        .capture_still(capture, &image)
+        .and_then(|()| fs::read(&image).context("ffmpeg produced no frame"));
+    let _ = fs::remove_file(&image);
+    result
+}
+
+fn capture_test_pattern(
+    pattern: &TestPattern,
+    capture: CaptureFormat,
+    format: ImageFormat,
+) -> Result<Vec<u8>> {
+    let image = temp_path("pattern", format);
+    let result = pattern
+        .capture_still(capture, &image)
+        .and_then(|()| fs::read(&image).context("ffmpeg produced no frame"));
+    let _ = fs::remove_file(&image);
+    result
+}
+
+// 녹화 중인 H.264 파일의 끝부분을 ffmpeg 로 디코딩해 마지막 프레임을 이미지로 만든다.
+fn latest_recorded_frame(video: &Path, format: ImageFormat) -> Result<Vec<u8>> {
+    let video = current_file(video).context("Recording file does not exist yet")?;
+    let mut file = File::open(&video).with_context(|| format!("Failed to open {:?}", video))?;
+    let len = file.metadata()?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(TAIL_BYTES)))?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)?;
+
+    let image = temp_path("frame", format);
+    let mut child = Command::new("ffmpeg")
+        .arg("-y")
+        .arg("-loglevel")
+        .arg("error")
+        .arg("-f")
+        .arg("h264")
+        .arg("-i")
+        .arg("pipe:0")
+        // 프레임마다 같은 파일을 덮어써 마지막 프레임만 남긴다.
+        .arg("-update")
+        .arg("1")
+        .arg(&image)
+        .stdin(Stdio::piped())
+        .spawn()
+        .context("Failed to run ffmpeg")?;
+    child
+        .stdin
+        .take()
+        .context("ffmpeg stdin unavailable")?
+        .write_all(&tail)
+        .context("Failed to feed ffmpeg")?;
+    let status = child.wait()?;
+
+    let result = if status.success() {
+        fs::read(&image).context("ffmpeg produced no frame")
+    } else {
+        Err(anyhow::anyhow!(
+            "ffmpeg failed to decode {:?}: {}",
+            video,
+            status
+        ))
+    };
+    let _ = fs::remove_file(&image);
+    result
+}
+
+pub fn grab_frame(source: &FrameSource, format: ImageFormat) -> Result<Vec<u8>> {
+    match source {
+        FrameSource::Camera {
+            index,
+            width,
+            height,
+            settings,
+        } => capture_still(*index, (*width, *height), settings.as_ref(), format),
+        FrameSource::Network(network) => capture_network(network, format),
+        FrameSource::Device(device, capture) => capture_device(device, *capture, format),
+        FrameSource::TestPattern(pattern, capture) => {
+            capture_test_pattern(pattern, *capture, format)
+        }
+        FrameSource::Recording(path) => latest_recorded_frame(path, format),
+    }
+}
+
+// 이미지를 돌리고 가림 영역을 칠한다 (filter 는 camera_filter).
+fn mask_image(image: &[u8], filter: &str, format: ImageFormat) -> Result<Vec<u8>> {
+    let input = temp_path("unmasked", format);
+    let output = temp_path("masked", format);
+    let result = (|| {
+        fs::write(&input, image)?;
+        let status = Command::new(compositor::FFMPEG)
+            .arg("-y")
+            .arg("-loglevel")
+            .arg("error")
+            .arg("-i")
+            .arg(&input)
+            .arg("-vf")
+            .arg(filter)
+            .arg("-frames:v")
+            .arg("1")
+            .arg(&output)
+            .status()
+            .context("Failed to run ffmpeg")?;
+        if !status.success() {
+            bail!("ffmpeg failed to rotate or mask the frame: {}", status);
+        }
+        fs::read(&output).context("ffmpeg produced no frame")
+    })();
+    let _ = fs::remove_file(&input);
+    let _ = fs::remove_file(&output);
+    result
+}
+
+// mask 는 그 카메라의 회전과 가림 영역 필터. 칠하지 못하면 이미지를 내주지 않는다.
+fn grab_masked(source: &FrameSource, mask: Option<&str>, format: ImageFormat) -> Result<Vec<u8>> {
+    let image = grab_frame(source, format)?;
+    match mask {
+        Some(mask) => mask_image(&image, mask, format),
+        None => Ok(image),
+    }
+}
+
+// 여러 카메라의 프레임을 녹화와 같은 레이아웃으로 합성. masks 는 sources 와 같은 순서
+pub fn grab_combined(
+    sources: &[FrameSource],
+    masks: &[Option<String>],
+    arrangement: &compositor::Arrangement,
+    format: ImageFormat,
+) -> Result<Vec<u8>> {
+    if sources.len() == 1 {
+        return grab_masked(&sources[0], masks[0].as_deref(), format);
+    }
+
+    let mut parts = Vec::with_capacity(sources.len());
+    let result = (|| {
+        for (i, (source, mask)) in sources.iter().zip(masks).enumerate() {
+            let path = temp_path(&format!("part{}", i), format);
+            fs::write(&path, grab_masked(source, mask.as_deref(), format)?)?;
+            parts.push(path);
+        }
+        let combined = temp_path("combined", format);
+        compositor::compose_image(&parts, arrangement, &combined)?;
+        let bytes = fs::read(&combined);
+        let _ = fs::remove_file(&combined);
+        Ok(bytes?)
+    })();
+    for part in &parts {
+        let _ = fs::remove_file(part);
+    }
+    result
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct SnapshotParams {
+    // 생략하면 기본 카메라 목록(녹화 중이면 녹화 중인 카메라)을 합성한 이미지
+    pub camera: Option<u32>,
+    pub format: ImageFormat,
+    // true 면 저장 디렉토리에도 남긴다.
+    pub save: bool,
+}
+
+pub async fn handle_snapshot(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<SnapshotParams>,
+) -> Result<Response, ApiError> {
+    // 카메라 설정과 가림 영역은 PUT 으로 바뀐 지금 값을 쓴다.
+    let defaults = &RecordingConfig {
+        camera_settings: state.camera_controls.all(),
+        masks: state.privacy_masks.all(),
+        ..state.config.recording.clone()
+    };
+    let (active_cameras, outputs, shown) = {
+        let running = state.sessions.lock().unwrap().running();
+        let mut cameras = Vec::new();
+        let mut outputs = BTreeMap::new();
+        let mut shown = None;
+        for session in running {
+            let stats = session.stats.lock().unwrap();
+            cameras.extend(stats.cameras.iter().copied());
+            outputs.extend(stats.camera_outputs.clone());
+            shown = shown.or(stats.active_camera);
+        }
+        (cameras, outputs, shown)
+    };
+
+    let cameras = match params.camera {
+        Some(camera) => vec![camera],
+        None if !active_cameras.is_empty() => active_cameras,
+        None => defaults.cameras.clone(),
+    };
+    let sources: Vec<FrameSource> = cameras
+        .iter()
+        .map(
+            |index| match (outputs.get(index), defaults.network_camera(*index)) {
+                (Some(path), _) => FrameSource::Recording(path.clone()),
+                (None, Some(network)) => FrameSource::Network(network.clone()),
+                (None, None) if let Some(device) = defaults.device_camera(*index) => {
+                    FrameSource::Device(device.clone(), defaults.format_for(*index))
+                }
+                (None, None) if defaults.test_patterns.contains(index) => FrameSource::TestPattern(
+                    TestPattern { camera: *index },
+                    defaults.format_for(*index),
+                ),
+                (None, None) => {
+                    let capture = defaults.format_for(*index);
+                    FrameSource::Camera {
+                        index: *index,
+                        width: capture.width,
+                        height: capture.height,
+                        settings: Some(state.camera_controls.get(*index)),
+                    }
+                }
+            },
+        )
+        .collect();
+
+    // 녹화 중인 카메라에도 지금 설정된 회전과 가림 영역을 적용한다.
+    let masks: Vec<Option<String>> = cameras
+        .iter()
+        .map(|&camera| defaults.camera_filter(camera))
+        .collect();
+
+    // 녹화를 기다리며 열어 둔 카메라는 닫아야 libcamera-still 이 열 수 있다.
+    if sources
+        .iter()
+        .any(|source| matches!(source, FrameSource::Camera { .. }))
+    {
+        state.warm_cameras.release();
+    }
+
+    let format = params.format;
+    let mut arrangement = defaults.arrangement();
+    // Layout::Switch 는 녹화 중 화면에 나오는 카메라를 보여 준다.
+    if let Some(input) = shown.and_then(|shown| cameras.iter().position(|&c| c == shown)) {
+        arrangement.switches = vec![(0.0, input)];
+    }
+    let image =
+        tokio::task::spawn_blocking(move || grab_combined(&sources, &masks, &arrangement, format))
+            .await
+            .map_err(|e| ApiError::internal(e.to_string()))?
+            .map_err(|e| ApiError::new(StatusCode::BAD_GATEWAY, format!("{:#}", e)))?;
+
+    if params.save {
+        let save_dir = state.config.save_dir();
+        let label = match params.camera {
+            Some(camera) => format!("cam{}", camera),
+            None => "combined".to_string(),
+        };
+        let name = format!(
+            "snapshot_{}_{}.{}",
+            chrono::Local::now().format("%Y%m%d_%H%M%S"),
+            label,
+            format.extension()
+        );
+        let path = save_dir.join(&name);
+        fs::create_dir_all(&save_dir)
+            .and_then(|_| fs::write(&path, &image))
+            .map_err(|e| ApiError::internal(format!("Failed to save snapshot: {}", e)))?;
+        info!("Snapshot saved to {:?}", path);
+    }
+
+    Ok(([(header::CONTENT_TYPE, format.content_type())], image).into_response())
+}
@@ -0,0 +1,225 @@
+// src/stats.rs
+use crate::{
+    ApiError, AppState,
+    events::{Event, EventKind},
+    recordings::RecordingEntry,
+    storage::CatalogQuery,
+};
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+use chrono::{DateTime, Local, NaiveDate};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct CameraCounters {
+    disconnects: u64,
+    failed_recordings: u64,
+}
+
+// 서버가 시작한 뒤 이벤트로 센 카메라별 끊김과 실패한 녹화 (다시 시작하면 0 부터)
+pub struct Counters {
+    since: DateTime<Local>,
+    cameras: Mutex<BTreeMap<u32, CameraCounters>>,
+    // 녹화 중인 세션의 카메라 (끝날 때 실패를 그 카메라들에 센다)
+    sessions: Mutex<HashMap<Uuid, Vec<u32>>>,
+}
+
+impl Default for Counters {
+    fn default() -> Self {
+        Self {
+            since: Local::now(),
+            cameras: Mutex::new(BTreeMap::new()),
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Counters {
+    fn count(&self, event: &Event) {
+        match &event.kind {
+            EventKind::RecordingStarted { cameras } => {
+                self.sessions
+                    .lock()
+                    .unwrap()
+                    .insert(event.session_id, cameras.clone());
+            }
+            EventKind::RecordingStopped { error, .. } => {
+                let cameras = self.sessions.lock().unwrap().remove(&event.session_id);
+                if error.is_some() {
+                    let mut counters = self.cameras.lock().unwrap();
+                    for camera in cameras.unwrap_or_default() {
+                        counters.entry(camera).or_default().failed_recordings += 1;
+                    }
+                }
+            }
+            EventKind::CameraDisconnected { camera, .. } => {
+                self.cameras
+                    .lock()
+                    .unwrap()
+                    .entry(*camera)
+                    .or_default()
+                    .disconnects += 1;
+            }
+            _ => {}
+        }
+    }
+}
+
+// 서버가 멈출 때까지 이벤트를 센다.
+pub async fn run(state: Arc<AppState>) {
+    let mut receiver = state.events.subscribe();
+    loop {
+        match receiver.recv().await {
+            Ok(event) => state.counters.count(&event),
+            Err(broadcast::error::RecvError::Lagged(_)) => {}
+            Err(broadcast::error::RecvError::Closed) => return,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct DailyStats {
+    pub date: NaiveDate,
+    pub recordings: u64,
+    pub hours: f64,
+    pub frames: u64,
+    pub dropped_frames: u64,
+    // 빠진 프레임 / (찍은 프레임 + 빠진 프레임). 프레임 정보가 없으면 None
+    pub drop_rate: Option<f64>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct CameraStats {
+    pub camera: u32,
+    pub recordings: u64,
+    pub hours: f64,
+    pub disconnects: u64,
+    pub failed_recordings: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StatsResponse {
+    pub recordings: u64,
+    pub sessions: u64,
+    pub total_hours: f64,
+    pub total_bytes: u64,
+    // 녹화 길이로 가중한 실제 FPS
+    pub average_fps: Option<f64>,
+    pub drop_rate: Option<f64>,
+    // 세션이 시작한 날짜별 (오래된 것부터)
+    pub daily: Vec<DailyStats>,
+    pub cameras: Vec<CameraStats>,
+    // 카메라별 disconnects 와 failed_recordings 를 세기 시작한 시각
+    pub counters_since: DateTime<Local>,
+}
+
+// 파일의 길이 (초). ffprobe 로 읽지 못했으면 사이드카의 시각으로 잰다.
+fn seconds(entry: &RecordingEntry) -> f64 {
+    entry.duration_seconds.unwrap_or_else(|| {
+        entry.metadata.as_ref().map_or(0.0, |metadata| {
+            (metadata.ended_at - metadata.started_at).num_milliseconds() as f64 / 1000.0
+        })
+    })
+}
+
+fn drop_rate(frames: u64, dropped: u64) -> Option<f64> {
+    let total = frames + dropped;
+    (total > 0).then(|| dropped as f64 / total as f64)
+}
+
+fn summarize(entries: &[RecordingEntry], counters: &Counters) -> StatsResponse {
+    let mut total_hours = 0.0;
+    let mut fps_seconds = (0.0, 0.0);
+    let mut daily: BTreeMap<NaiveDate, DailyStats> = BTreeMap::new();
+    let mut cameras: BTreeMap<u32, CameraStats> = BTreeMap::new();
+    // 프레임 수는 세션 전체 기준이라 (분할 녹화, 카메라별 파일) 세션마다 한 번만 센다.
+    let mut sessions: HashMap<Uuid, (NaiveDate, u64, u64)> = HashMap::new();
+    for entry in entries {
+        let seconds = seconds(entry);
+        total_hours += seconds / 3600.0;
+        let started = entry
+            .metadata
+            .as_ref()
+            .map_or(entry.created, |metadata| metadata.started_at);
+        let day = daily.entry(started.date_naive()).or_default();
+        day.recordings += 1;
+        day.hours += seconds / 3600.0;
+        let Some(metadata) = &entry.metadata else {
+            continue;
+        };
+        for &camera in &metadata.cameras {
+            let stats = cameras.entry(camera).or_default();
+            stats.recordings += 1;
+            stats.hours += seconds / 3600.0;
+        }
+        // 잘라 낸 파일은 원래 녹화의 통계를 그대로 가지고 있다.
+        if metadata.clip_of.is_some() {
+            continue;
+        }
+        if seconds > 0.0 {
+            fps_seconds.0 += metadata.actual_fps * seconds;
+            fps_seconds.1 += seconds;
+        }
+        let session = sessions
+            .entry(metadata.session_id)
+            .or_insert((started.date_naive(), 0, 0));
+        session.0 = session.0.min(started.date_naive());
+        session.1 = session.1.max(metadata.frame_count);
+        session.2 = session.2.max(metadata.dropped_frames);
+    }
+
+    let (mut frames, mut dropped) = (0, 0);
+    for &(date, session_frames, session_dropped) in sessions.values() {
+        frames += session_frames;
+        dropped += session_dropped;
+        let day = daily.entry(date).or_default();
+        day.frames += session_frames;
+        day.dropped_frames += session_dropped;
+    }
+    for (date, day) in daily.iter_mut() {
+        day.date = *date;
+        day.drop_rate = drop_rate(day.frames, day.dropped_frames);
+    }
+    for (&camera, counted) in counters.cameras.lock().unwrap().iter() {
+        let stats = cameras.entry(camera).or_default();
+        stats.disconnects = counted.disconnects;
+        stats.failed_recordings = counted.failed_recordings;
+    }
+    for (&camera, stats) in cameras.iter_mut() {
+        stats.camera = camera;
+    }
+
+    StatsResponse {
+        recordings: entries.len() as u64,
+        sessions: sessions.len() as u64,
+        total_hours,
+        total_bytes: entries.iter().map(|entry| entry.size).sum(),
+        average_fps: (fps_seconds.1 > 0.0).then(|| fps_seconds.0 / fps_seconds.1),
+        drop_rate: drop_rate(frames, dropped),
+        daily: daily.into_values().collect(),
+        cameras: cameras.into_values().collect(),
+        counters_since: counters.since,
+    }
+}
+
+// GET /stats - 카탈로그의 녹화 (GET /recordings 와 같은 조건으로 고를 수 있다) 와
+// 서버가 시작한 뒤 센 카메라 실패
+pub async fn handle_stats(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CatalogQuery>,
+) -> Result<Json<StatsResponse>, ApiError> {
+    let catalog = state.catalog.clone();
+    let entries = tokio::task::spawn_blocking(move || catalog.query(&query))
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))?
+        .map_err(|e| ApiError::internal(format!("{:#}", e)))?;
+    Ok(Json(summarize(&entries, &state.counters)))
+}
@@ -0,0 +1,529 @@
+// src/storage.rs
+use crate::{
+    audit::{AuditEntry, AuditQuery},
+    auth::Role,
+    projects::Project,
+    recordings::{self, RecordingEntry},
+};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use rusqlite::{Connection, params};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tracing::info;
+use uuid::Uuid;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS recordings (
+        name TEXT PRIMARY KEY,
+        size INTEGER NOT NULL,
+        duration_seconds REAL,
+        -- UTC RFC 3339 (자릿수가 고정되어 문자열 순서가 시간 순서)
+        created TEXT NOT NULL,
+        session_id TEXT,
+        -- 밀리초 단위 Unix 시각. 사이드카가 없으면 파일 생성 시각
+        started_ms INTEGER NOT NULL,
+        ended_ms INTEGER NOT NULL,
+        metadata TEXT
+    );
+    CREATE INDEX IF NOT EXISTS recordings_started ON recordings (started_ms);
+    CREATE INDEX IF NOT EXISTS recordings_session ON recordings (session_id);
+    CREATE TABLE IF NOT EXISTS recording_cameras (
+        name TEXT NOT NULL REFERENCES recordings (name) ON DELETE CASCADE,
+        camera INTEGER NOT NULL,
+        PRIMARY KEY (name, camera)
+    );
+    CREATE INDEX IF NOT EXISTS recording_cameras_camera ON recording_cameras (camera);
+";
+
+// 처음 만든 뒤 바뀐 스키마. PRAGMA user_version 에 적용한 개수를 기록한다.
+const MIGRATIONS: &[&str] = &[
+    "
+    ALTER TABLE recordings ADD COLUMN upload_status TEXT NOT NULL DEFAULT 'local';
+    -- 업로드한 객체의 키
+    ALTER TABLE recordings ADD COLUMN remote_key TEXT;
+",
+    "
+    CREATE TABLE projects (
+        name TEXT PRIMARY KEY,
+        description TEXT NOT NULL DEFAULT '',
+        -- UTC RFC 3339
+        created TEXT NOT NULL
+    );
+    -- 사이드카의 project
+    ALTER TABLE recordings ADD COLUMN project TEXT;
+    CREATE INDEX recordings_project ON recordings (project);
+",
+    "
+    CREATE TABLE audit (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        -- UTC RFC 3339
+        time TEXT NOT NULL,
+        source TEXT NOT NULL,
+        user TEXT,
+        role TEXT,
+        client_ip TEXT,
+        action TEXT NOT NULL,
+        status INTEGER NOT NULL,
+        session_id TEXT
+    );
+    CREATE INDEX audit_time ON audit (time);
+",
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UploadStatus {
+    #[default]
+    Local,
+    Uploading,
+    Uploaded,
+}
+
+impl UploadStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Local => "local",
+            Self::Uploading => "uploading",
+            Self::Uploaded => "uploaded",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "uploading" => Self::Uploading,
+            "uploaded" => Self::Uploaded,
+            _ => Self::Local,
+        }
+    }
+}
+
+// GET /recordings 의 검색 조건. 모두 생략하면 전체
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct CatalogQuery {
+    pub session_id: Option<Uuid>,
+    pub project: Option<String>,
+    // 이 카메라가 들어간 녹화만
+    pub camera: Option<u32>,
+    // 녹화 구간이 [from, to] 와 겹치는 것만 (RFC 3339, 예: 2025-01-31T09:00:00+09:00)
+    pub from: Option<DateTime<Local>>,
+    pub to: Option<DateTime<Local>>,
+}
+
+// 저장 디렉토리의 녹화 목록을 SQLite 에 보관한다. 녹화가 끝나면 추가하고,
+// 삭제하면 지우고, 시작할 때 디렉토리와 맞춘다.
+pub struct Catalog {
+    connection: Mutex<Connection>,
+}
+
+impl Catalog {
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {:?}", parent))?;
+        }
+        let connection = Connection::open(path)
+            .with_context(|| format!("Failed to open the recording catalog {:?}", path))?;
+        connection
+            .execute_batch("PRAGMA foreign_keys = ON;")
+            .and_then(|()| connection.execute_batch(SCHEMA))
+            .with_context(|| format!("Failed to initialize the recording catalog {:?}", path))?;
+        migrate(&connection)
+            .with_context(|| format!("Failed to migrate the recording catalog {:?}", path))?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    // 새로 확정된 녹화 파일을 추가한다 (이미 있으면 덮어쓴다).
+    pub fn add(&self, save_dir: &Path, paths: &[PathBuf]) -> Result<()> {
+        let mut entries = Vec::with_capacity(paths.len());
+        for path in paths {
+            let file = fs::metadata(path).with_context(|| format!("Failed to read {:?}", path))?;
+            entries.push(recordings::load_entry(save_dir, path, &file, true)?);
+        }
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        for entry in &entries {
+            upsert(&transaction, entry)?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    pub fn remove(&self, name: &str) -> Result<()> {
+        self.connection
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM recordings WHERE name = ?1", params![name])
+            .with_context(|| format!("Failed to remove {} from the catalog", name))?;
+        Ok(())
+    }
+
+    // 조건에 맞는 녹화 (최신순)
+    pub fn query(&self, query: &CatalogQuery) -> Result<Vec<RecordingEntry>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT name, size, duration_seconds, created, metadata, upload_status, remote_key
+             FROM recordings
+             WHERE (?1 IS NULL OR session_id = ?1)
+               AND (?2 IS NULL OR name IN
+                    (SELECT name FROM recording_cameras WHERE camera = ?2))
+               AND (?3 IS NULL OR ended_ms >= ?3)
+               AND (?4 IS NULL OR started_ms <= ?4)
+               AND (?5 IS NULL OR project = ?5)
+             ORDER BY created DESC",
+        )?;
+        let rows = statement.query_map(
+            params![
+                query.session_id.map(|id| id.to_string()),
+                query.camera,
+                query.from.map(|time| time.timestamp_millis()),
+                query.to.map(|time| time.timestamp_millis()),
+                query.project,
+            ],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, Option<f64>>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                    row.get::<_, String>(5)?,
+                    row.get::<_, Option<String>>(6)?,
+                ))
+            },
+        )?;
+
+        let mut entries = Vec::new();
+        for row in rows {
+            let (name, size, duration_seconds, created, metadata, upload_status, remote_key) = row?;
+            let created = parse_time(&created)?;
+            entries.push(RecordingEntry {
+                name,
+                size: size as u64,
+                duration_seconds,
+                created,
+                metadata: metadata.and_then(|json| serde_json::from_str(&json).ok()),
+                upload_status: UploadStatus::parse(&upload_status),
+                remote_key,
+            });
+        }
+        Ok(entries)
+    }
+
+    // 디렉토리에 없는 항목은 지우고, 목록에 없거나 크기가 바뀐 파일은 다시 읽어 넣는다.
+    // 업로드를 마친 항목은 로컬 파일이 없어도 남긴다.
+    pub fn reconcile(&self, save_dir: &Path) -> Result<()> {
+        let on_disk = recordings::scan(save_dir, false)?;
+        let known: HashMap<String, (u64, UploadStatus)> = {
+            let connection = self.connection.lock().unwrap();
+            let mut statement =
+                connection.prepare("SELECT name, size, upload_status FROM recordings")?;
+            statement
+                .query_map([], |row| {
+                    let status = UploadStatus::parse(&row.get::<_, String>(2)?);
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        (row.get::<_, i64>(1)? as u64, status),
+                    ))
+                })?
+                .collect::<Result<_, _>>()?
+        };
+
+        let names: HashSet<&String> = on_disk.iter().map(|entry| &entry.name).collect();
+        let mut stale: Vec<&String> = known
+            .iter()
+            .filter(|(name, (_, status))| {
+                !names.contains(name) && *status != UploadStatus::Uploaded
+            })
+            .map(|(name, _)| name)
+            .collect();
+        stale.sort();
+        let changed: Vec<PathBuf> = on_disk
+            .iter()
+            .filter(|entry| known.get(&entry.name).map(|(size, _)| *size) != Some(entry.size))
+            .map(|entry| save_dir.join(&entry.name))
+            .collect();
+
+        {
+            let mut connection = self.connection.lock().unwrap();
+            let transaction = connection.transaction()?;
+            for name in &stale {
+                transaction.execute("DELETE FROM recordings WHERE name = ?1", params![name])?;
+            }
+            transaction.commit()?;
+        }
+        if !changed.is_empty() {
+            info!(
+                "Indexing {} recording(s) in {:?}...",
+                changed.len(),
+                save_dir
+            );
+            self.add(save_dir, &changed)?;
+        }
+        info!(
+            "Recording catalog: {} recording(s), {} added or updated, {} removed.",
+            on_disk.len(),
+            changed.len(),
+            stale.len()
+        );
+        Ok(())
+    }
+
+    pub fn set_upload_status(
+        &self,
+        name: &str,
+        status: UploadStatus,
+        remote_key: Option<&str>,
+    ) -> Result<()> {
+        self.connection
+            .lock()
+            .unwrap()
+            .execute(
+                "UPDATE recordings SET upload_status = ?2, remote_key = ?3 WHERE name = ?1",
+                params![name, status.as_str(), remote_key],
+            )
+            .with_context(|| format!("Failed to update the upload status of {}", name))?;
+        Ok(())
+    }
+
+    // 새 프로젝트를 만든다. 같은 이름이 이미 있으면 false
+    pub fn create_project(&self, name: &str, description: &str) -> Result<bool> {
+        let created = self
+            .connection
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT OR IGNORE INTO projects (name, description, created) VALUES (?1, ?2, ?3)",
+                params![name, description, format_time(Local::now())],
+            )
+            .with_context(|| format!("Failed to create project {}", name))?;
+        Ok(created > 0)
+    }
+
+    // 프로젝트와 그 녹화의 수, 크기, 길이 합 (name 을 주면 그 프로젝트만, 만든 순서)
+    pub fn projects(&self, name: Option<&str>) -> Result<Vec<Project>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT projects.name, projects.description, projects.created,
+                    COUNT(recordings.name), COALESCE(SUM(recordings.size), 0),
+                    COALESCE(SUM(recordings.duration_seconds), 0)
+             FROM projects LEFT JOIN recordings ON recordings.project = projects.name
+             WHERE (?1 IS NULL OR projects.name = ?1)
+             GROUP BY projects.name
+             ORDER BY projects.created",
+        )?;
+        let rows = statement.query_map(params![name], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, i64>(4)?,
+                row.get::<_, f64>(5)?,
+            ))
+        })?;
+        let mut projects = Vec::new();
+        for row in rows {
+            let (name, description, created, recordings, total_size, total_duration_seconds) = row?;
+            projects.push(Project {
+                name,
+                description,
+                created: parse_time(&created)?,
+                recordings: recordings as u64,
+                total_size: total_size as u64,
+                total_duration_seconds,
+            });
+        }
+        Ok(projects)
+    }
+
+    pub fn project(&self, name: &str) -> Result<Option<Project>> {
+        Ok(self.projects(Some(name))?.pop())
+    }
+
+    // 프로젝트를 지운다. 없으면 false (녹화는 그대로 둔다)
+    pub fn delete_project(&self, name: &str) -> Result<bool> {
+        let deleted = self
+            .connection
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM projects WHERE name = ?1", params![name])
+            .with_context(|| format!("Failed to delete project {}", name))?;
+        Ok(deleted > 0)
+    }
+
+    pub fn add_audit(&self, entry: &AuditEntry) -> Result<()> {
+        self.connection
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO audit
+                     (time, source, user, role, client_ip, action, status, session_id)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    format_time(entry.timestamp),
+                    entry.source,
+                    entry.user,
+                    entry.role.map(Role::as_str),
+                    entry.client_ip.map(|ip| ip.to_string()),
+                    entry.action,
+                    entry.status,
+                    entry.session_id.map(|id| id.to_string()),
+                ],
+            )
+            .context("Failed to add to the audit log")?;
+        Ok(())
+    }
+
+    // 최신순
+    pub fn audit(&self, query: &AuditQuery) -> Result<Vec<AuditEntry>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT id, time, source, user, role, client_ip, action, status, session_id
+             FROM audit
+             WHERE (?1 IS NULL OR user = ?1)
+               AND (?2 IS NULL OR source = ?2)
+               AND (?3 IS NULL OR time >= ?3)
+               AND (?4 IS NULL OR time <= ?4)
+             ORDER BY id DESC
+             LIMIT ?5",
+        )?;
+        let rows = statement.query_map(
+            params![
+                query.user,
+                query.source,
+                query.from.map(format_time),
+                query.to.map(format_time),
+                query.limit() as i64,
+            ],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                    row.get::<_, Option<String>>(5)?,
+                    row.get::<_, String>(6)?,
+                    row.get::<_, u16>(7)?,
+                    row.get::<_, Option<String>>(8)?,
+                ))
+            },
+        )?;
+        let mut entries = Vec::new();
+        for row in rows {
+            let (id, time, source, user, role, client_ip, action, status, session_id) = row?;
+            entries.push(AuditEntry {
+                id,
+                timestamp: parse_time(&time)?,
+                source,
+                user,
+                role: role.as_deref().and_then(Role::parse),
+                client_ip: client_ip.and_then(|ip| ip.parse().ok()),
+                action,
+                status,
+                session_id: session_id.and_then(|id| id.parse().ok()),
+            });
+        }
+        Ok(entries)
+    }
+
+    // 아직 업로드하지 않았거나 업로드 중에 멈춘 녹화 (오래된 것부터)
+    pub fn pending_uploads(&self) -> Result<Vec<String>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT name FROM recordings WHERE upload_status != 'uploaded' ORDER BY created",
+        )?;
+        let names = statement
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        Ok(names)
+    }
+}
+
+fn parse_time(value: &str) -> Result<DateTime<Local>> {
+    Ok(DateTime::parse_from_rfc3339(value)
+        .with_context(|| format!("Invalid time in the catalog: {}", value))?
+        .with_timezone(&Local))
+}
+
+fn format_time(time: DateTime<Local>) -> String {
+    time.with_timezone(&Utc)
+        .to_rfc3339_opts(SecondsFormat::Nanos, true)
+}
+
+fn migrate(connection: &Connection) -> Result<()> {
+    let applied: usize = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(applied) {
+        connection.execute_batch(migration)?;
+        connection.pragma_update(None, "user_version", index + 1)?;
+    }
+    Ok(())
+}
+
+fn upsert(connection: &Connection, entry: &RecordingEntry) -> Result<()> {
+    let created_ms = entry.created.timestamp_millis();
+    let (session_id, started_ms, ended_ms, cameras) = match &entry.metadata {
+        Some(metadata) => (
+            Some(metadata.session_id.to_string()),
+            metadata.started_at.timestamp_millis(),
+            metadata.ended_at.timestamp_millis(),
+            metadata.cameras.clone(),
+        ),
+        None => (None, created_ms, created_ms, Vec::new()),
+    };
+    let project = entry
+        .metadata
+        .as_ref()
+        .and_then(|metadata| metadata.project.as_deref());
+    let created = format_time(entry.created);
+    let metadata = entry
+        .metadata
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()?;
+
+    connection.execute(
+        "DELETE FROM recordings WHERE name = ?1",
+        params![entry.name],
+    )?;
+    connection.execute(
+        "INSERT INTO recordings
+             (name, size, duration_seconds, created, session_id, started_ms, ended_ms, metadata,
+              project)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            entry.name,
+            entry.size as i64,
+            entry.duration_seconds,
+            created,
+            session_id,
+            started_ms,
+            ended_ms,
+            metadata,
+            project,
+        ],
+    )?;
+    // 카탈로그를 지우고 다시 만들었거나 다른 서버에서 옮겨 온 녹화도 프로젝트 목록에 나오게 한다.
+    if let Some(project) = project {
+        connection.execute(
+            "INSERT OR IGNORE INTO projects (name, created) VALUES (?1, ?2)",
+            params![project, created],
+        )?;
+    }
+    for camera in cameras {
+        connection.execute(
+            "INSERT INTO recording_cameras (name, camera) VALUES (?1, ?2)",
+            params![entry.name, camera],
+        )?;
+    }
+    Ok(())
+}
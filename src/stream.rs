@@ -0,0 +1,567 @@
+// src/stream.rs
+use crate::{
+    camera_handler::RecordingConfig,
+    camera_settings::CameraControls,
+    config::Config,
+    encoder,
+    feed::{self, Feeds, QueueStats, WriteQueueConfig},
+    masks::PrivacyMasks,
+    session::SessionManager,
+    sink,
+};
+use anyhow::{Context, Result};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, File},
+    io::Read,
+    path::{Path, PathBuf},
+    process::{Child, Stdio},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+// 소스(녹화 중인 세션 / 카메라 직접 / 없음)를 다시 정하는 주기
+const SOURCE_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+// 소스 ffmpeg 가 실패했을 때 다시 시작하기까지 기다리는 시간
+const SOURCE_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+// 소스를 바꿀 때 ffmpeg 가 스스로 끝나기를 기다리는 시간
+const SOURCE_STOP_GRACE: Duration = Duration::from_secs(1);
+
+// 느린 시청자가 이만큼 뒤처지면 다음 키프레임까지 건너뛴다.
+const UNIT_BUFFER: usize = 128;
+
+pub const CLOCK_RATE: u64 = 90_000;
+
+// 같은 시각에 찍힌 NAL 묶음 (프레임 하나)
+pub struct AccessUnit {
+    // 90kHz, 서버 시작 시각 기준
+    pub timestamp: u32,
+    pub nals: Vec<Vec<u8>>,
+    pub keyframe: bool,
+}
+
+// 시청자가 고를 수 있는 영상
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum View {
+    // 녹화와 같은 배치로 합성한 영상 (한 대면 그 카메라)
+    Composite,
+    // 카메라 한 대의 원본 스트림 (가림 영역이 없으면 다시 인코딩하지 않는다)
+    Camera(u32),
+}
+
+impl View {
+    fn fifo_name(self) -> String {
+        match self {
+            Self::Composite => "out_composite.fifo".to_string(),
+            Self::Camera(camera) => format!("out_cam{}.fifo", camera),
+        }
+    }
+}
+
+// 구독하는 동안 시청자 수에 포함된다. 시청자가 없으면 소스를 닫는다.
+pub struct Subscription {
+    pub units: broadcast::Receiver<Arc<AccessUnit>>,
+    viewers: Arc<AtomicUsize>,
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.viewers.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SourceKind {
+    // 녹화 중인 세션의 카메라 파일을 따라 읽는다.
+    Session(Uuid),
+    // 녹화가 없을 때 시청자를 위해 카메라를 직접 연다.
+    Idle,
+}
+
+// 지금 내보내는 영상을 만드는 프로세스들
+struct Source {
+    kind: SourceKind,
+    ffmpeg: Child,
+    feeds: Feeds,
+    // Idle 일 때: 캡처 FIFO 로 바로 쓰는 libcamera-vid (feeds 가 읽는다)
+    cameras: Vec<Child>,
+    dir: PathBuf,
+    camera_indices: Vec<u32>,
+    readers: Vec<JoinHandle<()>>,
+}
+
+impl Source {
+    fn stop(mut self) {
+        for camera in &mut self.cameras {
+            let _ = camera.kill();
+            let _ = camera.wait();
+        }
+        self.feeds.stop();
+        feed::wait_or_kill(&mut self.ffmpeg, SOURCE_STOP_GRACE);
+        // ffmpeg 가 출력을 열기 전에 끝났으면 읽는 스레드가 막혀 있으므로 풀어 준다.
+        for view in views(&self.camera_indices) {
+            drop(feed::open_writer(&self.dir.join(view.fifo_name())));
+        }
+        for reader in self.readers.drain(..) {
+            let _ = reader.join();
+        }
+        self.feeds.finish();
+    }
+}
+
+fn views(cameras: &[u32]) -> Vec<View> {
+    let mut views = vec![View::Composite];
+    views.extend(cameras.iter().map(|&camera| View::Camera(camera)));
+    views
+}
+
+// RTSP 와 WebRTC 가 함께 쓰는 영상 소스. 시청자가 있을 때만 ffmpeg 를 돌린다.
+pub struct StreamHub {
+    senders: Mutex<HashMap<View, broadcast::Sender<Arc<AccessUnit>>>>,
+    viewers: Arc<AtomicUsize>,
+    source: Mutex<Option<Source>>,
+    // 소스가 바뀌어도 타임스탬프가 이어지도록 서버 시작 시각을 기준으로 한다.
+    epoch: Instant,
+}
+
+impl Default for StreamHub {
+    fn default() -> Self {
+        Self {
+            senders: Mutex::new(HashMap::new()),
+            viewers: Arc::new(AtomicUsize::new(0)),
+            source: Mutex::new(None),
+            epoch: Instant::now(),
+        }
+    }
+}
+
+impl StreamHub {
+    pub fn subscribe(&self, view: View) -> Subscription {
+        self.viewers.fetch_add(1, Ordering::SeqCst);
+        Subscription {
+            units: self.sender(view).subscribe(),
+            viewers: self.viewers.clone(),
+        }
+    }
+
+    fn sender(&self, view: View) -> broadcast::Sender<Arc<AccessUnit>> {
+        self.senders
+            .lock()
+            .unwrap()
+            .entry(view)
+            .or_insert_with(|| broadcast::channel(UNIT_BUFFER).0)
+            .clone()
+    }
+
+    // 녹화를 시작하기 전에 호출한다. 카메라를 직접 열고 있으면 닫아 녹화가 열 수 있게 한다.
+    pub fn release_cameras(&self) {
+        let mut source = self.source.lock().unwrap();
+        if source
+            .as_ref()
+            .is_some_and(|source| source.kind == SourceKind::Idle)
+        {
+            info!("Releasing cameras held by the stream preview.");
+            if let Some(source) = source.take() {
+                source.stop();
+            }
+        }
+    }
+
+    // 지금 소스의 카메라별 쓰기 대기열 (소스가 없으면 비어 있다)
+    pub fn queue_stats(&self) -> BTreeMap<u32, QueueStats> {
+        self.source
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|source| source.feeds.queue_stats())
+            .unwrap_or_default()
+    }
+
+    fn timestamp(&self) -> u32 {
+        let ticks = self.epoch.elapsed().as_micros() as u64 * CLOCK_RATE / 1_000_000;
+        ticks as u32
+    }
+}
+
+// 소스 관리 스레드를 시작한다 (RTSP, WebRTC, 움직임 감지, 녹화 전 버퍼 중 하나라도 켠 경우에만).
+pub fn spawn(
+    hub: Arc<StreamHub>,
+    config: Arc<Config>,
+    sessions: Arc<Mutex<SessionManager>>,
+    controls: Arc<CameraControls>,
+    masks: Arc<PrivacyMasks>,
+) -> Result<()> {
+    let needed = config.rtsp.enabled
+        || config.webrtc.enabled
+        || config.motion.enabled
+        || config.pre_roll.enabled;
+    if !needed {
+        return Ok(());
+    }
+    thread::Builder::new()
+        .name("stream-source".to_string())
+        .spawn(move || manage_source(&hub, &config, &sessions, &controls, &masks))
+        .context("Failed to start stream source thread")?;
+    Ok(())
+}
+
+fn manage_source(
+    hub: &Arc<StreamHub>,
+    config: &Config,
+    sessions: &Mutex<SessionManager>,
+    controls: &CameraControls,
+    masks: &PrivacyMasks,
+) {
+    let mut retry_at: Option<Instant> = None;
+    loop {
+        thread::sleep(SOURCE_CHECK_INTERVAL);
+
+        let mut source = hub.source.lock().unwrap();
+        let session = sessions.lock().unwrap().running().pop();
+        let wanted = match &session {
+            _ if hub.viewers.load(Ordering::SeqCst) == 0 => None,
+            Some(session) => Some(SourceKind::Session(session.id)),
+            None => Some(SourceKind::Idle),
+        };
+
+        // 소스 ffmpeg 가 스스로 끝났으면 (입력 문제 등) 잠시 뒤 다시 시작한다.
+        let exited = source
+            .as_mut()
+            .is_some_and(|source| !matches!(source.ffmpeg.try_wait(), Ok(None)));
+        if exited {
+            warn!("Stream source ffmpeg exited. Restarting it shortly.");
+            if let Some(old) = source.take() {
+                old.stop();
+            }
+            retry_at = Some(Instant::now() + SOURCE_RETRY_DELAY);
+        }
+
+        if source.as_ref().map(|source| source.kind) != wanted {
+            if let Some(old) = source.take() {
+                old.stop();
+            }
+            if retry_at.is_some_and(|at| Instant::now() < at) {
+                continue;
+            }
+            retry_at = None;
+
+            let started = match (wanted, &session) {
+                (Some(SourceKind::Session(id)), Some(session)) => {
+                    let outputs: Vec<(u32, PathBuf)> = session
+                        .stats
+                        .lock()
+                        .unwrap()
+                        .camera_outputs
+                        .iter()
+                        .map(|(camera, path)| (*camera, path.clone()))
+                        .collect();
+                    // 카메라가 아직 열리지 않았으면 다음 주기에 다시 본다.
+                    if outputs.is_empty() {
+                        continue;
+                    }
+                    start_session_source(hub, id, &session.config, &outputs, &config.write_queue)
+                }
+                (Some(SourceKind::Idle), _) => {
+                    let defaults = RecordingConfig {
+                        camera_settings: controls.all(),
+                        masks: masks.all(),
+                        ..config.recording.clone()
+                    };
+                    start_idle_source(hub, &defaults, &config.write_queue)
+                }
+                _ => continue,
+            };
+            match started {
+                Ok(started) => *source = Some(started),
+                Err(e) => {
+                    warn!("Failed to start the stream source: {:#}", e);
+                    retry_at = Some(Instant::now() + SOURCE_RETRY_DELAY);
+                }
+            }
+        } else if let (Some(source), Some(session)) = (source.as_ref(), &session) {
+            // 재연결로 카메라 파일이 바뀌면 따라간다.
+            for (camera, path) in &session.stats.lock().unwrap().camera_outputs {
+                source.feeds.follow(*camera, path);
+            }
+        }
+    }
+}
+
+fn source_dir(kind: SourceKind) -> PathBuf {
+    let name = match kind {
+        SourceKind::Session(id) => id.to_string(),
+        SourceKind::Idle => "idle".to_string(),
+    };
+    std::env::temp_dir().join("server-stream").join(name)
+}
+
+fn start_session_source(
+    hub: &Arc<StreamHub>,
+    id: Uuid,
+    config: &RecordingConfig,
+    outputs: &[(u32, PathBuf)],
+    queue: &WriteQueueConfig,
+) -> Result<Source> {
+    let kind = SourceKind::Session(id);
+    let dir = source_dir(kind);
+    let feeds = Feeds::start(dir.clone(), outputs, queue)?;
+    let cameras: Vec<u32> = outputs.iter().map(|(camera, _)| *camera).collect();
+    let (ffmpeg, readers) = start_ffmpeg(hub, &dir, &feeds.fifos(), &cameras, config)?;
+    info!("Stream source switched to recording session {}.", id);
+    Ok(Source {
+        kind,
+        ffmpeg,
+        feeds,
+        cameras: Vec::new(),
+        dir,
+        camera_indices: cameras,
+        readers,
+    })
+}
+
+fn start_idle_source(
+    hub: &Arc<StreamHub>,
+    defaults: &RecordingConfig,
+    queue: &WriteQueueConfig,
+) -> Result<Source> {
+    let kind = SourceKind::Idle;
+    let dir = source_dir(kind);
+    // 이전에 비정상 종료하며 남긴 FIFO 가 있으면 지운다.
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    // 카메라는 캡처 FIFO 에 쓰고, feeds 가 그것을 읽어 대기열을 거쳐 ffmpeg 입력 FIFO 로 넘긴다.
+    let captures: Vec<(u32, PathBuf)> = defaults
+        .cameras
+        .iter()
+        .map(|&camera| (camera, dir.join(format!("capture_cam{}.fifo", camera))))
+        .collect();
+    for (_, capture) in &captures {
+        feed::make_fifo(capture)?;
+    }
+    let feeds = Feeds::start(dir.clone(), &captures, queue)?;
+
+    let (ffmpeg, readers) = start_ffmpeg(hub, &dir, &feeds.fifos(), &defaults.cameras, defaults)?;
+    let mut source = Source {
+        kind,
+        ffmpeg,
+        feeds,
+        cameras: Vec::new(),
+        dir,
+        camera_indices: defaults.cameras.clone(),
+        readers,
+    };
+    // 미리보기는 파일로 남기지 않으므로 분할하지 않고 타임스탬프도 버린다.
+    let preview = RecordingConfig {
+        segment_duration: None,
+        ..defaults.clone()
+    };
+    for (camera, capture) in &captures {
+        match preview
+            .source(*camera)
+            .spawn(&preview, capture, sink::NULL_DEVICE.as_ref())
+        {
+            Ok(child) => source.cameras.push(child),
+            Err(e) => {
+                source.stop();
+                return Err(e);
+            }
+        }
+    }
+    info!(
+        "Stream source opened cameras {:?} directly.",
+        defaults.cameras
+    );
+    Ok(source)
+}
+
+// 합성 영상과 카메라별 원본을 각각 출력 FIFO 로 내보내고, 읽는 스레드가 프레임 단위로 나눠 보낸다.
+fn start_ffmpeg(
+    hub: &Arc<StreamHub>,
+    dir: &Path,
+    fifos: &[PathBuf],
+    cameras: &[u32],
+    config: &RecordingConfig,
+) -> Result<(Child, Vec<JoinHandle<()>>)> {
+    let views = views(cameras);
+    for view in &views {
+        feed::make_fifo(&dir.join(view.fifo_name()))?;
+    }
+
+    let encoder = encoder::resolve(config.encoder);
+    let mut command = feed::ffmpeg_inputs(fifos, cameras, config, encoder);
+    if fifos.len() > 1 {
+        let layout = feed::composite_filter(cameras, config);
+        command
+            .arg("-filter_complex")
+            .arg(format!("{}[composite]", encoder.with_upload(&layout)))
+            .arg("-map")
+            .arg("[composite]")
+            .args(encoder.output_args());
+    } else {
+        command
+            .arg("-map")
+            .arg("0:v")
+            .args(feed::camera_output_args(cameras[0], config, encoder));
+    }
+    command
+        .arg("-f")
+        .arg("h264")
+        .arg(dir.join(View::Composite.fifo_name()));
+    for (input, &camera) in cameras.iter().enumerate() {
+        command
+            .arg("-map")
+            .arg(format!("{}:v", input))
+            .args(feed::camera_output_args(camera, config, encoder))
+            .arg("-f")
+            .arg("h264")
+            .arg(dir.join(View::Camera(camera).fifo_name()));
+    }
+    let ffmpeg = command
+        .stdin(Stdio::null())
+        .spawn()
+        .context("Failed to start ffmpeg for streaming")?;
+
+    let mut readers = Vec::with_capacity(views.len());
+    for view in views {
+        let hub = hub.clone();
+        let path = dir.join(view.fifo_name());
+        let reader = thread::Builder::new()
+            .name("stream-reader".to_string())
+            .spawn(move || {
+                // ffmpeg 가 쓰기 쪽을 열 때까지 여기서 기다린다.
+                match File::open(&path) {
+                    Ok(output) => read_units(&hub, view, output),
+                    Err(e) => warn!("Failed to open {:?}: {}", path, e),
+                }
+            })
+            .context("Failed to start stream reader thread")?;
+        readers.push(reader);
+    }
+    Ok((ffmpeg, readers))
+}
+
+// ffmpeg 가 내보내는 Annex B H.264 를 NAL 단위로 자르고 프레임별로 묶어 보낸다.
+fn read_units(hub: &StreamHub, view: View, mut output: File) {
+    let sender = hub.sender(view);
+    let mut pending: Vec<u8> = Vec::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut unit: Vec<Vec<u8>> = Vec::new();
+    loop {
+        let read = match output.read(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(read) => read,
+        };
+        pending.extend_from_slice(&buffer[..read]);
+        for nal in split_nals(&mut pending) {
+            if starts_access_unit(&nal) && unit.iter().any(|nal| is_vcl(nal)) {
+                publish_unit(hub, &sender, std::mem::take(&mut unit));
+            }
+            unit.push(nal);
+        }
+    }
+    if !unit.is_empty() {
+        publish_unit(hub, &sender, unit);
+    }
+}
+
+fn publish_unit(hub: &StreamHub, sender: &broadcast::Sender<Arc<AccessUnit>>, nals: Vec<Vec<u8>>) {
+    let keyframe = nals.iter().any(|nal| is_keyframe(nal));
+    // 받는 시청자가 없어도 실패로 보지 않는다.
+    let _ = sender.send(Arc::new(AccessUnit {
+        timestamp: hub.timestamp(),
+        nals,
+        keyframe,
+    }));
+}
+
+// 시작 코드(00 00 01) 사이의 NAL 을 꺼낸다. 마지막 NAL 은 끝을 알 수 없으므로 pending 에 남긴다.
+pub fn split_nals(pending: &mut Vec<u8>) -> Vec<Vec<u8>> {
+    let mut starts = Vec::new();
+    let mut i = 0;
+    while i + 3 <= pending.len() {
+        if pending[i] == 0 && pending[i + 1] == 0 && pending[i + 2] == 1 {
+            starts.push(i + 3);
+            i += 3;
+        } else {
+            i += 1;
+        }
+    }
+
+    let mut nals = Vec::new();
+    for window in starts.windows(2) {
+        let mut end = window[1] - 3;
+        // 4바이트 시작 코드의 앞쪽 0 은 이전 NAL 에 속하지 않는다.
+        while end > window[0] && pending[end - 1] == 0 {
+            end -= 1;
+        }
+        if end > window[0] {
+            nals.push(pending[window[0]..end].to_vec());
+        }
+    }
+    if let Some(&last) = starts.last() {
+        pending.drain(..last - 3);
+    }
+    nals
+}
+
+fn nal_type(nal: &[u8]) -> u8 {
+    nal.first().map_or(0, |header| header & 0x1f)
+}
+
+pub fn is_vcl(nal: &[u8]) -> bool {
+    matches!(nal_type(nal), 1..=5)
+}
+
+// IDR 슬라이스나 그 앞의 SPS 가 있으면 거기서부터 디코딩할 수 있다.
+pub fn is_keyframe(nal: &[u8]) -> bool {
+    matches!(nal_type(nal), 5 | 7)
+}
+
+// Annex B H.264 파일의 프레임 수 (first_mb_in_slice == 0 인 슬라이스를 센다). 조각씩 읽는다.
+pub fn count_frames(path: &Path) -> Result<u64> {
+    let mut file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    let mut chunk = vec![0; 1 << 16];
+    // 앞 조각 끝에 걸친 시작 코드와 NAL 헤더
+    let mut pending = Vec::new();
+    let mut frames = 0;
+    loop {
+        let read = file
+            .read(&mut chunk)
+            .with_context(|| format!("Failed to read {:?}", path))?;
+        if read == 0 {
+            return Ok(frames);
+        }
+        pending.extend_from_slice(&chunk[..read]);
+        let mut i = 0;
+        while i + 5 <= pending.len() {
+            if pending[i] == 0 && pending[i + 1] == 0 && pending[i + 2] == 1 {
+                let nal = &pending[i + 3..];
+                if is_vcl(nal) && starts_access_unit(nal) {
+                    frames += 1;
+                }
+                i += 3;
+            } else {
+                i += 1;
+            }
+        }
+        pending.drain(..i);
+    }
+}
+
+// AUD, SEI, SPS, PPS 나 프레임의 첫 슬라이스(first_mb_in_slice == 0)면 새 프레임이 시작된다.
+pub fn starts_access_unit(nal: &[u8]) -> bool {
+    match nal_type(nal) {
+        6..=9 => true,
+        1 | 5 => nal.get(1).is_some_and(|byte| byte & 0x80 != 0),
+        _ => false,
+    }
+}
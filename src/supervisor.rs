@@ -0,0 +1,133 @@
+// src/supervisor.rs
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use std::{future::Future, sync::Mutex, time::Duration};
+use tokio::task::JoinHandle;
+use tracing::{error, warn};
+use uuid::Uuid;
+
+// 서버가 멈출 때까지 도는 작업 (예약, 보존 규칙, MQTT) 이 패닉으로 끝났을 때의 처리
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SupervisorConfig {
+    // true 면 restart_backoff_secs 뒤에 다시 시작한다.
+    pub restart_on_panic: bool,
+    // 작업마다 다시 시작하는 최대 횟수. 넘으면 그 작업은 멈춘 채로 둔다.
+    pub max_restarts: u32,
+    pub restart_backoff_secs: u64,
+}
+
+impl Default for SupervisorConfig {
+    fn default() -> Self {
+        Self {
+            restart_on_panic: true,
+            max_restarts: 5,
+            restart_backoff_secs: 5,
+        }
+    }
+}
+
+impl SupervisorConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.restart_on_panic && self.restart_backoff_secs == 0 {
+            bail!("supervisor.restart_backoff_secs must be non-zero");
+        }
+        Ok(())
+    }
+}
+
+struct Job {
+    session_id: Uuid,
+    handle: JoinHandle<()>,
+}
+
+// 백그라운드 작업의 JoinHandle 을 들고 있다가 종료할 때 기다린다.
+pub struct TaskSupervisor {
+    config: SupervisorConfig,
+    // 녹화 세션마다 하나 (끝난 것은 새로 넣을 때 정리한다)
+    jobs: Mutex<Vec<Job>>,
+}
+
+impl TaskSupervisor {
+    pub fn new(config: &SupervisorConfig) -> Self {
+        Self {
+            config: config.clone(),
+            jobs: Mutex::new(Vec::new()),
+        }
+    }
+
+    // 세션의 녹화 작업. 패닉은 작업 안에서 실패로 처리하므로 다시 시작하지 않는다.
+    pub fn spawn_job<F>(&self, session_id: Uuid, job: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let handle = tokio::spawn(job);
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.retain(|job| !job.handle.is_finished());
+        jobs.push(Job { session_id, handle });
+    }
+
+    // 끝나지 않는 작업. 패닉하면 설정에 따라 factory 로 새로 만들어 다시 시작한다.
+    pub fn spawn_service<F, Fut>(&self, name: &'static str, factory: F)
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let config = self.config.clone();
+        tokio::spawn(async move {
+            let mut restarts = 0;
+            loop {
+                match tokio::spawn(factory()).await {
+                    Ok(()) => return,
+                    Err(e) if e.is_panic() && config.restart_on_panic => {
+                        if restarts >= config.max_restarts {
+                            error!(
+                                "{} panicked again after {} restart(s); leaving it stopped.",
+                                name, restarts
+                            );
+                            return;
+                        }
+                        restarts += 1;
+                        error!(
+                            "{} panicked. Restarting in {}s ({}/{})...",
+                            name, config.restart_backoff_secs, restarts, config.max_restarts
+                        );
+                        tokio::time::sleep(Duration::from_secs(config.restart_backoff_secs)).await;
+                    }
+                    Err(e) => {
+                        error!("{} stopped: {}", name, e);
+                        return;
+                    }
+                }
+            }
+        });
+    }
+
+    // 남은 녹화 작업이 모두 끝나기를 timeout 까지 기다린다. 제때 끝나면 true
+    pub async fn join_jobs(&self, timeout: Duration) -> bool {
+        let mut jobs = std::mem::take(&mut *self.jobs.lock().unwrap());
+        let joined = async {
+            for job in jobs.iter_mut() {
+                if let Err(e) = (&mut job.handle).await {
+                    warn!(session_id = %job.session_id, "Recording task ended abnormally: {}", e);
+                }
+            }
+        };
+        let finished = tokio::time::timeout(timeout, joined).await.is_ok();
+        // 이미 기다린 handle 은 다시 poll 하면 안 되므로 끝나지 않은 것만 돌려놓는다.
+        self.jobs
+            .lock()
+            .unwrap()
+            .extend(jobs.into_iter().filter(|job| !job.handle.is_finished()));
+        finished
+    }
+
+    // 아직 돌고 있는 녹화 작업 (세션 id)
+    pub fn running_jobs(&self) -> Vec<Uuid> {
+        let jobs = self.jobs.lock().unwrap();
+        jobs.iter()
+            .filter(|job| !job.handle.is_finished())
+            .map(|job| job.session_id)
+            .collect()
+    }
+}
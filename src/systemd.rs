@@ -0,0 +1,96 @@
+// src/systemd.rs
+use anyhow::Result;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+// systemd 의 Type=notify 서비스로 돌 때 상태를 알린다 (sd_notify 프로토콜).
+// NOTIFY_SOCKET 이 없으면 (systemd 밖에서 실행) 아무것도 하지 않는다.
+pub fn notify(message: &str) {
+    match send(message) {
+        Ok(true) => debug!("systemd notified: {:?}", message),
+        Ok(false) => {}
+        Err(e) => warn!("Failed to notify systemd: {:#}", e),
+    }
+}
+
+// 요청을 받을 준비가 됨
+pub fn ready(status: &str) {
+    notify(&format!("READY=1\nSTATUS={}", status));
+}
+
+pub fn status(status: &str) {
+    notify(&format!("STATUS={}", status));
+}
+
+// 녹화를 마무리하며 멈추는 중. 마무리를 기다리는 동안 systemd 가 TimeoutStopSec 으로 끊지 않게
+// extend 만큼 더 기다려 달라고 한다.
+pub fn stopping(status: &str, extend: Duration) {
+    let mut message = format!("STOPPING=1\nSTATUS={}", status);
+    if !extend.is_zero() {
+        message.push_str(&format!("\nEXTEND_TIMEOUT_USEC={}", extend.as_micros()));
+    }
+    notify(&message);
+}
+
+#[cfg(unix)]
+fn send(message: &str) -> Result<bool> {
+    use anyhow::Context;
+    use std::os::unix::net::UnixDatagram;
+
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return Ok(false);
+    };
+    let socket = UnixDatagram::unbound().context("Failed to create a notify socket")?;
+    let bytes = path.as_encoded_bytes();
+    // '@' 로 시작하면 Linux 의 추상 소켓 이름이다.
+    if let Some(name) = bytes.strip_prefix(b"@") {
+        #[cfg(target_os = "linux")]
+        {
+            use std::os::linux::net::SocketAddrExt;
+            let address = std::os::unix::net::SocketAddr::from_abstract_name(name)
+                .with_context(|| format!("Invalid NOTIFY_SOCKET {:?}", path))?;
+            socket
+                .send_to_addr(message.as_bytes(), &address)
+                .with_context(|| format!("Failed to send to {:?}", path))?;
+        }
+        #[cfg(not(target_os = "linux"))]
+        anyhow::bail!(
+            "Abstract NOTIFY_SOCKET @{} is only supported on Linux",
+            String::from_utf8_lossy(name)
+        );
+    } else {
+        socket
+            .send_to(message.as_bytes(), &path)
+            .with_context(|| format!("Failed to send to {:?}", path))?;
+    }
+    Ok(true)
+}
+
+#[cfg(not(unix))]
+fn send(_message: &str) -> Result<bool> {
+    Ok(false)
+}
+
+// WatchdogSec 으로 정한 간격. 다른 프로세스 (WATCHDOG_PID) 에게 온 것이면 None
+fn watchdog_interval() -> Option<Duration> {
+    if let Ok(pid) = std::env::var("WATCHDOG_PID")
+        && pid.parse::<u32>().ok() != Some(std::process::id())
+    {
+        return None;
+    }
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec))
+}
+
+// 서버가 멈출 때까지 watchdog 간격의 절반마다 살아 있다고 알린다. 런타임이 멈추면 알림도 멈춰
+// systemd 가 서비스를 다시 시작한다.
+pub async fn run_watchdog() {
+    let Some(timeout) = watchdog_interval() else {
+        return;
+    };
+    let mut interval = tokio::time::interval(timeout / 2);
+    loop {
+        interval.tick().await;
+        notify("WATCHDOG=1");
+    }
+}
@@ -0,0 +1,60 @@
+// src/timelapse.rs
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+
+// 평소처럼 녹화한 뒤 마무리 단계에서 interval_secs 마다 한 프레임만 골라
+// playback_fps 로 재생되는 영상을 만든다.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimelapseConfig {
+    pub enabled: bool,
+    // 몇 초마다 한 프레임을 남길지
+    pub interval_secs: f64,
+    // 완성본의 FPS (30 이고 10초마다 한 장이면 녹화 5분이 1초가 된다)
+    pub playback_fps: u32,
+    // 프레임마다 찍힌 시각을 그린다 (overlays 가 있는 카메라는 그 설정을 따른다).
+    pub clock: bool,
+}
+
+impl Default for TimelapseConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: 10.0,
+            playback_fps: 30,
+            clock: false,
+        }
+    }
+}
+
+impl TimelapseConfig {
+    // fps 는 촬영 FPS. 프레임 간격보다 자주 고를 수는 없다.
+    pub fn validate(&self, fps: u32) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        if !self.interval_secs.is_finite() || self.interval_secs <= 0.0 {
+            bail!("timelapse.interval_secs must be positive");
+        }
+        if self.playback_fps == 0 {
+            bail!("timelapse.playback_fps must be non-zero");
+        }
+        if self.interval_secs * fps as f64 <= 1.0 {
+            bail!(
+                "timelapse.interval_secs must be longer than one frame at {} fps",
+                fps
+            );
+        }
+        Ok(())
+    }
+
+    // 배치까지 마친 영상에서 interval_secs 마다 한 프레임을 골라 playback_fps 간격으로 다시 매긴다.
+    pub fn filter(&self) -> Option<String> {
+        self.enabled.then(|| {
+            format!(
+                "fps=fps=1/{:.3},setpts=N/({}*TB)",
+                self.interval_secs, self.playback_fps
+            )
+        })
+    }
+}
@@ -0,0 +1,117 @@
+// src/tls.rs
+use crate::config;
+use anyhow::{Context, Result, bail};
+use axum_server::tls_rustls::RustlsConfig;
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf};
+use tracing::info;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TlsConfig {
+    // true 면 HTTP 대신 HTTPS 로만 받는다.
+    pub enabled: bool,
+    // PEM 형식 인증서 (체인 포함) 와 개인 키
+    pub cert_path: String,
+    pub key_path: String,
+    // true 면 인증서 파일이 없을 때 자체 서명 인증서를 만든다 (LAN 용).
+    pub self_signed: bool,
+    // 자체 서명 인증서에 넣을 이름 (클라이언트가 접속하는 호스트 이름이나 IP)
+    pub hostnames: Vec<String>,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cert_path: "tls/cert.pem".to_string(),
+            key_path: "tls/key.pem".to_string(),
+            self_signed: false,
+            hostnames: vec!["localhost".to_string()],
+        }
+    }
+}
+
+impl TlsConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.cert_path.trim().is_empty() || self.key_path.trim().is_empty() {
+            bail!("tls.cert_path and tls.key_path must not be empty");
+        }
+        if self.hostnames.is_empty() {
+            bail!("tls.hostnames must not be empty");
+        }
+        Ok(())
+    }
+
+    pub fn cert_path(&self) -> PathBuf {
+        config::expand_path(&self.cert_path)
+    }
+
+    pub fn key_path(&self) -> PathBuf {
+        config::expand_path(&self.key_path)
+    }
+}
+
+// hostnames 용 자체 서명 인증서와 키를 cert_path, key_path 에 쓴다. 이미 있으면 덮어쓰지 않는다.
+pub fn generate_self_signed(config: &TlsConfig) -> Result<()> {
+    let (cert_path, key_path) = (config.cert_path(), config.key_path());
+    if cert_path.exists() || key_path.exists() {
+        bail!(
+            "{:?} or {:?} already exists; remove them to generate a new certificate",
+            cert_path,
+            key_path
+        );
+    }
+    let certified = rcgen::generate_simple_self_signed(config.hostnames.clone())
+        .context("Failed to generate a self-signed certificate")?;
+
+    for path in [&cert_path, &key_path] {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {:?}", parent))?;
+        }
+    }
+    fs::write(&cert_path, certified.cert.pem())
+        .with_context(|| format!("Failed to write {:?}", cert_path))?;
+    fs::write(&key_path, certified.key_pair.serialize_pem())
+        .with_context(|| format!("Failed to write {:?}", key_path))?;
+    restrict_permissions(&key_path)?;
+
+    info!(
+        "Generated a self-signed certificate for {:?} at {:?}.",
+        config.hostnames, cert_path
+    );
+    Ok(())
+}
+
+// 개인 키는 소유자만 읽을 수 있게 한다.
+#[cfg(unix)]
+fn restrict_permissions(path: &std::path::Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+        .with_context(|| format!("Failed to restrict permissions of {:?}", path))
+}
+
+#[cfg(not(unix))]
+fn restrict_permissions(_path: &std::path::Path) -> Result<()> {
+    Ok(())
+}
+
+pub async fn load(config: &TlsConfig) -> Result<RustlsConfig> {
+    // 의존하는 크레이트에 따라 여러 암호 라이브러리가 함께 링크될 수 있으므로 직접 고른다.
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    let (cert_path, key_path) = (config.cert_path(), config.key_path());
+    if config.self_signed && !cert_path.exists() && !key_path.exists() {
+        generate_self_signed(config)?;
+    }
+    RustlsConfig::from_pem_file(&cert_path, &key_path)
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to load the TLS certificate {:?} and key {:?}",
+                cert_path, key_path
+            )
+        })
+}
@@ -0,0 +1,314 @@
+// src/upload.rs
+use crate::{
+    recordings,
+    storage::{Catalog, UploadStatus},
+};
+use anyhow::{Context, Result, bail};
+use s3::{Bucket, Region, creds::Credentials};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    fs::File,
+    io::AsyncReadExt,
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+};
+use tracing::{info, warn};
+
+// 멀티파트 업로드의 조각 크기 (S3 는 마지막 조각을 빼고 5MB 이상이어야 한다)
+const PART_SIZE: usize = 8 * 1024 * 1024;
+
+// 재시도 간격의 상한
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UploadConfig {
+    // true 면 녹화가 끝난 파일을 S3 호환 저장소에 올린다.
+    pub enabled: bool,
+    // 예: "https://s3.ap-northeast-2.amazonaws.com", "http://minio.local:9000"
+    pub endpoint: String,
+    pub region: String,
+    pub bucket: String,
+    // 객체 키 앞에 붙는 경로 (예: "cameras/front"). 키는 prefix/저장 디렉토리 기준 이름
+    pub prefix: String,
+    // 생략하면 AWS_ACCESS_KEY_ID 등 표준 환경 변수나 ~/.aws/credentials 를 쓴다.
+    // /config 에는 노출하지 않는다.
+    #[serde(skip_serializing)]
+    pub access_key: Option<String>,
+    #[serde(skip_serializing)]
+    pub secret_key: Option<String>,
+    // MinIO 처럼 버킷을 호스트 이름이 아닌 경로로 지정하는 서버는 true
+    pub path_style: bool,
+    // 파일 하나를 올리는 최대 시도 횟수 (2초, 4초, ... 간격)
+    pub max_attempts: u32,
+    // true 면 업로드가 끝난 파일의 로컬 사본을 지운다 (카탈로그 항목은 남는다).
+    pub delete_local: bool,
+}
+
+impl Default for UploadConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: String::new(),
+            region: "us-east-1".to_string(),
+            bucket: String::new(),
+            prefix: String::new(),
+            access_key: None,
+            secret_key: None,
+            path_style: true,
+            max_attempts: 5,
+            delete_local: false,
+        }
+    }
+}
+
+impl UploadConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        if !(self.endpoint.starts_with("http://") || self.endpoint.starts_with("https://")) {
+            bail!("upload.endpoint must start with http:// or https://");
+        }
+        if self.bucket.trim().is_empty() {
+            bail!("upload.bucket must not be empty");
+        }
+        if self.max_attempts == 0 {
+            bail!("upload.max_attempts must be non-zero");
+        }
+        if self.access_key.is_some() != self.secret_key.is_some() {
+            bail!("upload.access_key and upload.secret_key must be set together");
+        }
+        Ok(())
+    }
+
+    fn object_key(&self, name: &str) -> String {
+        let prefix = self.prefix.trim_matches('/');
+        if prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", prefix, name)
+        }
+    }
+}
+
+// 업로드할 녹화를 하나씩 순서대로 올린다. 이름은 저장 디렉토리 기준 상대 경로
+pub struct Uploader {
+    config: UploadConfig,
+    catalog: Arc<Catalog>,
+    save_dir: PathBuf,
+    sender: UnboundedSender<String>,
+    receiver: Mutex<Option<UnboundedReceiver<String>>>,
+}
+
+impl Uploader {
+    pub fn new(config: &UploadConfig, catalog: Arc<Catalog>, save_dir: PathBuf) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        Self {
+            config: config.clone(),
+            catalog,
+            save_dir,
+            sender,
+            receiver: Mutex::new(Some(receiver)),
+        }
+    }
+
+    // 녹화가 끝난 파일을 업로드 대기열에 넣는다 (설정에서 끈 경우 무시).
+    pub fn enqueue(&self, paths: &[PathBuf]) {
+        if !self.config.enabled {
+            return;
+        }
+        for path in paths {
+            let _ = self
+                .sender
+                .send(recordings::relative_name(&self.save_dir, path));
+        }
+    }
+
+    async fn upload(&self, bucket: &Bucket, name: &str) {
+        let path = self.save_dir.join(name);
+        if !path.is_file() {
+            warn!(
+                "Skipping the upload of {}: the file no longer exists.",
+                name
+            );
+            return;
+        }
+        let key = self.config.object_key(name);
+        self.set_status(name, UploadStatus::Uploading, None);
+
+        let mut delay = Duration::from_secs(2);
+        for attempt in 1..=self.config.max_attempts {
+            let error = match put_file(bucket, &path, &key).await {
+                Ok(size) => {
+                    self.set_status(name, UploadStatus::Uploaded, Some(&key));
+                    info!(
+                        "Uploaded {} ({} bytes) to s3://{}/{}",
+                        name, size, self.config.bucket, key
+                    );
+                    if self.config.delete_local {
+                        match recordings::remove_recording(&path) {
+                            Ok(()) => info!("Deleted the local copy of {}", name),
+                            Err(e) => warn!("{:#}", e),
+                        }
+                    }
+                    return;
+                }
+                Err(e) => e,
+            };
+            if attempt == self.config.max_attempts {
+                warn!(
+                    "Giving up on uploading {} after {} attempt(s): {:#}",
+                    name, attempt, error
+                );
+                self.set_status(name, UploadStatus::Local, None);
+                return;
+            }
+            warn!(
+                "Upload of {} failed ({:#}). Retrying in {}s.",
+                name,
+                error,
+                delay.as_secs()
+            );
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(MAX_RETRY_DELAY);
+        }
+    }
+
+    fn set_status(&self, name: &str, status: UploadStatus, remote_key: Option<&str>) {
+        if let Err(e) = self.catalog.set_upload_status(name, status, remote_key) {
+            warn!("{:#}", e);
+        }
+    }
+}
+
+fn open_bucket(config: &UploadConfig) -> Result<Box<Bucket>> {
+    let credentials = Credentials::new(
+        config.access_key.as_deref(),
+        config.secret_key.as_deref(),
+        None,
+        None,
+        None,
+    )
+    .context("Failed to load S3 credentials")?;
+    let region = Region::Custom {
+        region: config.region.clone(),
+        endpoint: config.endpoint.trim_end_matches('/').to_string(),
+    };
+    let bucket = Bucket::new(&config.bucket, region, credentials)
+        .with_context(|| format!("Failed to set up the S3 bucket {}", config.bucket))?;
+    Ok(if config.path_style {
+        bucket.with_path_style()
+    } else {
+        bucket
+    })
+}
+
+// 업로드 작업을 시작하고, 카탈로그에서 아직 올리지 않은 녹화를 먼저 대기열에 넣는다.
+pub fn spawn(uploader: Arc<Uploader>) -> Result<()> {
+    if !uploader.config.enabled {
+        return Ok(());
+    }
+    let bucket = open_bucket(&uploader.config)?;
+    let Some(mut receiver) = uploader.receiver.lock().unwrap().take() else {
+        bail!("The uploader is already running");
+    };
+    let pending = uploader.catalog.pending_uploads()?;
+    if !pending.is_empty() {
+        info!("{} recording(s) waiting to be uploaded.", pending.len());
+    }
+    for name in pending {
+        let _ = uploader.sender.send(name);
+    }
+
+    info!(
+        "Uploading finished recordings to {} (bucket {}).",
+        uploader.config.endpoint, uploader.config.bucket
+    );
+    tokio::spawn(async move {
+        while let Some(name) = receiver.recv().await {
+            uploader.upload(&bucket, &name).await;
+        }
+    });
+    Ok(())
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("mp4") => "video/mp4",
+        Some("mkv") => "video/x-matroska",
+        Some("ts") => "video/mp2t",
+        Some("h264") => "video/h264",
+        _ => "application/octet-stream",
+    }
+}
+
+// PART_SIZE 보다 작으면 한 번에, 크면 조각 단위로 읽어 멀티파트로 올린다. 올린 바이트 수를 돌려준다.
+async fn put_file(bucket: &Bucket, path: &Path, key: &str) -> Result<u64> {
+    let mut file = File::open(path)
+        .await
+        .with_context(|| format!("Failed to open {:?}", path))?;
+    let content_type = content_type(path);
+
+    let first = read_part(&mut file).await?;
+    if first.len() < PART_SIZE {
+        let response = bucket
+            .put_object_with_content_type(key, &first, content_type)
+            .await?;
+        check_status(response.status_code())?;
+        return Ok(first.len() as u64);
+    }
+
+    let upload = bucket.initiate_multipart_upload(key, content_type).await?;
+    let result = async {
+        let mut parts = Vec::new();
+        let mut total = 0u64;
+        let mut chunk = first;
+        while !chunk.is_empty() {
+            total += chunk.len() as u64;
+            let number = parts.len() as u32 + 1;
+            parts.push(
+                bucket
+                    .put_multipart_chunk(chunk, key, number, &upload.upload_id, content_type)
+                    .await?,
+            );
+            chunk = read_part(&mut file).await?;
+        }
+        let response = bucket
+            .complete_multipart_upload(key, &upload.upload_id, parts)
+            .await?;
+        check_status(response.status_code())?;
+        Ok(total)
+    }
+    .await;
+    if result.is_err() {
+        let _ = bucket.abort_upload(key, &upload.upload_id).await;
+    }
+    result
+}
+
+// 파일 끝이 아니면 PART_SIZE 만큼 채워 읽는다.
+async fn read_part(file: &mut File) -> Result<Vec<u8>> {
+    let mut buffer = vec![0; PART_SIZE];
+    let mut filled = 0;
+    while filled < PART_SIZE {
+        let read = file.read(&mut buffer[filled..]).await?;
+        if read == 0 {
+            break;
+        }
+        filled += read;
+    }
+    buffer.truncate(filled);
+    Ok(buffer)
+}
+
+fn check_status(status: u16) -> Result<()> {
+    if !(200..300).contains(&status) {
+        bail!("HTTP {}", status);
+    }
+    Ok(())
+}
@@ -0,0 +1,107 @@
+// src/watermark.rs
+use crate::{
+    config,
+    overlay::{self, OverlayPosition},
+};
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// 완성본의 모든 프레임에 반투명하게 겹치는 PNG 로고 (알파 채널을 그대로 쓴다)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatermarkConfig {
+    pub enabled: bool,
+    // PNG 파일 경로 ('~' 확장)
+    pub path: String,
+    pub position: OverlayPosition,
+    // 0.0 (보이지 않음) ~ 1.0 (PNG 그대로)
+    pub opacity: f64,
+    // 로고 너비를 출력 너비의 이 비율로 맞춘다. 0 이면 PNG 원래 크기
+    pub scale: f64,
+    // 화면 가장자리와 로고 사이 여백 (픽셀)
+    pub margin: u32,
+}
+
+impl Default for WatermarkConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: String::new(),
+            position: OverlayPosition::BottomRight,
+            opacity: 0.5,
+            scale: 0.15,
+            margin: 16,
+        }
+    }
+}
+
+impl WatermarkConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        if self.path.trim().is_empty() {
+            bail!("watermark.path is required when the watermark is enabled");
+        }
+        if !Path::new(&self.expanded_path()).is_file() {
+            bail!("watermark.path {:?} is not a file", self.path);
+        }
+        if !(0.0..=1.0).contains(&self.opacity) {
+            bail!("watermark.opacity must be between 0.0 and 1.0");
+        }
+        if !(0.0..=1.0).contains(&self.scale) {
+            bail!("watermark.scale must be between 0.0 and 1.0");
+        }
+        Ok(())
+    }
+
+    fn expanded_path(&self) -> String {
+        config::expand_path(&self.path)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    // 출력 레이블이 없는 필터 그래프 graph 의 결과 위에 로고를 겹친 그래프 (꺼져 있으면 그대로)
+    pub fn apply(&self, graph: &str) -> String {
+        if !self.enabled {
+            return graph.to_string();
+        }
+        let mut logo = format!(
+            "movie=filename='{}',format=rgba,colorchannelmixer=aa={:.3}",
+            overlay::escape_option(&self.expanded_path()),
+            self.opacity
+        );
+        let mut chains = Vec::new();
+        let base = if self.scale > 0.0 {
+            // 합성본의 크기는 배치에 따라 달라지므로 scale2ref 로 출력 너비에 맞춘다.
+            logo.push_str("[logo]");
+            chains.push(logo);
+            chains.push(format!(
+                "[logo][marked]scale2ref=w=trunc(main_w*{:.3}/2)*2:h=ow/a[logo_fit][base]",
+                self.scale
+            ));
+            "[base][logo_fit]"
+        } else {
+            logo.push_str("[logo_fit]");
+            chains.push(logo);
+            "[marked][logo_fit]"
+        };
+        let margin = self.margin;
+        let (x, y) = match self.position {
+            OverlayPosition::TopLeft => (margin.to_string(), margin.to_string()),
+            OverlayPosition::TopRight => {
+                (format!("main_w-overlay_w-{}", margin), margin.to_string())
+            }
+            OverlayPosition::BottomLeft => {
+                (margin.to_string(), format!("main_h-overlay_h-{}", margin))
+            }
+            OverlayPosition::BottomRight => (
+                format!("main_w-overlay_w-{}", margin),
+                format!("main_h-overlay_h-{}", margin),
+            ),
+        };
+        chains.push(format!("{}overlay=x={}:y={}:format=auto", base, x, y));
+        format!("{}[marked];{}", graph, chains.join(";"))
+    }
+}
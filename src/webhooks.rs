@@ -0,0 +1,304 @@
+// src/webhooks.rs
+use crate::{ApiError, AppState, chat::ChatConfig};
+use anyhow::{Result, bail};
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use chrono::{DateTime, Local};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+// 본문의 HMAC-SHA256 서명 (secret 이 설정된 경우에만)
+const SIGNATURE_HEADER: &str = "X-Signature-256";
+const EVENT_HEADER: &str = "X-Event";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookConfig {
+    // 시작할 때 등록되는 주소 (API 로 추가한 주소는 재시작하면 사라진다)
+    pub urls: Vec<String>,
+    // /config 에는 노출하지 않는다.
+    #[serde(skip_serializing)]
+    pub secret: Option<String>,
+    // 실패 시 다시 보내는 횟수 (1초, 2초, 4초... 간격)
+    pub max_retries: u32,
+    pub timeout_secs: u64,
+    // Slack, Telegram 으로 보내는 사람이 읽는 알림
+    pub chat: ChatConfig,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            urls: Vec::new(),
+            secret: None,
+            max_retries: 3,
+            timeout_secs: 10,
+            chat: ChatConfig::default(),
+        }
+    }
+}
+
+impl WebhookConfig {
+    pub fn validate(&self) -> Result<()> {
+        for url in &self.urls {
+            validate_url(url)?;
+        }
+        if self.timeout_secs == 0 {
+            bail!("webhooks.timeout_secs must be non-zero");
+        }
+        self.chat.validate()
+    }
+}
+
+fn validate_url(url: &str) -> Result<()> {
+    if !(url.starts_with("http://") || url.starts_with("https://")) {
+        bail!("Webhook URL must start with http:// or https://: {}", url);
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event")]
+pub enum WebhookEvent {
+    #[serde(rename = "recording_started")]
+    Started { session_id: Uuid, cameras: Vec<u32> },
+    #[serde(rename = "recording_finished")]
+    Finished {
+        session_id: Uuid,
+        outputs: Vec<PathBuf>,
+        duration_seconds: f64,
+        frames_captured: u64,
+    },
+    #[serde(rename = "recording_failed")]
+    Failed { session_id: Uuid, error: String },
+}
+
+impl WebhookEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Started { .. } => "recording_started",
+            Self::Finished { .. } => "recording_finished",
+            Self::Failed { .. } => "recording_failed",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    #[serde(flatten)]
+    event: &'a WebhookEvent,
+    timestamp: DateTime<Local>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Webhook {
+    pub id: u64,
+    pub url: String,
+    // 설정 파일에서 온 주소인지
+    pub from_config: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookRequest {
+    pub url: String,
+}
+
+pub struct Webhooks {
+    client: reqwest::Client,
+    secret: Option<String>,
+    max_retries: u32,
+    hooks: Mutex<Vec<Webhook>>,
+    next_id: Mutex<u64>,
+}
+
+impl Webhooks {
+    pub fn new(config: &WebhookConfig) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()?;
+        let hooks: Vec<Webhook> = config
+            .urls
+            .iter()
+            .enumerate()
+            .map(|(index, url)| Webhook {
+                id: index as u64 + 1,
+                url: url.clone(),
+                from_config: true,
+            })
+            .collect();
+        Ok(Self {
+            client,
+            secret: config.secret.clone(),
+            max_retries: config.max_retries,
+            next_id: Mutex::new(hooks.len() as u64 + 1),
+            hooks: Mutex::new(hooks),
+        })
+    }
+
+    // 설정 파일에서 온 주소
+    pub fn configured_urls(&self) -> Vec<String> {
+        self.hooks
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|hook| hook.from_config)
+            .map(|hook| hook.url.clone())
+            .collect()
+    }
+
+    // 설정에서 온 주소를 바꾼다 (POST /config). 남는 주소는 id 를 그대로 두고, API 로 추가한 주소는 건드리지 않는다.
+    pub fn replace_configured(&self, urls: &[String]) {
+        let mut hooks = self.hooks.lock().unwrap();
+        let mut next_id = self.next_id.lock().unwrap();
+        hooks.retain(|hook| !hook.from_config || urls.contains(&hook.url));
+        for url in urls {
+            if hooks
+                .iter()
+                .any(|hook| hook.from_config && &hook.url == url)
+            {
+                continue;
+            }
+            hooks.push(Webhook {
+                id: *next_id,
+                url: url.clone(),
+                from_config: true,
+            });
+            *next_id += 1;
+        }
+    }
+
+    // 등록된 모든 주소로 백그라운드에서 보낸다. 녹화 경로를 막지 않는다.
+    pub fn notify(self: &Arc<Self>, event: WebhookEvent) {
+        let hooks = self.hooks.lock().unwrap().clone();
+        if hooks.is_empty() {
+            return;
+        }
+
+        let payload = Payload {
+            event: &event,
+            timestamp: Local::now(),
+        };
+        let body = match serde_json::to_vec(&payload) {
+            Ok(body) => body,
+            Err(e) => {
+                warn!("Failed to serialize webhook event: {}", e);
+                return;
+            }
+        };
+        let signature = self.secret.as_deref().map(|secret| sign(secret, &body));
+
+        for hook in hooks {
+            let webhooks = self.clone();
+            let body = body.clone();
+            let signature = signature.clone();
+            let name = event.name();
+            tokio::spawn(async move {
+                webhooks.deliver(&hook, name, body, signature).await;
+            });
+        }
+    }
+
+    async fn deliver(&self, hook: &Webhook, event: &str, body: Vec<u8>, signature: Option<String>) {
+        let mut delay = Duration::from_secs(1);
+        for attempt in 0..=self.max_retries {
+            let mut request = self
+                .client
+                .post(&hook.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(EVENT_HEADER, event)
+                .body(body.clone());
+            if let Some(signature) = &signature {
+                request = request.header(SIGNATURE_HEADER, format!("sha256={}", signature));
+            }
+
+            let error = match request.send().await {
+                Ok(response) if response.status().is_success() => {
+                    info!("Webhook {} delivered to {}", event, hook.url);
+                    return;
+                }
+                Ok(response) => format!("HTTP {}", response.status()),
+                Err(e) => e.to_string(),
+            };
+
+            if attempt == self.max_retries {
+                warn!(
+                    "Giving up on webhook {} to {} after {} attempt(s): {}",
+                    event,
+                    hook.url,
+                    attempt + 1,
+                    error
+                );
+                return;
+            }
+            warn!(
+                "Webhook {} to {} failed ({}). Retrying in {}s.",
+                event,
+                hook.url,
+                error,
+                delay.as_secs()
+            );
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+    }
+}
+
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+pub async fn handle_list(State(state): State<Arc<AppState>>) -> Json<Vec<Webhook>> {
+    Json(state.webhooks.hooks.lock().unwrap().clone())
+}
+
+pub async fn handle_create(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<WebhookRequest>,
+) -> Result<(StatusCode, Json<Webhook>), ApiError> {
+    validate_url(&request.url).map_err(|e| ApiError::bad_request(e.to_string()))?;
+
+    let id = {
+        let mut next_id = state.webhooks.next_id.lock().unwrap();
+        let id = *next_id;
+        *next_id += 1;
+        id
+    };
+    let hook = Webhook {
+        id,
+        url: request.url,
+        from_config: false,
+    };
+    state.webhooks.hooks.lock().unwrap().push(hook.clone());
+
+    info!("Webhook {} registered for {}", hook.id, hook.url);
+    Ok((StatusCode::CREATED, Json(hook)))
+}
+
+pub async fn handle_delete(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<u64>,
+) -> Result<StatusCode, ApiError> {
+    let mut hooks = state.webhooks.hooks.lock().unwrap();
+    let before = hooks.len();
+    hooks.retain(|hook| hook.id != id);
+    if hooks.len() == before {
+        return Err(ApiError::not_found(format!("Webhook {} not found", id)));
+    }
+
+    info!("Webhook {} removed.", id);
+    Ok(StatusCode::NO_CONTENT)
+}
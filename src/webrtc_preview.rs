@@ -0,0 +1,272 @@
+// src/webrtc_preview.rs
+use crate::{
+    ApiError, AppState,
+    stream::{AccessUnit, CLOCK_RATE, Subscription, View},
+};
+use anyhow::{Context, Result, bail};
+use axum::{Json, extract::State};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tokio::sync::{broadcast, watch};
+use tracing::{info, warn};
+use webrtc::{
+    api::{
+        APIBuilder,
+        interceptor_registry::register_default_interceptors,
+        media_engine::{MIME_TYPE_H264, MediaEngine},
+    },
+    ice_transport::ice_server::RTCIceServer,
+    interceptor::registry::Registry,
+    media::Sample,
+    peer_connection::{
+        configuration::RTCConfiguration, peer_connection_state::RTCPeerConnectionState,
+        sdp::session_description::RTCSessionDescription,
+    },
+    rtp_transceiver::rtp_codec::RTCRtpCodecCapability,
+    track::track_local::{TrackLocal, track_local_static_sample::TrackLocalStaticSample},
+};
+
+// 첫 프레임처럼 앞 프레임이 없을 때 쓰는 길이
+const DEFAULT_FRAME_DURATION: Duration = Duration::from_millis(33);
+
+const START_CODE: [u8; 4] = [0, 0, 0, 1];
+
+// answer 를 보낸 뒤 이 시간 안에 연결되지 않으면 포기한다.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebRtcConfig {
+    // true 면 POST /webrtc/offer 로 브라우저에 지연이 짧은 미리보기를 보낸다.
+    pub enabled: bool,
+    // 브라우저와 서버가 서로의 주소를 찾는 데 쓰는 STUN/TURN 서버
+    pub ice_servers: Vec<String>,
+}
+
+impl Default for WebRtcConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ice_servers: vec!["stun:stun.l.google.com:19302".to_string()],
+        }
+    }
+}
+
+impl WebRtcConfig {
+    pub fn validate(&self) -> Result<()> {
+        for server in &self.ice_servers {
+            if !["stun:", "stuns:", "turn:", "turns:"]
+                .iter()
+                .any(|scheme| server.starts_with(scheme))
+            {
+                bail!(
+                    "webrtc.ice_servers entry {:?} is not a STUN/TURN URL",
+                    server
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OfferRequest {
+    pub sdp: String,
+    // 생략하면 합성 영상
+    pub camera: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AnswerResponse {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub sdp: String,
+}
+
+// POST /webrtc/offer - 브라우저의 offer 를 받아 answer 를 돌려준다.
+// ICE 후보를 모두 모은 뒤 answer 에 넣어 보내므로 별도의 후보 교환은 없다.
+pub async fn handle_offer(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<OfferRequest>,
+) -> Result<Json<AnswerResponse>, ApiError> {
+    let config = &state.config.webrtc;
+    if !config.enabled {
+        return Err(ApiError::not_found("WebRTC preview is disabled"));
+    }
+    let view = match request.camera {
+        Some(camera) => {
+            let known = state.config.recording.cameras.contains(&camera)
+                || state
+                    .sessions
+                    .lock()
+                    .unwrap()
+                    .running()
+                    .iter()
+                    .any(|session| session.config.cameras.contains(&camera));
+            if !known {
+                return Err(ApiError::bad_request(format!(
+                    "Camera {} is not configured",
+                    camera
+                )));
+            }
+            View::Camera(camera)
+        }
+        None => View::Composite,
+    };
+
+    let offer = RTCSessionDescription::offer(request.sdp)
+        .map_err(|e| ApiError::bad_request(format!("Invalid SDP offer: {}", e)))?;
+    let subscription = state.streams.subscribe(view);
+    let sdp = connect(config, offer, subscription)
+        .await
+        .map_err(|e| ApiError::internal(format!("{:#}", e)))?;
+    info!("WebRTC preview of {:?} started.", view);
+    Ok(Json(AnswerResponse {
+        kind: "answer".to_string(),
+        sdp,
+    }))
+}
+
+async fn connect(
+    config: &WebRtcConfig,
+    offer: RTCSessionDescription,
+    subscription: Subscription,
+) -> Result<String> {
+    let mut media = MediaEngine::default();
+    media.register_default_codecs()?;
+    let registry = register_default_interceptors(Registry::new(), &mut media)?;
+    let api = APIBuilder::new()
+        .with_media_engine(media)
+        .with_interceptor_registry(registry)
+        .build();
+    let peer = Arc::new(
+        api.new_peer_connection(RTCConfiguration {
+            ice_servers: vec![RTCIceServer {
+                urls: config.ice_servers.clone(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        })
+        .await
+        .context("Failed to create the peer connection")?,
+    );
+
+    let track = Arc::new(TrackLocalStaticSample::new(
+        RTCRtpCodecCapability {
+            mime_type: MIME_TYPE_H264.to_string(),
+            clock_rate: CLOCK_RATE as u32,
+            ..Default::default()
+        },
+        "video".to_string(),
+        "camera-server".to_string(),
+    ));
+    let answer = async {
+        peer.add_track(track.clone() as Arc<dyn TrackLocal + Send + Sync>)
+            .await?;
+        peer.set_remote_description(offer).await?;
+        let answer = peer.create_answer(None).await?;
+        let mut gathered = peer.gathering_complete_promise().await;
+        peer.set_local_description(answer).await?;
+        let _ = gathered.recv().await;
+        peer.local_description()
+            .await
+            .context("No local description after ICE gathering")
+    }
+    .await;
+    let answer = match answer {
+        Ok(answer) => answer,
+        Err(e) => {
+            let _ = peer.close().await;
+            return Err(e);
+        }
+    };
+
+    let (states, mut state_rx) = watch::channel(RTCPeerConnectionState::New);
+    peer.on_peer_connection_state_change(Box::new(move |state| {
+        let _ = states.send(state);
+        Box::pin(async {})
+    }));
+
+    tokio::spawn(async move {
+        let connected = tokio::time::timeout(
+            CONNECT_TIMEOUT,
+            state_rx.wait_for(|state| *state == RTCPeerConnectionState::Connected),
+        )
+        .await
+        .is_ok_and(|state| state.is_ok());
+        if connected {
+            // 연결이 끊기거나 실패하면 프레임 전송을 멈춘다.
+            tokio::select! {
+                result = send_units(&track, subscription) => {
+                    if let Err(e) = result {
+                        warn!("WebRTC preview stopped: {:#}", e);
+                    }
+                }
+                _ = state_rx.wait_for(|state| matches!(
+                    state,
+                    RTCPeerConnectionState::Disconnected
+                        | RTCPeerConnectionState::Failed
+                        | RTCPeerConnectionState::Closed
+                )) => {}
+            }
+            info!("WebRTC preview client disconnected.");
+        } else {
+            warn!("WebRTC preview client did not connect.");
+        }
+        let _ = peer.close().await;
+    });
+
+    Ok(answer.sdp)
+}
+
+// 키프레임부터 시작해 프레임마다 Annex B 로 이어 붙여 보낸다 (패킷 분할은 트랙이 한다).
+async fn send_units(track: &TrackLocalStaticSample, mut subscription: Subscription) -> Result<()> {
+    let mut waiting_for_keyframe = true;
+    let mut previous: Option<u32> = None;
+    loop {
+        let unit = match subscription.units.recv().await {
+            Ok(unit) => unit,
+            Err(broadcast::error::RecvError::Lagged(_)) => {
+                waiting_for_keyframe = true;
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => return Ok(()),
+        };
+        if waiting_for_keyframe {
+            if !unit.keyframe {
+                continue;
+            }
+            waiting_for_keyframe = false;
+        }
+
+        let duration = match previous {
+            Some(previous) => {
+                let ticks = unit.timestamp.wrapping_sub(previous) as u64;
+                Duration::from_micros(ticks * 1_000_000 / CLOCK_RATE)
+            }
+            None => DEFAULT_FRAME_DURATION,
+        };
+        previous = Some(unit.timestamp);
+        track
+            .write_sample(&Sample {
+                data: annex_b(&unit),
+                timestamp: SystemTime::now(),
+                duration,
+                ..Default::default()
+            })
+            .await?;
+    }
+}
+
+fn annex_b(unit: &AccessUnit) -> Bytes {
+    let mut data = Vec::with_capacity(unit.nals.iter().map(|nal| nal.len() + 4).sum());
+    for nal in &unit.nals {
+        data.extend_from_slice(&START_CODE);
+        data.extend_from_slice(nal);
+    }
+    Bytes::from(data)
+}
@@ -0,0 +1,251 @@
+// tests/pipeline.rs
+// 서버를 띄워 test_patterns 카메라로 짧게 녹화하고 (run_recording_blocking) 마무리된 결과를 확인한다.
+// 테스트 패턴과 마무리에 ffmpeg 가 있어야 하므로 `cargo test -- --ignored` 로 돌린다.
+use serde_json::{Value, json};
+use std::{
+    fs,
+    net::TcpListener,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    time::{Duration, Instant},
+};
+
+const RECORD_FOR: Duration = Duration::from_secs(3);
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+// 끝날 때 서버를 멈추고 임시 디렉토리를 지운다.
+struct Server {
+    child: Child,
+    dir: PathBuf,
+    url: String,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn ffmpeg_available() -> bool {
+    ["ffmpeg", "ffprobe"].iter().all(|program| {
+        Command::new(program)
+            .arg("-version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    })
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("no free port")
+        .port()
+}
+
+async fn start_server(client: &reqwest::Client) -> Server {
+    let dir = std::env::temp_dir().join(format!("server-pipeline-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    let port = free_port();
+    let config = format!(
+        r#"
+port = {port}
+save_dir = "{dir}/recordings"
+schedules_file = "{dir}/schedules.json"
+profiles_file = "{dir}/profiles.json"
+catalog_file = "{dir}/recordings.db"
+
+[recording]
+cameras = [0]
+test_patterns = [0]
+width = 320
+height = 240
+fps = 15
+
+[self_test]
+on_startup = false
+"#,
+        dir = dir.display()
+    );
+    let config_path = dir.join("server.toml");
+    fs::write(&config_path, config).unwrap();
+    let child = Command::new(env!("CARGO_BIN_EXE_server"))
+        .arg("--config")
+        .arg(&config_path)
+        .current_dir(&dir)
+        .stdout(Stdio::null())
+        .spawn()
+        .expect("failed to start the server");
+    let server = Server {
+        child,
+        dir,
+        url: format!("http://127.0.0.1:{}", port),
+    };
+    let started = Instant::now();
+    while client
+        .get(format!("{}/healthz", server.url))
+        .send()
+        .await
+        .is_err()
+    {
+        assert!(started.elapsed() < TIMEOUT, "the server did not start");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    server
+}
+
+// dir 아래 (하위 디렉토리 포함) 의 모든 파일
+fn files_in(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            files.extend(files_in(&path));
+        } else {
+            files.push(path);
+        }
+    }
+    files
+}
+
+// body 로 녹화를 시작해 RECORD_FOR 동안 녹화하고 멈춘 뒤, 끝난 세션 (GET /sessions/:id) 을 돌려준다.
+async fn record(client: &reqwest::Client, server: &Server, body: Value) -> Value {
+    let started: Value = client
+        .post(format!("{}/start", server.url))
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .expect("POST /start failed")
+        .json()
+        .await
+        .unwrap();
+    let session_id = started["session_id"].as_str().unwrap().to_string();
+    tokio::time::sleep(RECORD_FOR).await;
+    client
+        .post(format!("{}/stop", server.url))
+        .json(&json!({ "session_id": session_id }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .expect("POST /stop failed");
+
+    let started = Instant::now();
+    loop {
+        let session: Value = client
+            .get(format!("{}/sessions/{}", server.url, session_id))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        if !["starting", "recording", "paused", "finalizing"]
+            .contains(&session["state"].as_str().unwrap())
+        {
+            return session;
+        }
+        assert!(
+            started.elapsed() < TIMEOUT,
+            "the recording did not finalize"
+        );
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+}
+
+fn outputs(stats: &Value) -> Vec<PathBuf> {
+    stats["segments"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|path| PathBuf::from(path.as_str().unwrap()))
+        .collect()
+}
+
+#[tokio::test]
+#[ignore = "requires ffmpeg"]
+async fn records_and_finalizes_a_test_pattern() {
+    assert!(ffmpeg_available(), "ffmpeg and ffprobe must be on PATH");
+    let client = reqwest::Client::new();
+    let server = start_server(&client).await;
+    let session = record(&client, &server, json!({})).await;
+    assert_eq!(session["state"], "finished", "{}", session);
+
+    let stats = &session["stats"];
+    assert!(stats["frames_captured"].as_u64().unwrap() > 0, "{}", stats);
+    assert!(stats["measured_fps"].as_f64().unwrap() > 0.0, "{}", stats);
+    let outputs = outputs(stats);
+    assert_eq!(outputs.len(), 1, "{}", stats);
+    let output = &outputs[0];
+    assert!(
+        fs::metadata(output).is_ok_and(|file| file.len() > 0),
+        "{:?} was not written",
+        output
+    );
+
+    // 카메라의 원본 스트림은 지워지고, 타임스탬프는 완성된 파일의 사이드카로만 남는다.
+    for file in files_in(&server.dir.join("recordings")) {
+        let extension = file.extension().and_then(|e| e.to_str());
+        assert_ne!(extension, Some("h264"), "{:?} was left behind", file);
+        if extension == Some("pts") {
+            assert_eq!(
+                file,
+                output.with_extension("pts"),
+                "{:?} was left behind",
+                file
+            );
+        }
+    }
+}
+
+#[tokio::test]
+#[ignore = "requires ffmpeg"]
+async fn null_sink_counts_the_recorded_frames() {
+    assert!(ffmpeg_available(), "ffmpeg and ffprobe must be on PATH");
+    let client = reqwest::Client::new();
+    let server = start_server(&client).await;
+    let session = record(&client, &server, json!({ "sink": { "backend": "null" } })).await;
+    assert_eq!(session["state"], "finished", "{}", session);
+
+    let stats = &session["stats"];
+    let outputs = outputs(stats);
+    assert_eq!(outputs.len(), 1, "{}", stats);
+    let summary = &outputs[0];
+    assert!(
+        summary.to_string_lossy().ends_with(".frames.json"),
+        "{:?} is not a frame summary",
+        summary
+    );
+    let summary: Value = serde_json::from_slice(&fs::read(summary).unwrap()).unwrap();
+    assert!(summary["frames"].as_u64().unwrap() > 0, "{}", summary);
+    assert_eq!(summary["width"], 320, "{}", summary);
+    assert_eq!(summary["height"], 240, "{}", summary);
+    assert_eq!(
+        summary["bytes"].as_u64().unwrap(),
+        summary["frames"].as_u64().unwrap() * 320 * 240 * 3 / 2,
+        "{}",
+        summary
+    );
+
+    // 영상 대신 요약만 남으므로 원본 스트림은 그대로 둔다.
+    let files = files_in(&server.dir.join("recordings"));
+    assert!(
+        !files
+            .iter()
+            .any(|file| file.extension().is_some_and(|e| e == "mp4")),
+        "{:?}",
+        files
+    );
+    assert!(
+        files
+            .iter()
+            .any(|file| file.extension().is_some_and(|e| e == "h264")),
+        "{:?}",
+        files
+    );
+}